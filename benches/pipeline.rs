@@ -0,0 +1,35 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use froggle::interpreter::Interpreter;
+use froggle::lexer::Lexer;
+use froggle::parser::Parser;
+use std::hint::black_box;
+
+// each program checks its own result with assertions, so a benchmark that gets faster by
+// computing the wrong thing fails instead
+const PROGRAMS: [(&str, &str); 3] = [
+    ("fib", include_str!("programs/fib.frog")),
+    ("loops", include_str!("programs/loops.frog")),
+    ("nested_scopes", include_str!("programs/nested_scopes.frog")),
+];
+
+fn pipeline(c: &mut Criterion) {
+    for (name, source) in PROGRAMS {
+        let tokens = Lexer::new(source).parse().unwrap();
+        let program = Parser::new(tokens.clone()).parse().unwrap();
+
+        let mut group = c.benchmark_group(name);
+        group.bench_function("lex", |b| {
+            b.iter(|| Lexer::new(black_box(source)).parse().unwrap())
+        });
+        group.bench_function("parse", |b| {
+            b.iter(|| Parser::new(black_box(tokens.clone())).parse().unwrap())
+        });
+        group.bench_function("interpret", |b| {
+            b.iter(|| Interpreter::new().interpret(black_box(&program)).unwrap())
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, pipeline);
+criterion_main!(benches);
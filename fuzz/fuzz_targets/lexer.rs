@@ -0,0 +1,9 @@
+#![no_main]
+
+use froggle::lexer::Lexer;
+use libfuzzer_sys::fuzz_target;
+
+// any text lexes to tokens or an error, never a panic
+fuzz_target!(|source: &str| {
+    let _ = Lexer::new(source).parse();
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use froggle::lexer::Lexer;
+use froggle::parser::Parser;
+use libfuzzer_sys::fuzz_target;
+
+// any text parses to a program or an error, never a panic, an endless loop or a stack
+// overflow, however deeply it nests; text that doesn't lex is still fed to the parser, which
+// stops at the lex error
+fuzz_target!(|source: &str| {
+    let _ = Parser::from_lexer(Lexer::new(source)).parse();
+});
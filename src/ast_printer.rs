@@ -0,0 +1,359 @@
+use crate::formatter::{format_pattern, quote, type_params_list};
+use crate::lexer::Span;
+use crate::parser::{ASTVisitor, Expression, ExpressionKind, MatchArm, Statement, Type};
+use crate::symbol::Symbol;
+
+const INDENT: &str = "  ";
+
+// renders an AST as an indented tree, one node per line followed by where it starts
+#[derive(Default)]
+pub struct AstPrinter {
+    output: String,
+    depth: usize,
+}
+
+impl AstPrinter {
+    pub fn new() -> AstPrinter {
+        AstPrinter {
+            output: String::new(),
+            depth: 0,
+        }
+    }
+
+    pub fn print(program: &[Statement]) -> String {
+        let mut printer = AstPrinter::new();
+        printer.statements(program);
+        printer.output
+    }
+
+    fn line(&mut self, text: &str, span: Span) {
+        for _ in 0..self.depth {
+            self.output.push_str(INDENT);
+        }
+        self.output.push_str(&format!("{} [{}]\n", text, span));
+    }
+
+    // label line without a node of its own, e.g. "condition" above an expression
+    fn label(&mut self, text: &str) {
+        for _ in 0..self.depth {
+            self.output.push_str(INDENT);
+        }
+        self.output.push_str(text);
+        self.output.push('\n');
+    }
+
+    fn nested(&mut self, print: impl FnOnce(&mut Self)) {
+        self.depth += 1;
+        print(self);
+        self.depth -= 1;
+    }
+
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            statement.accept(self);
+        }
+    }
+
+    fn labelled_statements(&mut self, label: &str, statements: &[Statement]) {
+        self.label(label);
+        self.nested(|printer| printer.statements(statements));
+    }
+
+    fn labelled_expression(&mut self, label: &str, expr: &Expression) {
+        self.label(label);
+        self.nested(|printer| printer.expression(expr));
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        match &expr.kind {
+            ExpressionKind::Number(n, _) => self.line(&format!("Number {}", n), expr.span),
+            ExpressionKind::Float(f, _) => self.line(&format!("Float {:?}", f), expr.span),
+            ExpressionKind::String(s, _) => self.line(&format!("String {}", quote(s)), expr.span),
+            ExpressionKind::Bool(b) => self.line(&format!("Bool {}", b), expr.span),
+            ExpressionKind::None => self.line("None", expr.span),
+            ExpressionKind::Variable(name) => self.line(&format!("Variable {}", name), expr.span),
+            ExpressionKind::BinaryOperation {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                self.line(&format!("Binary {}", operator), expr.span);
+                self.nested(|printer| {
+                    printer.expression(left);
+                    printer.expression(right);
+                });
+            }
+            ExpressionKind::UnaryOperation {
+                operator, operand, ..
+            } => {
+                self.line(&format!("Unary {}", operator), expr.span);
+                self.nested(|printer| printer.expression(operand));
+            }
+            ExpressionKind::FunctionCall { name, arguments } => {
+                self.line(&format!("Call {}", name), expr.span);
+                self.nested(|printer| {
+                    for argument in arguments {
+                        printer.expression(argument);
+                    }
+                });
+            }
+            ExpressionKind::EnumVariant { enum_name, variant } => {
+                self.line(&format!("Variant {}.{}", enum_name, variant), expr.span)
+            }
+            ExpressionKind::Range { start, end } => {
+                self.line("Range", expr.span);
+                self.nested(|printer| {
+                    printer.expression(start);
+                    printer.expression(end);
+                });
+            }
+            ExpressionKind::Block { statements, value } => {
+                self.line("BlockValue", expr.span);
+                self.nested(|printer| {
+                    printer.labelled_statements("body", statements);
+                    printer.labelled_expression("value", value);
+                });
+            }
+        }
+    }
+}
+
+impl ASTVisitor for AstPrinter {
+    type Output = ();
+
+    fn visit_declaration(
+        &mut self,
+        name: Symbol,
+        expr: &Expression,
+        declared_type: Option<&Type>,
+        span: Span,
+    ) {
+        let annotation = declared_type
+            .map(|t| format!(": {}", t))
+            .unwrap_or_default();
+        self.line(&format!("Let {}{}", name, annotation), span);
+        self.nested(|printer| printer.expression(expr));
+    }
+
+    fn visit_const(
+        &mut self,
+        name: Symbol,
+        expr: &Expression,
+        declared_type: Option<&Type>,
+        span: Span,
+    ) {
+        let annotation = declared_type
+            .map(|t| format!(": {}", t))
+            .unwrap_or_default();
+        self.line(&format!("Const {}{}", name, annotation), span);
+        self.nested(|printer| printer.expression(expr));
+    }
+
+    fn visit_assignment(&mut self, name: Symbol, expr: &Expression, span: Span) {
+        self.line(&format!("Assign {}", name), span);
+        self.nested(|printer| printer.expression(expr));
+    }
+
+    fn visit_print(&mut self, expr: &Expression, debug: bool, span: Span) {
+        self.line(if debug { "CroakDebug" } else { "Croak" }, span);
+        self.nested(|printer| printer.expression(expr));
+    }
+
+    fn visit_while(&mut self, condition: &Expression, body: &[Statement], span: Span) {
+        self.line("While", span);
+        self.nested(|printer| {
+            printer.labelled_expression("condition", condition);
+            printer.labelled_statements("body", body);
+        });
+    }
+
+    fn visit_do_while(&mut self, body: &[Statement], condition: &Expression, span: Span) {
+        self.line("DoWhile", span);
+        self.nested(|printer| {
+            printer.labelled_statements("body", body);
+            printer.labelled_expression("condition", condition);
+        });
+    }
+
+    fn visit_for(
+        &mut self,
+        variable: Symbol,
+        annotation: Option<&Type>,
+        start: &Expression,
+        end: &Expression,
+        body: &[Statement],
+        span: Span,
+    ) {
+        let annotation = annotation.map(|t| format!(": {}", t)).unwrap_or_default();
+        self.line(&format!("For {}{}", variable, annotation), span);
+        self.nested(|printer| {
+            printer.labelled_expression("from", start);
+            printer.labelled_expression("to", end);
+            printer.labelled_statements("body", body);
+        });
+    }
+
+    fn visit_for_in(
+        &mut self,
+        variable: Symbol,
+        annotation: Option<&Type>,
+        iterable: &Expression,
+        body: &[Statement],
+        span: Span,
+    ) {
+        let annotation = annotation.map(|t| format!(": {}", t)).unwrap_or_default();
+        self.line(&format!("ForIn {}{}", variable, annotation), span);
+        self.nested(|printer| {
+            printer.labelled_expression("in", iterable);
+            printer.labelled_statements("body", body);
+        });
+    }
+
+    fn visit_block(&mut self, statements: &[Statement], span: Span) {
+        self.line("Block", span);
+        self.nested(|printer| printer.statements(statements));
+    }
+
+    fn visit_function_declaration(
+        &mut self,
+        name: Symbol,
+        type_params: &[Symbol],
+        params: &[(Symbol, Type)],
+        return_type: &Type,
+        body: &[Statement],
+        span: Span,
+    ) {
+        let params: Vec<String> = params
+            .iter()
+            .map(|(name, t)| format!("{}: {}", name, t))
+            .collect();
+        self.line(
+            &format!(
+                "Function {}{}({}): {}",
+                name,
+                type_params_list(type_params),
+                params.join(", "),
+                return_type
+            ),
+            span,
+        );
+        self.nested(|printer| printer.statements(body));
+    }
+
+    fn visit_if(
+        &mut self,
+        condition: &Expression,
+        body: &[Statement],
+        else_branch: Option<&[Statement]>,
+        span: Span,
+    ) {
+        self.line("If", span);
+        self.nested(|printer| {
+            printer.labelled_expression("condition", condition);
+            printer.labelled_statements("then", body);
+            if let Some(else_branch) = else_branch {
+                printer.labelled_statements("else", else_branch);
+            }
+        });
+    }
+
+    fn visit_match(&mut self, subject: &Expression, arms: &[MatchArm], span: Span) {
+        self.line("Match", span);
+        self.nested(|printer| {
+            printer.labelled_expression("subject", subject);
+            for arm in arms {
+                printer.line(&format!("Arm {}", format_pattern(&arm.pattern)), arm.span);
+                printer.nested(|printer| printer.statements(&arm.body));
+            }
+        });
+    }
+
+    fn visit_enum(&mut self, name: Symbol, variants: &[Symbol], span: Span) {
+        let variants: Vec<&str> = variants.iter().map(|variant| variant.as_str()).collect();
+        self.line(
+            &format!("Enum {} {{ {} }}", name, variants.join(", ")),
+            span,
+        );
+    }
+
+    fn visit_expression(&mut self, expr: &Expression, span: Span) {
+        self.line("Expression", span);
+        self.nested(|printer| printer.expression(expr));
+    }
+
+    fn visit_assert(&mut self, condition: &Expression, span: Span) {
+        self.line("Assert", span);
+        self.nested(|printer| printer.expression(condition));
+    }
+
+    fn visit_assert_eq(&mut self, left: &Expression, right: &Expression, span: Span) {
+        self.line("AssertEq", span);
+        self.nested(|printer| {
+            printer.expression(left);
+            printer.expression(right);
+        });
+    }
+
+    fn visit_return(&mut self, expr: &Expression, span: Span) {
+        self.line("Return", span);
+        self.nested(|printer| printer.expression(expr));
+    }
+
+    fn visit_try(&mut self, body: &[Statement], error: Symbol, handler: &[Statement], span: Span) {
+        self.line("Try", span);
+        self.nested(|printer| {
+            printer.labelled_statements("body", body);
+            printer.labelled_statements(&format!("handle {}", error), handler);
+        });
+    }
+
+    fn visit_raise(&mut self, message: &Expression, span: Span) {
+        self.line("Raise", span);
+        self.nested(|printer| printer.expression(message));
+    }
+
+    fn visit_break(&mut self, span: Span) {
+        self.line("Break", span);
+    }
+
+    fn visit_continue(&mut self, span: Span) {
+        self.line("Continue", span);
+    }
+
+    fn visit_import(&mut self, path: &str, span: Span) {
+        self.line(&format!("Import \"{}\"", path), span);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_print_tree() {
+        let source = "let x: number = 1 + y;\nwhile x > 0 {\n  x = x - 1;\n}";
+        let tokens = Lexer::new(source).parse().unwrap();
+        let ast = Parser::new(tokens).parse().unwrap();
+
+        let expected = "\
+Let x: number [1:1]
+  Binary + [1:17]
+    Number 1 [1:17]
+    Variable y [1:21]
+While [2:1]
+  condition
+    Binary > [2:7]
+      Variable x [2:7]
+      Number 0 [2:11]
+  body
+    Assign x [3:3]
+      Binary - [3:7]
+        Variable x [3:7]
+        Number 1 [3:11]
+";
+        assert_eq!(AstPrinter::print(&ast), expected);
+    }
+}
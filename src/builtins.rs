@@ -0,0 +1,614 @@
+use crate::interpreter::{RuntimeErrorKind, Value};
+use crate::parser::Type;
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::sync::LazyLock;
+
+// native function callable from froggle code like any declared function
+#[derive(Debug)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub params: &'static [Type],
+    pub return_type: Type,
+    pub function: fn(&[Value]) -> Result<Value, RuntimeErrorKind>,
+}
+
+impl Builtin {
+    // file access is opt-in, see `Limits::allow_fs`
+    pub fn uses_file_system(&self) -> bool {
+        matches!(self.name, "read_file" | "write_file")
+    }
+}
+
+thread_local! {
+    // arguments the script was started with, after its path on the command line
+    static SCRIPT_ARGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+// makes `args` what arg_count and arg see
+pub fn set_script_args(args: Vec<String>) {
+    SCRIPT_ARGS.with(|script_args| *script_args.borrow_mut() = args);
+}
+
+// a static rather than a const since optional return types are boxed
+pub static BUILTINS: LazyLock<Vec<Builtin>> = LazyLock::new(|| {
+    vec![
+        Builtin {
+            name: "abs",
+            params: &[Type::Number],
+            return_type: Type::Number,
+            function: abs,
+        },
+        Builtin {
+            name: "min",
+            params: &[Type::Number, Type::Number],
+            return_type: Type::Number,
+            function: min,
+        },
+        Builtin {
+            name: "max",
+            params: &[Type::Number, Type::Number],
+            return_type: Type::Number,
+            function: max,
+        },
+        Builtin {
+            name: "pow",
+            params: &[Type::Number, Type::Number],
+            return_type: Type::Number,
+            function: pow,
+        },
+        Builtin {
+            name: "sqrt_int",
+            params: &[Type::Number],
+            return_type: Type::Number,
+            function: sqrt_int,
+        },
+        Builtin {
+            name: "random",
+            params: &[Type::Number, Type::Number],
+            return_type: Type::Number,
+            function: random,
+        },
+        Builtin {
+            name: "input",
+            params: &[],
+            return_type: Type::String,
+            function: input,
+        },
+        Builtin {
+            name: "input_number",
+            params: &[],
+            return_type: Type::Number,
+            function: input_number,
+        },
+        Builtin {
+            name: "length",
+            params: &[Type::String],
+            return_type: Type::Number,
+            function: length,
+        },
+        Builtin {
+            name: "substring",
+            params: &[Type::String, Type::Number, Type::Number],
+            return_type: Type::String,
+            function: substring,
+        },
+        Builtin {
+            name: "to_upper",
+            params: &[Type::String],
+            return_type: Type::String,
+            function: to_upper,
+        },
+        Builtin {
+            name: "to_lower",
+            params: &[Type::String],
+            return_type: Type::String,
+            function: to_lower,
+        },
+        Builtin {
+            name: "contains",
+            params: &[Type::String, Type::String],
+            return_type: Type::Boolean,
+            function: contains,
+        },
+        Builtin {
+            name: "split",
+            params: &[Type::String, Type::String, Type::Number],
+            return_type: Type::String,
+            function: split,
+        },
+        Builtin {
+            name: "to_string",
+            params: &[Type::Number],
+            return_type: Type::String,
+            function: to_string,
+        },
+        Builtin {
+            name: "parse_number",
+            params: &[Type::String],
+            return_type: Type::Optional(Box::new(Type::Number)),
+            function: parse_number,
+        },
+        Builtin {
+            name: "to_bool",
+            params: &[Type::String],
+            return_type: Type::Boolean,
+            function: to_bool,
+        },
+        Builtin {
+            name: "typeof",
+            params: &[Type::Any],
+            return_type: Type::String,
+            function: type_of,
+        },
+        Builtin {
+            name: "exit",
+            params: &[Type::Number],
+            return_type: Type::Void,
+            function: exit,
+        },
+        Builtin {
+            name: "read_file",
+            params: &[Type::String],
+            return_type: Type::String,
+            function: read_file,
+        },
+        Builtin {
+            name: "write_file",
+            params: &[Type::String, Type::String],
+            return_type: Type::Void,
+            function: write_file,
+        },
+        Builtin {
+            name: "arg_count",
+            params: &[],
+            return_type: Type::Number,
+            function: arg_count,
+        },
+        Builtin {
+            name: "arg",
+            params: &[Type::Number],
+            return_type: Type::String,
+            function: arg,
+        },
+    ]
+});
+
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+// arguments are typechecked against the signature before the call, so anything else
+// means the builtin was called around the typechecker
+fn numbers<const N: usize>(name: &str, args: &[Value]) -> Result<[i64; N], RuntimeErrorKind> {
+    check_arity(name, args, N)?;
+    let mut numbers = [0; N];
+    for (number, arg) in numbers.iter_mut().zip(args) {
+        *number = number_arg(name, arg)?;
+    }
+    Ok(numbers)
+}
+
+fn check_arity(name: &str, args: &[Value], expected: usize) -> Result<(), RuntimeErrorKind> {
+    if args.len() != expected {
+        return Err(RuntimeErrorKind::ArityMismatch {
+            name: name.to_string(),
+            expected,
+            found: args.len(),
+        });
+    }
+    Ok(())
+}
+
+fn string_arg<'a>(name: &str, arg: &'a Value) -> Result<&'a str, RuntimeErrorKind> {
+    match arg {
+        Value::String(s) => Ok(s),
+        _ => Err(RuntimeErrorKind::InvalidArgument(format!(
+            "{} expects a string argument, got {}",
+            name, arg
+        ))),
+    }
+}
+
+fn number_arg(name: &str, arg: &Value) -> Result<i64, RuntimeErrorKind> {
+    match arg {
+        Value::Number(n) => Ok(*n),
+        _ => Err(RuntimeErrorKind::InvalidArgument(format!(
+            "{} expects a number argument, got {}",
+            name, arg
+        ))),
+    }
+}
+
+fn abs(args: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    let [n] = numbers("abs", args)?;
+    n.checked_abs()
+        .map(Value::Number)
+        .ok_or(RuntimeErrorKind::Overflow("abs".to_string()))
+}
+
+fn min(args: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    let [a, b] = numbers("min", args)?;
+    Ok(Value::Number(a.min(b)))
+}
+
+fn max(args: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    let [a, b] = numbers("max", args)?;
+    Ok(Value::Number(a.max(b)))
+}
+
+fn pow(args: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    let [base, exponent] = numbers("pow", args)?;
+    if exponent < 0 {
+        return Err(RuntimeErrorKind::InvalidArgument(format!(
+            "pow exponent must not be negative, got {}",
+            exponent
+        )));
+    }
+    u32::try_from(exponent)
+        .ok()
+        .and_then(|exponent| base.checked_pow(exponent))
+        .map(Value::Number)
+        .ok_or(RuntimeErrorKind::Overflow("pow".to_string()))
+}
+
+fn sqrt_int(args: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    let [n] = numbers("sqrt_int", args)?;
+    if n < 0 {
+        return Err(RuntimeErrorKind::InvalidArgument(format!(
+            "sqrt_int of negative number {}",
+            n
+        )));
+    }
+    Ok(Value::Number((n as u64).isqrt() as i64))
+}
+
+// random number between both bounds, inclusive
+fn random(args: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    let [low, high] = numbers("random", args)?;
+    if low > high {
+        return Err(RuntimeErrorKind::InvalidArgument(format!(
+            "random range {} to {} is empty",
+            low, high
+        )));
+    }
+    // every RandomState is seeded with fresh keys, which is plenty random for a toy language
+    let seed = RandomState::new().build_hasher().finish();
+    let range = (high as i128 - low as i128 + 1) as u128;
+    Ok(Value::Number(
+        (low as i128 + (seed as u128 % range) as i128) as i64,
+    ))
+}
+
+// reads one line from stdin, without the trailing newline
+fn read_line(name: &str) -> Result<String, RuntimeErrorKind> {
+    let mut line = String::new();
+    io::stdin()
+        .read_line(&mut line)
+        .map_err(|e| RuntimeErrorKind::InvalidArgument(format!("{} failed: {}", name, e)))?;
+    Ok(line.trim_end_matches(['\n', '\r']).to_string())
+}
+
+fn input(_: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    Ok(Value::String(read_line("input")?))
+}
+
+fn input_number(_: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    number_from("input_number", &read_line("input_number")?).map(Value::Number)
+}
+
+// surrounding whitespace is ignored, anything else that isn't a number is an error
+fn number_from(name: &str, text: &str) -> Result<i64, RuntimeErrorKind> {
+    text.trim().parse().map_err(|_| {
+        RuntimeErrorKind::InvalidArgument(format!("{} expected a number, got '{}'", name, text))
+    })
+}
+
+// strings are indexed by character, not byte, so "héllo" has length 5
+fn length(args: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    check_arity("length", args, 1)?;
+    let s = string_arg("length", &args[0])?;
+    Ok(Value::Number(s.chars().count() as i64))
+}
+
+// characters from `start` up to but not including `end`
+fn substring(args: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    check_arity("substring", args, 3)?;
+    let s = string_arg("substring", &args[0])?;
+    let start = number_arg("substring", &args[1])?;
+    let end = number_arg("substring", &args[2])?;
+
+    let length = s.chars().count() as i64;
+    if start < 0 || start > end || end > length {
+        return Err(RuntimeErrorKind::InvalidArgument(format!(
+            "substring range {} to {} is out of bounds for a string of length {}",
+            start, end, length
+        )));
+    }
+    let substring = s
+        .chars()
+        .skip(start as usize)
+        .take((end - start) as usize)
+        .collect();
+    Ok(Value::String(substring))
+}
+
+fn to_upper(args: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    check_arity("to_upper", args, 1)?;
+    Ok(Value::String(
+        string_arg("to_upper", &args[0])?.to_uppercase(),
+    ))
+}
+
+fn to_lower(args: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    check_arity("to_lower", args, 1)?;
+    Ok(Value::String(
+        string_arg("to_lower", &args[0])?.to_lowercase(),
+    ))
+}
+
+fn contains(args: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    check_arity("contains", args, 2)?;
+    let s = string_arg("contains", &args[0])?;
+    let part = string_arg("contains", &args[1])?;
+    Ok(Value::Bool(s.contains(part)))
+}
+
+// froggle has no lists, so split hands out one piece at a time: the piece at `index` of
+// the string cut at every `separator`
+fn split(args: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    check_arity("split", args, 3)?;
+    let s = string_arg("split", &args[0])?;
+    let separator = string_arg("split", &args[1])?;
+    let index = number_arg("split", &args[2])?;
+
+    if separator.is_empty() {
+        return Err(RuntimeErrorKind::InvalidArgument(
+            "split separator must not be empty".to_string(),
+        ));
+    }
+    let pieces: Vec<&str> = s.split(separator).collect();
+    usize::try_from(index)
+        .ok()
+        .and_then(|i| pieces.get(i))
+        .map(|piece| Value::String(piece.to_string()))
+        .ok_or_else(|| {
+            RuntimeErrorKind::InvalidArgument(format!(
+                "split index {} is out of range for {} pieces",
+                index,
+                pieces.len()
+            ))
+        })
+}
+
+fn to_string(args: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    let [n] = numbers("to_string", args)?;
+    Ok(Value::String(n.to_string()))
+}
+
+// the type of the value at runtime, so an optional variable holding a number is a "number"
+fn type_of(args: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    check_arity("typeof", args, 1)?;
+    Ok(Value::String(type_name(&args[0])))
+}
+
+pub fn type_name(value: &Value) -> String {
+    match value {
+        Value::Number(_) => "number".to_string(),
+        Value::Float(_) => "float".to_string(),
+        Value::Bool(_) => "bool".to_string(),
+        Value::String(_) => "string".to_string(),
+        Value::Function(_) => "func".to_string(),
+        Value::Enum(name, _) => name.to_string(),
+        Value::Range(..) => "range".to_string(),
+        Value::None => "none".to_string(),
+        Value::Void => "void".to_string(),
+    }
+}
+
+// none for text that isn't a number, so scripts can check for it
+fn parse_number(args: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    check_arity("parse_number", args, 1)?;
+    let text = string_arg("parse_number", &args[0])?;
+    Ok(number_from("parse_number", text).map_or(Value::None, Value::Number))
+}
+
+// the inverse of how bools are printed: "true" or "false", give or take surrounding whitespace
+fn to_bool(args: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    check_arity("to_bool", args, 1)?;
+    let text = string_arg("to_bool", &args[0])?;
+    match text.trim() {
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        _ => Err(RuntimeErrorKind::InvalidArgument(format!(
+            "to_bool expected true or false, got '{}'",
+            text
+        ))),
+    }
+}
+
+// stops the program; exit codes are a single byte on most platforms
+fn exit(args: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    let [code] = numbers("exit", args)?;
+    let code = u8::try_from(code).map_err(|_| {
+        RuntimeErrorKind::InvalidArgument(format!(
+            "exit code must be between 0 and 255, got {}",
+            code
+        ))
+    })?;
+    Err(RuntimeErrorKind::Exit(code))
+}
+
+// paths are relative to the directory froggle runs in
+fn read_file(args: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    check_arity("read_file", args, 1)?;
+    let path = string_arg("read_file", &args[0])?;
+    fs::read_to_string(path).map(Value::String).map_err(|e| {
+        RuntimeErrorKind::InvalidArgument(format!("read_file failed for {}: {}", path, e))
+    })
+}
+
+// creates the file, or replaces its contents if it exists
+fn write_file(args: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    check_arity("write_file", args, 2)?;
+    let path = string_arg("write_file", &args[0])?;
+    let contents = string_arg("write_file", &args[1])?;
+    fs::write(path, contents).map(|_| Value::Void).map_err(|e| {
+        RuntimeErrorKind::InvalidArgument(format!("write_file failed for {}: {}", path, e))
+    })
+}
+
+fn arg_count(_: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    Ok(Value::Number(
+        SCRIPT_ARGS.with(|args| args.borrow().len()) as i64
+    ))
+}
+
+// froggle has no lists, so arguments are fetched one by one, counting from 0
+fn arg(args: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    let [index] = numbers("arg", args)?;
+    SCRIPT_ARGS.with(|script_args| {
+        let script_args = script_args.borrow();
+        usize::try_from(index)
+            .ok()
+            .and_then(|i| script_args.get(i))
+            .map(|arg| Value::String(arg.clone()))
+            .ok_or_else(|| {
+                RuntimeErrorKind::InvalidArgument(format!(
+                    "arg index {} is out of range for {} arguments",
+                    index,
+                    script_args.len()
+                ))
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, args: &[i64]) -> Result<Value, RuntimeErrorKind> {
+        let args: Vec<Value> = args.iter().map(|n| Value::Number(*n)).collect();
+        (lookup(name).unwrap().function)(&args)
+    }
+
+    #[test]
+    fn test_math_builtins() {
+        assert_eq!(call("abs", &[-7]), Ok(Value::Number(7)));
+        assert_eq!(call("min", &[3, -2]), Ok(Value::Number(-2)));
+        assert_eq!(call("max", &[3, -2]), Ok(Value::Number(3)));
+        assert_eq!(call("pow", &[2, 10]), Ok(Value::Number(1024)));
+        assert_eq!(call("sqrt_int", &[17]), Ok(Value::Number(4)));
+        assert!(call("sqrt_int", &[-1]).is_err());
+        assert_eq!(call("pow", &[2, 31]), Ok(Value::Number(2_147_483_648)));
+        assert_eq!(
+            call("pow", &[2, 63]),
+            Err(RuntimeErrorKind::Overflow("pow".to_string()))
+        );
+
+        for _ in 0..20 {
+            let Ok(Value::Number(n)) = call("random", &[1, 3]) else {
+                panic!("random should return a number");
+            };
+            assert!((1..=3).contains(&n));
+        }
+    }
+
+    #[test]
+    fn test_input_number_parsing() {
+        assert_eq!(number_from("input_number", " 42 "), Ok(42));
+        assert_eq!(number_from("input_number", "-7"), Ok(-7));
+        assert!(number_from("input_number", "ribbit").is_err());
+        assert!(number_from("input_number", "").is_err());
+    }
+
+    #[test]
+    fn test_conversion_builtins() {
+        let string = |s: &str| Value::String(s.to_string());
+        let call = |name: &str, args: &[Value]| (lookup(name).unwrap().function)(args);
+
+        assert_eq!(call("to_string", &[Value::Number(-12)]), Ok(string("-12")));
+        assert_eq!(
+            call("parse_number", &[string("-12")]),
+            Ok(Value::Number(-12))
+        );
+        assert_eq!(call("parse_number", &[string("12abc")]), Ok(Value::None));
+        assert_eq!(
+            call("to_bool", &[string(" false\n")]),
+            Ok(Value::Bool(false))
+        );
+        assert!(call("to_bool", &[string("yes")]).is_err());
+        assert_eq!(call("typeof", &[Value::Float(1.5)]), Ok(string("float")));
+        assert_eq!(
+            call("typeof", &[Value::Enum("Color".into(), "Red".into())]),
+            Ok(string("Color"))
+        );
+    }
+
+    #[test]
+    fn test_script_args() {
+        assert_eq!(call("arg_count", &[]), Ok(Value::Number(0)));
+        set_script_args(vec!["10".to_string(), "frog".to_string()]);
+        assert_eq!(call("arg_count", &[]), Ok(Value::Number(2)));
+        assert_eq!(call("arg", &[1]), Ok(Value::String("frog".to_string())));
+        assert!(call("arg", &[2]).is_err());
+        assert!(call("arg", &[-1]).is_err());
+    }
+
+    #[test]
+    fn test_exit() {
+        assert_eq!(call("exit", &[3]), Err(RuntimeErrorKind::Exit(3)));
+        assert!(matches!(
+            call("exit", &[256]),
+            Err(RuntimeErrorKind::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_string_builtins() {
+        let string = |s: &str| Value::String(s.to_string());
+        let call = |name: &str, args: &[Value]| (lookup(name).unwrap().function)(args);
+
+        assert_eq!(call("length", &[string("héllo")]), Ok(Value::Number(5)));
+        assert_eq!(
+            call(
+                "substring",
+                &[string("héllo"), Value::Number(1), Value::Number(3)]
+            ),
+            Ok(string("él"))
+        );
+        assert!(
+            call(
+                "substring",
+                &[string("frog"), Value::Number(2), Value::Number(5)]
+            )
+            .is_err()
+        );
+        assert!(
+            call(
+                "substring",
+                &[string("frog"), Value::Number(3), Value::Number(1)]
+            )
+            .is_err()
+        );
+        assert_eq!(call("to_upper", &[string("Frog")]), Ok(string("FROG")));
+        assert_eq!(call("to_lower", &[string("Frog")]), Ok(string("frog")));
+        assert_eq!(
+            call("contains", &[string("lily pad"), string("pad")]),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(
+            call("split", &[string("a,b,,c"), string(","), Value::Number(3)]),
+            Ok(string("c"))
+        );
+        assert_eq!(
+            call("split", &[string("a,b,,c"), string(","), Value::Number(2)]),
+            Ok(string(""))
+        );
+        assert!(call("split", &[string("a,b"), string(","), Value::Number(2)]).is_err());
+        assert!(call("split", &[string("a,b"), string(""), Value::Number(0)]).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use crate::error::Severity;
+
+// how diagnostics are worded. Every voice keeps the kind, code and location of a problem, and
+// the plain message; only the words around them change
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Voice {
+    #[default]
+    Plain,
+    // frog-themed, for `--ribbit`
+    Ribbit,
+}
+
+// what a frog says about a problem, by diagnostic code, then by phase for codes not listed
+const RIBBIT: &[(&str, &str)] = &[
+    (
+        "runtime/DivisionByZero",
+        "not even a frog can split flies zero ways",
+    ),
+    ("runtime/AssertionFailed", "the frog was sure of this one"),
+    ("runtime/Raised", "a frog raised its voice"),
+    (
+        "runtime/StepLimitExceeded",
+        "the frog hopped until its legs gave out",
+    ),
+    ("runtime/TimedOut", "the frog fell asleep on its lily pad"),
+    (
+        "runtime/CallDepthExceeded",
+        "the frog dove deeper than the pond goes",
+    ),
+    (
+        "type/UnknownVariable",
+        "no frog by that name lives in this pond",
+    ),
+    (
+        "type/UnknownFunction",
+        "no frog in this pond knows that trick",
+    ),
+    ("lint/UnusedVariable", "a lonely frog nobody visits"),
+    ("lint/UnusedFunction", "a trick no frog ever performs"),
+    ("lint/UnreachableCode", "no frog can hop this far"),
+    ("syntax", "the frog choked on a fly"),
+    ("import", "the frog couldn't hop over to that pond"),
+    ("type", "these lily pads don't fit together"),
+    ("runtime", "the frog slipped off its lily pad"),
+    ("lint", "something smells fishy in the pond"),
+];
+
+impl Voice {
+    pub fn severity(self, severity: Severity) -> &'static str {
+        match (self, severity) {
+            (Voice::Plain, Severity::Error) => "error",
+            (Voice::Plain, Severity::Warning) => "warning",
+            (Voice::Ribbit, Severity::Error) => "croak",
+            (Voice::Ribbit, Severity::Warning) => "ribbit",
+        }
+    }
+
+    // the message of a diagnostic with code `code`, worded in this voice
+    pub fn message(self, code: &str, message: &str) -> String {
+        let phase = code.split('/').next().unwrap_or_default();
+        let saying = RIBBIT
+            .iter()
+            .find(|(key, _)| *key == code)
+            .or_else(|| RIBBIT.iter().find(|(key, _)| *key == phase));
+        match (self, saying) {
+            (Voice::Ribbit, Some((_, saying))) => format!("{} ({})", saying, message),
+            _ => message.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ribbit_keeps_the_plain_message() {
+        let message = "division by zero";
+        assert_eq!(
+            Voice::Plain.message("runtime/DivisionByZero", message),
+            message
+        );
+        assert_eq!(
+            Voice::Ribbit.message("runtime/DivisionByZero", message),
+            "not even a frog can split flies zero ways (division by zero)"
+        );
+        // kinds without a saying of their own get their phase's
+        assert_eq!(
+            Voice::Ribbit.message("type/ArityMismatch", "expects 1 arguments"),
+            "these lily pads don't fit together (expects 1 arguments)"
+        );
+        assert_eq!(Voice::Ribbit.severity(Severity::Warning), "ribbit");
+    }
+}
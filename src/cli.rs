@@ -0,0 +1,415 @@
+use froggle::interpreter::Limits;
+use std::time::Duration;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+pub const USAGE: &str = "\
+Usage: froggle [command] [options] [file] [script arguments]
+
+Commands:
+  run <file>     run a program (the default when only a file is given); arguments after
+                 the file that aren't run options are passed to the program
+  repl           start the interactive REPL (the default without arguments);
+                 takes the limit options below, applied to every entry
+  check <file>   lex, parse, typecheck and lint a program without running it; with
+                 --error-format=json print the errors as a JSON array instead, with
+                 --deny-warnings fail on warnings too
+  tokens <file>  print the token stream of a program
+  ast <file>     print the syntax tree of a program
+  fmt <file>     format a program in place; with --check only report whether it is formatted
+  test <path>    run every *_test.frog file under a directory, or a single test file
+  bench <file>   run a program and report the wall time of each stage on stderr
+  debug <file>   run a program one statement at a time, with breakpoints; type help at
+                 the debugger's prompt for its commands
+  lsp            start a language server on stdin and stdout, for editors
+
+Options:
+  -h, --help     print this help
+  -V, --version  print the version
+  --no-color     print errors without ANSI colors, as does setting NO_COLOR; colors are
+                 only used when stderr is a terminal
+  --ribbit       word errors and warnings like a frog would
+
+Run options:
+  --dump-tokens      print the token stream instead of running the program
+  --dump-ast         print the syntax tree instead of running the program
+  --watch            run the program again, on a cleared screen, whenever the file changes
+  --trace            log every statement and expression as it runs, with its location,
+                     scope depth and value, to stderr
+  --trace-file <f>   write that log to the file f instead
+  --profile          count the calls to every function and the runs of every kind of
+                     statement, with the time they take, and print them on stderr at the end
+  --max-steps <n>    stop with an error after running n statements or loop iterations
+  --timeout-ms <ms>  stop with an error after running for ms milliseconds
+  --max-call-depth <n>
+                     stop with an error when calls nest more than n deep; 500 unless given,
+                     and at most 10000
+  --allow-fs         let the program read and write files with read_file and write_file";
+
+// deepest --max-call-depth, what the stack main runs the interpreter with can hold
+pub const MAX_CALL_DEPTH: usize = 10_000;
+
+#[derive(Debug, PartialEq, Default)]
+pub struct RunOptions {
+    pub path: String,
+    pub dump_tokens: bool,
+    pub dump_ast: bool,
+    // rerun whenever the file changes
+    pub watch: bool,
+    // where to log what the program runs
+    pub trace: Option<Trace>,
+    // time functions and statements, reporting at the end
+    pub profile: bool,
+    pub limits: Limits,
+    // arguments for the script, available to it through arg_count and arg
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Trace {
+    Stderr,
+    File(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Run(RunOptions),
+    Repl(Limits),
+    Check {
+        path: String,
+        json: bool,
+        // report lint warnings as errors, failing the check
+        deny_warnings: bool,
+    },
+    Tokens(String),
+    Ast(String),
+    Fmt {
+        path: String,
+        check: bool,
+    },
+    Test(String),
+    Bench(String),
+    Debug(String),
+    Lsp,
+    Version,
+    Help,
+}
+
+// removes a flag any command takes, like --no-color, from the arguments; whether it was there
+pub fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let count = args.len();
+    args.retain(|arg| arg != flag);
+    args.len() != count
+}
+
+// parses the arguments after the program name
+pub fn parse_args(args: &[String]) -> Result<Command, String> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let with_file = |command: &str, make: fn(String) -> Command| match &args[1..] {
+        [file] => Ok(make(file.to_string())),
+        [] => Err(format!("'{}' expects a file", command)),
+        [_, extra, ..] => Err(format!("unexpected argument '{}'", extra)),
+    };
+
+    match args.as_slice() {
+        [] => Ok(Command::Repl(Limits::default())),
+        ["repl", rest @ ..] => {
+            let mut limits = Limits::default();
+            let mut rest = rest.iter();
+            while let Some(arg) = rest.next() {
+                if !parse_limit(arg, &mut rest, &mut limits)? {
+                    return Err(format!("unexpected argument '{}'", arg));
+                }
+            }
+            Ok(Command::Repl(limits))
+        }
+        ["-h" | "--help" | "help", ..] => Ok(Command::Help),
+        ["-V" | "--version", ..] => Ok(Command::Version),
+        ["run", rest @ ..] => parse_run(rest).map(Command::Run),
+        ["check", rest @ ..] => {
+            let mut json = false;
+            let mut deny_warnings = false;
+            let mut files = Vec::new();
+            for arg in rest {
+                match *arg {
+                    "--error-format=json" => json = true,
+                    "--error-format=text" => json = false,
+                    "--deny-warnings" => deny_warnings = true,
+                    flag if flag.starts_with('-') => {
+                        return Err(format!("unknown option '{}'", flag));
+                    }
+                    file => files.push(file),
+                }
+            }
+            match files.as_slice() {
+                [file] => Ok(Command::Check {
+                    path: file.to_string(),
+                    json,
+                    deny_warnings,
+                }),
+                [] => Err("'check' expects a file".to_string()),
+                [_, extra, ..] => Err(format!("unexpected argument '{}'", extra)),
+            }
+        }
+        ["tokens", ..] => with_file("tokens", Command::Tokens),
+        ["ast", ..] => with_file("ast", Command::Ast),
+        ["test", ..] => with_file("test", Command::Test),
+        ["bench", ..] => with_file("bench", Command::Bench),
+        ["debug", ..] => with_file("debug", Command::Debug),
+        ["lsp"] => Ok(Command::Lsp),
+        ["fmt", rest @ ..] => {
+            let check = rest.contains(&"--check");
+            let files: Vec<&str> = rest.iter().copied().filter(|a| *a != "--check").collect();
+            match files.as_slice() {
+                [file] => Ok(Command::Fmt {
+                    path: file.to_string(),
+                    check,
+                }),
+                [] => Err("'fmt' expects a file".to_string()),
+                [_, extra, ..] => Err(format!("unexpected argument '{}'", extra)),
+            }
+        }
+        // without a subcommand the arguments are those of `run`
+        _ => parse_run(&args).map(Command::Run),
+    }
+}
+
+fn parse_run(args: &[&str]) -> Result<RunOptions, String> {
+    let mut options = RunOptions::default();
+    let mut path = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--dump-tokens" => options.dump_tokens = true,
+            "--dump-ast" => options.dump_ast = true,
+            "--watch" => options.watch = true,
+            "--trace" => options.trace = Some(Trace::Stderr),
+            "--trace-file" => {
+                let path = args.next().ok_or("'--trace-file' expects a file")?;
+                options.trace = Some(Trace::File(path.to_string()));
+            }
+            "--profile" => options.profile = true,
+            flag if parse_limit(flag, &mut args, &mut options.limits)? => {}
+            flag if flag.starts_with('-') && path.is_none() => {
+                return Err(format!("unknown option '{}'", flag));
+            }
+            file if path.is_none() => path = Some(file.to_string()),
+            arg => options.args.push(arg.to_string()),
+        }
+    }
+
+    if options.profile && options.trace.is_some() {
+        return Err("'--profile' and '--trace' can't be used together".to_string());
+    }
+    options.path = path.ok_or("'run' expects a file")?;
+    Ok(options)
+}
+
+// applies `flag` if it is a limit option, taking its value, if it has one, from `rest`
+fn parse_limit<'a>(
+    flag: &str,
+    rest: &mut impl Iterator<Item = &'a &'a str>,
+    limits: &mut Limits,
+) -> Result<bool, String> {
+    let mut value = || -> Result<u64, String> {
+        let value = rest
+            .next()
+            .ok_or_else(|| format!("'{}' expects a value", flag))?;
+        value
+            .parse()
+            .map_err(|_| format!("invalid value '{}' for '{}'", value, flag))
+    };
+
+    match flag {
+        "--max-steps" => limits.max_steps = Some(value()?),
+        "--timeout-ms" => limits.timeout = Some(Duration::from_millis(value()?)),
+        "--max-call-depth" => {
+            let depth = value()?;
+            if depth > MAX_CALL_DEPTH as u64 {
+                return Err(format!(
+                    "'{}' can be at most {}, deeper calls would overflow the stack",
+                    flag, MAX_CALL_DEPTH
+                ));
+            }
+            limits.max_call_depth = Some(depth as usize);
+        }
+        "--allow-fs" => limits.allow_fs = true,
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse_args(&args)
+    }
+
+    #[test]
+    fn test_subcommands() {
+        assert_eq!(parse(&[]), Ok(Command::Repl(Limits::default())));
+        assert_eq!(
+            parse(&["check", "a.frog"]),
+            Ok(Command::Check {
+                path: "a.frog".into(),
+                json: false,
+                deny_warnings: false
+            })
+        );
+        assert_eq!(
+            parse(&["check", "--error-format=json", "a.frog", "--deny-warnings"]),
+            Ok(Command::Check {
+                path: "a.frog".into(),
+                json: true,
+                deny_warnings: true
+            })
+        );
+        assert_eq!(parse(&["ast", "a.frog"]), Ok(Command::Ast("a.frog".into())));
+        assert_eq!(
+            parse(&["test", "tests/"]),
+            Ok(Command::Test("tests/".into()))
+        );
+        assert_eq!(
+            parse(&["bench", "fib.frog"]),
+            Ok(Command::Bench("fib.frog".into()))
+        );
+        assert_eq!(
+            parse(&["debug", "fib.frog"]),
+            Ok(Command::Debug("fib.frog".into()))
+        );
+        assert_eq!(parse(&["--version"]), Ok(Command::Version));
+        assert_eq!(
+            parse(&["fmt", "--check", "a.frog"]),
+            Ok(Command::Fmt {
+                path: "a.frog".into(),
+                check: true
+            })
+        );
+        // a bare file is run, as before subcommands existed
+        assert_eq!(
+            parse(&["a.frog"]),
+            Ok(Command::Run(RunOptions {
+                path: "a.frog".into(),
+                ..RunOptions::default()
+            }))
+        );
+    }
+
+    #[test]
+    fn test_run_dump_flags() {
+        let expected = Ok(Command::Run(RunOptions {
+            path: "a.frog".into(),
+            dump_tokens: true,
+            dump_ast: true,
+            ..RunOptions::default()
+        }));
+        assert_eq!(
+            parse(&["run", "--dump-tokens", "a.frog", "--dump-ast"]),
+            expected
+        );
+        assert_eq!(parse(&["--dump-ast", "--dump-tokens", "a.frog"]), expected);
+        assert_eq!(
+            parse(&["run", "a.frog", "--watch"]),
+            Ok(Command::Run(RunOptions {
+                path: "a.frog".into(),
+                watch: true,
+                ..RunOptions::default()
+            }))
+        );
+        assert_eq!(
+            parse(&["--trace-file", "trace.txt", "a.frog"]),
+            Ok(Command::Run(RunOptions {
+                path: "a.frog".into(),
+                trace: Some(Trace::File("trace.txt".into())),
+                ..RunOptions::default()
+            }))
+        );
+    }
+
+    #[test]
+    fn test_limit_options() {
+        let limits = Limits {
+            max_steps: Some(1000),
+            timeout: Some(Duration::from_millis(50)),
+            max_call_depth: Some(20),
+            allow_fs: false,
+        };
+        assert_eq!(
+            parse(&[
+                "a.frog",
+                "--max-steps",
+                "1000",
+                "--timeout-ms",
+                "50",
+                "--max-call-depth",
+                "20"
+            ]),
+            Ok(Command::Run(RunOptions {
+                path: "a.frog".into(),
+                limits,
+                ..RunOptions::default()
+            }))
+        );
+        assert_eq!(
+            parse(&[
+                "repl",
+                "--max-call-depth",
+                "20",
+                "--timeout-ms",
+                "50",
+                "--max-steps",
+                "1000"
+            ]),
+            Ok(Command::Repl(limits))
+        );
+        assert_eq!(
+            parse(&["repl", "--allow-fs"]),
+            Ok(Command::Repl(Limits {
+                allow_fs: true,
+                ..Limits::default()
+            }))
+        );
+        assert!(parse(&["a.frog", "--max-steps"]).is_err());
+        assert!(parse(&["a.frog", "--trace-file"]).is_err());
+        assert!(parse(&["a.frog", "--trace", "--profile"]).is_err());
+        assert!(parse(&["a.frog", "--timeout-ms", "soon"]).is_err());
+        assert!(parse(&["a.frog", "--max-call-depth", "10001"]).is_err());
+    }
+
+    #[test]
+    fn test_script_arguments() {
+        assert_eq!(
+            parse(&["run", "a.frog", "10", "--dump-ast", "-v"]),
+            Ok(Command::Run(RunOptions {
+                path: "a.frog".into(),
+                dump_ast: true,
+                args: vec!["10".into(), "-v".into()],
+                ..RunOptions::default()
+            }))
+        );
+    }
+
+    #[test]
+    fn test_global_flags_anywhere() {
+        let mut args: Vec<String> = ["check", "--no-color", "a.frog", "--ribbit"]
+            .map(String::from)
+            .into();
+        assert!(take_flag(&mut args, "--no-color"));
+        assert!(take_flag(&mut args, "--ribbit"));
+        assert_eq!(args, ["check", "a.frog"]);
+        assert!(!take_flag(&mut args, "--no-color"));
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        assert!(parse(&["check"]).is_err());
+        assert!(parse(&["bench", "a.frog", "b.frog"]).is_err());
+        assert!(parse(&["run", "--frog", "a.frog"]).is_err());
+        assert!(parse(&["--frog"]).is_err());
+    }
+}
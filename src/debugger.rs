@@ -0,0 +1,368 @@
+use froggle::interpreter::{Env, Hook, Interpreter, Value};
+use froggle::lexer::Span;
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const PROMPT: &str = "(debug) ";
+
+const HELP: &str = "\
+Commands:
+  step, s            run to the next statement, going into calls
+  next, n            run to the next statement, going over calls
+  continue, c        run to the next breakpoint
+  break, b <line>    stop whenever the program gets to the line; without a line, list
+                     the breakpoints
+  delete, d <line>   remove the breakpoint on the line
+  locals, l          print the local variables, innermost scope first
+  globals, g         print the global variables
+  print, p <name>    print a variable
+  quit, q            stop the program
+  help, h            print this help
+An empty line repeats the last command.";
+
+// when the debugger stops next
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    // at any statement
+    Step,
+    // at a statement no deeper in calls than this
+    Next(usize),
+    // at a breakpoint
+    Continue,
+}
+
+// the interpreter's hook for `froggle debug`: stops before statements, reads commands until
+// one of them lets the program go on
+struct Debugger {
+    source: Vec<String>,
+    breakpoints: BTreeSet<usize>,
+    mode: Mode,
+    // statement the debugger last stopped at; a breakpoint stops once when its line is
+    // reached, not at every statement on it, but again when a loop or call comes back to it
+    stopped: Option<Span>,
+    last_command: String,
+    // set to stop the program when quitting
+    interrupt: Arc<AtomicBool>,
+    commands: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+}
+
+// runs a program under the debugger, stopped before its first statement
+pub fn debug(path: &str) -> ExitCode {
+    let Some(source) = crate::read_source(path) else {
+        return ExitCode::FAILURE;
+    };
+    let mut interpreter = Interpreter::new();
+    let quit = Arc::new(AtomicBool::new(false));
+    interpreter.interrupt = Some(quit.clone());
+    interpreter.hook = Some(Box::new(Debugger::new(
+        &source,
+        quit.clone(),
+        Box::new(io::stdin().lock()),
+        Box::new(io::stdout()),
+    )));
+
+    let mut exit = None;
+    let exit_code = crate::with_program(path, &mut interpreter, |src_code, interpreter| {
+        match froggle::run(src_code, Path::new(path), interpreter) {
+            // the program was stopped by quitting, there is nothing to report
+            Err(_) if quit.load(Ordering::Relaxed) => Ok(()),
+            Err(e) if e.exit_code().is_some() => {
+                exit = e.exit_code();
+                Ok(())
+            }
+            result => result.map(|_| ()),
+        }
+    });
+    exit.map(ExitCode::from).unwrap_or(exit_code)
+}
+
+impl Debugger {
+    fn new(
+        source: &str,
+        interrupt: Arc<AtomicBool>,
+        commands: Box<dyn BufRead>,
+        output: Box<dyn Write>,
+    ) -> Debugger {
+        Debugger {
+            source: source.lines().map(str::to_string).collect(),
+            breakpoints: BTreeSet::new(),
+            mode: Mode::Step,
+            stopped: None,
+            last_command: "step".to_string(),
+            interrupt,
+            commands,
+            output,
+        }
+    }
+
+    fn stops(&self, span: Span, env: &Env) -> bool {
+        match self.mode {
+            Mode::Step => true,
+            Mode::Next(depth) => env.depth() <= depth,
+            Mode::Continue => {
+                // later statements on the line, or nested in the one stopped at, are part of
+                // the same visit
+                let same_visit = self
+                    .stopped
+                    .is_some_and(|stopped| stopped.line == span.line && span.start > stopped.start);
+                !same_visit && self.breakpoints.contains(&span.line)
+            }
+        }
+    }
+
+    // reads and runs commands until one of them resumes the program
+    fn prompt(&mut self, env: &Env) -> io::Result<()> {
+        loop {
+            write!(self.output, "{}", PROMPT)?;
+            self.output.flush()?;
+            let mut line = String::new();
+            if self.commands.read_line(&mut line)? == 0 {
+                // out of commands, as when stdin is closed
+                return self.quit();
+            }
+            let line = match line.trim() {
+                "" => self.last_command.clone(),
+                command => command.to_string(),
+            };
+            self.last_command = line.clone();
+            if self.command(&line, env)? {
+                return Ok(());
+            }
+        }
+    }
+
+    // runs one command, returning whether the program goes on
+    fn command(&mut self, line: &str, env: &Env) -> io::Result<bool> {
+        let (command, argument) = line
+            .split_once(char::is_whitespace)
+            .map(|(command, argument)| (command, argument.trim()))
+            .unwrap_or((line, ""));
+
+        match command {
+            "step" | "s" => self.mode = Mode::Step,
+            "next" | "n" => self.mode = Mode::Next(env.depth()),
+            "continue" | "c" => self.mode = Mode::Continue,
+            "quit" | "q" => self.quit()?,
+            "break" | "b" if argument.is_empty() => {
+                if self.breakpoints.is_empty() {
+                    writeln!(self.output, "no breakpoints")?;
+                }
+                for line in &self.breakpoints {
+                    writeln!(self.output, "breakpoint on line {}", line)?;
+                }
+                return Ok(false);
+            }
+            "break" | "b" => {
+                if let Some(line) = self.line_argument(argument)? {
+                    self.breakpoints.insert(line);
+                    writeln!(self.output, "breakpoint on line {}", line)?;
+                }
+                return Ok(false);
+            }
+            "delete" | "d" => {
+                if let Some(line) = self.line_argument(argument)? {
+                    if self.breakpoints.remove(&line) {
+                        writeln!(self.output, "removed the breakpoint on line {}", line)?;
+                    } else {
+                        writeln!(self.output, "no breakpoint on line {}", line)?;
+                    }
+                }
+                return Ok(false);
+            }
+            "locals" | "l" => {
+                let scopes = env.locals();
+                if scopes.iter().all(Vec::is_empty) {
+                    writeln!(self.output, "no local variables")?;
+                }
+                for (depth, scope) in scopes.iter().enumerate() {
+                    for (name, value) in scope {
+                        writeln!(self.output, "[{}] {} = {}", depth, name, show(value))?;
+                    }
+                }
+                return Ok(false);
+            }
+            "globals" | "g" => {
+                for (name, value) in env.globals() {
+                    writeln!(self.output, "{} = {}", name, show(&value))?;
+                }
+                return Ok(false);
+            }
+            "print" | "p" => {
+                // the innermost variable with the name, as the program would see it
+                let value = env
+                    .locals()
+                    .into_iter()
+                    .flatten()
+                    .chain(env.globals())
+                    .find(|(name, _)| name == argument);
+                match value {
+                    Some((name, value)) => writeln!(self.output, "{} = {}", name, show(&value))?,
+                    None => writeln!(self.output, "no variable called {}", argument)?,
+                }
+                return Ok(false);
+            }
+            "help" | "h" => {
+                writeln!(self.output, "{}", HELP)?;
+                return Ok(false);
+            }
+            _ => {
+                writeln!(self.output, "unknown command {}, see help", command)?;
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // the line a breakpoint command is about, if the program has it
+    fn line_argument(&mut self, argument: &str) -> io::Result<Option<usize>> {
+        match argument.parse() {
+            Ok(line) if (1..=self.source.len()).contains(&line) => Ok(Some(line)),
+            _ => {
+                writeln!(self.output, "no line {} in the program", argument)?;
+                Ok(None)
+            }
+        }
+    }
+
+    // lets the program run to its end, stopping it at the next statement
+    fn quit(&mut self) -> io::Result<()> {
+        self.interrupt.store(true, Ordering::Relaxed);
+        self.breakpoints.clear();
+        self.mode = Mode::Continue;
+        Ok(())
+    }
+}
+
+impl Hook for Debugger {
+    fn on_statement(&mut self, span: Span, env: &Env) {
+        if self.stops(span, env) {
+            self.stopped = Some(span);
+            let source = self
+                .source
+                .get(span.line - 1)
+                .map_or("", |line| line.trim());
+            let stopped = writeln!(self.output, "line {}: {}", span.line, source)
+                .and_then(|()| self.prompt(env));
+            // without a terminal to talk to there is no one to debug for
+            if stopped.is_err() {
+                let _ = self.quit();
+            }
+        }
+    }
+}
+
+// strings are quoted, so their spaces and emptiness show
+pub fn show(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("{:?}", s),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // what the debugger writes, kept after the interpreter is done with it
+    #[derive(Clone, Default)]
+    struct Transcript(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Transcript {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn debug(source: &str, commands: &str) -> String {
+        let transcript = Transcript::default();
+        let mut interpreter = Interpreter::new();
+        interpreter.capture_output();
+        interpreter.hook = Some(Box::new(Debugger::new(
+            source,
+            Arc::new(AtomicBool::new(false)),
+            Box::new(io::Cursor::new(commands.to_string())),
+            Box::new(transcript.clone()),
+        )));
+        froggle::run(source, Path::new("test.frog"), &mut interpreter).unwrap();
+        let output = transcript.0.borrow();
+        String::from_utf8_lossy(&output).replace(PROMPT, "> ")
+    }
+
+    const PROGRAM: &str = "\
+func double(n: number): number {
+    let twice = n * 2;
+    return twice;
+}
+let a = double(1);
+let b = double(a);
+croak b;";
+
+    #[test]
+    fn test_step_and_next() {
+        assert_eq!(
+            debug(PROGRAM, "s\n\nl\nn\nn\np a\nc\n"),
+            "\
+line 1: func double(n: number): number {
+> line 5: let a = double(1);
+> line 2: let twice = n * 2;
+> [0] n = 1
+> line 3: return twice;
+> line 6: let b = double(a);
+> a = 2
+> "
+        );
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        assert_eq!(
+            debug(PROGRAM, "b 3\nb 9\nc\np n\nd 3\nc\n"),
+            "\
+line 1: func double(n: number): number {
+> breakpoint on line 3
+> no line 9 in the program
+> line 3: return twice;
+> n = 1
+> removed the breakpoint on line 3
+> "
+        );
+    }
+
+    #[test]
+    fn test_breakpoints_in_loops_stop_every_iteration() {
+        let source = "let i = 0;\nwhile i < 3 {\n    i = i + 1;\n}\ncroak i;";
+        assert_eq!(
+            debug(source, "b 3\nc\np i\nc\np i\nc\np i\nc\n"),
+            "\
+line 1: let i = 0;
+> breakpoint on line 3
+> line 3: i = i + 1;
+> i = 0
+> line 3: i = i + 1;
+> i = 1
+> line 3: i = i + 1;
+> i = 2
+> "
+        );
+        // a loop written on one line stops once
+        assert_eq!(
+            debug("let i = 0;\nwhile i < 3 { i = i + 1; }", "b 2\nc\nc\n"),
+            "\
+line 1: let i = 0;
+> breakpoint on line 2
+> line 2: while i < 3 { i = i + 1; }
+> "
+        );
+    }
+}
@@ -0,0 +1,457 @@
+use crate::catalog::Voice;
+use crate::interpreter::{Frame, RuntimeError, RuntimeErrorKind};
+use crate::json::Json;
+use crate::lexer::{LexError, Span};
+use crate::module::ImportError;
+use crate::parser::{ParseError, ParseErrorKind};
+use crate::typechecker::TypeError;
+use std::fmt;
+
+// error from any phase of the pipeline, so callers can use `?` end to end
+#[derive(Debug, PartialEq, Clone)]
+pub enum Error {
+    Lex(LexError),
+    // the parser goes on past a missing semicolon, so it may find several, never an empty list
+    Parse(Vec<ParseError>),
+    // the typechecker reports every error in a program at once, never an empty list
+    Type(Vec<TypeError>),
+    Import(ImportError),
+    Runtime(RuntimeError),
+}
+
+impl Error {
+    pub fn span(&self) -> Span {
+        match self {
+            Error::Lex(e) => e.span,
+            Error::Parse(errors) => errors[0].span,
+            Error::Type(errors) => errors[0].span,
+            Error::Import(e) => e.span,
+            Error::Runtime(e) => e.span,
+        }
+    }
+
+    // every problem in the error, one per type error
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match self {
+            Error::Lex(e) => vec![Diagnostic::new("syntax", &e.kind, e.span)],
+            Error::Parse(errors) => errors
+                .iter()
+                .map(|e| match &e.kind {
+                    ParseErrorKind::Lex(kind) => Diagnostic::new("syntax", kind, e.span),
+                    kind => Diagnostic::new("syntax", kind, e.span),
+                })
+                .collect(),
+            Error::Type(errors) => errors
+                .iter()
+                .map(|e| Diagnostic::new("type", &e.kind, e.span))
+                .collect(),
+            Error::Import(e) => vec![Diagnostic::new("import", &e.kind, e.span)],
+            Error::Runtime(e) => {
+                let mut diagnostic = Diagnostic::new("runtime", &e.kind, e.span);
+                diagnostic.notes = trace_notes(&e.trace);
+                vec![diagnostic]
+            }
+        }
+    }
+
+    // the code a script asked to exit with by calling exit(), which ends it like an error
+    pub fn exit_code(&self) -> Option<u8> {
+        match self {
+            Error::Runtime(RuntimeError {
+                kind: RuntimeErrorKind::Exit(code),
+                ..
+            }) => Some(*code),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    // reported, but doesn't stop the program from running
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+// one problem, in a shape that can be serialized for editors and CI tooling
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    // phase and kind of the problem, e.g. "type/UnknownVariable"
+    pub code: String,
+    pub message: String,
+    pub span: Span,
+    // more about the problem, shown below it, like the calls a runtime error happened in
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    fn new(phase: &str, kind: &(impl fmt::Debug + fmt::Display), span: Span) -> Diagnostic {
+        Diagnostic::with_severity(Severity::Error, phase, kind, span)
+    }
+
+    pub(crate) fn warning(
+        phase: &str,
+        kind: &(impl fmt::Debug + fmt::Display),
+        span: Span,
+    ) -> Diagnostic {
+        Diagnostic::with_severity(Severity::Warning, phase, kind, span)
+    }
+
+    fn with_severity(
+        severity: Severity,
+        phase: &str,
+        kind: &(impl fmt::Debug + fmt::Display),
+        span: Span,
+    ) -> Diagnostic {
+        // the variant name of the kind is stable and already unique within its phase
+        let debug = format!("{:?}", kind);
+        let variant = debug
+            .split(|c: char| !c.is_alphanumeric())
+            .next()
+            .unwrap_or_default();
+        Diagnostic {
+            severity,
+            code: format!("{}/{}", phase, variant),
+            message: kind.to_string(),
+            span,
+            notes: Vec::new(),
+        }
+    }
+
+    // a JSON object on a single line
+    pub fn to_json(&self, path: &str) -> String {
+        format!(
+            "{{\"severity\":\"{}\",\"code\":{},\"message\":{},\"file\":{},\
+             \"span\":{{\"line\":{},\"column\":{},\"start\":{},\"end\":{}}}}}",
+            self.severity,
+            Json::from(self.code.as_str()),
+            Json::from(self.message.as_str()),
+            Json::from(path),
+            self.span.line,
+            self.span.column,
+            self.span.start,
+            self.span.end
+        )
+    }
+}
+
+// ANSI escape codes used by `Diagnostic::render`
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+
+// how `Diagnostic::render` shows a problem
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Style {
+    // ANSI colors, for terminals
+    pub color: bool,
+    pub voice: Voice,
+}
+
+impl Diagnostic {
+    // the problem the way rustc shows it: the message, where it is, then the source line with
+    // carets under the span. `source` is the file the span points into
+    pub fn render(&self, path: &str, source: &str, style: Style) -> String {
+        let paint = |code: &'static str| if style.color { code } else { "" };
+        let (highlight, blue, bold, reset) = (
+            paint(match self.severity {
+                Severity::Error => RED,
+                Severity::Warning => YELLOW,
+            }),
+            paint(BLUE),
+            paint(BOLD),
+            paint(RESET),
+        );
+
+        let mut rendered = format!(
+            "{highlight}{}[{}]{reset}{bold}: {}{reset}",
+            style.voice.severity(self.severity),
+            self.code,
+            style.voice.message(&self.code, &self.message)
+        );
+        let Some((line, prefix, length)) = snippet(source, self.span) else {
+            rendered.push_str(&format!("\n{blue}-->{reset} {}:{}", path, self.span));
+            for note in &self.notes {
+                rendered.push_str(&format!("\n{blue}={reset} note: {}", note));
+            }
+            return rendered;
+        };
+
+        let number = self.span.line.to_string();
+        let gutter = " ".repeat(number.len());
+        // tabs are kept so the carets line up with the text above them
+        let indent: String = prefix
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        rendered.push_str(&format!(
+            "\n{gutter}{blue}-->{reset} {path}:{span}\n\
+             {gutter} {blue}|{reset}\n\
+             {blue}{number} |{reset} {line}\n\
+             {gutter} {blue}|{reset} {indent}{highlight}{carets}{reset}",
+            span = self.span,
+            carets = "^".repeat(length),
+        ));
+        for note in &self.notes {
+            rendered.push_str(&format!("\n{gutter} {blue}={reset} note: {}", note));
+        }
+        rendered
+    }
+}
+
+// calls shown for a runtime error at most; deep recursion is cut short
+const MAX_TRACE_NOTES: usize = 10;
+
+// one note per call, innermost first, with a run of the same call, as in recursion, shown once
+fn trace_notes(trace: &[Frame]) -> Vec<String> {
+    let mut notes = Vec::new();
+    let mut frames = trace.iter().peekable();
+    let mut shown = 0;
+    while let Some(frame) = frames.next() {
+        let mut repeats = 1;
+        while frames.next_if_eq(&frame).is_some() {
+            repeats += 1;
+        }
+        if notes.len() == MAX_TRACE_NOTES {
+            notes.push(format!("and {} more calls", trace.len() - shown));
+            break;
+        }
+        let mut note = format!("in {}, called at {}", frame.function, frame.call);
+        if repeats > 1 {
+            note.push_str(&format!(" ({} times)", repeats));
+        }
+        notes.push(note);
+        shown += repeats;
+    }
+    notes
+}
+
+// the source line a span starts on, the part of it before the span and how many characters of
+// the span are on it (at least one, so errors at the end of a line still get a caret); None if
+// the span isn't in `source`
+fn snippet(source: &str, span: Span) -> Option<(&str, &str, usize)> {
+    if !source.is_char_boundary(span.start) {
+        return None;
+    }
+    let line_start = source[..span.start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[span.start..]
+        .find('\n')
+        .map_or(source.len(), |i| span.start + i);
+    let line = source[line_start..line_end].trim_end_matches('\r');
+    let end = span.end.clamp(span.start, line_start + line.len());
+    let length = source.get(span.start..end)?.chars().count();
+    Some((line, &source[line_start..span.start], length.max(1)))
+}
+
+// `1:5: warning: variable x is never read`
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.span, self.severity, self.message)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.span())?;
+        match self {
+            Error::Lex(e) => write!(f, "syntax error: {}", e.kind),
+            Error::Parse(errors) => {
+                for (i, e) in errors.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "\n{}: ", e.span)?;
+                    }
+                    write!(f, "syntax error: {}", e.kind)?;
+                }
+                Ok(())
+            }
+            // one error per line, each with its own location
+            Error::Type(errors) => {
+                for (i, e) in errors.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "\n{}: ", e.span)?;
+                    }
+                    write!(f, "type error: {}", e.kind)?;
+                }
+                Ok(())
+            }
+            Error::Import(e) => write!(f, "import error: {}", e.kind),
+            Error::Runtime(e) => write!(f, "runtime error: {}", e.kind),
+        }
+    }
+}
+
+impl From<LexError> for Error {
+    fn from(e: LexError) -> Self {
+        Error::Lex(e)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        match e.kind {
+            // met by a parser pulling tokens from the lexer as it goes
+            ParseErrorKind::Lex(kind) => Error::Lex(LexError { kind, span: e.span }),
+            _ => Error::Parse(vec![e]),
+        }
+    }
+}
+
+impl From<Vec<ParseError>> for Error {
+    fn from(mut errors: Vec<ParseError>) -> Self {
+        if errors.len() == 1 {
+            return errors.remove(0).into();
+        }
+        Error::Parse(errors)
+    }
+}
+
+impl From<TypeError> for Error {
+    fn from(e: TypeError) -> Self {
+        Error::Type(vec![e])
+    }
+}
+
+impl From<Vec<TypeError>> for Error {
+    fn from(errors: Vec<TypeError>) -> Self {
+        Error::Type(errors)
+    }
+}
+
+impl From<ImportError> for Error {
+    fn from(e: ImportError) -> Self {
+        Error::Import(e)
+    }
+}
+
+impl From<RuntimeError> for Error {
+    fn from(e: RuntimeError) -> Self {
+        Error::Runtime(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typechecker::TypeChecker;
+
+    #[test]
+    fn test_type_errors_as_json_diagnostics() {
+        let tokens = crate::lexer::Lexer::new("let x: string = 1;\nghost = \"a\";")
+            .parse()
+            .unwrap();
+        let ast = crate::parser::Parser::new(tokens).parse().unwrap();
+        let error = Error::from(TypeChecker::new().check(&ast).unwrap_err());
+
+        let diagnostics = error.diagnostics();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].code, "type/DeclarationMismatch");
+        assert_eq!(
+            diagnostics[1].to_json("a.frog"),
+            "{\"severity\":\"error\",\"code\":\"type/UnknownVariable\",\
+             \"message\":\"no variable ghost in existing scopes\",\"file\":\"a.frog\",\
+             \"span\":{\"line\":2,\"column\":1,\"start\":19,\"end\":31}}"
+        );
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "1:17: error: type mismatch in declaration of x: expected string, got number"
+        );
+        // escaped the way the language server's messages are
+        assert!(
+            diagnostics[0]
+                .to_json("say \"hi\"\\\t.frog")
+                .contains("\"file\":\"say \\\"hi\\\"\\\\\\t.frog\"")
+        );
+    }
+
+    #[test]
+    fn test_trace_notes_fold_recursion() {
+        let frame = |function: &str, line| Frame {
+            function: function.into(),
+            call: Span {
+                start: 0,
+                end: 0,
+                line,
+                column: 1,
+            },
+        };
+        let mut trace = vec![frame("down", 3); 40];
+        trace.push(frame("down", 6));
+        trace.push(frame("main", 8));
+        assert_eq!(
+            trace_notes(&trace),
+            [
+                "in down, called at 3:1 (40 times)",
+                "in down, called at 6:1",
+                "in main, called at 8:1"
+            ]
+        );
+
+        let trace: Vec<Frame> = (1..=15).map(|line| frame("f", line)).collect();
+        let notes = trace_notes(&trace);
+        assert_eq!(notes.len(), MAX_TRACE_NOTES + 1);
+        assert_eq!(notes.last().unwrap(), "and 5 more calls");
+    }
+
+    #[test]
+    fn test_render_with_source_line() {
+        let source = "let a = 1;\n\tlet x: string = a + 2;\n";
+        let diagnostic = Diagnostic::new(
+            "type",
+            &crate::typechecker::TypeErrorKind::UnknownVariable("a".to_string()),
+            Span {
+                start: 28,
+                end: 33,
+                line: 2,
+                column: 17,
+            },
+        );
+        assert_eq!(
+            diagnostic.render("a.frog", source, Style::default()),
+            "error[type/UnknownVariable]: no variable a in existing scopes\n \
+             --> a.frog:2:17\n  \
+             |\n\
+             2 | \tlet x: string = a + 2;\n  \
+             | \t                ^^^^^"
+        );
+        assert_eq!(
+            diagnostic
+                .render(
+                    "a.frog",
+                    source,
+                    Style {
+                        color: true,
+                        ..Style::default()
+                    }
+                )
+                .lines()
+                .last(),
+            Some("  \x1b[1;34m|\x1b[0m \t                \x1b[1;31m^^^^^\x1b[0m")
+        );
+        let mut traced = diagnostic.clone();
+        traced.notes = vec!["in f, called at 3:1".to_string()];
+        assert_eq!(
+            traced
+                .render("a.frog", source, Style::default())
+                .lines()
+                .last(),
+            Some("  = note: in f, called at 3:1")
+        );
+        // a span from another file only gets its location
+        assert_eq!(
+            diagnostic.render("a.frog", "", Style::default()),
+            "error[type/UnknownVariable]: no variable a in existing scopes\n--> a.frog:2:17"
+        );
+    }
+}
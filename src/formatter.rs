@@ -0,0 +1,587 @@
+use crate::lexer::{Operator, Span, word_operator};
+use crate::parser::{
+    ASTVisitor, BinOp, Expression, ExpressionKind, MatchArm, Pattern, Spelling, Statement,
+    StatementKind, Type, UnaryOp,
+};
+use crate::symbol::Symbol;
+
+const INDENT: &str = "    ";
+
+// turns an AST back into froggle source in a canonical layout: one statement per line,
+// four space indentation and only the parentheses the precedence rules require
+#[derive(Default)]
+pub struct Formatter {
+    output: String,
+    depth: usize,
+}
+
+impl Formatter {
+    pub fn new() -> Formatter {
+        Formatter {
+            output: String::new(),
+            depth: 0,
+        }
+    }
+
+    pub fn format(program: &[Statement]) -> String {
+        let mut formatter = Formatter::new();
+        formatter.format_statements(program);
+        formatter.output
+    }
+
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.depth {
+            self.output.push_str(INDENT);
+        }
+        self.output.push_str(text);
+        self.output.push('\n');
+    }
+
+    // function declarations get a blank line on either side to set them apart
+    fn format_statements(&mut self, statements: &[Statement]) {
+        for (i, statement) in statements.iter().enumerate() {
+            let is_function =
+                |s: &Statement| matches!(s.kind, StatementKind::FunctionDeclaration { .. });
+            if i > 0 && (is_function(statement) || is_function(&statements[i - 1])) {
+                self.output.push('\n');
+            }
+            statement.accept(self);
+        }
+    }
+
+    fn declaration(
+        &mut self,
+        keyword: &str,
+        name: Symbol,
+        expr: &Expression,
+        declared_type: Option<&Type>,
+    ) {
+        let annotation = declared_type
+            .map(|t| format!(": {}", t))
+            .unwrap_or_default();
+        self.line(&format!(
+            "{} {}{} = {};",
+            keyword,
+            name,
+            annotation,
+            format_expression(expr)
+        ));
+    }
+
+    // writes `header {`, the indented body and the closing brace
+    fn block(&mut self, header: &str, body: &[Statement]) {
+        self.line(&format!("{}{{", header));
+        self.depth += 1;
+        self.format_statements(body);
+        self.depth -= 1;
+        self.line("}");
+    }
+}
+
+// binding strength of binary operators, higher binds tighter
+fn precedence(operator: BinOp) -> u8 {
+    match operator {
+        BinOp::Or => 1,
+        BinOp::And => 2,
+        BinOp::Eq | BinOp::Ne | BinOp::Gt | BinOp::Lt | BinOp::Ge | BinOp::Le => 3,
+        BinOp::Add | BinOp::Sub => 5,
+        BinOp::Mul | BinOp::Div | BinOp::Rem => 6,
+    }
+}
+
+// `..` binds looser than arithmetic and tighter than comparisons
+const RANGE_PRECEDENCE: u8 = 4;
+const UNARY_PRECEDENCE: u8 = 7;
+
+fn expression_precedence(expr: &Expression) -> u8 {
+    match &expr.kind {
+        ExpressionKind::BinaryOperation { operator, .. } => precedence(*operator),
+        ExpressionKind::UnaryOperation { .. } => UNARY_PRECEDENCE,
+        ExpressionKind::Range { .. } => RANGE_PRECEDENCE,
+        _ => u8::MAX,
+    }
+}
+
+// `expr`, parenthesized when it binds looser than `min_precedence`
+fn operand(expr: &Expression, min_precedence: u8) -> String {
+    let formatted = format_expression(expr);
+    if expression_precedence(expr) < min_precedence {
+        format!("({})", formatted)
+    } else {
+        formatted
+    }
+}
+
+// `<T, U>` for a generic function, nothing for any other
+pub fn type_params_list(type_params: &[Symbol]) -> String {
+    if type_params.is_empty() {
+        return String::new();
+    }
+    let names: Vec<&str> = type_params.iter().map(|name| name.as_str()).collect();
+    format!("<{}>", names.join(", "))
+}
+
+// a string as a literal that lexes back to it, with the characters that need it escaped
+pub fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            '\0' => quoted.push_str("\\0"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+pub fn format_expression(expr: &Expression) -> String {
+    match &expr.kind {
+        // literals are written as they were, `0xFF` staying `0xFF`
+        ExpressionKind::Number(_, literal)
+        | ExpressionKind::Float(_, literal)
+        | ExpressionKind::String(_, literal) => literal.clone(),
+        ExpressionKind::Bool(b) => b.to_string(),
+        ExpressionKind::None => "none".to_string(),
+        ExpressionKind::Variable(name) => name.to_string(),
+        // operators are left associative, so a right operand of equal precedence keeps its parentheses
+        ExpressionKind::BinaryOperation {
+            left,
+            operator,
+            right,
+            spelling,
+        } => {
+            let precedence = precedence(*operator);
+            format!(
+                "{} {} {}",
+                operand(left, precedence),
+                written(Operator::Binary(*operator), *spelling),
+                operand(right, precedence + 1)
+            )
+        }
+        ExpressionKind::UnaryOperation {
+            operator,
+            operand: inner,
+            spelling,
+        } => {
+            // spelled out, `not` needs a space before its operand
+            let operator = match (operator, spelling) {
+                (UnaryOp::Not, Spelling::Word) => format!("{} ", written(Operator::Not, *spelling)),
+                _ => operator.to_string(),
+            };
+            let inner = operand(inner, UNARY_PRECEDENCE);
+            // a negated negation keeps its parentheses, as `--x` reads as a decrement
+            if inner.starts_with('-') {
+                return format!("{}({})", operator, inner);
+            }
+            format!("{}{}", operator, inner)
+        }
+        ExpressionKind::FunctionCall { name, arguments } => {
+            let arguments: Vec<String> = arguments.iter().map(format_expression).collect();
+            format!("{}({})", name, arguments.join(", "))
+        }
+        ExpressionKind::EnumVariant { enum_name, variant } => format!("{}.{}", enum_name, variant),
+        // ranges don't chain, so both bounds of one inside another keep their parentheses
+        ExpressionKind::Range { start, end } => format!(
+            "{}..{}",
+            operand(start, RANGE_PRECEDENCE + 1),
+            operand(end, RANGE_PRECEDENCE + 1)
+        ),
+        // written on one line, whatever the statements in it
+        ExpressionKind::Block { statements, value } => {
+            let mut formatter = Formatter::new();
+            formatter.format_statements(statements);
+            let mut parts: Vec<&str> = formatter
+                .output
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .collect();
+            let value = format_expression(value);
+            parts.push(&value);
+            format!("{{ {} }}", parts.join(" "))
+        }
+    }
+}
+
+// an operator the way it was written, spelled out as `and`, `or` or `not` if it was
+fn written(operator: Operator, spelling: Spelling) -> String {
+    match (spelling, word_operator(operator)) {
+        (Spelling::Word, Some(word)) => word.to_string(),
+        _ => operator.to_string(),
+    }
+}
+
+pub fn format_pattern(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Value(value) => format_expression(value),
+        Pattern::Wildcard => "_".to_string(),
+    }
+}
+
+impl ASTVisitor for Formatter {
+    type Output = ();
+
+    fn visit_declaration(
+        &mut self,
+        name: Symbol,
+        expr: &Expression,
+        declared_type: Option<&Type>,
+        _: Span,
+    ) {
+        self.declaration("let", name, expr, declared_type);
+    }
+
+    fn visit_const(
+        &mut self,
+        name: Symbol,
+        expr: &Expression,
+        declared_type: Option<&Type>,
+        _: Span,
+    ) {
+        self.declaration("const", name, expr, declared_type);
+    }
+
+    fn visit_assignment(&mut self, name: Symbol, expr: &Expression, _: Span) {
+        match &expr.kind {
+            // `i++;` and `i--;` rather than the assignments they stand for
+            ExpressionKind::BinaryOperation {
+                operator,
+                spelling: Spelling::Step,
+                ..
+            } => self.line(&format!("{}{}{};", name, operator, operator)),
+            _ => self.line(&format!("{} = {};", name, format_expression(expr))),
+        }
+    }
+
+    fn visit_print(&mut self, expr: &Expression, debug: bool, _: Span) {
+        let keyword = if debug { "croak_debug" } else { "croak" };
+        self.line(&format!("{} {};", keyword, format_expression(expr)));
+    }
+
+    fn visit_while(&mut self, condition: &Expression, body: &[Statement], _: Span) {
+        self.block(&format!("while {} ", format_expression(condition)), body);
+    }
+
+    fn visit_do_while(&mut self, body: &[Statement], condition: &Expression, _: Span) {
+        self.block("do ", body);
+        // the condition goes behind the closing brace
+        self.output.truncate(self.output.len() - 1);
+        self.output
+            .push_str(&format!(" while {};\n", format_expression(condition)));
+    }
+
+    fn visit_for(
+        &mut self,
+        variable: Symbol,
+        annotation: Option<&Type>,
+        start: &Expression,
+        end: &Expression,
+        body: &[Statement],
+        _: Span,
+    ) {
+        let annotation = annotation.map(|t| format!(": {}", t)).unwrap_or_default();
+        let header = format!(
+            "for {}{} = {} to {} ",
+            variable,
+            annotation,
+            format_expression(start),
+            format_expression(end)
+        );
+        self.block(&header, body);
+    }
+
+    fn visit_for_in(
+        &mut self,
+        variable: Symbol,
+        annotation: Option<&Type>,
+        iterable: &Expression,
+        body: &[Statement],
+        _: Span,
+    ) {
+        let annotation = annotation.map(|t| format!(": {}", t)).unwrap_or_default();
+        let header = format!(
+            "for {}{} in {} ",
+            variable,
+            annotation,
+            format_expression(iterable)
+        );
+        self.block(&header, body);
+    }
+
+    fn visit_block(&mut self, statements: &[Statement], _: Span) {
+        self.block("", statements);
+    }
+
+    fn visit_function_declaration(
+        &mut self,
+        name: Symbol,
+        type_params: &[Symbol],
+        params: &[(Symbol, Type)],
+        return_type: &Type,
+        body: &[Statement],
+        _: Span,
+    ) {
+        let params: Vec<String> = params
+            .iter()
+            .map(|(name, t)| format!("{}: {}", name, t))
+            .collect();
+        let return_type = match return_type {
+            Type::Void => String::new(),
+            t => format!(": {}", t),
+        };
+        self.block(
+            &format!(
+                "func {}{}({}){} ",
+                name,
+                type_params_list(type_params),
+                params.join(", "),
+                return_type
+            ),
+            body,
+        );
+    }
+
+    fn visit_if(
+        &mut self,
+        condition: &Expression,
+        body: &[Statement],
+        else_branch: Option<&[Statement]>,
+        _: Span,
+    ) {
+        self.block(&format!("if {} ", format_expression(condition)), body);
+        if let Some(else_branch) = else_branch {
+            // glue `else {` onto the closing brace of the then block
+            self.output.truncate(self.output.len() - 1);
+            self.output.push_str(" else {\n");
+            self.depth += 1;
+            self.format_statements(else_branch);
+            self.depth -= 1;
+            self.line("}");
+        }
+    }
+
+    fn visit_match(&mut self, subject: &Expression, arms: &[MatchArm], _: Span) {
+        self.line(&format!("match {} {{", format_expression(subject)));
+        self.depth += 1;
+        for arm in arms {
+            self.block(&format!("{} => ", format_pattern(&arm.pattern)), &arm.body);
+        }
+        self.depth -= 1;
+        self.line("}");
+    }
+
+    fn visit_enum(&mut self, name: Symbol, variants: &[Symbol], _: Span) {
+        let variants: Vec<&str> = variants.iter().map(|variant| variant.as_str()).collect();
+        self.line(&format!("enum {} {{ {} }}", name, variants.join(", ")));
+    }
+
+    fn visit_expression(&mut self, expr: &Expression, _: Span) {
+        self.line(&format!("{};", format_expression(expr)));
+    }
+
+    fn visit_assert(&mut self, condition: &Expression, _: Span) {
+        self.line(&format!("assert {};", format_expression(condition)));
+    }
+
+    fn visit_assert_eq(&mut self, left: &Expression, right: &Expression, _: Span) {
+        self.line(&format!(
+            "assert_eq {}, {};",
+            format_expression(left),
+            format_expression(right)
+        ));
+    }
+
+    fn visit_return(&mut self, expr: &Expression, _: Span) {
+        self.line(&format!("return {};", format_expression(expr)));
+    }
+
+    fn visit_try(&mut self, body: &[Statement], error: Symbol, handler: &[Statement], _: Span) {
+        self.block("try ", body);
+        // like `else`, the handler starts on the line the body ends
+        self.output.truncate(self.output.len() - 1);
+        self.output.push_str(&format!(" handle {} {{\n", error));
+        self.depth += 1;
+        self.format_statements(handler);
+        self.depth -= 1;
+        self.line("}");
+    }
+
+    fn visit_raise(&mut self, message: &Expression, _: Span) {
+        self.line(&format!("raise {};", format_expression(message)));
+    }
+
+    fn visit_break(&mut self, _: Span) {
+        self.line("break;");
+    }
+
+    fn visit_continue(&mut self, _: Span) {
+        self.line("continue;");
+    }
+
+    fn visit_import(&mut self, path: &str, _: Span) {
+        self.line(&format!("import \"{}\";", path));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn format(source: &str) -> String {
+        let tokens = Lexer::new(source).parse().unwrap();
+        Formatter::format(&Parser::new(tokens).parse().unwrap())
+    }
+
+    #[test]
+    fn test_format_program() {
+        let source = "const limit=10;let x:number=(1+2)*3;func f(a:number,b:bool):number{if b{return a;}else{return -a;}}\
+                      while x>0{x=x-1;croak f(x,true);}do{x++;}while x<3;\
+                      try{raise \"no\";}handle e{croak e;}";
+
+        let expected = "\
+const limit = 10;
+let x: number = (1 + 2) * 3;
+
+func f(a: number, b: bool): number {
+    if b {
+        return a;
+    } else {
+        return -a;
+    }
+}
+
+while x > 0 {
+    x = x - 1;
+    croak f(x, true);
+}
+do {
+    x++;
+} while x < 3;
+try {
+    raise \"no\";
+} handle e {
+    croak e;
+}
+";
+        assert_eq!(format(source), expected);
+        // formatting is idempotent
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn test_format_keeps_required_parentheses() {
+        assert_eq!(
+            format("let a = (1 - 2) - (3 - 4);"),
+            "let a = 1 - 2 - (3 - 4);\n"
+        );
+        assert_eq!(
+            format("let b = !(x && (y || z));"),
+            "let b = !(x && (y || z));\n"
+        );
+        let expected = "let c = -(-x) + -(-9223372036854775808) - -1;\n";
+        assert_eq!(
+            format("let c = -(-x) + -(-9223372036854775808) - (-1);"),
+            expected
+        );
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn test_format_keeps_number_literals() {
+        let expected = "let mask = 0xFF + 0b1010_0101;\nlet big = 1_000_000 + 2_5.5;\n";
+        assert_eq!(
+            format("let mask=0xFF+0b1010_0101;let big=1_000_000+2_5.5;"),
+            expected
+        );
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn test_format_keeps_word_operators() {
+        let expected = "let ok = not a and (b or !c) && d;\n";
+        assert_eq!(format("let ok=not a and(b or !c)&&d;"), expected);
+        assert_eq!(format(expected), expected);
+        assert_eq!(format("let b = not (x and y);"), "let b = not (x and y);\n");
+    }
+
+    #[test]
+    fn test_format_keeps_increments() {
+        let expected = "i--;\ni++;\ni = i + 1;\n";
+        assert_eq!(format("i--;i++;i=i+1;"), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn test_format_escapes_strings() {
+        let source = r#"croak "say \"ribbit\"\n\tC:\\pond\u{7}";"#;
+        assert_eq!(format(source), format!("{}\n", source));
+        // escapes are kept as written rather than turned into the characters they stand for
+        let source = r#"croak "\u{1F438} \u{e9}";"#;
+        assert_eq!(format(source), format!("{}\n", source));
+    }
+
+    #[test]
+    fn test_format_for_annotation() {
+        assert_eq!(
+            format("for i:number=1 to 3{croak i;}"),
+            "for i: number = 1 to 3 {\n    croak i;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_generic_function() {
+        assert_eq!(
+            format("func pick<T,U>(a:T,b:U?):T{return a;}"),
+            "func pick<T, U>(a: T, b: U?): T {\n    return a;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_for_in() {
+        assert_eq!(
+            format("for i in 0..n+1{croak i;}"),
+            "for i in 0..n + 1 {\n    croak i;\n}\n"
+        );
+        // ranges bind tighter than comparisons
+        assert_eq!(format("let b=(1..2)==r;"), "let b = 1..2 == r;\n");
+    }
+
+    #[test]
+    fn test_format_block_expression() {
+        let expected = "let x = { let a = 1; if a > 0 { croak a; } a * (a + 2) };\n";
+        assert_eq!(
+            format("let x = {\n    let a=1;\n    if a>0 { croak a; }\n    a*(a+2)\n};"),
+            expected
+        );
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn test_format_match() {
+        let expected = "\
+match x {
+    1 => {
+        croak \"one\";
+    }
+    -1 => {
+    }
+    _ => {
+        croak \"other\";
+    }
+}
+";
+        assert_eq!(
+            format("match x{1=>{croak \"one\";},-1=>{} _=>{croak \"other\";}}"),
+            expected
+        );
+        assert_eq!(format(expected), expected);
+    }
+}
@@ -1,15 +1,226 @@
+use crate::builtins;
+use crate::builtins::Builtin;
 use crate::interpreter::Value::Bool;
-use crate::parser::{Expression, Statement, Type};
+use crate::lexer::Span;
+use crate::module::ModuleLoader;
+use crate::parser::{BinOp, Statement, UnaryOp};
+use crate::resolver::{self, Body, Code, Expr, ExprKind, Stmt, StmtKind, Variable};
+use crate::symbol::Symbol;
+use std::cell::RefCell;
 use std::cmp::PartialEq;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone)]
 pub enum Value {
-    Number(i32),
+    Number(i64),
+    Float(f64),
     Bool(bool),
+    String(String),
+    Function(Function),
+    // enum name and variant
+    Enum(Symbol, Symbol),
+    // numbers from the start up to, but not including, the end
+    Range(i64, i64),
+    // the absence of a value, held by variables of optional types
+    None,
     Void,
 }
 
+// callable value: a function declared in froggle code, a native builtin or a function
+// registered by the program embedding froggle
+#[derive(Debug, Clone)]
+pub enum Function {
+    Declared(Rc<DeclaredFunction>),
+    Native(&'static Builtin),
+    Host(Rc<HostFunction>),
+}
+
+type HostFn = dyn Fn(&[Value]) -> Value;
+
+pub struct HostFunction {
+    name: String,
+    arity: usize,
+    function: Box<HostFn>,
+}
+
+impl fmt::Debug for HostFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+pub struct DeclaredFunction {
+    code: Rc<Code>,
+    // local scope the function was declared in, which its body keeps seeing after that scope
+    // ends; None for functions declared at the top level
+    closure: Option<Rc<RefCell<Scope>>>,
+}
+
+// the closure is left out: it usually contains the function itself
+impl fmt::Debug for DeclaredFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeclaredFunction")
+            .field("name", &self.code.name)
+            .field("params", &self.code.params)
+            .finish_non_exhaustive()
+    }
+}
+
+// variables of a block, loop iteration or call, by the slot the resolver gave them, linked to
+// the scope it is nested in
+pub struct Scope {
+    values: Vec<Value>,
+    // what the variables are called, for hooks; slots past `values` aren't declared yet
+    names: Rc<[Symbol]>,
+    parent: Option<Rc<RefCell<Scope>>>,
+}
+
+impl Scope {
+    fn new(
+        values: Vec<Value>,
+        names: Rc<[Symbol]>,
+        parent: Option<Rc<RefCell<Scope>>>,
+    ) -> Rc<RefCell<Scope>> {
+        Rc::new(RefCell::new(Scope {
+            values,
+            names,
+            parent,
+        }))
+    }
+}
+
+// global variables by name, linked to the natives they can shadow
+#[derive(Default)]
+pub struct Environment {
+    values: HashMap<Symbol, Value>,
+    // names in `values` declared with `const`
+    constants: HashSet<Symbol>,
+    parent: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    fn new(parent: Option<Rc<RefCell<Environment>>>) -> Rc<RefCell<Environment>> {
+        Rc::new(RefCell::new(Environment {
+            values: HashMap::new(),
+            constants: HashSet::new(),
+            parent,
+        }))
+    }
+
+    fn get(&self, name: Symbol) -> Option<Value> {
+        match self.values.get(&name) {
+            Some(value) => Some(value.clone()),
+            None => self.parent.as_ref()?.borrow().get(name),
+        }
+    }
+
+    fn declare(&mut self, name: Symbol, value: Value, constant: bool) {
+        if constant {
+            self.constants.insert(name);
+        } else {
+            self.constants.remove(&name);
+        }
+        self.values.insert(name, value);
+    }
+
+    // updates the innermost variable called `name`
+    fn assign(&mut self, name: Symbol, value: Value) -> Result<(), RuntimeErrorKind> {
+        if self.constants.contains(&name) {
+            return Err(RuntimeErrorKind::AssignmentToConstant(name.to_string()));
+        }
+        match self.values.get_mut(&name) {
+            Some(slot) => {
+                *slot = value;
+                Ok(())
+            }
+            None => match &self.parent {
+                Some(parent) => parent.borrow_mut().assign(name, value),
+                None => Err(RuntimeErrorKind::UnknownVariable(name.to_string())),
+            },
+        }
+    }
+}
+
+impl Function {
+    fn name(&self) -> &str {
+        match self {
+            Function::Declared(function) => function.code.name.as_str(),
+            Function::Native(builtin) => builtin.name,
+            Function::Host(function) => &function.name,
+        }
+    }
+
+    // functions are equal only when they are the very same function
+    fn is_same(&self, other: &Function) -> bool {
+        match (self, other) {
+            (Function::Declared(a), Function::Declared(b)) => Rc::ptr_eq(a, b),
+            (Function::Native(a), Function::Native(b)) => a.name == b.name,
+            (Function::Host(a), Function::Host(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Value {
+    // numeric value widened to a float, used for mixed number/float arithmetic
+    fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n as f64),
+            Value::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    // whether `==` may compare the values, as the typechecker allows for their types: values
+    // of the same type, numbers with floats, and anything with none, as an optional value may
+    // be none
+    fn is_comparable(&self, other: &Value) -> bool {
+        (self.as_float().is_some() && other.as_float().is_some())
+            || matches!(self, Value::None)
+            || matches!(other, Value::None)
+            || builtins::type_name(self) == builtins::type_name(other)
+    }
+
+    // what `for ... in` goes over: the numbers of a range or the characters of a string, each
+    // a string of its own. Collections added to the language iterate here too
+    fn elements(&self) -> Option<Box<dyn Iterator<Item = Value>>> {
+        match self {
+            Value::Range(start, end) => Some(Box::new((*start..*end).map(Value::Number))),
+            Value::String(s) => {
+                let characters: Vec<Value> =
+                    s.chars().map(|c| Value::String(c.to_string())).collect();
+                Some(Box::new(characters.into_iter()))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", n),
+            // Debug keeps the fractional part of whole floats, so 2.0 doesn't print as 2
+            Value::Float(n) => write!(f, "{:?}", n),
+            Bool(b) => write!(f, "{}", b),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Function(function) => write!(f, "<func {}>", function.name()),
+            Value::Enum(name, variant) => write!(f, "{}.{}", name, variant),
+            Value::Range(start, end) => write!(f, "{}..{}", start, end),
+            Value::None => write!(f, "none"),
+            Value::Void => write!(f, "void"),
+        }
+    }
+}
+
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match self {
@@ -17,12 +228,45 @@ impl PartialEq for Value {
                 if let Value::Number(o) = other {
                     return n == o;
                 }
+                if let Value::Float(o) = other {
+                    return *n as f64 == *o;
+                }
+            }
+            Value::Float(f) => {
+                if let Some(o) = other.as_float() {
+                    return *f == o;
+                }
             }
             Bool(b) => {
                 if let Bool(o) = other {
                     return b == o;
                 }
             }
+            Value::String(s) => {
+                if let Value::String(o) = other {
+                    return s == o;
+                }
+            }
+            Value::Function(function) => {
+                if let Value::Function(o) = other {
+                    return function.is_same(o);
+                }
+            }
+            Value::Enum(name, variant) => {
+                if let Value::Enum(o_name, o_variant) = other {
+                    return name == o_name && variant == o_variant;
+                }
+            }
+            Value::Range(start, end) => {
+                if let Value::Range(o_start, o_end) = other {
+                    return start == o_start && end == o_end;
+                }
+            }
+            Value::None => {
+                if let Value::None = other {
+                    return true;
+                }
+            }
             Value::Void => {
                 if let Value::Void = other {
                     return true;
@@ -33,297 +277,2222 @@ impl PartialEq for Value {
     }
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub enum RuntimeErrorKind {
+    UnknownVariable(String),
+    AssignmentToConstant(String),
+    UnknownFunction(String),
+    NotCallable(String),
+    ArityMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    NonBooleanCondition,
+    NonNumericLoopBound,
+    // for ... in over a value that isn't a range, with its type
+    NotIterable(String),
+    UnsupportedOperation(String),
+    // `==` or `!=` on values of types the typechecker wouldn't let be compared, with the
+    // operator and the two types
+    Incomparable {
+        operator: String,
+        left: String,
+        right: String,
+    },
+    InvalidArgument(String),
+    DivisionByZero,
+    StepLimitExceeded(u64),
+    TimedOut(Duration),
+    // a call would have made more calls to froggle functions be in progress than the limit
+    CallDepthExceeded(usize),
+    // number operation whose result doesn't fit in 64 bits, with the operator or builtin
+    Overflow(String),
+    // what was asserted, and the values involved for assert_eq
+    AssertionFailed(String),
+    // exit(code) was called; unwinds the program like an error so the caller decides what to do
+    Exit(u8),
+    // the `interrupt` flag was raised while the program ran
+    Interrupted,
+    // a file system builtin was called without `Limits::allow_fs`
+    FileSystemDisabled(String),
+    // `raise` with the message it was given
+    Raised(String),
+    // return, break or continue in a block used as a value, which has to run to its end
+    JumpOutOfBlock(String),
+}
+
+impl RuntimeErrorKind {
+    // whether a try statement can handle the error; running out of steps or time, interrupts
+    // and exit() always end the program, or scripts could ignore them
+    fn is_handleable(&self) -> bool {
+        !matches!(
+            self,
+            RuntimeErrorKind::StepLimitExceeded(_)
+                | RuntimeErrorKind::TimedOut(_)
+                | RuntimeErrorKind::Interrupted
+                | RuntimeErrorKind::Exit(_)
+        )
+    }
+}
+
+impl fmt::Display for RuntimeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeErrorKind::UnknownVariable(name) => write!(f, "unknown variable {}", name),
+            RuntimeErrorKind::AssignmentToConstant(name) => {
+                write!(f, "cannot assign to constant {}", name)
+            }
+            RuntimeErrorKind::UnknownFunction(name) => write!(f, "unknown function {}", name),
+            RuntimeErrorKind::NotCallable(name) => write!(f, "{} is not a function", name),
+            RuntimeErrorKind::ArityMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "function {} expects {} arguments, got {}",
+                name, expected, found
+            ),
+            RuntimeErrorKind::NonBooleanCondition => write!(f, "condition is not a boolean"),
+            RuntimeErrorKind::NonNumericLoopBound => write!(f, "for loop bound is not a number"),
+            RuntimeErrorKind::NotIterable(found) => {
+                write!(f, "cannot go over a {} with for ... in", found)
+            }
+            RuntimeErrorKind::UnsupportedOperation(op) => {
+                write!(f, "unsupported operation: {}", op)
+            }
+            RuntimeErrorKind::Incomparable {
+                operator,
+                left,
+                right,
+            } => write!(f, "cannot compare {} and {} with {}", left, right, operator),
+            RuntimeErrorKind::InvalidArgument(message) => write!(f, "{}", message),
+            RuntimeErrorKind::DivisionByZero => write!(f, "division by zero"),
+            RuntimeErrorKind::StepLimitExceeded(steps) => {
+                write!(f, "step limit of {} exceeded", steps)
+            }
+            RuntimeErrorKind::TimedOut(timeout) => {
+                write!(f, "timed out after {}ms", timeout.as_millis())
+            }
+            RuntimeErrorKind::CallDepthExceeded(depth) => {
+                write!(f, "call depth limit of {} exceeded", depth)
+            }
+            RuntimeErrorKind::Overflow(op) => write!(f, "integer overflow in {}", op),
+            RuntimeErrorKind::AssertionFailed(message) => {
+                write!(f, "assertion failed: {}", message)
+            }
+            RuntimeErrorKind::Exit(code) => write!(f, "exited with code {}", code),
+            RuntimeErrorKind::Interrupted => write!(f, "interrupted"),
+            RuntimeErrorKind::FileSystemDisabled(name) => {
+                write!(f, "{} needs file access, run with --allow-fs", name)
+            }
+            RuntimeErrorKind::Raised(message) => write!(f, "{}", message),
+            RuntimeErrorKind::JumpOutOfBlock(keyword) => {
+                write!(f, "'{}' can't jump out of a block used as a value", keyword)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RuntimeError {
+    pub kind: RuntimeErrorKind,
+    pub span: Span,
+    // the calls the error happened in, innermost first; empty for errors at the top level
+    pub trace: Vec<Frame>,
+}
+
+impl RuntimeError {
+    // boxed, as errors are while they unwind through the interpreter: the results of its
+    // recursive functions stay small, and so do their frames on the stack
+    fn new(kind: RuntimeErrorKind, span: Span) -> Box<RuntimeError> {
+        Box::new(RuntimeError {
+            kind,
+            span,
+            trace: Vec::new(),
+        })
+    }
+}
+
+// a call to a froggle function in progress, and where it was called from; a tail call takes
+// the place of the call that made it
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Frame {
+    pub function: Symbol,
+    pub call: Span,
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.kind)
+    }
+}
+
+// how a statement finished: normally, or by jumping out of the enclosing loop or function
+#[derive(Debug, Clone)]
+enum ControlFlow {
+    Normal,
+    Return(Value),
+    // `return f(...)`: the call is made by `call_function` after the returning function's
+    // frame is gone, so tail recursion doesn't grow the Rust stack
+    TailCall {
+        function: Function,
+        args: Vec<Value>,
+        span: Span,
+    },
+    Break,
+    Continue,
+}
+
+// bounds on a single `interpret` call, so scripts that never finish can be stopped and
+// untrusted ones can't touch the file system
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    // statements and loop iterations
+    pub max_steps: Option<u64>,
+    pub timeout: Option<Duration>,
+    // calls to froggle functions in progress at once; deeper recursion would overflow the
+    // stack of the program running the interpreter, so there is a limit unless it is lifted
+    pub max_call_depth: Option<usize>,
+    // whether builtins reading and writing files may be called
+    pub allow_fs: bool,
+}
+
+// deep enough for any recursion that isn't runaway, shallow enough for the 8MB stack of a main
+// thread in a debug build
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 500;
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_steps: None,
+            timeout: None,
+            max_call_depth: Some(DEFAULT_MAX_CALL_DEPTH),
+            allow_fs: false,
+        }
+    }
+}
+
+// watches a program as it runs, to debug or trace it for example; set as `Interpreter::hook`.
+// Every method does nothing unless implemented
+pub trait Hook {
+    // called before every statement runs, with the variables it can see
+    fn on_statement(&mut self, _span: Span, _env: &Env) {}
+
+    // called after every statement, whether it ran to the end or failed, with the kind of
+    // statement it is, like "while"
+    fn on_statement_end(&mut self, _span: Span, _kind: &str, _env: &Env) {}
+
+    // called with the value of every expression evaluated, after its operands'
+    fn on_expression(&mut self, _span: Span, _value: &Value, _env: &Env) {}
+
+    // called when the body of a froggle function starts running, for a call at `span`
+    fn on_call(&mut self, _name: Symbol, _span: Span, _env: &Env) {}
+
+    // called when the body stops running, by returning, failing or making a tail call
+    fn on_return(&mut self, _name: Symbol, _env: &Env) {}
+}
+
+// a hook that is also looked at by the code that set it, e.g. to print what it collected
+impl<H: Hook> Hook for Rc<RefCell<H>> {
+    fn on_statement(&mut self, span: Span, env: &Env) {
+        self.borrow_mut().on_statement(span, env);
+    }
+
+    fn on_statement_end(&mut self, span: Span, kind: &str, env: &Env) {
+        self.borrow_mut().on_statement_end(span, kind, env);
+    }
+
+    fn on_expression(&mut self, span: Span, value: &Value, env: &Env) {
+        self.borrow_mut().on_expression(span, value, env);
+    }
+
+    fn on_call(&mut self, name: Symbol, span: Span, env: &Env) {
+        self.borrow_mut().on_call(name, span, env);
+    }
+
+    fn on_return(&mut self, name: Symbol, env: &Env) {
+        self.borrow_mut().on_return(name, env);
+    }
+}
+
+// two hooks watching the same program, the first one told about everything first
+impl<A: Hook, B: Hook> Hook for (A, B) {
+    fn on_statement(&mut self, span: Span, env: &Env) {
+        self.0.on_statement(span, env);
+        self.1.on_statement(span, env);
+    }
+
+    fn on_statement_end(&mut self, span: Span, kind: &str, env: &Env) {
+        self.0.on_statement_end(span, kind, env);
+        self.1.on_statement_end(span, kind, env);
+    }
+
+    fn on_expression(&mut self, span: Span, value: &Value, env: &Env) {
+        self.0.on_expression(span, value, env);
+        self.1.on_expression(span, value, env);
+    }
+
+    fn on_call(&mut self, name: Symbol, span: Span, env: &Env) {
+        self.0.on_call(name, span, env);
+        self.1.on_call(name, span, env);
+    }
+
+    fn on_return(&mut self, name: Symbol, env: &Env) {
+        self.0.on_return(name, env);
+        self.1.on_return(name, env);
+    }
+}
+
+// what a hook sees of the running program
+pub struct Env<'a> {
+    interpreter: &'a Interpreter,
+}
+
+impl Env<'_> {
+    // calls to froggle functions in progress, 0 at the top level
+    pub fn depth(&self) -> usize {
+        self.interpreter.calls.len()
+    }
+
+    // local scopes the running code is nested in, 0 at the top level
+    pub fn scopes(&self) -> usize {
+        let mut scopes = 0;
+        let mut scope = self.interpreter.scope.clone();
+        while let Some(current) = scope {
+            scopes += 1;
+            scope = current.borrow().parent.clone();
+        }
+        scopes
+    }
+
+    // the local scopes from the innermost out, each with the variables declared in it so far;
+    // a function's scopes are followed by those it was declared in, not by its caller's
+    pub fn locals(&self) -> Vec<Vec<(String, Value)>> {
+        let mut scopes = Vec::new();
+        let mut scope = self.interpreter.scope.clone();
+        while let Some(current) = scope {
+            let current = current.borrow();
+            let variables = current.names.iter().zip(&current.values);
+            scopes.push(
+                variables
+                    .map(|(name, value)| (name.to_string(), value.clone()))
+                    .collect(),
+            );
+            scope = current.parent.clone();
+        }
+        scopes
+    }
+
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        self.interpreter.globals()
+    }
+}
+
+// global variables at some point, to go back to with `Interpreter::restore`
 #[derive(Debug, Clone)]
-struct Function {
-    params: Vec<(String, Type)>,
-    body: Vec<Statement>,
+pub struct Snapshot {
+    values: HashMap<Symbol, Value>,
+    constants: HashSet<Symbol>,
 }
 
 pub struct Interpreter {
-    pub environments: Vec<HashMap<String, Value>>,
-    functions: HashMap<String, Function>,
+    // functions registered by the embedding program, the parent of `globals` so a reset
+    // keeps them
+    natives: Rc<RefCell<Environment>>,
+    globals: Rc<RefCell<Environment>>,
+    // innermost local scope of the code being run, None at the top level
+    scope: Option<Rc<RefCell<Scope>>>,
+    // modules stay cached for the interpreter's lifetime, e.g. across REPL entries
+    pub modules: ModuleLoader,
+    pub limits: Limits,
+    // once set, e.g. from a Ctrl+C handler, the running program stops at its next step
+    pub interrupt: Option<Arc<AtomicBool>>,
+    // what croak printed since `capture_output`, None while it goes to stdout
+    output: Option<String>,
+    // told about every statement and expression as it runs, when set
+    pub hook: Option<Box<dyn Hook>>,
+    // steps taken and when time runs out, for the `interpret` call in progress
+    steps: u64,
+    deadline: Option<Instant>,
+    // calls to froggle functions in progress, the innermost last
+    calls: Vec<Frame>,
+    // try bodies around the statement running, in the function it belongs to; a return in
+    // one can't be a tail call, or the call would only run once the try has ended
+    try_depth: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
 }
 
 impl Interpreter {
     pub fn new() -> Interpreter {
-        let mut environments = Vec::new();
-        environments.push(HashMap::new());
-
-        let functions = HashMap::new();
+        let natives = Environment::new(None);
+        let globals = Environment::new(Some(natives.clone()));
         Self {
-            environments,
-            functions,
+            scope: None,
+            natives,
+            globals,
+            modules: ModuleLoader::new(),
+            limits: Limits::default(),
+            interrupt: None,
+            output: None,
+            hook: None,
+            steps: 0,
+            deadline: None,
+            calls: Vec::new(),
+            try_depth: 0,
+        }
+    }
+
+    // exposes a Rust function to froggle code as a global; the typechecker has to be told its
+    // signature separately, see `TypeChecker::declare_native`
+    pub fn register_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&[Value]) -> Value + 'static,
+    ) {
+        let function = HostFunction {
+            name: name.to_string(),
+            arity,
+            function: Box::new(function),
+        };
+        self.natives.borrow_mut().declare(
+            Symbol::intern(name),
+            Value::Function(Function::Host(Rc::new(function))),
+            false,
+        );
+    }
+
+    // copies the global variables; state captured by closures is shared, not copied
+    pub fn snapshot(&self) -> Snapshot {
+        let globals = self.globals.borrow();
+        Snapshot {
+            values: globals.values.clone(),
+            constants: globals.constants.clone(),
         }
     }
 
+    // puts the global variables back the way they were at `snapshot`
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        let mut globals = self.globals.borrow_mut();
+        globals.values = snapshot.values;
+        globals.constants = snapshot.constants;
+    }
+
+    // forgets every global variable, keeping registered natives, limits and loaded modules
+    pub fn reset(&mut self) {
+        self.restore(Snapshot {
+            values: HashMap::new(),
+            constants: HashSet::new(),
+        });
+    }
+
+    // collects what croak prints from now on instead of writing it to stdout
+    pub fn capture_output(&mut self) {
+        self.output.get_or_insert_with(String::new);
+    }
+
+    // the output captured so far, leaving the capture empty
+    pub fn take_output(&mut self) -> String {
+        self.output.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    // global variables sorted by name
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        let mut globals: Vec<_> = self
+            .globals
+            .borrow()
+            .values
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+        globals.sort_by(|(a, _), (b, _)| a.cmp(b));
+        globals
+    }
+
     // scope & variables
-    fn enter_scope(&mut self) {
-        self.environments.push(HashMap::new());
+    fn enter_scope(&mut self, values: Vec<Value>, names: Rc<[Symbol]>) {
+        self.scope = Some(Scope::new(values, names, self.scope.take()));
     }
 
     fn exit_scope(&mut self) {
-        self.environments.pop();
+        let scope = self.scope.take().expect("exiting the global scope");
+        self.scope = scope.borrow().parent.clone();
     }
 
-    fn declare_variable(&mut self, name: String, value: Value) {
-        self.environments
-            .last_mut()
-            .expect(format!("error declaring variable {}", name).as_str())
-            .insert(name, value);
+    // the local scope `depth` scopes out from the innermost one
+    fn local_scope(&self, depth: usize) -> Rc<RefCell<Scope>> {
+        let mut scope = self
+            .scope
+            .clone()
+            .expect("local variable outside of any scope");
+        for _ in 0..depth {
+            let parent = scope.borrow().parent.clone();
+            scope = parent.expect("local variable deeper than the scopes");
+        }
+        scope
     }
 
-    fn assign_variable(&mut self, name: String, value: Value) {
-        for scope in self.environments.iter_mut().rev() {
-            if scope.contains_key(&name) {
-                scope.insert(name, value);
-                return;
+    fn declare_variable(
+        &mut self,
+        name: Symbol,
+        slot: Option<usize>,
+        value: Value,
+        constant: bool,
+    ) {
+        match (slot, &self.scope) {
+            (Some(index), Some(scope)) => {
+                let values = &mut scope.borrow_mut().values;
+                if index < values.len() {
+                    values[index] = value;
+                } else {
+                    values.push(value);
+                }
             }
+            _ => self.globals.borrow_mut().declare(name, value, constant),
         }
-        panic!("error assigning to non-existent variable {}", name);
     }
 
-    fn resolve_variable(&mut self, name: &String) -> Value {
-        for scope in self.environments.iter_mut().rev() {
-            if let Some(value) = scope.get(name) {
-                return value.clone();
+    fn assign_variable(
+        &mut self,
+        name: Symbol,
+        variable: Variable,
+        value: Value,
+        span: Span,
+    ) -> Result<(), Box<RuntimeError>> {
+        let assigned = match variable {
+            Variable::Local { constant: true, .. } => {
+                Err(RuntimeErrorKind::AssignmentToConstant(name.to_string()))
             }
-        }
-        panic!("error resolving variable {}", name);
+            Variable::Local { depth, index, .. } => {
+                self.local_scope(depth).borrow_mut().values[index] = value;
+                Ok(())
+            }
+            Variable::Global => self.globals.borrow_mut().assign(name, value),
+        };
+        assigned.map_err(|kind| RuntimeError::new(kind, span))
     }
 
-    pub fn interpret(&mut self, program: Vec<Statement>) {
-        for stmt in program {
-            self.eval_statement(stmt);
+    // builtins come last, so declarations can shadow them
+    fn resolve_variable(
+        &self,
+        name: Symbol,
+        variable: Variable,
+        span: Span,
+    ) -> Result<Value, Box<RuntimeError>> {
+        if let Variable::Local { depth, index, .. } = variable {
+            return Ok(self.local_scope(depth).borrow().values[index].clone());
+        }
+        if let Some(value) = self.globals.borrow().get(name) {
+            return Ok(value);
+        }
+        if let Some(builtin) = builtins::lookup(name.as_str()) {
+            return Ok(Value::Function(Function::Native(builtin)));
         }
+        Err(RuntimeError::new(
+            RuntimeErrorKind::UnknownVariable(name.to_string()),
+            span,
+        ))
     }
 
-    fn eval_statement(&mut self, statement: Statement) -> Option<Value> {
-        match statement {
-            Statement::Return(exp) => {
-                let value = self.eval_expression(exp);
-                Some(value)
-            }
-            Statement::Assignment(var, exp) => {
-                let value = self.eval_expression(exp);
-                self.assign_variable(var, value);
-                None
-            }
-            Statement::Declaration(var, exp, _) => {
-                let value = self.eval_expression(exp);
-                self.declare_variable(var, value);
-                None
-            }
-            Statement::Print(exp) => {
-                println!("{:?}", self.eval_expression(exp));
-                None
-            }
-            Statement::While { condition, body } => {
-                self.enter_scope();
-                self.eval_while_loop(condition, body);
-                self.exit_scope();
-                None
-            }
-            Statement::Block(statements) => {
-                self.enter_scope();
-                for statement in statements {
-                    self.eval_statement(statement);
-                }
-                self.exit_scope();
-                None
-            }
-            Statement::FunctionDeclaration {
-                name, params, body, ..
-            } => {
-                let func = Function { params, body };
-                self.functions.insert(name, func);
-                None
-            }
-            Statement::Expression(exp) => {
-                self.eval_expression(exp);
-                None
+    // runs a program, returning the value of its last statement if that is an expression
+    pub fn interpret(&mut self, program: &[Statement]) -> Result<Value, RuntimeError> {
+        self.steps = 0;
+        self.deadline = self.limits.timeout.map(|timeout| Instant::now() + timeout);
+        self.calls.clear();
+        self.try_depth = 0;
+
+        let program = resolver::resolve(program);
+        // top-level functions exist from the start, so they can be called before their
+        // declaration; the typechecker only allows that where it means the same function
+        for stmt in &program {
+            if let StmtKind::Function { slot, code } = &stmt.kind {
+                self.declare_function(*slot, code);
             }
-            Statement::If {
-                condition,
-                then_block,
-                else_block,
-            } => {
-                if self.eval_condition(condition) {
-                    for stmt in then_block {
-                        if let Some(value) = self.eval_statement(stmt) {
-                            return Some(value);
-                        }
-                    }
-                    return None;
-                }
+        }
 
-                match else_block {
-                    None => None,
-                    Some(else_block) => {
-                        for stmt in else_block {
-                            if let Some(value) = self.eval_statement(stmt) {
-                                return Some(value);
-                            }
-                        }
-                        None
-                    }
+        self.run_program(&program).map_err(|error| *error)
+    }
+
+    fn run_program(&mut self, program: &[Stmt]) -> Result<Value, Box<RuntimeError>> {
+        let mut last = Value::Void;
+        for stmt in program {
+            last = match &stmt.kind {
+                StmtKind::Expression(exp) => {
+                    self.notify(|hook, env| hook.on_statement(stmt.span, env));
+                    let value = self.eval_expression(exp);
+                    let kind = stmt.kind.name();
+                    self.notify(|hook, env| hook.on_statement_end(stmt.span, kind, env));
+                    value?
                 }
-            }
+                _ => {
+                    self.eval_statement(stmt)?;
+                    Value::Void
+                }
+            };
         }
+        Ok(last)
     }
 
-    fn eval_while_loop(&mut self, condition: Expression, body: Vec<Statement>) {
-        while self.eval_condition(condition.clone()) {
-            for statement in &body {
-                self.eval_statement(statement.clone());
-            }
+    // runs a body in a fresh scope starting out with `values`, making sure the scope is popped
+    // on error too
+    fn eval_body(
+        &mut self,
+        body: &Body,
+        values: Vec<Value>,
+    ) -> Result<ControlFlow, Box<RuntimeError>> {
+        self.enter_scope(values, body.names.clone());
+        let result = self.eval_block(&body.statements);
+        self.exit_scope();
+        result
+    }
+
+    // lets the hook, if there is one, look at the running program
+    fn notify(&mut self, event: impl FnOnce(&mut dyn Hook, &Env)) {
+        if let Some(mut hook) = self.hook.take() {
+            event(hook.as_mut(), &Env { interpreter: self });
+            self.hook = Some(hook);
         }
     }
 
-    fn eval_condition(&mut self, condition: Expression) -> bool {
-        match self.eval_expression(condition) {
-            Bool(b) => b,
-            _ => panic!("Condition is not a boolean"),
+    fn declare_function(&mut self, slot: Option<usize>, code: &Rc<Code>) {
+        let function = DeclaredFunction {
+            code: code.clone(),
+            closure: self.scope.clone(),
+        };
+        let value = Value::Function(Function::Declared(Rc::new(function)));
+        self.declare_variable(code.name, slot, value, false);
+    }
+
+    // counts one step against the limits, failing once either of them is used up or the
+    // program is interrupted
+    fn tick(&mut self, span: Span) -> Result<(), Box<RuntimeError>> {
+        if let Some(interrupt) = &self.interrupt
+            && interrupt.load(Ordering::Relaxed)
+        {
+            return Err(RuntimeError::new(RuntimeErrorKind::Interrupted, span));
+        }
+        self.steps += 1;
+        if let Some(max_steps) = self.limits.max_steps
+            && self.steps > max_steps
+        {
+            return Err(RuntimeError::new(
+                RuntimeErrorKind::StepLimitExceeded(max_steps),
+                span,
+            ));
+        }
+        if let (Some(deadline), Some(timeout)) = (self.deadline, self.limits.timeout)
+            && Instant::now() > deadline
+        {
+            return Err(RuntimeError::new(RuntimeErrorKind::TimedOut(timeout), span));
         }
+        Ok(())
     }
-    fn eval_expression(&mut self, expression: Expression) -> Value {
-        match expression {
-            Expression::Number(n) => Value::Number(n),
-            Expression::Bool(b) => Value::Bool(b),
-            Expression::Variable(name) => self.resolve_variable(&name),
-            Expression::BinaryOperation {
+
+    fn eval_statement(&mut self, statement: &Stmt) -> Result<ControlFlow, Box<RuntimeError>> {
+        self.tick(statement.span)?;
+        self.notify(|hook, env| hook.on_statement(statement.span, env));
+        let flow = self.execute(statement);
+        let kind = statement.kind.name();
+        self.notify(|hook, env| hook.on_statement_end(statement.span, kind, env));
+        flow
+    }
+
+    // runs a statement, without counting it as a step or telling the hook about it. Every
+    // froggle call recurses through here, so statements needing more than a few locals are
+    // run by functions of their own, keeping this frame small in debug builds
+    fn execute(&mut self, statement: &Stmt) -> Result<ControlFlow, Box<RuntimeError>> {
+        match &statement.kind {
+            StmtKind::Return(exp) => self.eval_return(exp),
+            StmtKind::Raise(message) => self.eval_raise(message, statement.span),
+            StmtKind::Try { body, handler } => self.eval_try(body, handler),
+            StmtKind::Break => Ok(ControlFlow::Break),
+            StmtKind::Continue => Ok(ControlFlow::Continue),
+            StmtKind::Nothing => Ok(ControlFlow::Normal),
+            StmtKind::Assign {
+                name,
+                variable,
+                value,
+            } => self.eval_assign(*name, *variable, value, statement.span),
+            StmtKind::Declare {
+                name,
+                slot,
+                value,
+                constant,
+            } => self.eval_declare(*name, *slot, value, *constant),
+            StmtKind::Print { value, debug } => self.eval_print(value, *debug),
+            StmtKind::While { condition, body } => self.eval_while_loop(condition, body),
+            StmtKind::DoWhile { body, condition } => self.eval_do_while_loop(body, condition),
+            StmtKind::For { start, end, body } => self.eval_for_loop(start, end, body),
+            StmtKind::ForIn { iterable, body } => self.eval_for_in(iterable, body),
+            StmtKind::Block(body) => self.eval_body(body, Vec::new()),
+            StmtKind::Function { slot, code } => {
+                self.declare_function(*slot, code);
+                Ok(ControlFlow::Normal)
+            }
+            StmtKind::Expression(exp) => self.eval_expression(exp).map(|_| ControlFlow::Normal),
+            StmtKind::Assert { condition, source } => {
+                self.eval_assert(condition, source, statement.span)
+            }
+            StmtKind::AssertEq {
                 left,
-                operator,
                 right,
-            } => {
-                let left = self.eval_expression(*left);
-                let right = self.eval_expression(*right);
+                source,
+            } => self.eval_assert_eq(left, right, source, statement.span),
+            StmtKind::Match { subject, arms } => self.eval_match(subject, arms),
+            StmtKind::If {
+                condition,
+                then_block,
+                else_block,
+            } => self.eval_if(condition, then_block, else_block.as_ref()),
+        }
+    }
 
-                match (left, operator.as_str(), right) {
-                    (Value::Number(left), "+", Value::Number(right)) => Value::Number(left + right),
-                    (Value::Number(left), "-", Value::Number(right)) => Value::Number(left - right),
-                    (Value::Number(left), "*", Value::Number(right)) => Value::Number(left * right),
-                    (Value::Number(left), "/", Value::Number(right)) => Value::Number(left / right),
+    fn eval_assign(
+        &mut self,
+        name: Symbol,
+        variable: Variable,
+        value: &Expr,
+        span: Span,
+    ) -> Result<ControlFlow, Box<RuntimeError>> {
+        let value = self.eval_expression(value)?;
+        self.assign_variable(name, variable, value, span)?;
+        Ok(ControlFlow::Normal)
+    }
 
-                    (Value::Number(left), ">", Value::Number(right)) => Value::Bool(left > right),
-                    (Value::Number(left), "<", Value::Number(right)) => Value::Bool(left < right),
+    fn eval_declare(
+        &mut self,
+        name: Symbol,
+        slot: Option<usize>,
+        value: &Expr,
+        constant: bool,
+    ) -> Result<ControlFlow, Box<RuntimeError>> {
+        let value = self.eval_expression(value)?;
+        self.declare_variable(name, slot, value, constant);
+        Ok(ControlFlow::Normal)
+    }
 
-                    (l, "==", r) => Bool(l == r),
-                    _ => panic!("unsupported operation: {}", operator.as_str()),
-                }
+    fn eval_return(&mut self, exp: &Expr) -> Result<ControlFlow, Box<RuntimeError>> {
+        match &exp.kind {
+            ExprKind::Call {
+                name,
+                function,
+                arguments,
+            } if self.try_depth == 0 => {
+                let (function, args) = self.eval_call(*name, *function, arguments, exp.span)?;
+                Ok(ControlFlow::TailCall {
+                    function,
+                    args,
+                    span: exp.span,
+                })
             }
-            Expression::FunctionCall { name, arguments } => {
-                let func = (*self
-                    .functions
-                    .get(&name)
-                    .expect(&format!("unknown function {}", name)))
-                .clone();
-
-                self.enter_scope();
-                if arguments.len() != func.params.len() {
-                    panic!(
-                        "function {} expects {} arguments, got {}",
-                        name,
-                        func.params.len(),
-                        arguments.len()
-                    );
-                }
-
-                for ((param_name, _param_type), arg) in func.params.iter().zip(arguments) {
-                    let val = self.eval_expression(arg);
-                    self.declare_variable(param_name.clone(), val);
-                }
+            _ => Ok(ControlFlow::Return(self.eval_expression(exp)?)),
+        }
+    }
 
-                let mut return_value = Value::Void;
-                for stmt in &func.body {
-                    if let Some(val) = self.eval_statement(stmt.clone()) {
-                        return_value = val;
-                        break;
-                    }
-                }
-                self.exit_scope();
+    fn eval_raise(&mut self, message: &Expr, span: Span) -> Result<ControlFlow, Box<RuntimeError>> {
+        let message = self.eval_expression(message)?;
+        Err(RuntimeError::new(
+            RuntimeErrorKind::Raised(message.to_string()),
+            span,
+        ))
+    }
 
-                return_value
+    fn eval_try(&mut self, body: &Body, handler: &Body) -> Result<ControlFlow, Box<RuntimeError>> {
+        self.try_depth += 1;
+        let flow = self.eval_body(body, Vec::new());
+        self.try_depth -= 1;
+        match flow {
+            Err(error) if error.kind.is_handleable() => {
+                let message = vec![Value::String(error.kind.to_string())];
+                self.eval_body(handler, message)
             }
+            flow => flow,
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::parser::{Expression, Statement};
-
-    fn number(n: i32) -> Expression {
-        Expression::Number(n)
+    fn eval_print(&mut self, value: &Expr, debug: bool) -> Result<ControlFlow, Box<RuntimeError>> {
+        let value = self.eval_expression(value)?;
+        let line = if debug {
+            format!("{:?}\n", value)
+        } else {
+            format!("{}\n", value)
+        };
+        match &mut self.output {
+            Some(output) => output.push_str(&line),
+            None => print!("{}", line),
+        }
+        Ok(ControlFlow::Normal)
     }
 
-    fn var(name: &str) -> Expression {
-        Expression::Variable(name.to_string())
+    fn eval_assert(
+        &mut self,
+        condition: &Expr,
+        source: &str,
+        span: Span,
+    ) -> Result<ControlFlow, Box<RuntimeError>> {
+        if !self.eval_condition(condition)? {
+            return Err(RuntimeError::new(
+                RuntimeErrorKind::AssertionFailed(source.to_string()),
+                span,
+            ));
+        }
+        Ok(ControlFlow::Normal)
     }
 
-    fn bin(left: Expression, op: &str, right: Expression) -> Expression {
-        Expression::BinaryOperation {
-            left: Box::new(left),
-            operator: op.to_string(),
-            right: Box::new(right),
+    fn eval_assert_eq(
+        &mut self,
+        left: &Expr,
+        right: &Expr,
+        source: &str,
+        span: Span,
+    ) -> Result<ControlFlow, Box<RuntimeError>> {
+        let (left, right) = (self.eval_expression(left)?, self.eval_expression(right)?);
+        if left != right {
+            return Err(RuntimeError::new(
+                RuntimeErrorKind::AssertionFailed(format!(
+                    "{} (left: {}, right: {})",
+                    source, left, right
+                )),
+                span,
+            ));
         }
+        Ok(ControlFlow::Normal)
     }
 
-    #[test]
-    fn test_variable_assignment() {
-        let program = vec![Statement::Assignment("x".to_string(), number(10))];
-        let mut interpreter = Interpreter::new();
-        interpreter.interpret(program);
+    fn eval_match(
+        &mut self,
+        subject: &Expr,
+        arms: &[(Option<Expr>, Body)],
+    ) -> Result<ControlFlow, Box<RuntimeError>> {
+        let subject = self.eval_expression(subject)?;
+        for (pattern, body) in arms {
+            let matched = match pattern {
+                None => true,
+                Some(value) => self.eval_expression(value)? == subject,
+            };
+            if matched {
+                return self.eval_body(body, Vec::new());
+            }
+        }
+        Ok(ControlFlow::Normal)
+    }
 
-        assert_eq!(
-            interpreter.environments.pop().unwrap().get("x"),
-            Some(&Value::Number(10))
-        );
+    fn eval_if(
+        &mut self,
+        condition: &Expr,
+        then_block: &Body,
+        else_block: Option<&Body>,
+    ) -> Result<ControlFlow, Box<RuntimeError>> {
+        let block = if self.eval_condition(condition)? {
+            then_block
+        } else {
+            match else_block {
+                Some(else_block) => else_block,
+                None => return Ok(ControlFlow::Normal),
+            }
+        };
+        self.eval_body(block, Vec::new())
+    }
+
+    // runs statements in order, stopping early at the first break, continue or return
+    fn eval_block(&mut self, statements: &[Stmt]) -> Result<ControlFlow, Box<RuntimeError>> {
+        for statement in statements {
+            let flow = self.eval_statement(statement)?;
+            if !matches!(flow, ControlFlow::Normal) {
+                return Ok(flow);
+            }
+        }
+        Ok(ControlFlow::Normal)
+    }
+
+    fn eval_while_loop(
+        &mut self,
+        condition: &Expr,
+        body: &Body,
+    ) -> Result<ControlFlow, Box<RuntimeError>> {
+        // every iteration gets a fresh scope, so the body's declarations start over each time
+        while self.eval_condition(condition)? {
+            // an empty body runs no statements, so the iteration itself is a step
+            self.tick(condition.span)?;
+            match self.eval_body(body, Vec::new())? {
+                ControlFlow::Break => break,
+                ControlFlow::Normal | ControlFlow::Continue => {}
+                flow @ (ControlFlow::Return(_) | ControlFlow::TailCall { .. }) => return Ok(flow),
+            }
+        }
+        Ok(ControlFlow::Normal)
+    }
+
+    // like a while loop with the condition checked after the body; `continue` goes on to the
+    // condition
+    fn eval_do_while_loop(
+        &mut self,
+        body: &Body,
+        condition: &Expr,
+    ) -> Result<ControlFlow, Box<RuntimeError>> {
+        loop {
+            self.tick(condition.span)?;
+            match self.eval_body(body, Vec::new())? {
+                ControlFlow::Break => break,
+                ControlFlow::Normal | ControlFlow::Continue => {}
+                flow @ (ControlFlow::Return(_) | ControlFlow::TailCall { .. }) => return Ok(flow),
+            }
+            if !self.eval_condition(condition)? {
+                break;
+            }
+        }
+        Ok(ControlFlow::Normal)
+    }
+
+    // counts from start to end inclusive; like a while loop, every iteration runs in a fresh
+    // scope, which also holds that iteration's counter
+    fn eval_for_loop(
+        &mut self,
+        start: &Expr,
+        end: &Expr,
+        body: &Body,
+    ) -> Result<ControlFlow, Box<RuntimeError>> {
+        let start_span = start.span;
+        let start = self.eval_number(start)?;
+        let end = self.eval_number(end)?;
+
+        self.iterate((start..=end).map(Value::Number), start_span, body)
+    }
+
+    fn eval_for_in(
+        &mut self,
+        iterable: &Expr,
+        body: &Body,
+    ) -> Result<ControlFlow, Box<RuntimeError>> {
+        let value = self.eval_expression(iterable)?;
+        match value.elements() {
+            Some(elements) => self.iterate(elements, iterable.span, body),
+            None => Err(RuntimeError::new(
+                RuntimeErrorKind::NotIterable(builtins::type_name(&value)),
+                iterable.span,
+            )),
+        }
+    }
+
+    // runs a loop's body with each value as its variable
+    fn iterate(
+        &mut self,
+        values: impl Iterator<Item = Value>,
+        span: Span,
+        body: &Body,
+    ) -> Result<ControlFlow, Box<RuntimeError>> {
+        for value in values {
+            self.tick(span)?;
+            match self.eval_body(body, vec![value])? {
+                ControlFlow::Break => break,
+                ControlFlow::Normal | ControlFlow::Continue => {}
+                flow @ (ControlFlow::Return(_) | ControlFlow::TailCall { .. }) => return Ok(flow),
+            }
+        }
+        Ok(ControlFlow::Normal)
+    }
+
+    fn eval_number(&mut self, expression: &Expr) -> Result<i64, Box<RuntimeError>> {
+        match self.eval_expression(expression)? {
+            Value::Number(n) => Ok(n),
+            _ => Err(RuntimeError::new(
+                RuntimeErrorKind::NonNumericLoopBound,
+                expression.span,
+            )),
+        }
+    }
+
+    fn eval_condition(&mut self, condition: &Expr) -> Result<bool, Box<RuntimeError>> {
+        match self.eval_expression(condition)? {
+            Bool(b) => Ok(b),
+            _ => Err(RuntimeError::new(
+                RuntimeErrorKind::NonBooleanCondition,
+                condition.span,
+            )),
+        }
+    }
+
+    fn eval_expression(&mut self, expression: &Expr) -> Result<Value, Box<RuntimeError>> {
+        let value = self.eval_operation(expression)?;
+        self.notify(|hook, env| hook.on_expression(expression.span, &value, env));
+        Ok(value)
+    }
+
+    // the value of an expression, without telling the hook about it; like `execute`, it only
+    // runs the cheap kinds itself
+    fn eval_operation(&mut self, expression: &Expr) -> Result<Value, Box<RuntimeError>> {
+        let span = expression.span;
+        match &expression.kind {
+            ExprKind::Literal(value) => Ok(value.clone()),
+            ExprKind::Variable(name, variable) => self.resolve_variable(*name, *variable, span),
+            ExprKind::Binary {
+                left,
+                operator,
+                right,
+            } => self.eval_binary(left, *operator, right, span),
+            ExprKind::Unary { operator, operand } => self.eval_unary(*operator, operand, span),
+            ExprKind::Range { start, end } => self.eval_range(start, end, span),
+            ExprKind::Call {
+                name,
+                function,
+                arguments,
+            } => {
+                let (function, args) = self.eval_call(*name, *function, arguments, span)?;
+                self.call_function(function, args, span)
+            }
+            ExprKind::Block { body, value } => self.eval_block_value(body, value, span),
+        }
+    }
+
+    fn eval_binary(
+        &mut self,
+        left: &Expr,
+        operator: BinOp,
+        right: &Expr,
+        span: Span,
+    ) -> Result<Value, Box<RuntimeError>> {
+        let left = self.eval_expression(left)?;
+
+        // && and || only evaluate the right operand when it decides the result
+        match (&left, operator) {
+            (Bool(false), BinOp::And) => return Ok(Bool(false)),
+            (Bool(true), BinOp::Or) => return Ok(Bool(true)),
+            _ => {}
+        }
+
+        let right = self.eval_expression(right)?;
+        binary_operation(left, operator, right, span)
+    }
+
+    fn eval_unary(
+        &mut self,
+        operator: UnaryOp,
+        operand: &Expr,
+        span: Span,
+    ) -> Result<Value, Box<RuntimeError>> {
+        let value = match (operator, self.eval_expression(operand)?) {
+            (UnaryOp::Not, Bool(b)) => Bool(!b),
+            (UnaryOp::Neg, Value::Number(n)) => match n.checked_neg() {
+                Some(n) => Value::Number(n),
+                None => {
+                    return Err(RuntimeError::new(
+                        RuntimeErrorKind::Overflow(operator.to_string()),
+                        span,
+                    ));
+                }
+            },
+            (UnaryOp::Neg, Value::Float(f)) => Value::Float(-f),
+            _ => {
+                return Err(RuntimeError::new(
+                    RuntimeErrorKind::UnsupportedOperation(operator.to_string()),
+                    span,
+                ));
+            }
+        };
+        Ok(value)
+    }
+
+    fn eval_range(
+        &mut self,
+        start: &Expr,
+        end: &Expr,
+        span: Span,
+    ) -> Result<Value, Box<RuntimeError>> {
+        let value = match (self.eval_expression(start)?, self.eval_expression(end)?) {
+            (Value::Number(start), Value::Number(end)) => Value::Range(start, end),
+            _ => {
+                return Err(RuntimeError::new(
+                    RuntimeErrorKind::UnsupportedOperation("..".to_string()),
+                    span,
+                ));
+            }
+        };
+        Ok(value)
+    }
+
+    // runs the statements of a block expression, then evaluates its value in the same scope
+    fn eval_block_value(
+        &mut self,
+        body: &Body,
+        value: &Expr,
+        span: Span,
+    ) -> Result<Value, Box<RuntimeError>> {
+        self.enter_scope(Vec::new(), body.names.clone());
+        let result = self.eval_block(&body.statements).and_then(|flow| {
+            let keyword = match flow {
+                ControlFlow::Normal => return self.eval_expression(value),
+                ControlFlow::Return(_) | ControlFlow::TailCall { .. } => "return",
+                ControlFlow::Break => "break",
+                ControlFlow::Continue => "continue",
+            };
+            Err(RuntimeError::new(
+                RuntimeErrorKind::JumpOutOfBlock(keyword.to_string()),
+                span,
+            ))
+        });
+        self.exit_scope();
+        result
+    }
+
+    // the function a call refers to and its evaluated arguments
+    fn eval_call(
+        &mut self,
+        name: Symbol,
+        variable: Variable,
+        arguments: &[Expr],
+        span: Span,
+    ) -> Result<(Function, Vec<Value>), Box<RuntimeError>> {
+        let function = match self.resolve_variable(name, variable, span) {
+            Ok(Value::Function(function)) => function,
+            Ok(_) => {
+                return Err(RuntimeError::new(
+                    RuntimeErrorKind::NotCallable(name.to_string()),
+                    span,
+                ));
+            }
+            Err(_) => {
+                return Err(RuntimeError::new(
+                    RuntimeErrorKind::UnknownFunction(name.to_string()),
+                    span,
+                ));
+            }
+        };
+
+        let args = arguments
+            .iter()
+            .map(|arg| self.eval_expression(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((function, args))
+    }
+
+    fn call_function(
+        &mut self,
+        mut function: Function,
+        mut args: Vec<Value>,
+        mut span: Span,
+    ) -> Result<Value, Box<RuntimeError>> {
+        // every tail call made by the body replaces the current call and goes round again
+        loop {
+            let declared = match function {
+                Function::Declared(function) => function,
+                Function::Native(builtin) => return self.call_native(builtin, &args, span),
+                Function::Host(host) => return call_host(&host, &args, span),
+            };
+
+            let code = &declared.code;
+            if args.len() != code.params.len() {
+                return Err(RuntimeError::new(
+                    RuntimeErrorKind::ArityMismatch {
+                        name: code.name.to_string(),
+                        expected: code.params.len(),
+                        found: args.len(),
+                    },
+                    span,
+                ));
+            }
+
+            // a tail call takes the place of its caller, so only calls that nest are counted
+            if let Some(max_call_depth) = self.limits.max_call_depth
+                && self.calls.len() >= max_call_depth
+            {
+                return Err(RuntimeError::new(
+                    RuntimeErrorKind::CallDepthExceeded(max_call_depth),
+                    span,
+                ));
+            }
+
+            // the body runs in a frame on top of the function's closure, not the caller's
+            // scope; the arguments are its first slots
+            let frame = Scope::new(args, code.body.names.clone(), declared.closure.clone());
+            let caller = self.scope.replace(frame);
+            self.calls.push(Frame {
+                function: code.name,
+                call: span,
+            });
+            self.notify(|hook, env| hook.on_call(code.name, span, env));
+            // the body's own returns can be tail calls whatever the caller is trying
+            let outer_try_depth = std::mem::take(&mut self.try_depth);
+            let result = self.eval_block(&code.body.statements).map_err(|mut error| {
+                // the innermost call an error leaves records all the calls it happened in
+                if error.trace.is_empty() {
+                    error.trace = self.calls.iter().rev().copied().collect();
+                }
+                error
+            });
+            self.try_depth = outer_try_depth;
+            self.notify(|hook, env| hook.on_return(code.name, env));
+            self.calls.pop();
+            self.scope = caller;
+
+            match result? {
+                ControlFlow::Return(val) => return Ok(val),
+                ControlFlow::TailCall {
+                    function: next,
+                    args: next_args,
+                    span: next_span,
+                } => {
+                    function = next;
+                    args = next_args;
+                    span = next_span;
+                }
+                _ => return Ok(Value::Void),
+            }
+        }
+    }
+
+    fn call_native(
+        &mut self,
+        builtin: &Builtin,
+        args: &[Value],
+        span: Span,
+    ) -> Result<Value, Box<RuntimeError>> {
+        if builtin.uses_file_system() && !self.limits.allow_fs {
+            return Err(RuntimeError::new(
+                RuntimeErrorKind::FileSystemDisabled(builtin.name.to_string()),
+                span,
+            ));
+        }
+        (builtin.function)(args).map_err(|kind| RuntimeError::new(kind, span))
+    }
+}
+
+fn call_host(host: &HostFunction, args: &[Value], span: Span) -> Result<Value, Box<RuntimeError>> {
+    if args.len() != host.arity {
+        return Err(RuntimeError::new(
+            RuntimeErrorKind::ArityMismatch {
+                name: host.name.clone(),
+                expected: host.arity,
+                found: args.len(),
+            },
+            span,
+        ));
+    }
+    Ok((host.function)(args))
+}
+
+// the value of a binary operation on operands already evaluated; kept out of `eval_binary`, which
+// every nested expression recurses through
+fn binary_operation(
+    left: Value,
+    operator: BinOp,
+    right: Value,
+    span: Span,
+) -> Result<Value, Box<RuntimeError>> {
+    let value = match (left, operator, right) {
+        (
+            Value::Number(left),
+            op @ (BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Rem),
+            Value::Number(right),
+        ) => {
+            let result = integer_arithmetic(left, op, right)
+                .map_err(|kind| RuntimeError::new(kind, span))?;
+            Value::Number(result)
+        }
+        (Value::String(left), BinOp::Add, Value::String(right)) => Value::String(left + &right),
+
+        (Value::Number(left), BinOp::Gt, Value::Number(right)) => Value::Bool(left > right),
+        (Value::Number(left), BinOp::Lt, Value::Number(right)) => Value::Bool(left < right),
+        (Value::Number(left), BinOp::Ge, Value::Number(right)) => Value::Bool(left >= right),
+        (Value::Number(left), BinOp::Le, Value::Number(right)) => Value::Bool(left <= right),
+
+        (l, op @ (BinOp::Eq | BinOp::Ne), r) => {
+            if !l.is_comparable(&r) {
+                return Err(RuntimeError::new(
+                    RuntimeErrorKind::Incomparable {
+                        operator: op.to_string(),
+                        left: builtins::type_name(&l),
+                        right: builtins::type_name(&r),
+                    },
+                    span,
+                ));
+            }
+            Bool((l == r) == (op == BinOp::Eq))
+        }
+        (Bool(_), BinOp::And | BinOp::Or, Bool(right)) => Bool(right),
+
+        // at least one operand is a float, so both are widened
+        (l, op, r) if l.as_float().is_some() && r.as_float().is_some() => {
+            let (left, right) = (l.as_float().unwrap(), r.as_float().unwrap());
+            match op {
+                BinOp::Add => Value::Float(left + right),
+                BinOp::Sub => Value::Float(left - right),
+                BinOp::Mul => Value::Float(left * right),
+                BinOp::Div => Value::Float(left / right),
+                BinOp::Rem => Value::Float(left % right),
+                BinOp::Gt => Value::Bool(left > right),
+                BinOp::Lt => Value::Bool(left < right),
+                BinOp::Ge => Value::Bool(left >= right),
+                BinOp::Le => Value::Bool(left <= right),
+                BinOp::Eq | BinOp::Ne | BinOp::And | BinOp::Or => {
+                    return Err(RuntimeError::new(
+                        RuntimeErrorKind::UnsupportedOperation(operator.to_string()),
+                        span,
+                    ));
+                }
+            }
+        }
+        _ => {
+            return Err(RuntimeError::new(
+                RuntimeErrorKind::UnsupportedOperation(operator.to_string()),
+                span,
+            ));
+        }
+    };
+    Ok(value)
+}
+
+// `+ - * / %` on numbers, which fail instead of wrapping around or panicking
+fn integer_arithmetic(left: i64, operator: BinOp, right: i64) -> Result<i64, RuntimeErrorKind> {
+    if matches!(operator, BinOp::Div | BinOp::Rem) && right == 0 {
+        return Err(RuntimeErrorKind::DivisionByZero);
+    }
+    let result = match operator {
+        BinOp::Add => left.checked_add(right),
+        BinOp::Sub => left.checked_sub(right),
+        BinOp::Mul => left.checked_mul(right),
+        BinOp::Div => left.checked_div(right),
+        _ => left.checked_rem(right),
+    };
+    result.ok_or_else(|| RuntimeErrorKind::Overflow(operator.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Expression, ExpressionKind, Spelling, Statement, StatementKind};
+
+    fn number(n: i64) -> Expression {
+        Expression::new(ExpressionKind::Number(n, n.to_string()), Span::default())
+    }
+
+    fn float(f: f64) -> Expression {
+        Expression::new(ExpressionKind::Float(f, f.to_string()), Span::default())
+    }
+
+    fn string(s: &str) -> Expression {
+        Expression::new(
+            ExpressionKind::String(s.to_string(), crate::formatter::quote(s)),
+            Span::default(),
+        )
+    }
+
+    fn var(name: &str) -> Expression {
+        Expression::new(ExpressionKind::Variable(name.into()), Span::default())
+    }
+
+    fn bin(left: Expression, op: BinOp, right: Expression) -> Expression {
+        Expression::new(
+            ExpressionKind::BinaryOperation {
+                left: Box::new(left),
+                operator: op,
+                right: Box::new(right),
+                spelling: Spelling::Symbol,
+            },
+            Span::default(),
+        )
+    }
+
+    fn parse(source: &str) -> Vec<Statement> {
+        let tokens = crate::lexer::Lexer::new(source).parse().unwrap();
+        crate::parser::Parser::new(tokens).parse().unwrap()
+    }
+
+    fn run(source: &str) -> Interpreter {
+        let program = parse(source);
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(&program).unwrap();
+        interpreter
+    }
+
+    // the error running `source` fails with
+    fn run_error(source: &str) -> RuntimeError {
+        Interpreter::new().interpret(&parse(source)).unwrap_err()
+    }
+
+    fn global(interpreter: &Interpreter, name: &str) -> Option<Value> {
+        interpreter
+            .globals
+            .borrow()
+            .values
+            .get(&Symbol::intern(name))
+            .cloned()
+    }
+
+    fn declare(name: &str, value: Expression) -> Statement {
+        Statement::new(
+            StatementKind::Declaration(name.into(), value, None),
+            Span::default(),
+        )
+    }
+
+    #[test]
+    fn test_variable_assignment() {
+        let program = vec![declare("x", number(10))];
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(&program).unwrap();
+
+        assert_eq!(global(&interpreter, "x"), Some(Value::Number(10)));
     }
 
     #[test]
     fn test_expression_evaluation() {
         let program = vec![
-            Statement::Assignment("x".to_string(), number(5)),
-            Statement::Assignment("y".to_string(), bin(var("x"), "+", number(3))),
+            declare("x", number(5)),
+            declare("y", bin(var("x"), BinOp::Add, number(3))),
         ];
 
         let mut interpreter = Interpreter::new();
-        interpreter.interpret(program);
+        interpreter.interpret(&program).unwrap();
 
-        assert_eq!(
-            interpreter.environments.pop().unwrap().get("y"),
-            Some(&Value::Number(8))
-        );
+        assert_eq!(global(&interpreter, "y"), Some(Value::Number(8)));
     }
 
     #[test]
     fn test_operator_precedence() {
         // x = 1 + 2 * 3
-        let expr = bin(number(1), "+", bin(number(2), "*", number(3)));
+        let expr = bin(number(1), BinOp::Add, bin(number(2), BinOp::Mul, number(3)));
 
-        let program = vec![Statement::Assignment("x".to_string(), expr)];
+        let program = vec![declare("x", expr)];
         let mut interpreter = Interpreter::new();
-        interpreter.interpret(program);
+        interpreter.interpret(&program).unwrap();
 
-        assert_eq!(
-            interpreter.environments.pop().unwrap().get("x"),
-            Some(&Value::Number(7))
-        );
+        assert_eq!(global(&interpreter, "x"), Some(Value::Number(7)));
     }
 
     #[test]
     fn test_parentheses_grouping() {
         // x = (1 + 2) * 3
-        let expr = bin(bin(number(1), "+", number(2)), "*", number(3));
+        let expr = bin(bin(number(1), BinOp::Add, number(2)), BinOp::Mul, number(3));
 
-        let program = vec![Statement::Assignment("x".to_string(), expr)];
+        let program = vec![declare("x", expr)];
         let mut interpreter = Interpreter::new();
-        interpreter.interpret(program);
+        interpreter.interpret(&program).unwrap();
+
+        assert_eq!(global(&interpreter, "x"), Some(Value::Number(9)));
+    }
+
+    #[test]
+    fn test_string_concatenation() {
+        let expr = bin(string("frog"), BinOp::Add, string("gy"));
+
+        let program = vec![declare("s", expr)];
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(&program).unwrap();
+
+        assert_eq!(
+            global(&interpreter, "s"),
+            Some(Value::String("froggy".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_mixed_float_arithmetic() {
+        // x = 1 / 4.0 + 2
+        let expr = bin(
+            bin(number(1), BinOp::Div, float(4.0)),
+            BinOp::Add,
+            number(2),
+        );
+
+        let program = vec![declare("x", expr)];
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(&program).unwrap();
+
+        assert_eq!(global(&interpreter, "x"), Some(Value::Float(2.25)));
+    }
+
+    #[test]
+    fn test_unknown_variable_is_an_error() {
+        let program = vec![Statement::new(
+            StatementKind::Print {
+                value: var("ghost"),
+                debug: false,
+            },
+            Span::default(),
+        )];
+        let mut interpreter = Interpreter::new();
+
+        assert_eq!(
+            interpreter.interpret(&program),
+            Err(*RuntimeError::new(
+                RuntimeErrorKind::UnknownVariable("ghost".to_string()),
+                Span::default(),
+            ))
+        );
+    }
+
+    #[test]
+    fn test_for_loop_is_inclusive() {
+        // let sum = 0; for i = 1 to 4 { sum = sum + i; }
+        let program = vec![
+            declare("sum", number(0)),
+            Statement::new(
+                StatementKind::For {
+                    variable: "i".into(),
+                    annotation: None,
+                    start: number(1),
+                    end: number(4),
+                    body: vec![Statement::new(
+                        StatementKind::Assignment(
+                            "sum".into(),
+                            bin(var("sum"), BinOp::Add, var("i")),
+                        ),
+                        Span::default(),
+                    )],
+                },
+                Span::default(),
+            ),
+        ];
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(&program).unwrap();
+
+        assert_eq!(global(&interpreter, "sum"), Some(Value::Number(10)));
+        assert_eq!(global(&interpreter, "i"), None);
+    }
+
+    #[test]
+    fn test_break_and_continue() {
+        let interpreter = run("
+            let evens = 0;
+            let i = 0;
+            while true {
+                i = i + 1;
+                if i > 10 { break; }
+                if i / 2 * 2 == i { evens = evens + i; continue; }
+            }
+            let last = 0;
+            for n = 1 to 100 {
+                { if n == 7 { break; } }
+                last = n;
+            }
+        ");
+
+        assert_eq!(global(&interpreter, "evens"), Some(Value::Number(30)));
+        assert_eq!(global(&interpreter, "last"), Some(Value::Number(6)));
+    }
+
+    #[test]
+    fn test_do_while_runs_at_least_once() {
+        let interpreter = run("
+            let runs = 0;
+            do { runs = runs + 1; } while false;
+            let i = 0;
+            let skipped = 0;
+            do {
+                i = i + 1;
+                if i == 2 { continue; }
+                if i == 5 { break; }
+                skipped = skipped + i;
+            } while i < 10;
+        ");
+
+        assert_eq!(global(&interpreter, "runs"), Some(Value::Number(1)));
+        // continue goes on to the condition, break leaves the loop
+        assert_eq!(global(&interpreter, "i"), Some(Value::Number(5)));
+        assert_eq!(global(&interpreter, "skipped"), Some(Value::Number(8)));
+    }
+
+    #[test]
+    fn test_try_handles_runtime_errors() {
+        let interpreter = run("
+            func divide(a: number, b: number): number {
+                let unused = 0;
+                return a / b;
+            }
+            let caught = \"\";
+            let after = 0;
+            {
+                let outer = 1;
+                try {
+                    let inner = 2;
+                    divide(1, 0);
+                    after = 1;
+                } handle err {
+                    caught = err;
+                    after = outer;
+                }
+            }
+            let custom = \"\";
+            try { raise \"frog \" + \"missing\"; } handle err { custom = err; }
+            func raising(): number { raise \"tail\"; }
+            func returns_raising(): number {
+                try { return raising(); } handle err { return 0; }
+            }
+            let returned = returns_raising();
+        ");
+
+        assert_eq!(
+            global(&interpreter, "caught"),
+            Some(Value::String("division by zero".to_string()))
+        );
+        // the handler runs in the scopes the try statement is in, not the failed call's
+        assert_eq!(global(&interpreter, "after"), Some(Value::Number(1)));
+        assert_eq!(
+            global(&interpreter, "custom"),
+            Some(Value::String("frog missing".to_string()))
+        );
+        // the call a return in a try body makes is caught like any other
+        assert_eq!(global(&interpreter, "returned"), Some(Value::Number(0)));
+
+        // limits can't be handled
+        let program = parse("try { while true { } } handle err { }");
+        let mut interpreter = Interpreter::new();
+        interpreter.limits.max_steps = Some(100);
+        assert_eq!(
+            interpreter.interpret(&program).map_err(|e| e.kind),
+            Err(RuntimeErrorKind::StepLimitExceeded(100))
+        );
+    }
+
+    #[test]
+    fn test_block_expressions() {
+        let interpreter = run("
+            let a = 10;
+            let x = { let a = 1; let b = a + 1; a + b };
+            let twice = { let n = 21; func double(): number { return n * 2; } double };
+            let answer = twice();
+        ");
+
+        assert_eq!(global(&interpreter, "x"), Some(Value::Number(3)));
+        // the block's declarations don't leak out of it
+        assert_eq!(global(&interpreter, "a"), Some(Value::Number(10)));
+        assert_eq!(global(&interpreter, "answer"), Some(Value::Number(42)));
+
+        assert_eq!(
+            run_error("while true { let x = { break; 1 }; }").kind,
+            RuntimeErrorKind::JumpOutOfBlock("break".to_string())
+        );
+    }
+
+    #[test]
+    fn test_return_from_inside_loop() {
+        let interpreter = run("
+            func first_over(limit: number): number {
+                let i = 0;
+                while true {
+                    i = i + 1;
+                    if i * i > limit { return i; }
+                }
+                return 0;
+            }
+            let root = first_over(50);
+        ");
+
+        assert_eq!(global(&interpreter, "root"), Some(Value::Number(8)));
+    }
+
+    #[test]
+    fn test_for_in_goes_over_ranges_and_strings() {
+        let interpreter = run("
+            let sum = 0;
+            let steps = 0..4;
+            for i in steps { sum = sum + i; }
+            for i in 5..5 { sum = 100; }
+            let kind = typeof(steps);
+        ");
+
+        assert_eq!(global(&interpreter, "sum"), Some(Value::Number(6)));
+        assert_eq!(global(&interpreter, "steps"), Some(Value::Range(0, 4)));
+        assert_eq!(
+            global(&interpreter, "kind"),
+            Some(Value::String("range".to_string()))
+        );
+        let interpreter = run("
+            let reversed = \"\";
+            for c in \"größe\" { reversed = c + reversed; }
+        ");
+        assert_eq!(
+            global(&interpreter, "reversed"),
+            Some(Value::String("eßörg".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_return_from_nested_blocks_skips_the_rest() {
+        let interpreter = run("
+            let calls = 0;
+            func find(target: number): number {
+                for i = 1 to 10 {
+                    {
+                        if i == target {
+                            return i * 100;
+                        }
+                    }
+                    calls = calls + 1;
+                }
+                calls = -1;
+                return 0;
+            }
+            let found = find(3);
+        ");
+
+        assert_eq!(global(&interpreter, "found"), Some(Value::Number(300)));
+        assert_eq!(global(&interpreter, "calls"), Some(Value::Number(2)));
+    }
+
+    #[test]
+    fn test_logical_operators_short_circuit() {
+        // the right-hand sides would fail with an unknown function if they were evaluated
+        let interpreter = run("
+            let a = false && missing();
+            let b = true || missing();
+            let c = !(1 > 2) && true;
+            let d = not true and missing();
+            let e = 1 < 2 or missing();
+        ");
+
+        assert_eq!(global(&interpreter, "a"), Some(Value::Bool(false)));
+        assert_eq!(global(&interpreter, "b"), Some(Value::Bool(true)));
+        assert_eq!(global(&interpreter, "c"), Some(Value::Bool(true)));
+        assert_eq!(global(&interpreter, "d"), Some(Value::Bool(false)));
+        assert_eq!(global(&interpreter, "e"), Some(Value::Bool(true)));
+    }
+
+    #[test]
+    fn test_unary_minus() {
+        let interpreter = run("
+            let a = -5;
+            let b = -(a * 2) - 1;
+            let c = -1.5;
+        ");
+
+        assert_eq!(global(&interpreter, "a"), Some(Value::Number(-5)));
+        assert_eq!(global(&interpreter, "b"), Some(Value::Number(9)));
+        assert_eq!(global(&interpreter, "c"), Some(Value::Float(-1.5)));
+    }
+
+    #[test]
+    fn test_modulo_and_comparisons() {
+        let interpreter = run("
+            let rem = 17 % 5;
+            let ge = rem >= 2;
+            let le = 2.5 <= 2;
+            let ne = rem != 3;
+        ");
+
+        assert_eq!(global(&interpreter, "rem"), Some(Value::Number(2)));
+        assert_eq!(global(&interpreter, "ge"), Some(Value::Bool(true)));
+        assert_eq!(global(&interpreter, "le"), Some(Value::Bool(false)));
+        assert_eq!(global(&interpreter, "ne"), Some(Value::Bool(true)));
+    }
+
+    #[test]
+    fn test_equality() {
+        let interpreter = run("
+            enum Color { Red, Green }
+            func hop(): number { return 1; }
+            let numbers = 2 == 2.0 and 1 != 2;
+            let strings = \"frog\" == \"frog\" and \"frog\" != \"toad\";
+            let variants = Color.Red == Color.Red and Color.Red != Color.Green;
+            let functions = hop == hop and hop != abs;
+            let ranges = 0..2 == 0..2;
+            let nothing = 1 != none and none == none;
+        ");
+        for name in [
+            "numbers",
+            "strings",
+            "variants",
+            "functions",
+            "ranges",
+            "nothing",
+        ] {
+            assert_eq!(
+                global(&interpreter, name),
+                Some(Value::Bool(true)),
+                "{}",
+                name
+            );
+        }
+
+        // values the typechecker wouldn't compare are an error rather than unequal
+        let error = run_error("let same = 1 == \"1\";");
+        assert_eq!(
+            error.kind,
+            RuntimeErrorKind::Incomparable {
+                operator: "==".to_string(),
+                left: "number".to_string(),
+                right: "string".to_string(),
+            }
+        );
+        assert_eq!(
+            error.kind.to_string(),
+            "cannot compare number and string with =="
+        );
+    }
+
+    #[test]
+    fn test_value_display() {
+        assert_eq!(Value::Number(-5).to_string(), "-5");
+        assert_eq!(Value::Float(2.0).to_string(), "2.0");
+        assert_eq!(Value::Float(0.25).to_string(), "0.25");
+        assert_eq!(Value::Bool(true).to_string(), "true");
+        assert_eq!(Value::String("frog".into()).to_string(), "frog");
+        assert_eq!(Value::Void.to_string(), "void");
+    }
+
+    #[test]
+    fn test_builtin_calls() {
+        let interpreter = run("
+            let a = max(abs(-4), pow(2, 3));
+            let b = sqrt_int(a + 1);
+        ");
+
+        assert_eq!(global(&interpreter, "a"), Some(Value::Number(8)));
+        assert_eq!(global(&interpreter, "b"), Some(Value::Number(3)));
+    }
+
+    #[test]
+    fn test_functions_as_values() {
+        let interpreter = run("
+            func twice(f: func(number): number, n: number): number {
+                return f(f(n));
+            }
+            func inc(n: number): number { return n + 1; }
+            func pick(up: bool): func(number): number {
+                if up { return inc; }
+                return abs;
+            }
+
+            let a = twice(inc, 5);
+            let g = pick(false);
+            let b = twice(g, -7);
+            let same = g == abs;
+        ");
+
+        assert_eq!(global(&interpreter, "a"), Some(Value::Number(7)));
+        assert_eq!(global(&interpreter, "b"), Some(Value::Number(7)));
+        assert_eq!(global(&interpreter, "same"), Some(Value::Bool(true)));
+    }
+
+    #[test]
+    fn test_closures_capture_their_scope() {
+        let interpreter = run("
+            func make_counter(): func(): number {
+                let count = 0;
+                func next(): number {
+                    count = count + 1;
+                    return count;
+                }
+                return next;
+            }
+
+            let counter = make_counter();
+            let other = make_counter();
+            counter();
+            counter();
+            let a = counter();
+            let b = other();
+        ");
+
+        assert_eq!(global(&interpreter, "a"), Some(Value::Number(3)));
+        assert_eq!(global(&interpreter, "b"), Some(Value::Number(1)));
+    }
+
+    #[test]
+    fn test_functions_cannot_see_caller_locals() {
+        let source = "
+            func peek(): number {
+                return secret;
+            }
+            func caller(): number {
+                let secret = 7;
+                return peek();
+            }
+            let x = caller();
+        ";
+        let program = parse(source);
+        let mut interpreter = Interpreter::new();
+
+        let Err(error) = interpreter.interpret(&program) else {
+            panic!("expected peek to fail");
+        };
+        assert_eq!(
+            error.kind,
+            RuntimeErrorKind::UnknownVariable("secret".to_string())
+        );
+        // the failed call leaves the interpreter back in the global scope
+        assert!(interpreter.scope.is_none());
+    }
+
+    #[test]
+    fn test_top_level_functions_are_hoisted() {
+        let interpreter = run("
+            let answer = main();
+            func main(): number {
+                return helper() * 2;
+            }
+            func helper(): number {
+                return 21;
+            }
+        ");
+        assert_eq!(global(&interpreter, "answer"), Some(Value::Number(42)));
+    }
+
+    #[test]
+    fn test_functions_declared_in_blocks_are_local() {
+        let interpreter = run("
+            func outer(): number {
+                func helper(n: number): number {
+                    return n * 2;
+                }
+                return helper(21);
+            }
+            let a = outer();
+            {
+                func helper(): number {
+                    return 1;
+                }
+                let b = helper();
+            }
+        ");
+        assert_eq!(global(&interpreter, "a"), Some(Value::Number(42)));
+        assert_eq!(global(&interpreter, "helper"), None);
+
+        let source = "{ func inner(): number { return 1; } } let c = inner();";
+        let error = run_error(source);
+        assert_eq!(
+            error.kind,
+            RuntimeErrorKind::UnknownFunction("inner".to_string())
+        );
+    }
+
+    #[test]
+    fn test_assigning_a_constant_is_a_runtime_error() {
+        // the typechecker rejects this program, the interpreter is the safety net
+        let program = parse("const x = 1; x = 2;");
+        let mut interpreter = Interpreter::new();
+
+        let Err(error) = interpreter.interpret(&program) else {
+            panic!("expected the assignment to fail");
+        };
+        assert_eq!(
+            error.kind,
+            RuntimeErrorKind::AssignmentToConstant("x".to_string())
+        );
+        assert_eq!(global(&interpreter, "x"), Some(Value::Number(1)));
+    }
+
+    #[test]
+    fn test_integer_arithmetic_errors() {
+        assert_eq!(
+            integer_arithmetic(1, BinOp::Div, 0),
+            Err(RuntimeErrorKind::DivisionByZero)
+        );
+        assert_eq!(
+            integer_arithmetic(1, BinOp::Rem, 0),
+            Err(RuntimeErrorKind::DivisionByZero)
+        );
+        assert_eq!(
+            integer_arithmetic(i64::MAX, BinOp::Add, 1),
+            Err(RuntimeErrorKind::Overflow("+".to_string()))
+        );
+        assert_eq!(
+            integer_arithmetic(i64::MIN, BinOp::Div, -1),
+            Err(RuntimeErrorKind::Overflow("/".to_string()))
+        );
+        assert_eq!(integer_arithmetic(7, BinOp::Rem, -3), Ok(1));
+    }
+
+    #[test]
+    fn test_division_by_zero_reports_the_expression() {
+        let error = run_error("let x = 0;\nlet y = 10 / x;");
+        assert_eq!(error.kind, RuntimeErrorKind::DivisionByZero);
+        assert_eq!((error.span.line, error.span.column), (2, 9));
+    }
+
+    #[test]
+    fn test_errors_record_the_calls_they_happened_in() {
+        let source = "\
+func inner(n: number): number { return 10 / n; }
+func outer(n: number): number {
+    try { inner(0); } handle e { }
+    let x = 1 + inner(n);
+    return x;
+}
+func tail(n: number): number { return outer(n); }
+croak tail(0);";
+
+        let error = run_error(source);
+        let trace: Vec<(String, usize, usize)> = error
+            .trace
+            .iter()
+            .map(|frame| {
+                let call = frame.call;
+                (frame.function.to_string(), call.line, call.column)
+            })
+            .collect();
+        // the handled error left no trace behind, and the tail call replaced `tail`
+        assert_eq!(
+            trace,
+            [("inner".to_string(), 4, 17), ("outer".to_string(), 7, 39)]
+        );
+    }
+
+    #[test]
+    fn test_loop_iterations_get_fresh_scopes() {
+        // each `get` closes over the `n` of its own iteration
+        let interpreter = run("
+            func zero(): number {
+                return 0;
+            }
+            let first = zero;
+            let second = zero;
+            for i = 1 to 2 {
+                let n = i * 10;
+                func get(): number {
+                    return n;
+                }
+                if i == 1 {
+                    first = get;
+                } else {
+                    second = get;
+                }
+            }
+            let a = first();
+            let b = second();
+        ");
+
+        assert_eq!(global(&interpreter, "a"), Some(Value::Number(10)));
+        assert_eq!(global(&interpreter, "b"), Some(Value::Number(20)));
+    }
+
+    #[test]
+    fn test_block_declarations_do_not_leak() {
+        let interpreter = run("
+            let x = 1;
+            let i = 0;
+            while i < 2 {
+                let x = 10;
+                i = i + 1;
+            }
+            if true {
+                let x = 2;
+            }
+        ");
+
+        assert_eq!(global(&interpreter, "x"), Some(Value::Number(1)));
+    }
+
+    #[test]
+    fn test_deep_tail_recursion() {
+        // far deeper than the Rust stack allows without reusing the frame
+        let interpreter = run("
+            func count(n: number, acc: number): number {
+                if n == 0 {
+                    return acc;
+                }
+                return count(n - 1, acc + 1);
+            }
+            let total = count(100000, 0);
+        ");
+
+        assert_eq!(global(&interpreter, "total"), Some(Value::Number(100000)));
+    }
+
+    #[test]
+    fn test_call_depth_limit() {
+        let mut interpreter = Interpreter::new();
+        interpreter.limits.max_call_depth = Some(50);
+        let source = "
+            func down(n: number): number {
+                if n == 0 {
+                    return 0;
+                }
+                return down(n - 1) + 1;
+            }
+        ";
+
+        let deep = parse(&format!("{}let d = down(50);", source));
+        let error = interpreter.interpret(&deep).unwrap_err();
+        assert_eq!(error.kind, RuntimeErrorKind::CallDepthExceeded(50));
+        assert_eq!(error.trace.len(), 50);
+
+        // unlike the other limits, a script can handle running into it
+        let handled = parse(&format!(
+            "{}let d = 0; try {{ d = down(1000); }} handle err {{ d = down(49); }}",
+            source
+        ));
+        interpreter.interpret(&handled).unwrap();
+        assert_eq!(global(&interpreter, "d"), Some(Value::Number(49)));
+    }
+
+    #[test]
+    fn test_limits_stop_endless_loops() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.limits.max_steps = Some(100);
+        let error = interpreter.interpret(&parse("while true {}")).unwrap_err();
+        assert_eq!(error.kind, RuntimeErrorKind::StepLimitExceeded(100));
+        // the budget is per run, not shared with the previous one
+        interpreter.interpret(&parse("let x = 1;")).unwrap();
+
+        interpreter.limits = Limits {
+            max_steps: None,
+            timeout: Some(Duration::from_millis(10)),
+            ..Limits::default()
+        };
+        let error = interpreter
+            .interpret(&parse("let i = 0; while true { i = i + 1; }"))
+            .unwrap_err();
+        assert_eq!(
+            error.kind,
+            RuntimeErrorKind::TimedOut(Duration::from_millis(10))
+        );
+
+        // an interrupt can't be handled either
+        interpreter.limits = Limits::default();
+        interpreter.interrupt = Some(Arc::new(AtomicBool::new(true)));
+        let error = interpreter
+            .interpret(&parse("try { while true {} } handle err {}"))
+            .unwrap_err();
+        assert_eq!(error.kind, RuntimeErrorKind::Interrupted);
+    }
+
+    #[test]
+    fn test_snapshot_restore_and_reset() {
+        let mut interpreter = Interpreter::new();
+        interpreter.register_native("seven", 0, |_| Value::Number(7));
+        interpreter.interpret(&parse("let x = 1;")).unwrap();
+
+        let snapshot = interpreter.snapshot();
+        interpreter.interpret(&parse("x = 2; let y = 3;")).unwrap();
+        interpreter.restore(snapshot);
+        assert_eq!(global(&interpreter, "x"), Some(Value::Number(1)));
+        assert_eq!(global(&interpreter, "y"), None);
+
+        interpreter.reset();
+        assert_eq!(global(&interpreter, "x"), None);
+        assert_eq!(
+            interpreter.interpret(&parse("seven();")),
+            Ok(Value::Number(7))
+        );
+    }
+
+    #[test]
+    fn test_match_runs_only_the_first_matching_arm() {
+        let interpreter = run("
+            func name(n: number): string {
+                match n {
+                    1 => { return \"one\"; }
+                    2 => { return \"two\"; }
+                    2 => { return \"unreachable\"; }
+                    _ => { return \"many\"; }
+                }
+            }
+            let a = name(2);
+            let b = name(7);
+            let c = 0;
+            match 5 {
+                1 => { c = 1; }
+            }
+        ");
+
+        assert_eq!(global(&interpreter, "a"), Some(Value::String("two".into())));
+        assert_eq!(
+            global(&interpreter, "b"),
+            Some(Value::String("many".into()))
+        );
+        // no arm matched, so nothing ran
+        assert_eq!(global(&interpreter, "c"), Some(Value::Number(0)));
+    }
+
+    #[test]
+    fn test_none_values() {
+        let interpreter = run("
+            func first_even(limit: number): number? {
+                for i = 1 to limit {
+                    if i % 2 == 0 { return i; }
+                }
+                return none;
+            }
+            let missing = first_even(1);
+            let found = first_even(5);
+            let is_missing = missing == none;
+        ");
+
+        assert_eq!(global(&interpreter, "missing"), Some(Value::None));
+        assert_eq!(global(&interpreter, "found"), Some(Value::Number(2)));
+        assert_eq!(global(&interpreter, "is_missing"), Some(Value::Bool(true)));
+        assert_eq!(Value::None.to_string(), "none");
+    }
+
+    #[test]
+    fn test_enum_values() {
+        let interpreter = run("
+            enum Color { Red, Green, Blue }
+            let c = Color.Green;
+            let same = c == Color.Green;
+            let different = c != Color.Blue;
+            let name = \"\";
+            match c {
+                Color.Red => { name = \"red\"; }
+                Color.Green => { name = \"green\"; }
+                Color.Blue => { name = \"blue\"; }
+            }
+        ");
+
+        assert_eq!(
+            global(&interpreter, "c"),
+            Some(Value::Enum("Color".into(), "Green".into()))
+        );
+        assert_eq!(global(&interpreter, "same"), Some(Value::Bool(true)));
+        assert_eq!(global(&interpreter, "different"), Some(Value::Bool(true)));
+        assert_eq!(
+            global(&interpreter, "name"),
+            Some(Value::String("green".into()))
+        );
+        assert_eq!(
+            global(&interpreter, "c").unwrap().to_string(),
+            "Color.Green"
+        );
+    }
+
+    #[test]
+    fn test_failed_assertions_report_their_statement() {
+        run("assert 1 < 2; assert_eq 2 + 2, 4.0;");
+
+        let failed = run_error("let x = 3;\nassert x > 5;");
+        assert_eq!(
+            failed.kind,
+            RuntimeErrorKind::AssertionFailed("x > 5".to_string())
+        );
+        assert_eq!((failed.span.line, failed.span.column), (2, 1));
+        assert_eq!(
+            run_error("assert_eq abs(-2), 3;").to_string(),
+            "1:1: assertion failed: abs(-2) == 3 (left: 2, right: 3)"
+        );
+    }
+
+    #[test]
+    fn test_file_access_needs_allow_fs() {
+        let path = std::env::temp_dir().join(format!("froggle-fs-{}.txt", std::process::id()));
+        let source = format!(
+            "write_file(\"{0}\", \"ribbit\"); let text = read_file(\"{0}\");",
+            path.display()
+        );
+        let program = || parse(&source);
+
+        let mut interpreter = Interpreter::new();
+        let error = interpreter.interpret(&program()).unwrap_err();
+        assert_eq!(
+            error.kind,
+            RuntimeErrorKind::FileSystemDisabled("write_file".to_string())
+        );
+        assert!(!path.exists());
 
+        interpreter.limits.allow_fs = true;
+        interpreter.interpret(&program()).unwrap();
         assert_eq!(
-            interpreter.environments.pop().unwrap().get("x"),
-            Some(&Value::Number(9))
+            global(&interpreter, "text"),
+            Some(Value::String("ribbit".into()))
         );
+        std::fs::remove_file(path).unwrap();
     }
 }
@@ -1,110 +1,737 @@
-use crate::lexer::Token::{EOF, Identifier, Keyword, Number, Operator, Punctuation};
+use crate::lexer::Token::{EOF, Identifier, Number, Punctuation};
+use crate::parser::BinOp;
+use crate::symbol::Symbol;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum Token {
     Punctuation(String),
-    Keyword(String),
-    Operator(String),
-    Identifier(String),
-    Number(i32),
+    Keyword(Keyword),
+    Operator(Operator),
+    // `and`, `or` and `not`, the same operators as `&&`, `||` and `!` spelled out
+    WordOperator(Operator),
+    Identifier(Symbol),
+    // the value and the literal it was written as, `255` and `0xFF`, which formatting keeps
+    Number(i64, String),
+    Float(f64, String),
+    // the value and the literal with its quotes and escapes as written, `\u{1F438}` staying
+    // `\u{1F438}` when formatted
+    String(String, String),
     Bool(bool),
     Type(String),
     EOF,
 }
 
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Punctuation(p) => write!(f, "'{}'", p),
+            Token::Keyword(k) => write!(f, "keyword '{}'", k),
+            Token::Operator(op) => write!(f, "operator '{}'", op),
+            Token::WordOperator(op) => write!(f, "operator '{}'", word_operator(*op).unwrap_or("")),
+            Identifier(name) => write!(f, "identifier '{}'", name),
+            Number(_, literal) => write!(f, "number {}", literal),
+            Token::Float(n, _) => write!(f, "float {}", n),
+            Token::String(s, _) => write!(f, "string \"{}\"", s),
+            Token::Bool(b) => write!(f, "'{}'", b),
+            Token::Type(t) => write!(f, "type '{}'", t),
+            EOF => write!(f, "end of file"),
+        }
+    }
+}
+
+// the words reserved by the language, which can't be used as names
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Keyword {
+    Let,
+    Const,
+    Croak,
+    CroakDebug,
+    While,
+    Do,
+    For,
+    To,
+    Break,
+    Continue,
+    Func,
+    Return,
+    If,
+    Else,
+    Import,
+    Match,
+    Enum,
+    Assert,
+    AssertEq,
+    None,
+    Try,
+    Handle,
+    Raise,
+    In,
+}
+
+// how every keyword is spelled; the lexer recognizes keywords by this table, so one the
+// parser handles can't be missing from it
+pub const KEYWORDS: &[(Keyword, &str)] = &[
+    (Keyword::Let, "let"),
+    (Keyword::Const, "const"),
+    (Keyword::Croak, "croak"),
+    (Keyword::CroakDebug, "croak_debug"),
+    (Keyword::While, "while"),
+    (Keyword::Do, "do"),
+    (Keyword::For, "for"),
+    (Keyword::To, "to"),
+    (Keyword::Break, "break"),
+    (Keyword::Continue, "continue"),
+    (Keyword::Func, "func"),
+    (Keyword::Return, "return"),
+    (Keyword::If, "if"),
+    (Keyword::Else, "else"),
+    (Keyword::Import, "import"),
+    (Keyword::Match, "match"),
+    (Keyword::Enum, "enum"),
+    (Keyword::Assert, "assert"),
+    (Keyword::AssertEq, "assert_eq"),
+    (Keyword::None, "none"),
+    (Keyword::Try, "try"),
+    (Keyword::Handle, "handle"),
+    (Keyword::Raise, "raise"),
+    (Keyword::In, "in"),
+];
+
+impl Keyword {
+    pub fn from_word(word: &str) -> Option<Keyword> {
+        KEYWORDS
+            .iter()
+            .find(|(_, spelling)| *spelling == word)
+            .map(|(keyword, _)| *keyword)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        KEYWORDS
+            .iter()
+            .find(|(keyword, _)| *keyword == self)
+            .map_or("", |(_, spelling)| spelling)
+    }
+}
+
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+// `-` is always lexed as subtraction, the parser reads it as negation in front of an operand
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Operator {
+    Binary(BinOp),
+    Not,
+    Assign,
+    // `=>` between a match arm's pattern and its body
+    Arrow,
+    // `..` between the bounds of a range
+    Range,
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operator::Binary(op) => write!(f, "{}", op),
+            Operator::Not => write!(f, "!"),
+            Operator::Assign => write!(f, "="),
+            Operator::Arrow => write!(f, "=>"),
+            Operator::Range => write!(f, ".."),
+        }
+    }
+}
+
+// spelled out forms of `&&`, `||` and `!`
+const WORD_OPERATORS: [(&str, Operator); 3] = [
+    ("and", Operator::Binary(BinOp::And)),
+    ("or", Operator::Binary(BinOp::Or)),
+    ("not", Operator::Not),
+];
+
+// the word `operator` can be written as, if it has one
+pub fn word_operator(operator: Operator) -> Option<&'static str> {
+    WORD_OPERATORS
+        .iter()
+        .find(|(_, op)| *op == operator)
+        .map(|(word, _)| *word)
+}
+
+// location of a piece of source code: byte offsets plus the line and column
+// (both starting at 1) where it begins
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    // span covering everything from the start of `self` to the end of `other`
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            end: other.end,
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+// what a piece of source is, as far as a syntax highlighter cares
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TokenClass {
+    Keyword,
+    Type,
+    Operator,
+    Punctuation,
+    Number,
+    String,
+    Identifier,
+    Comment,
+}
+
+impl TokenClass {
+    fn of(token: &Token) -> TokenClass {
+        match token {
+            Token::Keyword(_) | Token::Bool(_) => TokenClass::Keyword,
+            Token::Type(_) => TokenClass::Type,
+            Token::Operator(_) | Token::WordOperator(_) => TokenClass::Operator,
+            Punctuation(_) | EOF => TokenClass::Punctuation,
+            Number(..) | Token::Float(..) => TokenClass::Number,
+            Token::String(..) => TokenClass::String,
+            Identifier(_) => TokenClass::Identifier,
+        }
+    }
+}
+
+// classifies every token and comment in `source`, in order, for highlighting. Unlike parsing
+// this never fails: after a lex error it carries on with the next character, and unterminated
+// strings and comments are classified up to the end of the source
+pub fn classify(source: &str) -> Vec<(Span, TokenClass)> {
+    let mut lexer = Lexer::new(source);
+    let mut classes = Vec::new();
+    for result in &mut lexer {
+        match result {
+            Ok(SpannedToken { token: EOF, .. }) => {}
+            Ok(spanned) => classes.push((spanned.span, TokenClass::of(&spanned.token))),
+            Err(e) => match e.kind {
+                LexErrorKind::UnterminatedString => classes.push((e.span, TokenClass::String)),
+                LexErrorKind::UnterminatedComment => classes.push((e.span, TokenClass::Comment)),
+                _ => {}
+            },
+        }
+    }
+
+    classes.extend(
+        lexer
+            .comments
+            .iter()
+            .map(|&span| (span, TokenClass::Comment)),
+    );
+    classes.sort_by_key(|(span, _)| span.start);
+    classes
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum LexErrorKind {
+    UnexpectedCharacter(char),
+    UnterminatedString,
+    UnterminatedComment,
+    InvalidNumber(String),
+    // an integer literal too big for a number
+    NumberOutOfRange(String),
+    // a float literal too big for a float, which would be infinite
+    FloatOutOfRange(String),
+    // a word starting with a digit that isn't a number, like `1abc`
+    DigitLeadingIdentifier(String),
+    // a backslash in a string followed by a character with no escape meaning, like `\q`
+    InvalidEscape(String),
+    // a `\u{...}` escape that isn't well-formed or doesn't name a Unicode character
+    InvalidUnicodeEscape(String),
+}
+
+impl fmt::Display for LexErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected character '{}'", c),
+            LexErrorKind::UnterminatedString => write!(f, "unterminated string literal"),
+            LexErrorKind::UnterminatedComment => write!(f, "unterminated block comment"),
+            LexErrorKind::InvalidNumber(literal) => write!(f, "invalid number literal {}", literal),
+            LexErrorKind::NumberOutOfRange(literal) => write!(
+                f,
+                "number literal {} is out of range, numbers go from {} to {}",
+                literal,
+                i64::MIN,
+                i64::MAX
+            ),
+            LexErrorKind::FloatOutOfRange(literal) => {
+                write!(f, "float literal {} is out of range", literal)
+            }
+            LexErrorKind::DigitLeadingIdentifier(word) => {
+                write!(f, "invalid identifier {} starting with a digit", word)
+            }
+            LexErrorKind::InvalidEscape(sequence) => write!(
+                f,
+                "invalid escape sequence {}; strings can use \\n, \\t, \\r, \\0, \\\", \\\\ and \\u{{...}}",
+                sequence
+            ),
+            LexErrorKind::InvalidUnicodeEscape(sequence) => write!(
+                f,
+                "invalid unicode escape {}; it takes 1 to 6 hex digits naming a character, like \\u{{1F438}}",
+                sequence
+            ),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub span: Span,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.kind)
+    }
+}
+
 pub struct Lexer<'a> {
     input: &'a str,
+    // the characters left to lex, with their byte offsets; source is only ever walked a whole
+    // character at a time, so multi-byte characters can't be cut in half
+    chars: Peekable<CharIndices<'a>>,
+    // byte offset of the next character, counted from `offset`
     position: usize,
+    // where the input starts in a larger source its spans index into, 0 for most input
+    offset: usize,
+    line: usize,
+    column: usize,
+    // comments skipped so far; they don't make it into the token stream
+    comments: Vec<Span>,
+    // whether the EOF token has been produced, which ends the stream
+    finished: bool,
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(input: &'a str) -> Lexer<'a> {
-        Lexer { input, position: 0 }
+        Lexer::with_offset(input, 0)
     }
 
-    fn peek(&self) -> Option<char> {
-        if self.is_at_end() {
-            return None;
+    // lexes `input` as if it started `offset` bytes into a larger source, so the spans of
+    // several inputs, e.g. REPL entries, don't overlap. Lines and columns still count from the
+    // start of `input`
+    pub fn with_offset(input: &'a str, offset: usize) -> Lexer<'a> {
+        Lexer {
+            input,
+            chars: input.char_indices().peekable(),
+            position: offset,
+            offset,
+            line: 1,
+            column: 1,
+            comments: Vec::new(),
+            finished: false,
         }
-        self.input[self.position..].chars().next()
+    }
+
+    pub fn has_comments(&self) -> bool {
+        !self.comments.is_empty()
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, c)| c)
     }
 
     fn peek_next(&self) -> Option<char> {
-        if self.is_at_end() {
-            return None;
+        let mut ahead = self.chars.clone();
+        ahead.next();
+        ahead.next().map(|(_, c)| c)
+    }
+
+    // consumes the current character, keeping line and column up to date
+    fn bump(&mut self) {
+        if let Some((_, c)) = self.chars.next() {
+            self.position = self.offset + self.chars.peek().map_or(self.input.len(), |&(i, _)| i);
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
         }
-        self.input[self.position..].chars().nth(1)
     }
 
-    fn is_at_end(&self) -> bool {
-        self.position >= self.input.len()
+    // zero-width span at the current position
+    fn location(&self) -> Span {
+        Span {
+            start: self.position,
+            end: self.position,
+            line: self.line,
+            column: self.column,
+        }
     }
 
-    //
-    pub fn parse(&mut self) -> Vec<Token> {
-        let mut token_stream = Vec::new();
+    // span from `start` up to the current position
+    // the character an escape sequence in a string stands for, after its backslash
+    fn escape(&mut self) -> Result<char, LexErrorKind> {
+        let Some(c) = self.peek() else {
+            // the string is unterminated, which is reported instead
+            return Ok('\\');
+        };
+        self.bump();
+        let escaped = match c {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '"' => '"',
+            '\\' => '\\',
+            'u' => return self.unicode_escape(),
+            c => return Err(LexErrorKind::InvalidEscape(format!("\\{}", c))),
+        };
+        Ok(escaped)
+    }
 
+    // \u{1F438}: one to six hex digits naming a Unicode character, after the `\u`
+    fn unicode_escape(&mut self) -> Result<char, LexErrorKind> {
+        if self.peek() != Some('{') {
+            return Err(LexErrorKind::InvalidUnicodeEscape("\\u".to_string()));
+        }
+        self.bump();
+        let mut digits = String::new();
+        while let Some(c) = self.peek().filter(char::is_ascii_hexdigit) {
+            digits.push(c);
+            self.bump();
+        }
+        let closed = self.peek() == Some('}');
+        let sequence = format!("\\u{{{}{}", digits, if closed { "}" } else { "" });
+        if !closed {
+            return Err(LexErrorKind::InvalidUnicodeEscape(sequence));
+        }
+        self.bump();
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .filter(|_| digits.len() <= 6)
+            .and_then(char::from_u32)
+            .ok_or(LexErrorKind::InvalidUnicodeEscape(sequence))
+    }
+
+    // a word of letters, digits and underscores, starting at the next character
+    fn word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_alphanumeric() || c == '_' {
+                word.push(c);
+                self.bump();
+            } else {
+                break;
+            }
+        }
+        word
+    }
+
+    fn span_from(&self, start: Span) -> Span {
+        Span {
+            end: self.position,
+            ..start
+        }
+    }
+
+    // skips a /* ... */ comment, the opening delimiter being the current character
+    fn skip_block_comment(&mut self, start: Span) -> Result<(), LexError> {
+        self.bump();
+        self.bump();
         loop {
-            if let Some(c) = self.peek() {
-                match c {
-                    '(' | ')' | ',' | ';' | ':' | '{' | '}' => {
-                        token_stream.push(Punctuation(c.to_string()));
-                        self.position += 1;
-                    }
-                    '0'..='9' | 'a'..='z' | 'A'..='Z' | '_' => {
-                        let mut word = c.to_string();
-                        self.position += 1;
+            match self.peek() {
+                Some('*') if self.peek_next() == Some('/') => {
+                    self.bump();
+                    self.bump();
+                    return Ok(());
+                }
+                Some(_) => self.bump(),
+                None => {
+                    return Err(LexError {
+                        kind: LexErrorKind::UnterminatedComment,
+                        span: self.span_from(start),
+                    });
+                }
+            }
+        }
+    }
+
+    // the whole token stream at once, up to the first error
+    pub fn parse(&mut self) -> Result<Vec<SpannedToken>, LexError> {
+        self.collect()
+    }
+
+    // lexes the next token, None at the end of the input. An error is already consumed when
+    // it is returned, so lexing can resume right after it
+    fn next_token(&mut self) -> Result<Option<SpannedToken>, LexError> {
+        while let Some(c) = self.peek() {
+            let start = self.location();
+
+            let token = match c {
+                '.' if self.peek_next() == Some('.') => {
+                    self.bump();
+                    self.bump();
+                    Token::Operator(Operator::Range)
+                }
+                '(' | ')' | ',' | ';' | ':' | '{' | '}' | '.' | '?' => {
+                    self.bump();
+                    Punctuation(c.to_string())
+                }
+                // numbers are ASCII digits; letters right after them make the whole word
+                // invalid, instead of a number followed by an identifier
+                c if c.is_ascii_digit() => {
+                    let mut word = self.word();
+
+                    // fractional part of a float literal, e.g. 3.14
+                    let is_integer = word.chars().all(|c| c.is_ascii_digit() || c == '_');
+                    if is_integer
+                        && self.peek() == Some('.')
+                        && self.peek_next().is_some_and(|c| c.is_ascii_digit())
+                    {
+                        word.push('.');
+                        self.bump();
 
                         while let Some(c) = self.peek() {
-                            if c.is_alphanumeric() || c == '_' {
+                            if c.is_ascii_digit() {
                                 word.push(c);
-                                self.position += 1;
+                                self.bump();
                             } else {
                                 break;
                             }
                         }
 
-                        let token = match word.as_str() {
-                            "let" | "croak" | "while" | "func" | "return" | "if" | "else" => {
-                                Keyword(word)
+                        match word.replace('_', "").parse::<f64>() {
+                            Ok(number) if number.is_finite() => Token::Float(number, word),
+                            Ok(_) => {
+                                return Err(LexError {
+                                    kind: LexErrorKind::FloatOutOfRange(word),
+                                    span: self.span_from(start),
+                                });
                             }
-                            "bool" | "number" => Token::Type(word),
-                            "true" | "false" => Token::Bool(word.as_str() == "true"),
-                            _ => match word.parse::<i32>() {
-                                Ok(number) => Number(number),
-                                Err(_) => Identifier(word),
-                            },
-                        };
-
-                        token_stream.push(token);
+                            Err(_) => {
+                                return Err(LexError {
+                                    kind: LexErrorKind::InvalidNumber(word),
+                                    span: self.span_from(start),
+                                });
+                            }
+                        }
+                    } else {
+                        match integer_literal(&word) {
+                            Ok(number) => Number(number, word),
+                            Err(kind) => {
+                                return Err(LexError {
+                                    kind,
+                                    span: self.span_from(start),
+                                });
+                            }
+                        }
                     }
-                    ' ' | '\n' | '\t' | '\r' => {
-                        self.position += 1;
+                }
+                // identifiers can use letters of any script, and digits after the first letter
+                c if c.is_alphabetic() || c == '_' => {
+                    let word = self.word();
+                    match word.as_str() {
+                        "bool" | "number" | "float" | "string" | "range" | "void" => {
+                            Token::Type(word)
+                        }
+                        "true" | "false" => Token::Bool(word.as_str() == "true"),
+                        _ => match WORD_OPERATORS
+                            .iter()
+                            .find(|(spelling, _)| *spelling == word)
+                        {
+                            Some((_, operator)) => Token::WordOperator(*operator),
+                            None => match Keyword::from_word(&word) {
+                                Some(keyword) => Token::Keyword(keyword),
+                                None => Identifier(Symbol::intern(&word)),
+                            },
+                        },
                     }
-                    '=' => {
-                        if let Some('=') = self.peek_next() {
-                            token_stream.push(Operator("==".to_string()));
-                            self.position += 2;
-                        } else {
-                            token_stream.push(Operator("=".to_string()));
-                            self.position += 1;
+                }
+                '"' => {
+                    self.bump();
+                    let mut literal = String::new();
+                    // a bad escape is reported once the whole string is consumed, so lexing
+                    // resumes after the string rather than in the middle of it
+                    let mut invalid = None;
+
+                    loop {
+                        match self.peek() {
+                            Some('"') => {
+                                self.bump();
+                                break;
+                            }
+                            Some('\\') => {
+                                let escape_start = self.location();
+                                self.bump();
+                                match self.escape() {
+                                    Ok(c) => literal.push(c),
+                                    Err(kind) => {
+                                        invalid.get_or_insert(LexError {
+                                            kind,
+                                            span: self.span_from(escape_start),
+                                        });
+                                    }
+                                }
+                            }
+                            Some(c) => {
+                                literal.push(c);
+                                self.bump();
+                            }
+                            None => {
+                                return Err(LexError {
+                                    kind: LexErrorKind::UnterminatedString,
+                                    span: self.span_from(start),
+                                });
+                            }
                         }
                     }
-                    '+' | '-' | '*' | '/' | '>' | '<' => {
-                        token_stream.push(Operator(c.to_string()));
-                        self.position += 1;
+
+                    if let Some(error) = invalid {
+                        return Err(error);
                     }
-                    _ => {
-                        panic!("Unknown character: {}", c);
+                    let written =
+                        &self.input[start.start - self.offset..self.position - self.offset];
+                    Token::String(literal, written.to_string())
+                }
+                ' ' | '\n' | '\t' | '\r' => {
+                    self.bump();
+                    continue;
+                }
+                '/' if self.peek_next() == Some('/') => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.bump();
                     }
+                    self.comments.push(self.span_from(start));
+                    continue;
                 }
-            } else {
-                token_stream.push(EOF);
-                break;
-            }
+                '/' if self.peek_next() == Some('*') => {
+                    self.skip_block_comment(start)?;
+                    self.comments.push(self.span_from(start));
+                    continue;
+                }
+                '=' if self.peek_next() == Some('>') => {
+                    self.bump();
+                    self.bump();
+                    Token::Operator(Operator::Arrow)
+                }
+                '=' | '>' | '<' | '!' => {
+                    self.bump();
+                    let with_equals = self.peek() == Some('=');
+                    if with_equals {
+                        self.bump();
+                    }
+                    Token::Operator(match (c, with_equals) {
+                        ('=', true) => Operator::Binary(BinOp::Eq),
+                        ('!', true) => Operator::Binary(BinOp::Ne),
+                        ('>', true) => Operator::Binary(BinOp::Ge),
+                        ('<', true) => Operator::Binary(BinOp::Le),
+                        ('=', _) => Operator::Assign,
+                        ('!', _) => Operator::Not,
+                        ('>', _) => Operator::Binary(BinOp::Gt),
+                        _ => Operator::Binary(BinOp::Lt),
+                    })
+                }
+                '&' | '|' if self.peek_next() == Some(c) => {
+                    self.bump();
+                    self.bump();
+                    let op = if c == '&' { BinOp::And } else { BinOp::Or };
+                    Token::Operator(Operator::Binary(op))
+                }
+                '+' | '-' | '*' | '/' | '%' => {
+                    self.bump();
+                    Token::Operator(Operator::Binary(match c {
+                        '+' => BinOp::Add,
+                        '-' => BinOp::Sub,
+                        '*' => BinOp::Mul,
+                        '/' => BinOp::Div,
+                        _ => BinOp::Rem,
+                    }))
+                }
+                _ => {
+                    self.bump();
+                    return Err(LexError {
+                        kind: LexErrorKind::UnexpectedCharacter(c),
+                        span: self.span_from(start),
+                    });
+                }
+            };
+
+            return Ok(Some(SpannedToken {
+                token,
+                span: self.span_from(start),
+            }));
         }
+        Ok(None)
+    }
+}
 
-        token_stream
+// value of an integer literal: decimal, hexadecimal after `0x` or binary after `0b`, with
+// underscores after the first digit to group digits (`1_000_000`, `0b1010_0101`)
+fn integer_literal(word: &str) -> Result<i64, LexErrorKind> {
+    let (digits, radix) = if let Some(digits) = word.strip_prefix("0x") {
+        (digits, 16)
+    } else if let Some(digits) = word.strip_prefix("0b") {
+        (digits, 2)
+    } else {
+        (word, 10)
+    };
+    let digits: String = digits.chars().filter(|&c| c != '_').collect();
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        // `0x` and `0b12` are broken numbers, `1abc` was meant as a name
+        return Err(if radix == 10 {
+            LexErrorKind::DigitLeadingIdentifier(word.to_string())
+        } else {
+            LexErrorKind::InvalidNumber(word.to_string())
+        });
+    }
+    // the digits are all valid, so only overflow can fail. One past the largest number is
+    // let through as the smallest, which the parser only accepts with a minus in front
+    match u64::from_str_radix(&digits, radix) {
+        Ok(n) if n == i64::MIN.unsigned_abs() => Ok(i64::MIN),
+        Ok(n) => i64::try_from(n).map_err(|_| LexErrorKind::NumberOutOfRange(word.to_string())),
+        Err(_) => Err(LexErrorKind::NumberOutOfRange(word.to_string())),
+    }
+}
+
+// tokens are lexed one at a time as they are pulled, the last one being EOF
+impl Iterator for Lexer<'_> {
+    type Item = Result<SpannedToken, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.next_token() {
+            Ok(Some(spanned)) => Some(Ok(spanned)),
+            Ok(None) => {
+                self.finished = true;
+                Some(Ok(SpannedToken {
+                    token: EOF,
+                    span: self.location(),
+                }))
+            }
+            Err(e) => Some(Err(e)),
+        }
     }
 }
 
@@ -112,27 +739,36 @@ impl<'a> Lexer<'a> {
 mod test {
     use super::*;
 
+    fn tokens(lexer: &mut Lexer) -> Vec<Token> {
+        lexer
+            .parse()
+            .unwrap()
+            .into_iter()
+            .map(|spanned| spanned.token)
+            .collect()
+    }
+
     #[test]
     fn test_single_identifier() {
         let mut lexer = Lexer::new("frog");
-        let tokens = lexer.parse();
+        let tokens = tokens(&mut lexer);
 
         assert_eq!(tokens.len(), 2);
-        assert!(matches!(tokens[0], Identifier(ref s) if s == "frog"));
+        assert!(matches!(tokens[0], Identifier(s) if s == "frog"));
         assert!(matches!(tokens[1], EOF));
     }
 
     #[test]
     fn test_let_assignment() {
         let mut lexer = Lexer::new("let x = 42;");
-        let tokens = lexer.parse();
+        let tokens = tokens(&mut lexer);
         println!("{:?}", tokens);
 
         assert_eq!(tokens.len(), 6);
-        assert!(matches!(tokens[0], Keyword(ref s) if s == "let"));
-        assert!(matches!(tokens[1], Identifier(ref s) if s == "x"));
-        assert!(matches!(tokens[2], Operator(ref s) if s == "="));
-        assert!(matches!(tokens[3], Number(n) if n == 42));
+        assert!(matches!(tokens[0], Token::Keyword(Keyword::Let)));
+        assert!(matches!(tokens[1], Identifier(s) if s == "x"));
+        assert!(matches!(tokens[2], Token::Operator(Operator::Assign)));
+        assert!(matches!(tokens[3], Number(n, _) if n == 42));
         assert!(matches!(tokens[4], Punctuation(ref s) if s == ";"));
         assert!(matches!(tokens[5], EOF));
     }
@@ -140,14 +776,421 @@ mod test {
     #[test]
     fn test_arithmetic_expression() {
         let mut lexer = Lexer::new("1 + 2 * 3");
-        let tokens = lexer.parse();
+        let tokens = tokens(&mut lexer);
 
         assert_eq!(tokens.len(), 6);
-        assert!(matches!(tokens[0], Number(1)));
-        assert!(matches!(tokens[1], Operator(ref s) if s == "+"));
-        assert!(matches!(tokens[2], Number(2)));
-        assert!(matches!(tokens[3], Operator(ref s) if s == "*"));
-        assert!(matches!(tokens[4], Number(3)));
+        assert!(matches!(tokens[0], Number(1, _)));
+        assert!(matches!(
+            tokens[1],
+            Token::Operator(Operator::Binary(BinOp::Add))
+        ));
+        assert!(matches!(tokens[2], Number(2, _)));
+        assert!(matches!(
+            tokens[3],
+            Token::Operator(Operator::Binary(BinOp::Mul))
+        ));
+        assert!(matches!(tokens[4], Number(3, _)));
         assert!(matches!(tokens[5], EOF));
     }
+
+    #[test]
+    fn test_logical_operators() {
+        let mut lexer = Lexer::new("!a && b || c");
+        let tokens = tokens(&mut lexer);
+
+        assert_eq!(tokens.len(), 7);
+        assert!(matches!(tokens[0], Token::Operator(Operator::Not)));
+        assert!(matches!(
+            tokens[2],
+            Token::Operator(Operator::Binary(BinOp::And))
+        ));
+        assert!(matches!(
+            tokens[4],
+            Token::Operator(Operator::Binary(BinOp::Or))
+        ));
+
+        // the same operators spelled out, kept apart so formatting can write them back
+        let words: Vec<Token> = Lexer::new("not a and b or c")
+            .map(|spanned| spanned.unwrap().token)
+            .collect();
+        let spelled: Vec<Token> = tokens
+            .into_iter()
+            .map(|token| match token {
+                Token::Operator(op) if word_operator(op).is_some() => Token::WordOperator(op),
+                token => token,
+            })
+            .collect();
+        assert_eq!(words, spelled);
+        assert_eq!(words[2].to_string(), "operator 'and'");
+
+        let mut lexer = Lexer::new("a & b");
+        assert_eq!(
+            lexer.parse().unwrap_err().kind,
+            LexErrorKind::UnexpectedCharacter('&')
+        );
+    }
+
+    #[test]
+    fn test_two_char_comparison_operators() {
+        let mut lexer = Lexer::new("a >= b <= c != d % e > !f");
+        let operators: Vec<Token> = tokens(&mut lexer)
+            .into_iter()
+            .filter(|t| matches!(t, Token::Operator(_)))
+            .collect();
+
+        let expected = [
+            Operator::Binary(BinOp::Ge),
+            Operator::Binary(BinOp::Le),
+            Operator::Binary(BinOp::Ne),
+            Operator::Binary(BinOp::Rem),
+            Operator::Binary(BinOp::Gt),
+            Operator::Not,
+        ];
+        assert_eq!(operators, expected.map(Token::Operator).to_vec());
+    }
+
+    #[test]
+    fn test_comments_are_skipped() {
+        let mut lexer = Lexer::new("let a = 4 / 2; // halve\n/* multi\nline */ croak a;");
+        let tokens = tokens(&mut lexer);
+
+        assert_eq!(tokens.len(), 11);
+        assert!(matches!(
+            tokens[4],
+            Token::Operator(Operator::Binary(BinOp::Div))
+        ));
+        assert!(matches!(tokens[7], Token::Keyword(Keyword::Croak)));
+
+        let mut lexer = Lexer::new("let a = 1; /* never closed");
+        let err = lexer.parse().unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::UnterminatedComment);
+        assert_eq!((err.span.line, err.span.column), (1, 12));
+    }
+
+    #[test]
+    fn test_classify() {
+        let source = "let x: number = 4.5 + y; // frog\ncroak \"ribbit";
+        let classes: Vec<(&str, TokenClass)> = classify(source)
+            .into_iter()
+            .map(|(span, class)| (&source[span.start..span.end], class))
+            .collect();
+
+        assert_eq!(
+            classes,
+            [
+                ("let", TokenClass::Keyword),
+                ("x", TokenClass::Identifier),
+                (":", TokenClass::Punctuation),
+                ("number", TokenClass::Type),
+                ("=", TokenClass::Operator),
+                ("4.5", TokenClass::Number),
+                ("+", TokenClass::Operator),
+                ("y", TokenClass::Identifier),
+                (";", TokenClass::Punctuation),
+                ("// frog", TokenClass::Comment),
+                ("croak", TokenClass::Keyword),
+                ("\"ribbit", TokenClass::String),
+            ]
+        );
+
+        // a bad character is left out without losing what follows it
+        let classes = classify("a $ b");
+        assert_eq!(classes.len(), 2);
+        assert_eq!(classes[1].0.start, 4);
+    }
+
+    #[test]
+    fn test_float_literal() {
+        let mut lexer = Lexer::new("3.25 * 2");
+        let tokens = tokens(&mut lexer);
+
+        assert_eq!(tokens.len(), 4);
+        assert!(matches!(tokens[0], Token::Float(f, _) if f == 3.25));
+        assert!(matches!(
+            tokens[1],
+            Token::Operator(Operator::Binary(BinOp::Mul))
+        ));
+        assert!(matches!(tokens[2], Number(2, _)));
+        assert!(matches!(tokens[3], EOF));
+    }
+
+    #[test]
+    fn test_integer_literal_forms() {
+        let mut lexer = Lexer::new("0x1F 0b1010 1_000_000 0xff_ff 2_5.5 1700000000000");
+        let tokens = tokens(&mut lexer);
+
+        assert!(matches!(tokens[0], Number(31, _)));
+        assert!(matches!(tokens[1], Number(10, _)));
+        assert!(matches!(tokens[2], Number(1_000_000, _)));
+        assert!(matches!(tokens[3], Number(65_535, _)));
+        // bigger than 32 bits, like a timestamp in milliseconds
+        assert!(matches!(tokens[5], Number(1_700_000_000_000, _)));
+        assert!(matches!(tokens[4], Token::Float(f, _) if f == 25.5));
+
+        let error = |source: &str| Lexer::new(source).parse().unwrap_err();
+        assert_eq!(
+            error("let x = 0x1_0000_0000_0000_0000;"),
+            LexError {
+                kind: LexErrorKind::NumberOutOfRange("0x1_0000_0000_0000_0000".to_string()),
+                span: Span {
+                    start: 8,
+                    end: 31,
+                    line: 1,
+                    column: 9,
+                },
+            }
+        );
+        // one past the largest number, left for a minus in front to make the smallest
+        assert!(matches!(
+            Lexer::new("9223372036854775808").parse().unwrap()[0].token,
+            Number(i64::MIN, _)
+        ));
+        assert_eq!(
+            error("9223372036854775809").kind,
+            LexErrorKind::NumberOutOfRange("9223372036854775809".to_string())
+        );
+        assert_eq!(
+            error("99999999999999999999").kind,
+            LexErrorKind::NumberOutOfRange("99999999999999999999".to_string())
+        );
+        assert_eq!(
+            error("0b102").kind,
+            LexErrorKind::InvalidNumber("0b102".to_string())
+        );
+        assert_eq!(
+            error("0x").kind,
+            LexErrorKind::InvalidNumber("0x".to_string())
+        );
+        let huge = format!("1{}.5", "0".repeat(400));
+        assert_eq!(error(&huge).kind, LexErrorKind::FloatOutOfRange(huge));
+    }
+
+    #[test]
+    fn test_identifiers_cannot_start_with_a_digit() {
+        assert_eq!(
+            Lexer::new("let 1abc = 2;").parse(),
+            Err(LexError {
+                kind: LexErrorKind::DigitLeadingIdentifier("1abc".to_string()),
+                span: Span {
+                    start: 4,
+                    end: 8,
+                    line: 1,
+                    column: 5,
+                },
+            })
+        );
+        let mut lexer = Lexer::new("abc1 _2");
+        let tokens = tokens(&mut lexer);
+        assert!(matches!(tokens[0], Identifier(name) if name.as_str() == "abc1"));
+        assert!(matches!(tokens[1], Identifier(name) if name.as_str() == "_2"));
+    }
+
+    #[test]
+    fn test_every_keyword_is_lexed() {
+        for (keyword, spelling) in KEYWORDS {
+            let tokens = Lexer::new(spelling).parse().unwrap();
+            assert_eq!(tokens[0].token, Token::Keyword(*keyword));
+            assert_eq!(keyword.to_string(), *spelling);
+        }
+        // only whole words are keywords
+        let mut lexer = Lexer::new("iffy format");
+        assert!(
+            tokens(&mut lexer)[..2]
+                .iter()
+                .all(|t| matches!(t, Identifier(_)))
+        );
+    }
+
+    #[test]
+    fn test_string_literal() {
+        let mut lexer = Lexer::new("croak \"hello, frog\";");
+        let tokens = tokens(&mut lexer);
+
+        assert_eq!(tokens.len(), 4);
+        assert!(matches!(tokens[0], Token::Keyword(Keyword::Croak)));
+        assert!(matches!(tokens[1], Token::String(ref s, _) if s == "hello, frog"));
+        assert!(matches!(tokens[2], Punctuation(ref s) if s == ";"));
+        assert!(matches!(tokens[3], EOF));
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let mut lexer = Lexer::new(r#""tab\there\n\"quoted\" back\\slash \u{1F438}\0""#);
+        let tokens = tokens(&mut lexer);
+        assert!(
+            matches!(tokens[0], Token::String(ref s, _) if s == "tab\there\n\"quoted\" back\\slash 🐸\0")
+        );
+
+        let error = |source: &str| Lexer::new(source).parse().unwrap_err();
+        assert_eq!(
+            error(r#"croak "a\qb";"#),
+            LexError {
+                kind: LexErrorKind::InvalidEscape(r"\q".to_string()),
+                span: Span {
+                    start: 8,
+                    end: 10,
+                    line: 1,
+                    column: 9,
+                },
+            }
+        );
+        for escape in [r"\u{D800}", r"\u{}", r"\u{1234567}", r"\u{12", r"\u"] {
+            let source = format!("\"{}\"", escape);
+            assert!(
+                matches!(error(&source).kind, LexErrorKind::InvalidUnicodeEscape(_)),
+                "{} was accepted",
+                escape
+            );
+        }
+        // lexing goes on after the string with the bad escape
+        let mut lexer = Lexer::new(r#""\q" 1"#);
+        assert!(lexer.next().unwrap().is_err());
+        assert!(matches!(
+            lexer.next(),
+            Some(Ok(SpannedToken {
+                token: Number(1, _),
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_unterminated_string_literal() {
+        let mut lexer = Lexer::new("\"ribbit");
+        assert_eq!(
+            lexer.parse().unwrap_err().kind,
+            LexErrorKind::UnterminatedString
+        );
+    }
+
+    #[test]
+    fn test_unexpected_character() {
+        let mut lexer = Lexer::new("let x = 4 $ 2;");
+        assert_eq!(
+            lexer.parse(),
+            Err(LexError {
+                kind: LexErrorKind::UnexpectedCharacter('$'),
+                span: Span {
+                    start: 10,
+                    end: 11,
+                    line: 1,
+                    column: 11,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_token_spans() {
+        let mut lexer = Lexer::new("let x = 1;\n  croak x;");
+        let tokens = lexer.parse().unwrap();
+
+        assert_eq!(
+            tokens[0].span,
+            Span {
+                start: 0,
+                end: 3,
+                line: 1,
+                column: 1
+            }
+        );
+        assert_eq!(
+            tokens[3].span,
+            Span {
+                start: 8,
+                end: 9,
+                line: 1,
+                column: 9
+            }
+        );
+        assert_eq!(
+            tokens[5].span,
+            Span {
+                start: 13,
+                end: 18,
+                line: 2,
+                column: 3
+            }
+        );
+        assert_eq!(
+            tokens[6].span,
+            Span {
+                start: 19,
+                end: 20,
+                line: 2,
+                column: 9
+            }
+        );
+        assert!(matches!(tokens[8].token, EOF));
+        assert_eq!(tokens[8].span.line, 2);
+
+        // an offset moves the byte offsets but not lines and columns
+        let tokens = Lexer::with_offset("let x = 1;\n  croak x;", 100)
+            .parse()
+            .unwrap();
+        assert_eq!(
+            tokens[5].span,
+            Span {
+                start: 113,
+                end: 118,
+                line: 2,
+                column: 3
+            }
+        );
+        assert_eq!(tokens[8].span.start, 121);
+    }
+
+    #[test]
+    fn test_lexer_is_a_token_iterator() {
+        let mut lexer = Lexer::new("a $ b");
+        assert!(
+            matches!(lexer.next(), Some(Ok(SpannedToken { token: Identifier(a), .. })) if a == "a")
+        );
+        assert!(matches!(
+            lexer.next(),
+            Some(Err(LexError {
+                kind: LexErrorKind::UnexpectedCharacter('$'),
+                ..
+            }))
+        ));
+        // lexing carries on after an error, and the stream ends after EOF
+        assert!(
+            matches!(lexer.next(), Some(Ok(SpannedToken { token: Identifier(b), .. })) if b == "b")
+        );
+        assert!(matches!(
+            lexer.next(),
+            Some(Ok(SpannedToken { token: EOF, .. }))
+        ));
+        assert_eq!(lexer.next(), None);
+    }
+
+    #[test]
+    fn test_unicode_identifiers() {
+        let mut lexer = Lexer::new("let größe = 1;\nlet ñandú = größe; // 🐸\ncroak \"🐸\";");
+        let tokens = lexer.parse().unwrap();
+
+        assert!(matches!(tokens[1].token, Identifier(s) if s == "größe"));
+        assert!(matches!(tokens[6].token, Identifier(s) if s == "ñandú"));
+        assert!(matches!(tokens[8].token, Identifier(s) if s == "größe"));
+        assert!(matches!(tokens[11].token, Token::String(ref s, _) if s == "🐸"));
+        // columns count characters, offsets count bytes
+        assert_eq!(
+            tokens[8].span,
+            Span {
+                start: 31,
+                end: 38,
+                line: 2,
+                column: 13
+            }
+        );
+        assert_eq!((tokens[10].span.line, tokens[10].span.column), (3, 1));
+
+        let mut lexer = Lexer::new("let frog = 🐸;");
+        let err = lexer.parse().unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::UnexpectedCharacter('🐸'));
+        assert_eq!(
+            (err.span.start, err.span.end, err.span.column),
+            (11, 15, 12)
+        );
+    }
 }
@@ -0,0 +1,304 @@
+//! Froggle, a small statically typed scripting language.
+//!
+//! The pipeline is lexer, parser, module loader, typechecker and a tree-walking interpreter,
+//! which first resolves every local variable to a slot in its scope.
+//! [`Froggle`] runs source through all of them so the language can be embedded in other Rust
+//! programs:
+//!
+//! ```
+//! let mut froggle = froggle::Froggle::new();
+//! froggle.eval("func double(n: number): number { return n * 2; }").unwrap();
+//! assert_eq!(froggle.eval("double(21);").unwrap().to_string(), "42");
+//! ```
+
+// errors carry types and spans by value; they are only built on the failure path, so their
+// size doesn't matter
+#![allow(clippy::result_large_err)]
+
+pub mod ast_printer;
+pub mod builtins;
+pub mod catalog;
+pub mod error;
+pub mod formatter;
+pub mod interpreter;
+pub mod json;
+pub mod lexer;
+pub mod lint;
+pub mod module;
+pub mod parser;
+mod resolver;
+pub mod symbol;
+pub mod typechecker;
+#[cfg(feature = "wasm-bindgen")]
+pub mod wasm;
+
+pub use error::Error;
+pub use interpreter::{Limits, Value};
+pub use parser::Type;
+
+use interpreter::Interpreter;
+use lexer::Lexer;
+use parser::{Expression, ExpressionKind, Parser, Statement, StatementKind};
+use std::path::Path;
+use typechecker::{TypeChecker, TypeError, TypeErrorKind};
+
+/// An interpreter session. Declarations made by one [`eval`](Froggle::eval) stay visible to
+/// the next, like entries in the REPL.
+pub struct Froggle {
+    typechecker: TypeChecker,
+    interpreter: Interpreter,
+}
+
+impl Froggle {
+    pub fn new() -> Froggle {
+        Froggle {
+            typechecker: TypeChecker::new(),
+            interpreter: Interpreter::new(),
+        }
+    }
+
+    /// Makes a Rust function callable from froggle code as `name`. Scripts are typechecked
+    /// against `params` and `return_type`, so `function` can rely on getting arguments of
+    /// those types and should return a value of `return_type`.
+    ///
+    /// ```
+    /// use froggle::{Froggle, Type, Value};
+    ///
+    /// let mut froggle = Froggle::new();
+    /// froggle.register_native("twice", vec![Type::String], Type::String, |args| {
+    ///     Value::String(args[0].to_string().repeat(2))
+    /// });
+    /// assert_eq!(froggle.eval("twice(\"ribbit\");").unwrap().to_string(), "ribbitribbit");
+    /// ```
+    pub fn register_native(
+        &mut self,
+        name: &str,
+        params: Vec<Type>,
+        return_type: Type,
+        function: impl Fn(&[Value]) -> Value + 'static,
+    ) {
+        self.interpreter
+            .register_native(name, params.len(), function);
+        self.typechecker.declare_native(name, params, return_type);
+    }
+
+    /// Bounds every following [`eval`](Froggle::eval), so untrusted scripts can't run forever.
+    /// A script going over a limit fails with a runtime error. File access is off unless
+    /// `allow_fs` is set.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.interpreter.limits = limits;
+    }
+
+    /// Collects what `croak` prints in every following [`eval`](Froggle::eval) instead of
+    /// writing it to stdout, until it is read with [`take_output`](Froggle::take_output).
+    ///
+    /// ```
+    /// let mut froggle = froggle::Froggle::new();
+    /// froggle.capture_output();
+    /// froggle.eval("croak 1 + 2; croak \"ribbit\";").unwrap();
+    /// assert_eq!(froggle.take_output(), "3\nribbit\n");
+    /// ```
+    pub fn capture_output(&mut self) {
+        self.interpreter.capture_output();
+    }
+
+    /// The output captured since the last call, empty if output isn't captured.
+    pub fn take_output(&mut self) -> String {
+        self.interpreter.take_output()
+    }
+
+    /// Typechecks and runs `source`, returning the value of its last statement if that is an
+    /// expression statement, or [`Value::Void`] otherwise. Imports are resolved relative to the
+    /// current directory.
+    ///
+    /// Nothing runs if the source has a syntax, import or type error, and a source that fails
+    /// to typecheck leaves no declarations behind.
+    pub fn eval(&mut self, source: &str) -> Result<Value, Error> {
+        let program = Parser::from_lexer(Lexer::new(source)).parse()?;
+        let program = self.interpreter.modules.resolve(program, Path::new(""))?;
+
+        let checkpoint = self.typechecker.clone();
+        if let Err(errors) = self.typechecker.check(&program) {
+            self.typechecker = checkpoint;
+            return Err(errors.into());
+        }
+
+        Ok(self.interpreter.interpret(&program)?)
+    }
+}
+
+/// Runs `source`, read from the file at `path`, through the whole pipeline: lexer, parser,
+/// module loader, typechecker and `interpreter`. Imports are resolved relative to `path`.
+///
+/// A file declaring a `main` function has it called once its top level has run, unless the top
+/// level calls `main();` itself. `main` takes no arguments; if it returns a number, written out
+/// or inferred, the program exits with that code as if it had called `exit`.
+///
+/// The spans of errors index into the sources in `interpreter.modules.sources`, which `source`
+/// is added to first; an error in an imported function is in its module's source.
+pub fn run(source: &str, path: &Path, interpreter: &mut Interpreter) -> Result<Value, Error> {
+    let offset = interpreter
+        .modules
+        .sources
+        .add(path.display().to_string(), source);
+    let mut program = Parser::from_lexer(Lexer::with_offset(source, offset)).parse()?;
+    let entry = entry_point(&program)?;
+    let calls_main = entry.is_some();
+    program.extend(entry);
+    let mut program = interpreter.modules.resolve(program, path)?;
+    let mut checker = TypeChecker::new();
+    checker.check(&program)?;
+    if let Some(entry) = program.last_mut().filter(|_| calls_main) {
+        exit_with_main(entry, &checker);
+    }
+    Ok(interpreter.interpret(&program)?)
+}
+
+/// The call to `main` that [`run`] adds to the end of a file, `main();`. `None` if there is no
+/// `main`, or the file calls it already. A `main` called this way can't take parameters, which
+/// is an error.
+pub fn entry_point(program: &[Statement]) -> Result<Option<Statement>, Error> {
+    let calls_main = |expression: &Expression| matches!(&expression.kind, ExpressionKind::FunctionCall { name, .. } if *name == "main");
+    let Some((span, params)) = program.iter().find_map(|statement| match &statement.kind {
+        StatementKind::FunctionDeclaration { name, params, .. } if *name == "main" => {
+            Some((statement.span, params))
+        }
+        _ => None,
+    }) else {
+        return Ok(None);
+    };
+    if program
+        .iter()
+        .any(|statement| matches!(&statement.kind, StatementKind::Expression(e) if calls_main(e)))
+    {
+        return Ok(None);
+    }
+    if !params.is_empty() {
+        return Err(Error::Type(vec![TypeError {
+            kind: TypeErrorKind::MainParameters,
+            span,
+        }]));
+    }
+
+    Ok(Some(Statement::new(
+        StatementKind::Expression(call("main", Vec::new(), span)),
+        span,
+    )))
+}
+
+/// Turns the [`entry_point`] of a program `checker` has checked into `exit(main());` if `main`
+/// returns a number, the program's exit code. Its return type may have been left out, so this
+/// is only known once the program is checked.
+pub fn exit_with_main(entry: &mut Statement, checker: &TypeChecker) {
+    let returns_code = matches!(
+        checker.global_type("main"),
+        Some(Type::Function(_, return_type)) if **return_type == Type::Number
+    );
+    if returns_code {
+        let call_main = call("main", Vec::new(), entry.span);
+        entry.kind = StatementKind::Expression(call("exit", vec![call_main], entry.span));
+    }
+}
+
+fn call(name: &str, arguments: Vec<Expression>, span: lexer::Span) -> Expression {
+    Expression::new(
+        ExpressionKind::FunctionCall {
+            name: name.into(),
+            arguments,
+        },
+        span,
+    )
+}
+
+impl Default for Froggle {
+    fn default() -> Self {
+        Froggle::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval_keeps_state_between_calls() {
+        let mut froggle = Froggle::new();
+        assert_eq!(froggle.eval("let x = 20;"), Ok(Value::Void));
+        assert_eq!(froggle.eval("abs(x - 41);"), Ok(Value::Number(21)));
+    }
+
+    #[test]
+    fn test_native_functions_are_typechecked() {
+        let mut froggle = Froggle::new();
+        froggle.register_native(
+            "add",
+            vec![Type::Number, Type::Number],
+            Type::Number,
+            |args| match args {
+                [Value::Number(a), Value::Number(b)] => Value::Number(a + b),
+                _ => Value::Void,
+            },
+        );
+
+        assert_eq!(froggle.eval("add(2, 3);"), Ok(Value::Number(5)));
+        assert!(matches!(froggle.eval("add(2, true);"), Err(Error::Type(_))));
+    }
+
+    #[test]
+    fn test_eval_rejects_ill_typed_source() {
+        let mut froggle = Froggle::new();
+        assert!(matches!(
+            froggle.eval("let y = 1 + true;"),
+            Err(Error::Type(_))
+        ));
+        // the failed declaration didn't stick
+        assert!(froggle.eval("y;").is_err());
+    }
+
+    #[test]
+    fn test_literal_out_of_range_is_a_lex_error() {
+        // not an unknown variable called 99999999999999999999
+        let mut froggle = Froggle::new();
+        let error = froggle.eval("let big = 99999999999999999999;").unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Lex(lexer::LexError {
+                kind: lexer::LexErrorKind::NumberOutOfRange(_),
+                ..
+            })
+        ));
+        assert_eq!(error.span().start, 10);
+    }
+
+    #[test]
+    fn test_run_calls_main() {
+        let run = |source: &str| {
+            let mut interpreter = Interpreter::new();
+            interpreter.capture_output();
+            let result = run(source, Path::new("main.frog"), &mut interpreter);
+            (result, interpreter.take_output())
+        };
+
+        let (result, output) =
+            run("const greeting = \"ribbit\"; func main() { croak greeting; } croak \"first\";");
+        assert_eq!(result, Ok(Value::Void));
+        assert_eq!(output, "first\nribbit\n");
+        // a number returned by main is the exit code
+        let (result, _) = run("func main(): number { return 3; }");
+        assert_eq!(result.unwrap_err().exit_code(), Some(3));
+        // also when the return type is left out
+        let (result, _) = run("func main() { if arg_count() > 99 { return 1; } return 4; }");
+        assert_eq!(result.unwrap_err().exit_code(), Some(4));
+        // a file calling main itself runs it once
+        let (_, output) = run("func main() { croak 1; } main();");
+        assert_eq!(output, "1\n");
+        // and may pass it arguments, which it can't take otherwise
+        let (_, output) = run("func main(n: number) { croak n; } main(2);");
+        assert_eq!(output, "2\n");
+        let (result, _) = run("func main(n: number) { croak n; }");
+        assert!(matches!(
+            result,
+            Err(Error::Type(errors)) if errors[0].kind == TypeErrorKind::MainParameters
+        ));
+    }
+}
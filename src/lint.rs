@@ -0,0 +1,457 @@
+use crate::error::Diagnostic;
+use crate::lexer::Span;
+use crate::parser::{
+    ASTVisitor, Expression, ExpressionKind, MatchArm, Statement, StatementKind, Type,
+};
+use crate::symbol::Symbol;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum WarningKind {
+    UnusedVariable(String),
+    UnusedFunction(String),
+    // first statement after a return, raise, break or continue in the same block
+    UnreachableCode,
+    // a name declared again in the scope it was declared in, hiding the earlier declaration
+    Redeclared(String),
+}
+
+impl fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WarningKind::UnusedVariable(name) => write!(f, "variable {} is never read", name),
+            WarningKind::UnusedFunction(name) => write!(f, "function {} is never called", name),
+            WarningKind::UnreachableCode => write!(f, "unreachable statement"),
+            WarningKind::Redeclared(name) => {
+                write!(
+                    f,
+                    "{} is already declared in this scope and is shadowed",
+                    name
+                )
+            }
+        }
+    }
+}
+
+// something suspicious that doesn't stop the program from running
+#[derive(Debug, PartialEq, Clone)]
+pub struct Warning {
+    pub kind: WarningKind,
+    pub span: Span,
+}
+
+impl Warning {
+    pub fn diagnostic(&self) -> Diagnostic {
+        Diagnostic::warning("lint", &self.kind, self.span)
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: warning: {}", self.span, self.kind)
+    }
+}
+
+// a name declared in the program, and whether anything reads it
+struct Declared {
+    span: Span,
+    function: bool,
+    used: bool,
+}
+
+// finds dead code and unused names in a parsed program. Imported modules aren't looked at, so
+// calls to their functions are simply not resolved; names starting with `_` are never reported
+#[derive(Default)]
+pub struct Linter {
+    scopes: Vec<HashMap<Symbol, Declared>>,
+    // declarations of the functions whose bodies are being linted; calling itself doesn't
+    // make a function used
+    functions: Vec<Span>,
+    warnings: Vec<Warning>,
+}
+
+pub fn lint(program: &[Statement]) -> Vec<Warning> {
+    let mut linter = Linter::default();
+    linter.scoped(|linter| {
+        // top-level functions can be called before their declaration, unless something else
+        // at the top level is declared with the same name, as in the typechecker
+        let mut declarations: HashMap<Symbol, usize> = HashMap::new();
+        for statement in program {
+            if let StatementKind::FunctionDeclaration { name, .. }
+            | StatementKind::Declaration(name, ..)
+            | StatementKind::Const(name, ..) = &statement.kind
+            {
+                *declarations.entry(*name).or_default() += 1;
+            }
+        }
+        for statement in program {
+            if let StatementKind::FunctionDeclaration { name, .. } = &statement.kind
+                && declarations[name] == 1
+            {
+                linter.declare(*name, statement.span, true);
+            }
+        }
+        linter.statements(program);
+    });
+    linter.warnings.sort_by_key(|warning| warning.span.start);
+    linter.warnings
+}
+
+impl Linter {
+    fn scoped(&mut self, lint: impl FnOnce(&mut Self)) {
+        self.scopes.push(HashMap::new());
+        lint(self);
+        let scope = self.scopes.pop().unwrap_or_default();
+        for (name, declared) in scope {
+            self.report_unused(name, declared);
+        }
+    }
+
+    fn report_unused(&mut self, name: Symbol, declared: Declared) {
+        if declared.used || name.as_str().starts_with('_') {
+            return;
+        }
+        let kind = if declared.function {
+            WarningKind::UnusedFunction(name.to_string())
+        } else {
+            WarningKind::UnusedVariable(name.to_string())
+        };
+        self.warnings.push(Warning {
+            kind,
+            span: declared.span,
+        });
+    }
+
+    fn declare(&mut self, name: Symbol, span: Span, function: bool) {
+        self.insert(name, span, function, false);
+    }
+
+    // parameters are part of the signature, so they're in scope but never reported
+    fn declare_parameter(&mut self, name: Symbol, span: Span) {
+        self.insert(name, span, false, true);
+    }
+
+    fn insert(&mut self, name: Symbol, span: Span, function: bool, used: bool) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        let declared = Declared {
+            span,
+            function,
+            used,
+        };
+        match scope.insert(name, declared) {
+            // the declaration of a hoisted function, which may have been called already
+            Some(hoisted) if hoisted.span == span => {
+                if let Some(declared) = scope.get_mut(&name) {
+                    declared.used |= hoisted.used;
+                }
+            }
+            Some(hidden) => {
+                self.warnings.push(Warning {
+                    kind: WarningKind::Redeclared(name.to_string()),
+                    span,
+                });
+                self.report_unused(name, hidden);
+            }
+            None => {}
+        }
+    }
+
+    fn use_name(&mut self, name: Symbol) {
+        let declared = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(&name));
+        if let Some(declared) = declared
+            && !self.functions.contains(&declared.span)
+        {
+            declared.used = true;
+        }
+    }
+
+    fn statements(&mut self, statements: &[Statement]) {
+        let mut reachable = true;
+        let mut reported = false;
+        for statement in statements {
+            // only the first unreachable statement is reported, but the rest is still linted
+            if !reachable && !reported {
+                self.warnings.push(Warning {
+                    kind: WarningKind::UnreachableCode,
+                    span: statement.span,
+                });
+                reported = true;
+            }
+            statement.accept(self);
+            if matches!(
+                statement.kind,
+                StatementKind::Return(_)
+                    | StatementKind::Raise(_)
+                    | StatementKind::Break
+                    | StatementKind::Continue
+            ) {
+                reachable = false;
+            }
+        }
+    }
+
+    fn block(&mut self, statements: &[Statement]) {
+        self.scoped(|linter| linter.statements(statements));
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        match &expr.kind {
+            ExpressionKind::Variable(name) => self.use_name(*name),
+            ExpressionKind::BinaryOperation { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            ExpressionKind::UnaryOperation { operand, .. } => self.expression(operand),
+            ExpressionKind::Range { start, end } => {
+                self.expression(start);
+                self.expression(end);
+            }
+            ExpressionKind::Block { statements, value } => self.scoped(|linter| {
+                linter.statements(statements);
+                linter.expression(value);
+            }),
+            ExpressionKind::FunctionCall { name, arguments } => {
+                self.use_name(*name);
+                for argument in arguments {
+                    self.expression(argument);
+                }
+            }
+            ExpressionKind::Number(..)
+            | ExpressionKind::Float(..)
+            | ExpressionKind::String(..)
+            | ExpressionKind::Bool(_)
+            | ExpressionKind::None
+            | ExpressionKind::EnumVariant { .. } => {}
+        }
+    }
+}
+
+impl ASTVisitor for Linter {
+    type Output = ();
+
+    fn visit_declaration(&mut self, name: Symbol, expr: &Expression, _: Option<&Type>, span: Span) {
+        // the value is read before the name exists, so `let x = x + 1;` reads the outer x
+        self.expression(expr);
+        self.declare(name, span, false);
+    }
+
+    fn visit_const(&mut self, name: Symbol, expr: &Expression, _: Option<&Type>, span: Span) {
+        self.expression(expr);
+        self.declare(name, span, false);
+    }
+
+    // assigning to a variable doesn't read it
+    fn visit_assignment(&mut self, _: Symbol, expr: &Expression, _: Span) {
+        self.expression(expr);
+    }
+
+    fn visit_print(&mut self, expr: &Expression, _: bool, _: Span) {
+        self.expression(expr);
+    }
+
+    fn visit_while(&mut self, condition: &Expression, body: &[Statement], _: Span) {
+        self.expression(condition);
+        self.block(body);
+    }
+
+    fn visit_do_while(&mut self, body: &[Statement], condition: &Expression, _: Span) {
+        self.block(body);
+        self.expression(condition);
+    }
+
+    fn visit_for(
+        &mut self,
+        variable: Symbol,
+        _: Option<&Type>,
+        start: &Expression,
+        end: &Expression,
+        body: &[Statement],
+        span: Span,
+    ) {
+        self.expression(start);
+        self.expression(end);
+        self.scoped(|linter| {
+            linter.declare(variable, span, false);
+            linter.statements(body);
+        });
+    }
+
+    fn visit_for_in(
+        &mut self,
+        variable: Symbol,
+        _: Option<&Type>,
+        iterable: &Expression,
+        body: &[Statement],
+        span: Span,
+    ) {
+        self.expression(iterable);
+        self.scoped(|linter| {
+            linter.declare(variable, span, false);
+            linter.statements(body);
+        });
+    }
+
+    fn visit_block(&mut self, statements: &[Statement], _: Span) {
+        self.block(statements);
+    }
+
+    fn visit_function_declaration(
+        &mut self,
+        name: Symbol,
+        _: &[Symbol],
+        params: &[(Symbol, Type)],
+        _: &Type,
+        body: &[Statement],
+        span: Span,
+    ) {
+        self.declare(name, span, true);
+        self.functions.push(span);
+        self.scoped(|linter| {
+            for (param, _) in params {
+                linter.declare_parameter(*param, span);
+            }
+            linter.statements(body);
+        });
+        self.functions.pop();
+    }
+
+    fn visit_if(
+        &mut self,
+        condition: &Expression,
+        body: &[Statement],
+        else_branch: Option<&[Statement]>,
+        _: Span,
+    ) {
+        self.expression(condition);
+        self.block(body);
+        if let Some(else_branch) = else_branch {
+            self.block(else_branch);
+        }
+    }
+
+    fn visit_match(&mut self, subject: &Expression, arms: &[MatchArm], _: Span) {
+        self.expression(subject);
+        for arm in arms {
+            self.block(&arm.body);
+        }
+    }
+
+    fn visit_enum(&mut self, _: Symbol, _: &[Symbol], _: Span) {}
+
+    fn visit_expression(&mut self, expr: &Expression, _: Span) {
+        self.expression(expr);
+    }
+
+    fn visit_assert(&mut self, condition: &Expression, _: Span) {
+        self.expression(condition);
+    }
+
+    fn visit_assert_eq(&mut self, left: &Expression, right: &Expression, _: Span) {
+        self.expression(left);
+        self.expression(right);
+    }
+
+    fn visit_return(&mut self, expr: &Expression, _: Span) {
+        self.expression(expr);
+    }
+
+    fn visit_try(&mut self, body: &[Statement], error: Symbol, handler: &[Statement], span: Span) {
+        self.block(body);
+        // the handler may ignore the error, so it is never reported as unused
+        self.scoped(|linter| {
+            linter.declare_parameter(error, span);
+            linter.statements(handler);
+        });
+    }
+
+    fn visit_raise(&mut self, message: &Expression, _: Span) {
+        self.expression(message);
+    }
+
+    fn visit_break(&mut self, _: Span) {}
+
+    fn visit_continue(&mut self, _: Span) {}
+
+    fn visit_import(&mut self, _: &str, _: Span) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn warnings(source: &str) -> Vec<String> {
+        let tokens = Lexer::new(source).parse().unwrap();
+        lint(&Parser::new(tokens).parse().unwrap())
+            .iter()
+            .map(Warning::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_unused_names() {
+        let source = "\
+let used = 1;
+let unused = used;
+let _ignored = 2;
+func helper(n: number): number { return helper(n); }
+func main() {
+    let x = 0;
+    x = 5;
+    for i = 1 to 3 { croak \"ribbit\"; }
+}
+main();";
+        assert_eq!(
+            warnings(source),
+            [
+                "2:1: warning: variable unused is never read",
+                "4:1: warning: function helper is never called",
+                "6:5: warning: variable x is never read",
+                "8:5: warning: variable i is never read",
+            ]
+        );
+        // a use in a nested scope counts, a shadowing declaration doesn't
+        assert_eq!(
+            warnings("let a = 1; { let a = 2; croak a; } croak a;"),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            warnings("let a = 1; let a = a + 1; croak a;"),
+            ["1:12: warning: a is already declared in this scope and is shadowed"]
+        );
+        // top-level functions may be called before they are declared
+        assert_eq!(
+            warnings("main(); func main() { helper(); } func helper() { }"),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_unreachable_code() {
+        let source = "\
+func f(): number {
+    return 1;
+    croak \"never\";
+    croak \"again\";
+}
+while true {
+    break;
+    croak f();
+}";
+        assert_eq!(
+            warnings(source),
+            [
+                "3:5: warning: unreachable statement",
+                "8:5: warning: unreachable statement",
+            ]
+        );
+    }
+}
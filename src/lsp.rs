@@ -0,0 +1,463 @@
+use froggle::Error;
+use froggle::error::{Diagnostic, Severity};
+use froggle::json::{self, Json};
+use froggle::lexer::{Lexer, Span, Token};
+use froggle::lint;
+use froggle::module::ModuleLoader;
+use froggle::parser::{Parser, Statement, StatementKind};
+use froggle::typechecker::{IncrementalChecker, Symbol, TypeChecker};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+// JSON-RPC error code for requests the server doesn't implement
+const METHOD_NOT_FOUND: i32 = -32601;
+
+// language server speaking LSP over stdin and stdout, until the client sends `exit`
+pub fn start() -> ExitCode {
+    let mut server = Server::default();
+    let mut stdin = io::stdin().lock();
+    let mut stdout = io::stdout().lock();
+
+    while let Some(message) = read_message(&mut stdin) {
+        let message = match json::parse(&message) {
+            Ok(message) => message,
+            Err(e) => {
+                eprintln!("froggle lsp: invalid message: {}", e);
+                continue;
+            }
+        };
+        if message.get("method").as_str() == Some("exit") {
+            break;
+        }
+        for reply in server.handle(&message) {
+            let body = reply.to_string();
+            let written = write!(stdout, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+            if written.and_then(|_| stdout.flush()).is_err() {
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    // exiting without a shutdown request first is a failure by the protocol
+    if server.shut_down {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+// body of the next message, None once the client hangs up
+fn read_message(input: &mut impl BufRead) -> Option<String> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header).ok()? == 0 {
+            return None;
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse().ok();
+        }
+    }
+
+    let mut body = vec![0; length?];
+    input.read_exact(&mut body).ok()?;
+    String::from_utf8(body).ok()
+}
+
+// an open file, with what the last check found in it
+struct Document {
+    text: String,
+    symbols: Vec<Symbol>,
+    // rechecks only what changed since the last version
+    checker: IncrementalChecker,
+}
+
+#[derive(Default)]
+pub struct Server {
+    documents: HashMap<String, Document>,
+    shut_down: bool,
+}
+
+impl Server {
+    // answers one message, returning the messages to send back
+    pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = message.get("method").as_str().unwrap_or_default();
+        let params = message.get("params");
+        let id = message.get("id");
+
+        let result = match method {
+            "initialize" => Json::object([(
+                "capabilities",
+                Json::object([
+                    // the client sends the whole text on every change
+                    ("textDocumentSync", 1.into()),
+                    ("hoverProvider", true.into()),
+                    ("definitionProvider", true.into()),
+                ]),
+            )]),
+            "shutdown" => {
+                self.shut_down = true;
+                Json::Null
+            }
+            "textDocument/didOpen" => {
+                let document = params.get("textDocument");
+                return self.update(document.get("uri"), document.get("text"));
+            }
+            "textDocument/didChange" => {
+                let Json::Array(changes) = params.get("contentChanges") else {
+                    return Vec::new();
+                };
+                let Some(change) = changes.last() else {
+                    return Vec::new();
+                };
+                return self.update(params.get("textDocument").get("uri"), change.get("text"));
+            }
+            "textDocument/didClose" => {
+                if let Some(uri) = params.get("textDocument").get("uri").as_str() {
+                    self.documents.remove(uri);
+                }
+                return Vec::new();
+            }
+            "textDocument/hover" => self.symbol_at(params).map_or(Json::Null, |(text, symbol)| {
+                Json::object([
+                    (
+                        "contents",
+                        Json::object([
+                            ("kind", "plaintext".into()),
+                            (
+                                "value",
+                                format!("{}: {}", symbol.name, symbol.datatype).into(),
+                            ),
+                        ]),
+                    ),
+                    ("range", range(text, symbol.span)),
+                ])
+            }),
+            "textDocument/definition" => self
+                .symbol_at(params)
+                .and_then(|(text, symbol)| {
+                    let definition = declared_name(text, &symbol.name, symbol.definition?);
+                    Some(Json::object([
+                        ("uri", params.get("textDocument").get("uri").clone()),
+                        ("range", range(text, definition)),
+                    ]))
+                })
+                .unwrap_or(Json::Null),
+            // notifications we don't act on, e.g. `initialized`, get no answer
+            _ if *id == Json::Null => return Vec::new(),
+            _ => {
+                return vec![Json::object([
+                    ("jsonrpc", "2.0".into()),
+                    ("id", id.clone()),
+                    (
+                        "error",
+                        Json::object([
+                            ("code", Json::Number(METHOD_NOT_FOUND as f64)),
+                            ("message", format!("unsupported method {}", method).into()),
+                        ]),
+                    ),
+                ])];
+            }
+        };
+
+        vec![Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("id", id.clone()),
+            ("result", result),
+        ])]
+    }
+
+    // rechecks a document, publishing its diagnostics
+    fn update(&mut self, uri: &Json, text: &Json) -> Vec<Json> {
+        let (Some(uri), Some(text)) = (uri.as_str(), text.as_str()) else {
+            return Vec::new();
+        };
+        let document = self
+            .documents
+            .entry(uri.to_string())
+            .or_insert_with(|| Document {
+                text: String::new(),
+                symbols: Vec::new(),
+                checker: IncrementalChecker::new(TypeChecker::new()),
+            });
+        let (diagnostics, symbols) = analyze(text, uri_to_path(uri), &mut document.checker);
+        document.text = text.to_string();
+        document.symbols = symbols;
+
+        let diagnostics = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                Json::object([
+                    ("range", range(text, diagnostic.span)),
+                    ("severity", severity(diagnostic.severity)),
+                    ("code", diagnostic.code.clone().into()),
+                    ("source", "froggle".into()),
+                    ("message", diagnostic.message.clone().into()),
+                ])
+            })
+            .collect();
+        vec![Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("method", "textDocument/publishDiagnostics".into()),
+            (
+                "params",
+                Json::object([
+                    ("uri", uri.into()),
+                    ("diagnostics", Json::Array(diagnostics)),
+                ]),
+            ),
+        ])]
+    }
+
+    // the symbol under the cursor of a hover or definition request, with the document's text
+    fn symbol_at(&self, params: &Json) -> Option<(&str, &Symbol)> {
+        let document = self
+            .documents
+            .get(params.get("textDocument").get("uri").as_str()?)?;
+        let cursor = params.get("position");
+        let offset = offset(
+            &document.text,
+            cursor.get("line").as_usize()?,
+            cursor.get("character").as_usize()?,
+        );
+        let symbol = document
+            .symbols
+            .iter()
+            .find(|symbol| symbol.span.start <= offset && offset < symbol.span.end)?;
+        Some((&document.text, symbol))
+    }
+}
+
+// diagnostics and symbols of a document; imports are resolved relative to its path
+fn analyze(
+    text: &str,
+    path: PathBuf,
+    checker: &mut IncrementalChecker,
+) -> (Vec<Diagnostic>, Vec<Symbol>) {
+    let mut program = match parse(text) {
+        Ok(program) => program,
+        Err(e) => return (e.diagnostics(), Vec::new()),
+    };
+    let entry = froggle::entry_point(&program);
+    if let Ok(entry) = &entry {
+        program.extend(entry.clone());
+    }
+
+    let mut diagnostics: Vec<Diagnostic> = lint::lint(&program)
+        .iter()
+        .map(lint::Warning::diagnostic)
+        .collect();
+    if let Err(e) = entry {
+        diagnostics.extend(e.diagnostics());
+    }
+
+    // imported functions are checked on their own first, so the symbols found afterwards
+    // all belong to this document and their spans index into its text
+    let mut loader = ModuleLoader::new();
+    // modules are lexed after the document, so their spans don't overlap its text
+    loader.sources.add(path.display().to_string(), text);
+    let mut imported = Vec::new();
+    let mut own = Vec::new();
+    for statement in program {
+        if matches!(statement.kind, StatementKind::Import(_)) {
+            match loader.resolve(vec![statement], &path) {
+                Ok(statements) => imported.extend(statements),
+                Err(e) => diagnostics.extend(e.diagnostics()),
+            }
+        } else {
+            own.push(statement);
+        }
+    }
+
+    if let Err(errors) = checker.check(text, &imported, &own) {
+        diagnostics.extend(Error::from(errors).diagnostics());
+    }
+    (diagnostics, checker.symbols())
+}
+
+fn severity(severity: Severity) -> Json {
+    match severity {
+        Severity::Error => 1.into(),
+        Severity::Warning => 2.into(),
+    }
+}
+
+fn parse(text: &str) -> Result<Vec<Statement>, Error> {
+    Ok(Parser::from_lexer(Lexer::new(text)).parse()?)
+}
+
+// file:///home/frog/a%20b.frog -> /home/frog/a b.frog
+fn uri_to_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    let mut bytes = Vec::new();
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = tail
+            .get(..2)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(decoded) if byte == b'%' => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+// where `name` is declared in the statement declaring it: the first time the statement names
+// it, like the `n` of `func twice(n: number)`. The whole statement if the name can't be found,
+// e.g. in a stale span
+fn declared_name(text: &str, name: &str, statement: Span) -> Span {
+    let Some(source) = text.get(statement.start..statement.end) else {
+        return statement;
+    };
+    Lexer::with_offset(source, statement.start)
+        .filter_map(Result::ok)
+        .find(|spanned| matches!(&spanned.token, Token::Identifier(found) if *found == name))
+        .map_or(statement, |spanned| spanned.span)
+}
+
+// LSP positions count lines from 0 and characters in UTF-16 code units
+fn position(text: &str, offset: usize) -> Json {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+    Json::object([
+        ("line", before.matches('\n').count().into()),
+        ("character", character.into()),
+    ])
+}
+
+fn range(text: &str, span: Span) -> Json {
+    Json::object([
+        ("start", position(text, span.start)),
+        ("end", position(text, span.end)),
+    ])
+}
+
+// byte offset of an LSP position, clamped to the end of its line
+fn offset(text: &str, line: usize, character: usize) -> usize {
+    let line_start: usize = text.split_inclusive('\n').take(line).map(str::len).sum();
+    let mut units = 0;
+    for (i, c) in text[line_start.min(text.len())..].char_indices() {
+        if units >= character || c == '\n' {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, params: Json) -> Json {
+        Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("id", 1.into()),
+            ("method", method.into()),
+            ("params", params),
+        ])
+    }
+
+    fn cursor(line: usize, character: usize) -> Json {
+        Json::object([
+            (
+                "textDocument",
+                Json::object([("uri", "file:///a.frog".into())]),
+            ),
+            (
+                "position",
+                Json::object([("line", line.into()), ("character", character.into())]),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_diagnostics_hover_and_definition() {
+        let mut server = Server::default();
+        let text =
+            "let frog = \"ribbit\";\nfunc jump(n: number): number {\n    return n;\n}\njump(frog);";
+        let open = Json::object([(
+            "textDocument",
+            Json::object([("uri", "file:///a.frog".into()), ("text", text.into())]),
+        )]);
+
+        let published = server.handle(&request("textDocument/didOpen", open));
+        let diagnostics = published[0].get("params").get("diagnostics");
+        let Json::Array(diagnostics) = diagnostics else {
+            panic!("expected a list of diagnostics");
+        };
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].get("code").as_str(),
+            Some("type/ArgumentMismatch")
+        );
+        assert_eq!(
+            diagnostics[0].get("range").get("start"),
+            &position(text, text.rfind("frog").unwrap())
+        );
+
+        let hover = server.handle(&request("textDocument/hover", cursor(4, 1)));
+        assert_eq!(
+            hover[0].get("result").get("contents").get("value").as_str(),
+            Some("jump: func(number): number")
+        );
+
+        // right after the name is no longer on it
+        let after = server.handle(&request("textDocument/hover", cursor(4, 4)));
+        assert_eq!(after[0].get("result"), &Json::Null);
+
+        // a parameter is declared by its name, not by the function it belongs to
+        let range_on_line = |line: usize, start: usize, end: usize| {
+            Json::object([
+                (
+                    "start",
+                    Json::object([("line", line.into()), ("character", start.into())]),
+                ),
+                (
+                    "end",
+                    Json::object([("line", line.into()), ("character", end.into())]),
+                ),
+            ])
+        };
+        let definition = server.handle(&request("textDocument/definition", cursor(2, 11)));
+        assert_eq!(
+            definition[0].get("result").get("range"),
+            &range_on_line(1, 10, 11)
+        );
+        let definition = server.handle(&request("textDocument/definition", cursor(4, 0)));
+        assert_eq!(
+            definition[0].get("result").get("range"),
+            &range_on_line(1, 5, 9)
+        );
+    }
+
+    #[test]
+    fn test_positions_count_utf16_units() {
+        let text = "let a = \"🐸\";\nlet b = 1;";
+        assert_eq!(
+            position(text, text.find(';').unwrap()),
+            Json::object([("line", 0.into()), ("character", 12.into())])
+        );
+        assert_eq!(offset(text, 0, 12), text.find(';').unwrap());
+        assert_eq!(offset(text, 1, 4), text.find('b').unwrap());
+        assert_eq!(
+            uri_to_path("file:///a%20b.frog"),
+            PathBuf::from("/a b.frog")
+        );
+    }
+}
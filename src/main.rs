@@ -1,69 +1,392 @@
-use std::io::Write;
-use std::{env, fs, io};
+// errors carry types and spans by value; they are only built on the failure path, so their
+// size doesn't matter
+#![allow(clippy::result_large_err)]
 
-mod interpreter;
-mod lexer;
-mod parser;
-mod typechecker;
+use crate::cli::{Command, RunOptions};
+use froggle::Error;
+use froggle::catalog::Voice;
+use froggle::error::{Diagnostic, Severity, Style};
+use froggle::{
+    ast_printer, builtins, formatter, interpreter, lexer, lint, module, parser, typechecker,
+};
+use std::cell::RefCell;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::process::ExitCode;
+use std::rc::Rc;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use std::{env, fs, thread};
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+mod cli;
+mod debugger;
+mod lsp;
+mod profile;
+mod repl;
+mod test_runner;
+mod trace;
+mod watch;
 
-    if args.len() <= 1 {
-        repl();
-        return;
+// how errors are printed, decided once at startup
+static STYLE: OnceLock<Style> = OnceLock::new();
+
+// stack of the thread everything runs on: each froggle call takes a few kilobytes of it in a
+// debug build, so this holds calls nested as deep as cli::MAX_CALL_DEPTH allows with room to
+// spare. Pages are only used once touched, so shallow programs don't pay for it
+const STACK_SIZE: usize = 1 << 30;
+
+fn main() -> ExitCode {
+    thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(start)
+        .and_then(|interpreter| {
+            interpreter
+                .join()
+                .map_err(|_| io::Error::other("the interpreter thread panicked"))
+        })
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        })
+}
+
+fn start() -> ExitCode {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    // NO_COLOR counts when it is set to anything but the empty string, see no-color.org
+    let no_color = cli::take_flag(&mut args, "--no-color")
+        || env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let voice = if cli::take_flag(&mut args, "--ribbit") {
+        Voice::Ribbit
+    } else {
+        Voice::Plain
+    };
+    STYLE.get_or_init(|| Style {
+        color: !no_color && io::stderr().is_terminal(),
+        voice,
+    });
+
+    let command = match cli::parse_args(&args) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match command {
+        Command::Repl(limits) => return repl::start(limits),
+        Command::Run(options) if options.watch => return watch::watch(&options),
+        Command::Run(options) => return run_file(&options),
+        Command::Check {
+            path,
+            json,
+            deny_warnings,
+        } => return check_file(&path, json, deny_warnings),
+        Command::Tokens(path) => return dump_tokens(&path),
+        Command::Ast(path) => return dump_ast(&path),
+        Command::Fmt { path, check } => return format_file(&path, check),
+        Command::Test(path) => return test_runner::run_tests(&path),
+        Command::Bench(path) => return bench_file(&path),
+        Command::Debug(path) => return debugger::debug(&path),
+        Command::Lsp => return lsp::start(),
+        Command::Version => println!("froggle {}", cli::VERSION),
+        Command::Help => println!("{}", cli::USAGE),
     }
-    let filename = &args[1];
-    run_file(filename);
+    ExitCode::SUCCESS
 }
 
-fn repl() {
-    println!("Froggle REPL mode! 🐸 Type your code below (Ctrl+C to finish):");
+fn read_source(path: &str) -> Option<String> {
+    let source = fs::read_to_string(path).ok();
+    if source.is_none() {
+        eprintln!("Error reading file {}. Exiting.", path);
+    }
+    source
+}
 
-    let mut interpreter = interpreter::Interpreter::new();
-    loop {
-        // read
-        print!("froggle🐸> ");
-        io::stdout().lock().flush().unwrap();
-
-        let mut line = String::new();
-        if io::stdin().read_line(&mut line).is_err() {
-            println!("Error reading line. Exiting.");
-            break;
+// reads a file and applies `stage` to its source, reporting errors against the path
+fn with_source(path: &str, stage: impl FnOnce(&str) -> Result<(), Error>) -> ExitCode {
+    let Some(src_code) = read_source(path) else {
+        return ExitCode::FAILURE;
+    };
+
+    match stage(&src_code) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            report(path, &src_code, &e.diagnostics());
+            ExitCode::FAILURE
         }
+    }
+}
 
-        let line = line.trim();
+// like with_source, for stages running the file with `interpreter`: errors are shown in the
+// source they are in, which may be a module the file imported
+fn with_program(
+    path: &str,
+    interpreter: &mut interpreter::Interpreter,
+    stage: impl FnOnce(&str, &mut interpreter::Interpreter) -> Result<(), Error>,
+) -> ExitCode {
+    let Some(src_code) = read_source(path) else {
+        return ExitCode::FAILURE;
+    };
 
-        if line == "exit" {
-            break;
+    match stage(&src_code, interpreter) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            report_in(&interpreter.modules.sources, &e.diagnostics());
+            ExitCode::FAILURE
         }
+    }
+}
 
-        if line.is_empty() {
-            continue;
+fn style() -> Style {
+    STYLE.get().copied().unwrap_or_default()
+}
+
+// prints problems to stderr with the lines of `source` they are on, separated by blank lines
+fn report(path: &str, source: &str, diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        eprintln!("{}\n", diagnostic.render(path, source, style()));
+    }
+}
+
+// like report, with every problem shown in the source of `sources` it points into
+fn report_in(sources: &module::Sources, diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        match sources.locate(diagnostic) {
+            Some((name, source, diagnostic)) => report(name, source, &[diagnostic]),
+            None => report("<unknown>", "", std::slice::from_ref(diagnostic)),
         }
+    }
+}
 
-        // evaluate
-        let mut lexer = lexer::Lexer::new(&line);
-        let mut parser = parser::Parser::new(lexer.parse());
-        let ast = parser.parse();
-        typechecker::TypeChecker::new().check(ast.clone());
-        interpreter.interpret(ast);
+fn run_file(options: &RunOptions) -> ExitCode {
+    let path = &options.path;
+    if options.dump_tokens || options.dump_ast {
+        if options.dump_tokens && dump_tokens(path) == ExitCode::FAILURE {
+            return ExitCode::FAILURE;
+        }
+        if options.dump_ast {
+            return dump_ast(path);
+        }
+        return ExitCode::SUCCESS;
+    }
 
-        // print
-        println!("Environment:");
-        println!("{:#?}", interpreter.environments);
+    builtins::set_script_args(options.args.clone());
+    let mut interpreter = interpreter::Interpreter::new();
+    interpreter.limits = options.limits;
+    let tracer = match options.trace.as_ref().map(trace::Tracer::open).transpose() {
+        Ok(tracer) => tracer,
+        Err(e) => {
+            eprintln!("Error opening the trace file: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let profiler = options
+        .profile
+        .then(|| Rc::new(RefCell::new(profile::Profiler::default())));
+    interpreter.hook = match (tracer, profiler.clone()) {
+        (Some(tracer), Some(profiler)) => Some(Box::new((tracer, profiler))),
+        (Some(tracer), None) => Some(Box::new(tracer)),
+        (None, Some(profiler)) => Some(Box::new(profiler)),
+        (None, None) => None,
+    };
+    let mut exit = None;
+    let exit_code = with_program(path, &mut interpreter, |src_code, interpreter| {
+        match froggle::run(src_code, Path::new(path), interpreter) {
+            // exit() isn't an error to report, the script chose its exit code
+            Err(e) if e.exit_code().is_some() => {
+                exit = e.exit_code();
+                Ok(())
+            }
+            result => result.map(|_| ()),
+        }
+    });
+    if let Some(profiler) = profiler {
+        eprint!("\n{}", profiler.borrow().report());
     }
+    exit.map(ExitCode::from).unwrap_or(exit_code)
 }
 
-fn run_file(path: &str) {
-    if let Ok(src_code) = fs::read_to_string(path) {
-        let mut lexer = lexer::Lexer::new(&src_code);
-        let mut parser = parser::Parser::new(lexer.parse());
-        let ast = parser.parse();
-        typechecker::TypeChecker::new().check(ast.clone());
-        let mut interpreter = interpreter::Interpreter::new();
-        interpreter.interpret(ast);
+// runs a program like `run` does, then prints how long each stage took. The times go to stderr
+// so they don't mix with the program's own output
+fn bench_file(path: &str) -> ExitCode {
+    let mut interpreter = interpreter::Interpreter::new();
+    let mut timings = Vec::new();
+    let exit_code = with_program(path, &mut interpreter, |src_code, interpreter| {
+        let offset = interpreter.modules.sources.add(path.to_string(), src_code);
+        let tokens = timed(&mut timings, "lex", || {
+            lexer::Lexer::with_offset(src_code, offset).parse()
+        })?;
+        let mut ast = timed(&mut timings, "parse", || {
+            parser::Parser::new(tokens).parse()
+        })?;
+        let entry = froggle::entry_point(&ast)?;
+        let calls_main = entry.is_some();
+        ast.extend(entry);
+        let mut ast = timed(&mut timings, "modules", || {
+            interpreter.modules.resolve(ast, Path::new(path))
+        })?;
+        let mut checker = typechecker::TypeChecker::new();
+        timed(&mut timings, "typecheck", || checker.check(&ast))?;
+        if let Some(entry) = ast.last_mut().filter(|_| calls_main) {
+            froggle::exit_with_main(entry, &checker);
+        }
+        let ran = timed(&mut timings, "interpret", || interpreter.interpret(&ast));
+        match ran.map_err(Error::from) {
+            // a script ending itself with exit() still ran to completion
+            Err(e) if e.exit_code().is_none() => Err(e),
+            _ => Ok(()),
+        }
+    });
+
+    let total: Duration = timings.iter().map(|(_, elapsed)| *elapsed).sum();
+    for (stage, elapsed) in timings.iter().chain([&("total", total)]) {
+        eprintln!("{:<10}{:>12.3?}", stage, elapsed);
+    }
+    exit_code
+}
+
+fn timed<T>(
+    timings: &mut Vec<(&'static str, Duration)>,
+    stage: &'static str,
+    run: impl FnOnce() -> T,
+) -> T {
+    let start = Instant::now();
+    let result = run();
+    timings.push((stage, start.elapsed()));
+    result
+}
+
+// everything but the interpreter, so a program can be validated without running it
+fn check_file(path: &str, json: bool, deny_warnings: bool) -> ExitCode {
+    let mut checker = typechecker::TypeChecker::new();
+    if json {
+        return check_file_json(path, &mut checker, deny_warnings);
+    }
+
+    let mut denied = false;
+    let exit_code = with_source(path, |src_code| {
+        let mut warnings = Vec::new();
+        let checked = check(src_code, path, &mut checker, &mut warnings);
+        // warnings come first so errors end up at the bottom
+        report(path, src_code, &lint_diagnostics(&warnings, deny_warnings));
+        checked?;
+        denied = deny_warnings && !warnings.is_empty();
+        if !denied {
+            println!("{}: ok", path);
+        }
+        Ok(())
+    });
+
+    let errors = checker.diagnostics().len();
+    if errors > 1 {
+        eprintln!("{}: {} type errors", path, errors);
+    }
+    if denied { ExitCode::FAILURE } else { exit_code }
+}
+
+// prints a JSON array with one object per warning and error, empty when the program checks
+// cleanly; only errors fail the check
+fn check_file_json(
+    path: &str,
+    checker: &mut typechecker::TypeChecker,
+    deny_warnings: bool,
+) -> ExitCode {
+    let Some(src_code) = read_source(path) else {
+        return ExitCode::FAILURE;
+    };
+
+    let mut warnings = Vec::new();
+    let mut diagnostics = match check(&src_code, path, checker, &mut warnings) {
+        Ok(()) => Vec::new(),
+        Err(e) => e.diagnostics(),
+    };
+    diagnostics.splice(0..0, lint_diagnostics(&warnings, deny_warnings));
+    let objects: Vec<String> = diagnostics.iter().map(|d| d.to_json(path)).collect();
+    println!("[{}]", objects.join(","));
+
+    if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        ExitCode::FAILURE
     } else {
-        panic!("Error reading file {}. Exiting.", path);
+        ExitCode::SUCCESS
     }
 }
+
+// with --deny-warnings every warning is reported as an error
+fn lint_diagnostics(warnings: &[lint::Warning], deny_warnings: bool) -> Vec<Diagnostic> {
+    warnings
+        .iter()
+        .map(|warning| {
+            let mut diagnostic = warning.diagnostic();
+            if deny_warnings {
+                diagnostic.severity = Severity::Error;
+            }
+            diagnostic
+        })
+        .collect()
+}
+
+// lint warnings are collected as soon as the program parses, even if it then fails to check
+fn check(
+    src_code: &str,
+    path: &str,
+    checker: &mut typechecker::TypeChecker,
+    warnings: &mut Vec<lint::Warning>,
+) -> Result<(), Error> {
+    let mut ast = parse(src_code)?;
+    ast.extend(froggle::entry_point(&ast)?);
+    warnings.extend(lint::lint(&ast));
+    let mut loader = module::ModuleLoader::new();
+    // the file was lexed at the start, so it comes first and modules after it
+    loader.sources.add(path.to_string(), src_code);
+    let ast = loader.resolve(ast, Path::new(path))?;
+    checker.check(&ast)?;
+    Ok(())
+}
+
+fn dump_tokens(path: &str) -> ExitCode {
+    with_source(path, |src_code| {
+        for spanned in lexer::Lexer::new(src_code).parse()? {
+            println!("{:<8}{}", spanned.span.to_string(), spanned.token);
+        }
+        Ok(())
+    })
+}
+
+fn dump_ast(path: &str) -> ExitCode {
+    with_source(path, |src_code| {
+        print!("{}", ast_printer::AstPrinter::print(&parse(src_code)?));
+        Ok(())
+    })
+}
+
+fn format_file(path: &str, check: bool) -> ExitCode {
+    let mut failed = false;
+    let exit_code = with_source(path, |src_code| {
+        let mut lexer = lexer::Lexer::new(src_code);
+        let ast = parser::Parser::from_lexer(&mut lexer).parse()?;
+        let formatted = formatter::Formatter::format(&ast);
+
+        if lexer.has_comments() {
+            // comments never make it into the AST, so formatting would drop them
+            eprintln!("{}: files with comments can't be formatted yet", path);
+            failed = true;
+        } else if formatted == src_code {
+            // already formatted, nothing to do in either mode
+        } else if check {
+            println!("{}: not formatted", path);
+            failed = true;
+        } else if fs::write(path, formatted).is_err() {
+            eprintln!("Error writing file {}.", path);
+            failed = true;
+        }
+        Ok(())
+    });
+
+    if failed { ExitCode::FAILURE } else { exit_code }
+}
+
+fn parse(src_code: &str) -> Result<Vec<parser::Statement>, Error> {
+    let mut parser = parser::Parser::from_lexer(lexer::Lexer::new(src_code));
+    Ok(parser.parse()?)
+}
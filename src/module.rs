@@ -0,0 +1,368 @@
+use crate::error::{Diagnostic, Error};
+use crate::lexer::{Lexer, Span};
+use crate::parser::{Parser, Statement, StatementKind};
+use crate::typechecker::TypeChecker;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ImportErrorKind {
+    Unreadable { path: String, reason: String },
+    Cycle(Vec<String>),
+    // error inside the imported file, with its own location
+    InModule { path: String, error: Box<Error> },
+}
+
+impl fmt::Display for ImportErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportErrorKind::Unreadable { path, reason } => {
+                write!(f, "cannot read module {}: {}", path, reason)
+            }
+            ImportErrorKind::Cycle(paths) => write!(f, "import cycle: {}", paths.join(" -> ")),
+            // the error may hold several lines, each is marked with the module it comes from
+            ImportErrorKind::InModule { path, error } => {
+                let lines: Vec<String> = error
+                    .to_string()
+                    .lines()
+                    .map(|line| format!("in {}: {}", path, line))
+                    .collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ImportError {
+    pub kind: ImportErrorKind,
+    pub span: Span,
+}
+
+impl ImportError {
+    fn new(kind: ImportErrorKind, span: Span) -> ImportError {
+        ImportError { kind, span }
+    }
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.kind)
+    }
+}
+
+// every source a program was read from, for showing an error in the one it happened in; an
+// error in an imported function points into its module, not into the file that called it.
+// Each source is lexed at the offset where the previous one ended, so spans tell them apart
+#[derive(Default)]
+pub struct Sources {
+    // name shown in errors, source and the offset it was lexed at
+    sources: Vec<(String, String, usize)>,
+    end: usize,
+}
+
+impl Sources {
+    // records a source, returning the offset to lex it at
+    pub fn add(&mut self, name: String, source: &str) -> usize {
+        let offset = self.end;
+        // one past the end, so a span at the very end of a source isn't taken for the next
+        self.end += source.len() + 1;
+        self.sources.push((name, source.to_string(), offset));
+        offset
+    }
+
+    // the name and source a diagnostic is in, with its span made relative to that source
+    pub fn locate(&self, diagnostic: &Diagnostic) -> Option<(&str, &str, Diagnostic)> {
+        let (name, source, offset) = self
+            .sources
+            .iter()
+            .rev()
+            .find(|(_, _, offset)| *offset <= diagnostic.span.start)?;
+        let mut diagnostic = diagnostic.clone();
+        diagnostic.span.start -= offset;
+        diagnostic.span.end -= offset;
+        Some((name, source, diagnostic))
+    }
+}
+
+// loads imported files, replacing every top-level `import` with the functions, enums and
+// top-level variables the file declares
+#[derive(Default)]
+pub struct ModuleLoader {
+    // declarations of every module loaded so far, by canonical path, those of the modules it
+    // imports included; each is paired with the module it was declared in
+    cache: HashMap<PathBuf, Vec<(PathBuf, Statement)>>,
+    // modules being loaded right now, innermost last, so cycles can be reported
+    loading: Vec<PathBuf>,
+    // the files and entries lexed so far, modules included; whoever lexes the program that
+    // imports them adds it first
+    pub sources: Sources,
+}
+
+impl ModuleLoader {
+    pub fn new() -> ModuleLoader {
+        ModuleLoader::default()
+    }
+
+    // `importer` is the file the program was read from; import paths are relative to its directory
+    pub fn resolve(
+        &mut self,
+        program: Vec<Statement>,
+        importer: &Path,
+    ) -> Result<Vec<Statement>, Error> {
+        let root = fs::canonicalize(importer).ok();
+        if let Some(root) = &root {
+            self.loading.push(root.clone());
+        }
+        let origin = root.clone().unwrap_or_else(|| importer.to_path_buf());
+        let dir = importer.parent().unwrap_or(Path::new(""));
+        let result = self.resolve_imports(program, dir, &origin);
+        if root.is_some() {
+            self.loading.pop();
+        }
+        Ok(result?
+            .into_iter()
+            .map(|(_, statement)| statement)
+            .collect())
+    }
+
+    // the statements of `program`, declared in `origin`, with each import replaced by the
+    // declarations it brings in. A module reached by several imports is merged only once, so
+    // its top-level variables aren't declared again over the state it already has
+    fn resolve_imports(
+        &mut self,
+        program: Vec<Statement>,
+        dir: &Path,
+        origin: &Path,
+    ) -> Result<Vec<(PathBuf, Statement)>, Error> {
+        let mut merged = HashSet::new();
+        let mut resolved = Vec::new();
+        for statement in program {
+            match statement.kind {
+                StatementKind::Import(path) => {
+                    let declarations: Vec<_> = self
+                        .load(&path, dir, statement.span)?
+                        .into_iter()
+                        .filter(|(module, _)| !merged.contains(module))
+                        .collect();
+                    merged.extend(declarations.iter().map(|(module, _)| module.clone()));
+                    resolved.extend(declarations);
+                }
+                _ => resolved.push((origin.to_path_buf(), statement)),
+            }
+        }
+        Ok(resolved)
+    }
+
+    fn load(
+        &mut self,
+        path: &str,
+        dir: &Path,
+        span: Span,
+    ) -> Result<Vec<(PathBuf, Statement)>, ImportError> {
+        let unreadable = |e: std::io::Error| {
+            ImportError::new(
+                ImportErrorKind::Unreadable {
+                    path: path.to_string(),
+                    reason: e.to_string(),
+                },
+                span,
+            )
+        };
+        let canonical = fs::canonicalize(dir.join(path)).map_err(unreadable)?;
+
+        if let Some(functions) = self.cache.get(&canonical) {
+            return Ok(functions.clone());
+        }
+        if let Some(start) = self.loading.iter().position(|p| *p == canonical) {
+            let cycle = self.loading[start..]
+                .iter()
+                .chain([&canonical])
+                .map(|p| p.display().to_string())
+                .collect();
+            return Err(ImportError::new(ImportErrorKind::Cycle(cycle), span));
+        }
+
+        let source = fs::read_to_string(&canonical).map_err(unreadable)?;
+        let offset = self.sources.add(canonical.display().to_string(), &source);
+        self.loading.push(canonical.clone());
+        let result = self.load_module(&source, offset, &canonical);
+        self.loading.pop();
+
+        let functions = result.map_err(|error| {
+            ImportError::new(
+                ImportErrorKind::InModule {
+                    path: path.to_string(),
+                    error: Box::new(error),
+                },
+                span,
+            )
+        })?;
+        self.cache.insert(canonical, functions.clone());
+        Ok(functions)
+    }
+
+    // only the declarations of a module are merged into the importer: its functions, enums
+    // and top-level variables, which its functions may use. Its other statements never run
+    fn load_module(
+        &mut self,
+        source: &str,
+        offset: usize,
+        path: &Path,
+    ) -> Result<Vec<(PathBuf, Statement)>, Error> {
+        let program = Parser::from_lexer(Lexer::with_offset(source, offset)).parse()?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let (modules, program): (Vec<_>, Vec<_>) = self
+            .resolve_imports(program, dir, path)?
+            .into_iter()
+            .unzip();
+        TypeChecker::new().check(&program)?;
+
+        Ok(modules
+            .into_iter()
+            .zip(program)
+            .filter(|(_, statement)| {
+                matches!(
+                    statement.kind,
+                    StatementKind::FunctionDeclaration { .. }
+                        | StatementKind::Enum { .. }
+                        | StatementKind::Declaration(..)
+                        | StatementKind::Const(..)
+                )
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // writes the given files into a fresh directory under the system temp dir
+    fn module_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("froggle-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (file, source) in files {
+            fs::write(dir.join(file), source).unwrap();
+        }
+        dir
+    }
+
+    fn resolve(dir: &Path, main: &str) -> Result<Vec<Statement>, Error> {
+        let source = fs::read_to_string(dir.join(main)).unwrap();
+        let program = Parser::new(Lexer::new(&source).parse().unwrap())
+            .parse()
+            .unwrap();
+        let mut loader = ModuleLoader::new();
+        loader.sources.add(main.to_string(), &source);
+        loader.resolve(program, &dir.join(main))
+    }
+
+    #[test]
+    fn test_import_merges_functions() {
+        let dir = module_dir(
+            "merge",
+            &[
+                (
+                    "math.frog",
+                    "func double(n: number): number { return n * 2; } croak 1;",
+                ),
+                ("main.frog", "import \"math.frog\"; let x = double(4);"),
+            ],
+        );
+
+        let program = resolve(&dir, "main.frog").unwrap();
+
+        // the module's croak is dropped, only its function comes along
+        assert_eq!(program.len(), 2);
+        assert!(matches!(
+            &program[0].kind,
+            StatementKind::FunctionDeclaration { name, .. } if name == "double"
+        ));
+    }
+
+    #[test]
+    fn test_module_functions_use_module_variables() {
+        let dir = module_dir(
+            "variables",
+            &[
+                (
+                    "limits.frog",
+                    "const limit = 3;\nfunc check(n: number): number {\n    \
+                     if n > limit { raise \"too big\"; }\n    return n;\n}",
+                ),
+                (
+                    "main.frog",
+                    "import \"limits.frog\";\nlet small = check(2);\ncheck(5);",
+                ),
+            ],
+        );
+        let main = dir.join("main.frog");
+        let mut interpreter = crate::interpreter::Interpreter::new();
+
+        let source = fs::read_to_string(&main).unwrap();
+        let error = crate::run(&source, &main, &mut interpreter).unwrap_err();
+
+        // the error is shown in the module, where the function raising it is
+        let sources = &interpreter.modules.sources;
+        let (name, source, diagnostic) = sources.locate(&error.diagnostics()[0]).unwrap();
+        assert!(name.ends_with("limits.frog"));
+        assert_eq!(
+            &source[diagnostic.span.start..diagnostic.span.end],
+            "raise \"too big\";"
+        );
+        assert_eq!(diagnostic.span.line, 3);
+    }
+
+    #[test]
+    fn test_module_imported_twice_is_merged_once() {
+        let dir = module_dir(
+            "diamond",
+            &[
+                (
+                    "counter.frog",
+                    "let count = 0;\nfunc bump(): number { count = count + 1; return count; }",
+                ),
+                ("b.frog", "import \"counter.frog\";"),
+                ("c.frog", "import \"counter.frog\";"),
+                (
+                    "main.frog",
+                    "import \"b.frog\";\nbump();\nimport \"c.frog\";\nassert_eq count, 1;",
+                ),
+            ],
+        );
+        let main = dir.join("main.frog");
+        let source = fs::read_to_string(&main).unwrap();
+
+        // the second import doesn't declare `count` again, resetting it
+        let mut interpreter = crate::interpreter::Interpreter::new();
+        crate::run(&source, &main, &mut interpreter).unwrap();
+    }
+
+    #[test]
+    fn test_import_cycle_is_an_error() {
+        let dir = module_dir(
+            "cycle",
+            &[
+                ("a.frog", "import \"b.frog\";"),
+                ("b.frog", "import \"a.frog\";"),
+            ],
+        );
+
+        let Err(Error::Import(error)) = resolve(&dir, "a.frog") else {
+            panic!("expected an import error");
+        };
+        let ImportErrorKind::InModule { error, .. } = error.kind else {
+            panic!("expected the cycle to be reported from b.frog");
+        };
+        assert!(matches!(
+            *error,
+            Error::Import(ImportError {
+                kind: ImportErrorKind::Cycle(ref paths),
+                ..
+            }) if paths.len() == 3
+        ));
+    }
+}
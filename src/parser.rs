@@ -1,22 +1,58 @@
-use crate::lexer::Token;
-use crate::parser::Expression::BinaryOperation;
-use crate::parser::Statement::{If, While};
-use std::collections::HashMap;
+use crate::lexer::{Keyword, LexError, LexErrorKind, Operator, Span, SpannedToken, Token};
+use crate::parser::ExpressionKind::BinaryOperation;
+use crate::parser::StatementKind::{If, While};
+use crate::symbol::Symbol;
+use std::collections::VecDeque;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
 
 // Vec<Statement>
 #[derive(Debug, PartialEq, Clone)]
-pub enum Statement {
-    Declaration(String, Expression, Option<Type>),
-    Assignment(String, Expression),
-    Print(Expression),
+pub enum StatementKind {
+    Declaration(Symbol, Expression, Option<Type>),
+    // like a declaration, but the variable can't be assigned to afterwards
+    Const(Symbol, Expression, Option<Type>),
+    Assignment(Symbol, Expression),
+    // croak_debug prints the raw representation of the value instead of its display form
+    Print {
+        value: Expression,
+        debug: bool,
+    },
     While {
         condition: Expression,
         body: Vec<Statement>,
     },
+    // do { ... } while condition; runs the body once before the condition is first checked
+    DoWhile {
+        body: Vec<Statement>,
+        condition: Expression,
+    },
+    // for i: number = 1 to 10 { ... }; the annotation is optional
+    For {
+        variable: Symbol,
+        annotation: Option<Type>,
+        start: Expression,
+        end: Expression,
+        body: Vec<Statement>,
+    },
+    // for x in 0..10 { ... }, over the numbers of a range
+    ForIn {
+        variable: Symbol,
+        annotation: Option<Type>,
+        iterable: Expression,
+        body: Vec<Statement>,
+    },
     Block(Vec<Statement>),
+    // func name<T, U>(params): return_type { body }, type parameters optional
     FunctionDeclaration {
-        name: String,
-        params: Vec<(String, Type)>,
+        name: Symbol,
+        type_params: Vec<Symbol>,
+        params: Vec<(Symbol, Type)>,
         return_type: Type,
         body: Vec<Statement>,
     },
@@ -25,457 +61,1516 @@ pub enum Statement {
         then_block: Vec<Statement>,
         else_block: Option<Vec<Statement>>,
     },
+    // runs the first arm whose pattern equals the subject, if any
+    Match {
+        subject: Expression,
+        arms: Vec<MatchArm>,
+    },
+    // enum Color { Red, Green, Blue }
+    Enum {
+        name: Symbol,
+        variants: Vec<Symbol>,
+    },
     Expression(Expression),
+    // stops the program with an error unless the condition holds
+    Assert(Expression),
+    // stops the program with an error unless both values are equal
+    AssertEq(Expression, Expression),
     Return(Expression),
+    // runs the body; a runtime error raised in it runs the handler instead, with the error's
+    // message in `error`
+    Try {
+        body: Vec<Statement>,
+        error: Symbol,
+        handler: Vec<Statement>,
+    },
+    // raise "message"; fails like a runtime error, which a surrounding try can handle
+    Raise(Expression),
+    // path of the imported file, relative to the importing one
+    Import(String),
+    Break,
+    Continue,
 }
 
 impl Statement {
-    pub fn accept<V: ASTVisitor>(&self, visitor: &mut V) {
-        match self {
-            Statement::Declaration(name, exp, declared_type ) => {
-                visitor.visit_declaration(name.clone(), exp.clone(), declared_type.clone())
+    pub fn new(kind: StatementKind, span: Span) -> Statement {
+        Statement { kind, span }
+    }
+
+    pub fn accept<V: ASTVisitor>(&self, visitor: &mut V) -> V::Output {
+        let span = self.span;
+        match &self.kind {
+            StatementKind::Declaration(name, exp, declared_type) => {
+                visitor.visit_declaration(*name, exp, declared_type.as_ref(), span)
             }
-            Statement::Assignment(name, exp) => visitor.visit_assignment(name.clone(), exp.clone()),
+            StatementKind::Const(name, exp, declared_type) => {
+                visitor.visit_const(*name, exp, declared_type.as_ref(), span)
+            }
+            StatementKind::Assignment(name, exp) => visitor.visit_assignment(*name, exp, span),
+
+            StatementKind::Print { value, debug } => visitor.visit_print(value, *debug, span),
 
-            Statement::Print(exp) => visitor.visit_print(exp.clone()),
+            While { condition, body } => visitor.visit_while(condition, body, span),
 
-            While { condition, body } => visitor.visit_while(condition.clone(), body.clone()),
+            StatementKind::DoWhile { body, condition } => {
+                visitor.visit_do_while(body, condition, span)
+            }
+
+            StatementKind::For {
+                variable,
+                annotation,
+                start,
+                end,
+                body,
+            } => visitor.visit_for(*variable, annotation.as_ref(), start, end, body, span),
 
-            Statement::Block(stmt) => visitor.visit_block(stmt.clone()),
-            Statement::FunctionDeclaration {
+            StatementKind::ForIn {
+                variable,
+                annotation,
+                iterable,
+                body,
+            } => visitor.visit_for_in(*variable, annotation.as_ref(), iterable, body, span),
+
+            StatementKind::Block(stmt) => visitor.visit_block(stmt, span),
+            StatementKind::FunctionDeclaration {
                 name,
+                type_params,
                 params,
                 return_type,
                 body,
             } => visitor.visit_function_declaration(
-                name.clone(),
-                params.clone(),
-                return_type.clone(),
-                body.clone(),
+                *name,
+                type_params,
+                params,
+                return_type,
+                body,
+                span,
             ),
 
             If {
                 condition,
                 then_block,
                 else_block,
-            } => visitor.visit_if(condition.clone(), then_block.clone(), else_block.clone()),
+            } => visitor.visit_if(condition, then_block, else_block.as_deref(), span),
+
+            StatementKind::Match { subject, arms } => visitor.visit_match(subject, arms, span),
+
+            StatementKind::Enum { name, variants } => visitor.visit_enum(*name, variants, span),
+
+            StatementKind::Expression(exp) => visitor.visit_expression(exp, span),
+
+            StatementKind::Assert(condition) => visitor.visit_assert(condition, span),
+
+            StatementKind::AssertEq(left, right) => visitor.visit_assert_eq(left, right, span),
+
+            StatementKind::Return(ret) => visitor.visit_return(ret, span),
+
+            StatementKind::Try {
+                body,
+                error,
+                handler,
+            } => visitor.visit_try(body, *error, handler, span),
 
-            Statement::Expression(exp) => visitor.visit_expression(exp.clone()),
+            StatementKind::Raise(message) => visitor.visit_raise(message, span),
 
-            Statement::Return(ret) => visitor.visit_return(ret.clone()),
+            StatementKind::Break => visitor.visit_break(span),
+
+            StatementKind::Continue => visitor.visit_continue(span),
+
+            StatementKind::Import(path) => visitor.visit_import(path, span),
         }
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub enum Expression {
-    Number(i32),
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Vec<Statement>,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    // a literal, compared to the subject with ==
+    Value(Expression),
+    // `_`, matching anything
+    Wildcard,
+}
+
+// whether the arms cover every possible subject; without `_` only bools can be covered here,
+// enums need their declaration to know the variants
+pub fn is_exhaustive(arms: &[MatchArm]) -> bool {
+    let covers = |b: bool| {
+        arms.iter().any(|arm| {
+            matches!(&arm.pattern, Pattern::Value(value) if value.kind == ExpressionKind::Bool(b))
+        })
+    };
+    arms.iter().any(|arm| arm.pattern == Pattern::Wildcard) || (covers(true) && covers(false))
+}
+
+// whether a statement starting with `token` is an expression statement
+fn starts_expression(token: &Token) -> bool {
+    match token {
+        Token::Identifier(_)
+        | Token::Number(..)
+        | Token::Float(..)
+        | Token::String(..)
+        | Token::Bool(_)
+        | Token::Keyword(Keyword::None)
+        | Token::Operator(Operator::Not | Operator::Binary(BinOp::Sub))
+        | Token::WordOperator(Operator::Not) => true,
+        Token::Punctuation(p) => p == "(",
+        _ => false,
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+}
+
+impl Expression {
+    pub fn new(kind: ExpressionKind, span: Span) -> Expression {
+        Expression { kind, span }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ExpressionKind {
+    // the value and the literal as written, so formatting keeps `0xFF` and `1_000`
+    Number(i64, String),
+    Float(f64, String),
+    // the value and the literal as written, escapes included
+    String(String, String),
     Bool(bool),
-    Variable(String),
+    None,
+    Variable(Symbol),
     BinaryOperation {
         left: Box<Expression>,
-        operator: String,
+        operator: BinOp,
         right: Box<Expression>,
+        spelling: Spelling,
+    },
+    UnaryOperation {
+        operator: UnaryOp,
+        operand: Box<Expression>,
+        spelling: Spelling,
     },
     FunctionCall {
-        name: String,
+        name: Symbol,
         arguments: Vec<Expression>,
     },
+    // Color.Red
+    EnumVariant {
+        enum_name: Symbol,
+        variant: Symbol,
+    },
+    // 0..10, the numbers from start up to but not including end
+    Range {
+        start: Box<Expression>,
+        end: Box<Expression>,
+    },
+    // { let a = 1; a + 2 }, running the statements in a scope of their own and evaluating to
+    // the expression at the end
+    Block {
+        statements: Vec<Statement>,
+        value: Box<Expression>,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    And,
+    Or,
+}
+
+impl fmt::Display for BinOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Rem => "%",
+            BinOp::Eq => "==",
+            BinOp::Ne => "!=",
+            BinOp::Lt => "<",
+            BinOp::Gt => ">",
+            BinOp::Le => "<=",
+            BinOp::Ge => ">=",
+            BinOp::And => "&&",
+            BinOp::Or => "||",
+        };
+        write!(f, "{}", operator)
+    }
+}
+
+// how an operator was written, so formatting writes it back the same way: `&&`, `||` and `!`
+// can be spelled out as `and`, `or` and `not`, and `i++;` and `i--;` stand for `i = i + 1;` and
+// `i = i - 1;`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Spelling {
+    Symbol,
+    Word,
+    Step,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum UnaryOp {
+    Not,
+    Neg,
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnaryOp::Not => write!(f, "!"),
+            UnaryOp::Neg => write!(f, "-"),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Type {
     Number,
+    Float,
     Boolean,
+    String,
     Void,
+    // parameter types and return type, written `func(number, bool): number`
+    Function(Vec<Type>, Box<Type>),
+    // a declared enum, by name
+    Enum(Symbol),
+    // a value of the inner type or none, written `number?`
+    Optional(Box<Type>),
+    Range,
+    // a type parameter of a generic function, standing for whatever type a call passes
+    Parameter(Symbol),
+    // type of the `none` literal, which fits every optional type
+    None,
+    // parameter of builtins that take a value of any type, like typeof; programs can't
+    // write it
+    Any,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Number => write!(f, "number"),
+            Type::Float => write!(f, "float"),
+            Type::Boolean => write!(f, "bool"),
+            Type::String => write!(f, "string"),
+            Type::Void => write!(f, "void"),
+            Type::Enum(name) => write!(f, "{}", name),
+            // the typechecker tells a generic function's type parameters apart with a `'`
+            Type::Parameter(name) => write!(f, "{}", name.as_str().split('\'').next().unwrap()),
+            Type::Optional(inner) => write!(f, "{}?", inner),
+            Type::Range => write!(f, "range"),
+            Type::None => write!(f, "none"),
+            Type::Any => write!(f, "any"),
+            Type::Function(params, return_type) => {
+                let params: Vec<String> = params.iter().map(|t| t.to_string()).collect();
+                write!(f, "func({})", params.join(", "))?;
+                match **return_type {
+                    Type::Void => Ok(()),
+                    ref t => write!(f, ": {}", t),
+                }
+            }
+        }
+    }
 }
 
+// visitors borrow the tree, so walking it never copies a statement
 pub trait ASTVisitor {
-    fn visit_declaration(&mut self, name: String, expr: Expression, declared_type: Option<Type>);
-    fn visit_assignment(&mut self, name: String, expr: Expression);
-    fn visit_print(&mut self, expr: Expression);
-    fn visit_while(&mut self, condition: Expression, body: Vec<Statement>);
-    fn visit_block(&mut self, statements: Vec<Statement>);
+    type Output;
+
+    fn visit_declaration(
+        &mut self,
+        name: Symbol,
+        expr: &Expression,
+        declared_type: Option<&Type>,
+        span: Span,
+    ) -> Self::Output;
+    fn visit_const(
+        &mut self,
+        name: Symbol,
+        expr: &Expression,
+        declared_type: Option<&Type>,
+        span: Span,
+    ) -> Self::Output;
+    fn visit_assignment(&mut self, name: Symbol, expr: &Expression, span: Span) -> Self::Output;
+    fn visit_print(&mut self, expr: &Expression, debug: bool, span: Span) -> Self::Output;
+    fn visit_while(
+        &mut self,
+        condition: &Expression,
+        body: &[Statement],
+        span: Span,
+    ) -> Self::Output;
+    fn visit_do_while(
+        &mut self,
+        body: &[Statement],
+        condition: &Expression,
+        span: Span,
+    ) -> Self::Output;
+    fn visit_for(
+        &mut self,
+        variable: Symbol,
+        annotation: Option<&Type>,
+        start: &Expression,
+        end: &Expression,
+        body: &[Statement],
+        span: Span,
+    ) -> Self::Output;
+    fn visit_for_in(
+        &mut self,
+        variable: Symbol,
+        annotation: Option<&Type>,
+        iterable: &Expression,
+        body: &[Statement],
+        span: Span,
+    ) -> Self::Output;
+    fn visit_block(&mut self, statements: &[Statement], span: Span) -> Self::Output;
     fn visit_function_declaration(
         &mut self,
-        name: String,
-        params: Vec<(String, Type)>,
-        return_type: Type,
-        body: Vec<Statement>,
-    );
+        name: Symbol,
+        type_params: &[Symbol],
+        params: &[(Symbol, Type)],
+        return_type: &Type,
+        body: &[Statement],
+        span: Span,
+    ) -> Self::Output;
     fn visit_if(
         &mut self,
-        condition: Expression,
-        body: Vec<Statement>,
-        else_branch: Option<Vec<Statement>>,
-    );
-    fn visit_expression(&mut self, expr: Expression);
-    fn visit_return(&mut self, expr: Expression);
+        condition: &Expression,
+        body: &[Statement],
+        else_branch: Option<&[Statement]>,
+        span: Span,
+    ) -> Self::Output;
+    fn visit_match(&mut self, subject: &Expression, arms: &[MatchArm], span: Span) -> Self::Output;
+    fn visit_enum(&mut self, name: Symbol, variants: &[Symbol], span: Span) -> Self::Output;
+    fn visit_expression(&mut self, expr: &Expression, span: Span) -> Self::Output;
+    fn visit_assert(&mut self, condition: &Expression, span: Span) -> Self::Output;
+    fn visit_assert_eq(
+        &mut self,
+        left: &Expression,
+        right: &Expression,
+        span: Span,
+    ) -> Self::Output;
+    fn visit_return(&mut self, expr: &Expression, span: Span) -> Self::Output;
+    fn visit_try(
+        &mut self,
+        body: &[Statement],
+        error: Symbol,
+        handler: &[Statement],
+        span: Span,
+    ) -> Self::Output;
+    fn visit_raise(&mut self, message: &Expression, span: Span) -> Self::Output;
+    fn visit_break(&mut self, span: Span) -> Self::Output;
+    fn visit_continue(&mut self, span: Span) -> Self::Output;
+    fn visit_import(&mut self, path: &str, span: Span) -> Self::Output;
 }
 
-pub struct Parser {
-    tokens: Vec<Token>,
-    type_envs: Vec<HashMap<String, Type>>,
-    current: usize,
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParseErrorKind {
+    UnexpectedToken { expected: String, found: Token },
+    UnexpectedEof { expected: String },
+    // the input ended before the delimiter opened at the error's span was closed
+    Unclosed { delimiter: String },
+    // a statement that doesn't end with ';', reported at its last token
+    MissingSemicolon { found: Token },
+    // lexing fails partway through when the parser pulls tokens from the lexer as it goes
+    Lex(LexErrorKind),
+    // brackets, blocks or unary operators nested deeper than MAX_DEPTH, reported where the
+    // limit is crossed
+    TooDeep,
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
-        let mut type_envs = Vec::new();
-        type_envs.push(HashMap::new());
-        Self {
-            tokens,
-            current: 0,
-            type_envs,
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::UnexpectedToken { expected, found } => {
+                write!(f, "expected {}, but got {}", expected, found)
+            }
+            ParseErrorKind::UnexpectedEof { expected } => {
+                write!(f, "expected {}, but reached end of input", expected)
+            }
+            ParseErrorKind::Unclosed { delimiter } => {
+                write!(f, "'{}' is never closed", delimiter)
+            }
+            ParseErrorKind::MissingSemicolon { found } => {
+                write!(f, "missing ';' at end of statement, found {}", found)
+            }
+            ParseErrorKind::Lex(kind) => write!(f, "{}", kind),
+            ParseErrorKind::TooDeep => {
+                write!(f, "code is nested more than {} levels deep", MAX_DEPTH)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: Span,
+}
+
+impl ParseError {
+    fn unexpected(expected: &str, found: Option<&Token>, span: Span) -> ParseError {
+        let kind = match found {
+            Some(token) => ParseErrorKind::UnexpectedToken {
+                expected: expected.to_string(),
+                found: token.clone(),
+            },
+            None => ParseErrorKind::UnexpectedEof {
+                expected: expected.to_string(),
+            },
+        };
+        ParseError { kind, span }
+    }
+    fn unclosed(delimiter: &str, open: Span) -> ParseError {
+        ParseError {
+            kind: ParseErrorKind::Unclosed {
+                delimiter: delimiter.to_string(),
+            },
+            span: open,
+        }
+    }
+}
+
+impl From<LexError> for ParseError {
+    fn from(e: LexError) -> ParseError {
+        ParseError {
+            kind: ParseErrorKind::Lex(e.kind),
+            span: e.span,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.kind)
+    }
+}
+
+// how deep statements, expressions and types may nest. The parser and every pass after it
+// recurse once per level, so this keeps them well within the stack of any thread
+pub const MAX_DEPTH: usize = 128;
+
+// where the parser pulls its tokens from, a lexer or tokens lexed up front
+type TokenStream<'a> = Box<dyn Iterator<Item = Result<SpannedToken, LexError>> + 'a>;
+
+pub struct Parser<'a> {
+    tokens: TokenStream<'a>,
+    // tokens pulled from the stream but not consumed yet; the parser never looks more than
+    // three tokens ahead, so it never holds more
+    lookahead: VecDeque<SpannedToken>,
+    previous: Span,
+    // the error the stream stopped at; an EOF token takes its place so parsing winds down
+    lex_error: Option<LexError>,
+    // type parameters of the generic functions being parsed, which types can name
+    type_params: Vec<Symbol>,
+    // missing semicolons parsing went on past, reported with whatever error ends it
+    errors: Vec<ParseError>,
+    // statements, expressions and types being parsed, one inside the other
+    depth: usize,
+}
+
+impl Parser<'static> {
+    pub fn new(tokens: Vec<SpannedToken>) -> Self {
+        Parser::from_lexer(tokens.into_iter().map(Ok))
+    }
+}
+
+impl<'a> Parser<'a> {
+    // parses while lexing, pulling each token only when it is needed instead of lexing the
+    // whole source first
+    pub fn from_lexer(tokens: impl Iterator<Item = Result<SpannedToken, LexError>> + 'a) -> Self {
+        let mut parser = Parser {
+            tokens: Box::new(tokens),
+            lookahead: VecDeque::new(),
+            previous: Span::default(),
+            lex_error: None,
+            type_params: Vec::new(),
+            errors: Vec::new(),
+            depth: 0,
+        };
+        parser.fill();
+        parser
+    }
+
+    fn fill(&mut self) {
+        while self.lookahead.len() < 3 && self.lex_error.is_none() {
+            match self.tokens.next() {
+                Some(Ok(spanned)) => self.lookahead.push_back(spanned),
+                Some(Err(e)) => {
+                    self.lookahead.push_back(SpannedToken {
+                        token: Token::EOF,
+                        span: e.span,
+                    });
+                    self.lex_error = Some(e);
+                }
+                None => break,
+            }
+        }
+    }
+
+    // a parse that ran into the end of a stream cut short by a lex error fails with that
+    // error; anything that went wrong before it is reported as it is
+    fn finish<T>(&self, result: Result<T, ParseError>) -> Result<T, ParseError> {
+        match &self.lex_error {
+            Some(e) if matches!(self.peek(), None | Some(Token::EOF)) => Err(e.clone().into()),
+            _ => result,
         }
     }
 
     fn peek(&self) -> Option<&Token> {
-        if self.current < self.tokens.len() {
-            return Some(&self.tokens[self.current]);
+        self.lookahead.front().map(|t| &t.token)
+    }
+
+    // the token after the next one
+    fn peek_second(&self) -> Option<&SpannedToken> {
+        self.lookahead.get(1)
+    }
+
+    // span of the next token, or of the last one once the input is exhausted
+    fn peek_span(&self) -> Span {
+        self.lookahead
+            .front()
+            .map(|t| t.span)
+            .unwrap_or(self.previous)
+    }
+
+    // span of the most recently consumed token
+    fn previous_span(&self) -> Span {
+        self.previous
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let spanned = self.lookahead.pop_front()?;
+        self.previous = spanned.span;
+        self.fill();
+        Some(spanned.token)
+    }
+
+    // `++` or `--`, lexed as two adjacent operators, `index` tokens ahead
+    fn step_at(&self, index: usize) -> Option<BinOp> {
+        let (first, second) = (self.lookahead.get(index)?, self.lookahead.get(index + 1)?);
+        match (&first.token, &second.token) {
+            (Token::Operator(Operator::Binary(a)), Token::Operator(Operator::Binary(b)))
+                if a == b
+                    && matches!(a, BinOp::Add | BinOp::Sub)
+                    && first.span.end == second.span.start =>
+            {
+                Some(*a)
+            }
+            _ => None,
         }
-        None
     }
 
-    fn advance(&mut self) -> Option<&Token> {
-        let token = self.tokens.get(self.current)?;
-        self.current += 1;
-        Some(token)
+    // consumes `++` or `--`, returning the single operator
+    fn step_operator(&mut self) -> Option<BinOp> {
+        let operator = self.step_at(0)?;
+        self.advance();
+        self.advance();
+        Some(operator)
     }
 
-    fn enter_scope(&mut self) {
-        self.type_envs.push(HashMap::new());
+    // whether the name about to be parsed is assigned to, by `=`, `++` or `--`, rather than
+    // starting an expression statement
+    fn starts_assignment(&self) -> bool {
+        let assign = Token::Operator(Operator::Assign);
+        self.peek_second().map(|t| &t.token) == Some(&assign) || self.step_at(1).is_some()
     }
 
-    fn exit_scope(&mut self) {
-        self.type_envs.pop();
+    // runs `parse` one level deeper, failing instead once that is past MAX_DEPTH
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        if self.depth == MAX_DEPTH {
+            return Err(ParseError {
+                kind: ParseErrorKind::TooDeep,
+                span: self.peek_span(),
+            });
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
     }
 
-    pub fn parse(&mut self) -> Vec<Statement> {
+    // every syntax error found, in the order of the source; never an empty list
+    pub fn parse(&mut self) -> Result<Vec<Statement>, Vec<ParseError>> {
         let mut statements = Vec::new();
-        while let Some(stmt) = self.parse_statement() {
-            statements.push(stmt);
+        let parsed = loop {
+            match self.parse_statement() {
+                Ok(Some(stmt)) => statements.push(stmt),
+                Ok(None) => break Ok(statements),
+                Err(e) => break Err(e),
+            }
+        };
+        let mut errors = std::mem::take(&mut self.errors);
+        match self.finish(parsed) {
+            Ok(statements) if errors.is_empty() => Ok(statements),
+            Ok(_) => Err(errors),
+            Err(e) => {
+                errors.push(e);
+                Err(errors)
+            }
         }
-        statements
     }
 
-    fn parse_statement(&mut self) -> Option<Statement> {
-        match self.peek() {
-            Some(Token::Keyword(k)) if k == "let" => {
+    // parses input consisting of exactly one expression, e.g. for the REPL's :type
+    pub fn parse_single_expression(&mut self) -> Result<Expression, ParseError> {
+        let parsed = self
+            .parse_expression()
+            .and_then(|expression| self.expect(Token::EOF).map(|_| expression));
+        self.finish(parsed)
+    }
+
+    fn parse_statement(&mut self) -> Result<Option<Statement>, ParseError> {
+        self.nested(Self::parse_statement_kind)
+    }
+
+    fn parse_statement_kind(&mut self) -> Result<Option<Statement>, ParseError> {
+        let start = self.peek_span();
+
+        let kind = match self.peek() {
+            Some(Token::Keyword(keyword @ (Keyword::Let | Keyword::Const))) => {
+                let constant = *keyword == Keyword::Const;
                 self.advance();
+                let span = self.peek_span();
                 let name = match self.advance() {
-                    Some(Token::Identifier(name)) => name.clone(),
-                    _ => panic!("Expected identifier after 'let'"),
+                    Some(Token::Identifier(name)) => name,
+                    t => {
+                        let keyword = if constant { "const" } else { "let" };
+                        let expected = format!("identifier after '{}'", keyword);
+                        return Err(ParseError::unexpected(&expected, t.as_ref(), span));
+                    }
                 };
 
-                match self.advance() {
+                let span = self.peek_span();
+                let (expr, declared_type) = match self.advance() {
                     // implicit type declaration
-                    Some(Token::Operator(op)) if op == "=" => {
-                        let expr = self.parse_expression();
-                        self.expect(Token::Punctuation(";".to_string()));
-                        Some(Statement::Declaration(name, expr, None))
+                    Some(Token::Operator(Operator::Assign)) => {
+                        let expr = self.parse_expression()?;
+                        self.expect_semicolon()?;
+                        (expr, None)
                     }
                     // explicit type declaration
                     Some(Token::Punctuation(op)) if op == ":" => {
-                        let declared_data_type = match self.advance() {
-                            Some(Token::Type(s)) if s.as_str() == "bool" => Type::Boolean,
-                            Some(Token::Type(s)) if s.as_str() == "number" => Type::Number,
-                            _ => panic!("Expected type after :"),
-                        };
+                        let declared_data_type = self.parse_type()?;
 
-                        self.expect(Token::Operator("=".to_string()));
+                        self.expect(Token::Operator(Operator::Assign))?;
 
-                        let expr = self.parse_expression();
-                        self.expect(Token::Punctuation(";".to_string()));
+                        let expr = self.parse_expression()?;
+                        self.expect_semicolon()?;
 
-                        Some(Statement::Declaration(name, expr, Some(declared_data_type)))
+                        (expr, Some(declared_data_type))
                     }
-                    _ => panic!("Unknown declaration structure"),
+                    t => {
+                        return Err(ParseError::unexpected(
+                            "'=' or ':' in declaration",
+                            t.as_ref(),
+                            span,
+                        ));
+                    }
+                };
+
+                if constant {
+                    StatementKind::Const(name, expr, declared_type)
+                } else {
+                    StatementKind::Declaration(name, expr, declared_type)
                 }
             }
 
-            Some(Token::Identifier(name)) => {
-                let name = name.clone();
+            Some(Token::Identifier(name)) if self.starts_assignment() => {
+                let name = *name;
                 self.advance();
 
-                if Some(&Token::Punctuation("(".to_string())) == self.peek() {
-                    self.advance();
-
-                    let arguments = self.parse_function_args();
-                    self.expect(Token::Punctuation(")".to_string()));
-                    self.expect(Token::Punctuation(";".to_string()));
-                    Some(Statement::Expression(Expression::FunctionCall {
-                        name,
-                        arguments,
-                    }))
+                if let Some(operator) = self.step_operator() {
+                    // `i++;` and `i--;` are sugar for `i = i + 1;` and `i = i - 1;`
+                    let span = start.to(self.previous_span());
+                    self.expect_semicolon()?;
+                    let step = ExpressionKind::BinaryOperation {
+                        left: Box::new(Expression::new(ExpressionKind::Variable(name), start)),
+                        operator,
+                        right: Box::new(Expression::new(
+                            ExpressionKind::Number(1, "1".to_string()),
+                            span,
+                        )),
+                        spelling: Spelling::Step,
+                    };
+                    StatementKind::Assignment(name, Expression::new(step, span))
                 } else {
-                    self.expect(Token::Operator("=".to_string()));
-                    let expr = self.parse_expression();
-                    self.expect(Token::Punctuation(";".to_string()));
-                    Some(Statement::Assignment(name, expr))
+                    self.expect(Token::Operator(Operator::Assign))?;
+                    let expr = self.parse_expression()?;
+                    self.expect_semicolon()?;
+                    StatementKind::Assignment(name, expr)
                 }
             }
 
-            Some(Token::Keyword(k)) if k == "croak" => {
-                self.advance(); // consume "print"
-                let expr = self.parse_expression();
-                self.expect(Token::Punctuation(";".to_string()));
-                Some(Statement::Print(expr))
+            Some(Token::Keyword(keyword @ (Keyword::Croak | Keyword::CroakDebug))) => {
+                let debug = *keyword == Keyword::CroakDebug;
+                self.advance(); // consume "croak"
+                let value = self.parse_expression()?;
+                self.expect_semicolon()?;
+                StatementKind::Print { value, debug }
+            }
+
+            // assert x > 0;
+            Some(Token::Keyword(Keyword::Assert)) => {
+                self.advance();
+                let condition = self.parse_expression()?;
+                self.expect_semicolon()?;
+                StatementKind::Assert(condition)
+            }
+
+            // assert_eq f(2), 4;
+            Some(Token::Keyword(Keyword::AssertEq)) => {
+                self.advance();
+                let left = self.parse_expression()?;
+                self.expect(Token::Punctuation(",".to_string()))?;
+                let right = self.parse_expression()?;
+                self.expect_semicolon()?;
+                StatementKind::AssertEq(left, right)
             }
 
-            Some(Token::Keyword(k)) if k == "return" => {
+            Some(Token::Keyword(Keyword::Return)) => {
                 self.advance();
-                let expr = self.parse_expression();
-                self.expect(Token::Punctuation(";".to_string()));
-                Some(Statement::Return(expr))
+                let expr = self.parse_expression()?;
+                self.expect_semicolon()?;
+                StatementKind::Return(expr)
             }
 
-            Some(Token::Keyword(k)) if k == "while" => {
+            // try { ... } handle err { ... }
+            Some(Token::Keyword(Keyword::Try)) => {
                 self.advance();
 
-                let condition = self.parse_expression();
-                self.expect(Token::Punctuation("{".to_string()));
+                let body = self.parse_block()?;
+
+                self.expect(Token::Keyword(Keyword::Handle))?;
+                let span = self.peek_span();
+                let error = match self.advance() {
+                    Some(Token::Identifier(name)) => name,
+                    t => {
+                        return Err(ParseError::unexpected(
+                            "identifier after 'handle'",
+                            t.as_ref(),
+                            span,
+                        ));
+                    }
+                };
 
-                let body = self.parse_block();
-                self.expect(Token::Punctuation("}".to_string()));
+                let handler = self.parse_block()?;
 
-                Some(While { condition, body })
+                StatementKind::Try {
+                    body,
+                    error,
+                    handler,
+                }
             }
 
-            Some(Token::Punctuation(p)) if p == "{" => {
+            Some(Token::Keyword(Keyword::Raise)) => {
                 self.advance();
+                let message = self.parse_expression()?;
+                self.expect_semicolon()?;
+                StatementKind::Raise(message)
+            }
 
-                let block = self.parse_block();
+            // import "other.frog";
+            Some(Token::Keyword(Keyword::Import)) => {
+                self.advance();
 
-                self.expect(Token::Punctuation("}".to_string()));
+                let span = self.peek_span();
+                let path = match self.advance() {
+                    Some(Token::String(path, _)) => path,
+                    t => {
+                        return Err(ParseError::unexpected(
+                            "module path after 'import'",
+                            t.as_ref(),
+                            span,
+                        ));
+                    }
+                };
+                self.expect_semicolon()?;
+                StatementKind::Import(path)
+            }
 
-                Some(Statement::Block(block))
+            Some(Token::Keyword(Keyword::Break)) => {
+                self.advance();
+                self.expect_semicolon()?;
+                StatementKind::Break
             }
 
-            Some(Token::Keyword(k)) if k == "if" => {
+            Some(Token::Keyword(Keyword::Continue)) => {
                 self.advance();
+                self.expect_semicolon()?;
+                StatementKind::Continue
+            }
 
-                let condition = self.parse_expression();
-                self.expect(Token::Punctuation("{".to_string()));
+            Some(Token::Keyword(Keyword::While)) => {
+                self.advance();
 
-                let then_block = self.parse_block();
-                self.expect(Token::Punctuation("}".to_string()));
+                let condition = self.parse_expression()?;
+                let body = self.parse_block()?;
 
-                if self.peek() != Some(&Token::Keyword("else".to_string())) {
-                    return Some(If {
-                        condition,
-                        then_block,
-                        else_block: None,
-                    });
+                While { condition, body }
+            }
+
+            Some(Token::Keyword(Keyword::Do)) => {
+                self.advance();
+
+                let body = self.parse_block()?;
+
+                self.expect(Token::Keyword(Keyword::While))?;
+                let condition = self.parse_expression()?;
+                self.expect_semicolon()?;
+
+                StatementKind::DoWhile { body, condition }
+            }
+
+            // for i = 0 to 10 { ... } or for i in 0..11 { ... }, with an optional annotation
+            Some(Token::Keyword(Keyword::For)) => {
+                self.advance();
+
+                let span = self.peek_span();
+                let variable = match self.advance() {
+                    Some(Token::Identifier(name)) => name,
+                    t => {
+                        return Err(ParseError::unexpected(
+                            "identifier after 'for'",
+                            t.as_ref(),
+                            span,
+                        ));
+                    }
+                };
+
+                let annotation = if self.peek() == Some(&Token::Punctuation(":".to_string())) {
+                    self.advance();
+                    Some(self.parse_type()?)
+                } else {
+                    None
+                };
+
+                if self.peek() == Some(&Token::Keyword(Keyword::In)) {
+                    self.advance();
+                    let iterable = self.parse_expression()?;
+                    let body = self.parse_block()?;
+
+                    StatementKind::ForIn {
+                        variable,
+                        annotation,
+                        iterable,
+                        body,
+                    }
+                } else {
+                    self.expect(Token::Operator(Operator::Assign))?;
+                    let start = self.parse_expression()?;
+
+                    self.expect(Token::Keyword(Keyword::To))?;
+                    let end = self.parse_expression()?;
+
+                    let body = self.parse_block()?;
+
+                    StatementKind::For {
+                        variable,
+                        annotation,
+                        start,
+                        end,
+                        body,
+                    }
                 }
+            }
+
+            Some(Token::Punctuation(p)) if p == "{" => StatementKind::Block(self.parse_block()?),
+
+            Some(Token::Keyword(Keyword::If)) => {
                 self.advance();
-                self.expect(Token::Punctuation("{".to_string()));
 
-                let else_block = self.parse_block();
-                self.expect(Token::Punctuation("}".to_string()));
+                let condition = self.parse_expression()?;
+                let then_block = self.parse_block()?;
 
-                Some(If {
+                let else_block = if self.peek() == Some(&Token::Keyword(Keyword::Else)) {
+                    self.advance();
+                    Some(self.parse_block()?)
+                } else {
+                    None
+                };
+
+                If {
                     condition,
                     then_block,
-                    else_block: Some(else_block),
-                })
+                    else_block,
+                }
+            }
+
+            // match x { 1 => { ... } _ => { ... } }
+            Some(Token::Keyword(Keyword::Match)) => {
+                self.advance();
+
+                let subject = self.parse_expression()?;
+                self.expect(Token::Punctuation("{".to_string()))?;
+
+                let mut arms = Vec::new();
+                while self.peek() != Some(&Token::Punctuation("}".to_string())) {
+                    let arm_start = self.peek_span();
+                    let pattern = self.parse_pattern()?;
+                    self.expect(Token::Operator(Operator::Arrow))?;
+                    let body = self.parse_block()?;
+                    arms.push(MatchArm {
+                        pattern,
+                        body,
+                        span: arm_start.to(self.previous_span()),
+                    });
+
+                    // arms may be separated by commas
+                    if self.peek() == Some(&Token::Punctuation(",".to_string())) {
+                        self.advance();
+                    }
+                }
+                self.expect(Token::Punctuation("}".to_string()))?;
+
+                StatementKind::Match { subject, arms }
             }
 
-            Some(Token::Keyword(k)) if k == "func" => {
+            // enum Color { Red, Green, Blue }
+            Some(Token::Keyword(Keyword::Enum)) => {
                 self.advance();
 
+                let span = self.peek_span();
                 let name = match self.advance() {
-                    Some(Token::Identifier(s)) => s.clone(),
-                    a => panic!("Expected identifier after 'func', got: {:?}", a),
+                    Some(Token::Identifier(name)) => name,
+                    t => {
+                        return Err(ParseError::unexpected(
+                            "identifier after 'enum'",
+                            t.as_ref(),
+                            span,
+                        ));
+                    }
                 };
+                self.expect(Token::Punctuation("{".to_string()))?;
 
-                self.expect(Token::Punctuation("(".to_string()));
+                let mut variants = Vec::new();
+                while let Some(Token::Identifier(variant)) = self.peek() {
+                    variants.push(*variant);
+                    self.advance();
 
-                let mut params = Vec::new();
+                    if self.peek() != Some(&Token::Punctuation(",".to_string())) {
+                        break;
+                    }
+                    self.advance();
+                }
+                self.expect(Token::Punctuation("}".to_string()))?;
+
+                StatementKind::Enum { name, variants }
+            }
+
+            Some(Token::Keyword(Keyword::Func)) => {
+                self.advance();
+
+                let span = self.peek_span();
+                let name = match self.advance() {
+                    Some(Token::Identifier(s)) => s,
+                    t => {
+                        return Err(ParseError::unexpected(
+                            "identifier after 'func'",
+                            t.as_ref(),
+                            span,
+                        ));
+                    }
+                };
+
+                let type_params = self.parse_type_params()?;
+                // the names stay types of their own through the signature and the body
+                let outer_type_params = self.type_params.len();
+                self.type_params.extend(&type_params);
+                let declaration = self.parse_function(name, type_params);
+                self.type_params.truncate(outer_type_params);
+                declaration?
+            }
+
+            Some(Token::EOF) => return Ok(None),
+            Some(t) if starts_expression(t) => {
+                let expr = self.parse_expression()?;
+                self.expect_semicolon()?;
+                StatementKind::Expression(expr)
+            }
+            t => return Err(ParseError::unexpected("statement", t, start)),
+        };
+
+        Ok(Some(Statement::new(kind, start.to(self.previous_span()))))
+    }
 
-                while let Some(Token::Identifier(param_name)) = self.peek() {
-                    let param_name = param_name.clone();
+    // `<T, U>` after a function's name, if it has any
+    fn parse_type_params(&mut self) -> Result<Vec<Symbol>, ParseError> {
+        let mut type_params = Vec::new();
+        if self.peek() != Some(&Token::Operator(Operator::Binary(BinOp::Lt))) {
+            return Ok(type_params);
+        }
+        self.advance();
+        loop {
+            let span = self.peek_span();
+            match self.advance() {
+                Some(Token::Identifier(name)) => type_params.push(name),
+                t => {
+                    return Err(ParseError::unexpected(
+                        "type parameter name",
+                        t.as_ref(),
+                        span,
+                    ));
+                }
+            }
+            match self.peek() {
+                Some(Token::Punctuation(p)) if p == "," => {
                     self.advance();
+                }
+                _ => break,
+            }
+        }
+        self.expect(Token::Operator(Operator::Binary(BinOp::Gt)))?;
+        Ok(type_params)
+    }
 
-                    self.expect(Token::Punctuation(":".to_string()));
+    // the rest of a function declaration, from the parameter list on
+    fn parse_function(
+        &mut self,
+        name: Symbol,
+        type_params: Vec<Symbol>,
+    ) -> Result<StatementKind, ParseError> {
+        self.expect(Token::Punctuation("(".to_string()))?;
 
-                    let param_type = match self.advance() {
-                        Some(Token::Type(t)) if t == "bool" => Type::Boolean,
-                        Some(Token::Type(t)) if t == "number" => Type::Number,
-                        a => panic!("Expected type, got: {:?}", a),
-                    };
-                    params.push((param_name, param_type));
+        let mut params = Vec::new();
 
-                    if self.peek() == Some(&Token::Punctuation(",".to_string())) {
-                        self.advance();
-                        continue;
-                    } else {
+        while let Some(Token::Identifier(param_name)) = self.peek() {
+            let param_name = *param_name;
+            self.advance();
+
+            self.expect(Token::Punctuation(":".to_string()))?;
+
+            let param_type = self.parse_type()?;
+            params.push((param_name, param_type));
+
+            if self.peek() == Some(&Token::Punctuation(",".to_string())) {
+                self.advance();
+                continue;
+            } else {
+                break;
+            }
+        }
+
+        self.expect(Token::Punctuation(")".to_string()))?;
+
+        let return_type = match self.peek() {
+            Some(Token::Punctuation(p)) if p == ":" => {
+                self.advance();
+                self.parse_type()?
+            }
+            Some(Token::Punctuation(p)) if p == "{" => Type::Void,
+            t => {
+                return Err(ParseError::unexpected(
+                    "return type or '{'",
+                    t,
+                    self.peek_span(),
+                ));
+            }
+        };
+
+        let body = self.parse_block()?;
+
+        Ok(StatementKind::FunctionDeclaration {
+            name,
+            type_params,
+            params,
+            return_type,
+            body,
+        })
+    }
+
+    fn parse_type(&mut self) -> Result<Type, ParseError> {
+        let span = self.peek_span();
+        let datatype = match self.advance() {
+            Some(Token::Type(t)) if t == "number" => Type::Number,
+            Some(Token::Type(t)) if t == "float" => Type::Float,
+            Some(Token::Type(t)) if t == "bool" => Type::Boolean,
+            Some(Token::Type(t)) if t == "string" => Type::String,
+            Some(Token::Type(t)) if t == "range" => Type::Range,
+            Some(Token::Type(t)) if t == "void" => Type::Void,
+            Some(Token::Identifier(name)) if self.type_params.contains(&name) => {
+                Type::Parameter(name)
+            }
+            Some(Token::Identifier(name)) => Type::Enum(name),
+            // func(number, number): number, the return type may be left out for void
+            Some(Token::Keyword(Keyword::Func)) => {
+                self.expect(Token::Punctuation("(".to_string()))?;
+                let mut params = Vec::new();
+                while self.peek() != Some(&Token::Punctuation(")".to_string())) {
+                    params.push(self.nested(Self::parse_type)?);
+                    if self.peek() != Some(&Token::Punctuation(",".to_string())) {
                         break;
                     }
+                    self.advance();
                 }
+                self.expect(Token::Punctuation(")".to_string()))?;
 
-                self.expect(Token::Punctuation(")".to_string()));
-
-                let return_type = match self.peek() {
-                    Some(Token::Punctuation(p)) if p == ":" => {
-                        self.advance();
-                        match self.advance() {
-                            Some(Token::Type(t)) if t == "number" => Type::Number,
-                            Some(Token::Type(t)) if t == "bool" => Type::Boolean,
-                            a => panic!("Expected type, got: {:?}", a),
-                        }
-                    }
-                    Some(Token::Punctuation(p)) if p == "{" => Type::Void,
-                    a => panic!("Expected type, got: {:?}", a),
+                let return_type = if self.peek() == Some(&Token::Punctuation(":".to_string())) {
+                    self.advance();
+                    self.nested(Self::parse_type)?
+                } else {
+                    Type::Void
                 };
+                Type::Function(params, Box::new(return_type))
+            }
+            t => return Err(ParseError::unexpected("type", t.as_ref(), span)),
+        };
 
-                self.expect(Token::Punctuation("{".to_string()));
-
-                let body = self.parse_block();
+        // `number?` is a number or none
+        if self.peek() == Some(&Token::Punctuation("?".to_string())) {
+            self.advance();
+            return Ok(Type::Optional(Box::new(datatype)));
+        }
+        Ok(datatype)
+    }
 
-                self.expect(Token::Punctuation("}".to_string()));
+    // `_`, an enum variant, `none` or a literal, optionally negated
+    fn parse_pattern(&mut self) -> Result<Pattern, ParseError> {
+        let is_number = |token: Option<&SpannedToken>| {
+            matches!(
+                token.map(|t| &t.token),
+                Some(Token::Number(..) | Token::Float(..))
+            )
+        };
 
-                Some(Statement::FunctionDeclaration {
-                    name,
-                    params,
-                    return_type,
-                    body,
-                })
+        match self.peek() {
+            Some(Token::Identifier(name)) if name == "_" => {
+                self.advance();
+                Ok(Pattern::Wildcard)
             }
-
-            Some(Token::EOF) => None,
-            statement => panic!("unknown statement: {:?}", statement),
+            Some(Token::Identifier(_))
+                if self.peek_second().map(|t| &t.token)
+                    == Some(&Token::Punctuation(".".to_string())) =>
+            {
+                Ok(Pattern::Value(self.parse_factor()?))
+            }
+            Some(Token::Number(..) | Token::Float(..) | Token::String(..) | Token::Bool(_)) => {
+                Ok(Pattern::Value(self.parse_factor()?))
+            }
+            Some(Token::Keyword(Keyword::None)) => Ok(Pattern::Value(self.parse_factor()?)),
+            Some(Token::Operator(Operator::Binary(BinOp::Sub)))
+                if is_number(self.peek_second()) =>
+            {
+                Ok(Pattern::Value(self.parse_unary()?))
+            }
+            t => Err(ParseError::unexpected(
+                "literal, enum variant or '_' in match arm",
+                t,
+                self.peek_span(),
+            )),
         }
     }
 
-    fn parse_block(&mut self) -> Vec<Statement> {
-        let mut block = Vec::new();
+    // `{ statements }`, braces included; running out of input before the closing brace is
+    // reported at the opening one
+    fn parse_block(&mut self) -> Result<Vec<Statement>, ParseError> {
+        let open = self.peek_span();
+        self.expect(Token::Punctuation("{".to_string()))?;
+        self.parse_block_body(open)
+    }
 
-        while let Some(t) = self.peek() {
-            if t == &Token::Punctuation("}".to_string()) {
-                break;
+    // the statements of a block whose `{` at `open` was just consumed, up to its closing brace
+    fn parse_block_body(&mut self, open: Span) -> Result<Vec<Statement>, ParseError> {
+        let mut block = Vec::new();
+        loop {
+            match self.peek() {
+                Some(Token::Punctuation(p)) if p == "}" => break,
+                None | Some(Token::EOF) => return Err(ParseError::unclosed("{", open)),
+                _ => {}
             }
-
-            if let Some(stmt) = self.parse_statement() {
+            if let Some(stmt) = self.parse_statement()? {
                 block.push(stmt);
             }
         }
+        self.advance();
 
-        block
+        Ok(block)
     }
 
-    fn parse_expression(&mut self) -> Expression {
-        let mut expression = self.parse_addition();
+    // builds a binary operation spanning both of its operands
+    fn binary_operation(
+        left: Expression,
+        operator: BinOp,
+        right: Expression,
+        spelling: Spelling,
+    ) -> Expression {
+        let span = left.span.to(right.span);
+        Expression::new(
+            BinaryOperation {
+                left: Box::new(left),
+                operator,
+                right: Box::new(right),
+                spelling,
+            },
+            span,
+        )
+    }
 
-        while let Some(Token::Operator(op)) = self.peek() {
-            if op == "==" || op == ">" || op == "<" {
-                let op = op.clone();
-                self.advance();
+    fn parse_expression(&mut self) -> Result<Expression, ParseError> {
+        self.nested(Self::parse_or)
+    }
 
-                let right = self.parse_addition();
-                expression = BinaryOperation {
-                    left: Box::new(expression),
-                    operator: op,
-                    right: Box::new(right),
-                };
-            } else {
+    // parses `operand (operator operand)*` for the operators `accepts`, left associative
+    fn parse_binary(
+        &mut self,
+        accepts: fn(BinOp) -> bool,
+        operand: fn(&mut Self) -> Result<Expression, ParseError>,
+    ) -> Result<Expression, ParseError> {
+        let mut expression = operand(self)?;
+
+        loop {
+            let (op, spelling) = match self.peek() {
+                Some(&Token::Operator(Operator::Binary(op))) => (op, Spelling::Symbol),
+                Some(&Token::WordOperator(Operator::Binary(op))) => (op, Spelling::Word),
+                _ => break,
+            };
+            if !accepts(op) {
                 break;
             }
+            self.advance();
+
+            let right = operand(self)?;
+            expression = Self::binary_operation(expression, op, right, spelling);
         }
-        expression
+        Ok(expression)
     }
 
-    fn parse_addition(&mut self) -> Expression {
-        let mut expression = self.parse_term();
+    fn parse_or(&mut self) -> Result<Expression, ParseError> {
+        self.parse_binary(|op| op == BinOp::Or, Self::parse_and)
+    }
 
-        while let Some(Token::Operator(op)) = self.peek() {
-            if op == "+" || op == "-" {
-                let op = op.clone();
-                self.advance();
+    fn parse_and(&mut self) -> Result<Expression, ParseError> {
+        self.parse_binary(|op| op == BinOp::And, Self::parse_comparison)
+    }
 
-                let right = self.parse_term();
-                expression = Expression::BinaryOperation {
-                    left: Box::new(expression),
-                    operator: op,
-                    right: Box::new(right),
-                }
-            } else {
-                break;
-            }
+    fn parse_comparison(&mut self) -> Result<Expression, ParseError> {
+        self.parse_binary(
+            |op| {
+                matches!(
+                    op,
+                    BinOp::Eq | BinOp::Ne | BinOp::Gt | BinOp::Lt | BinOp::Ge | BinOp::Le
+                )
+            },
+            Self::parse_range,
+        )
+    }
+
+    // `start..end`; ranges don't chain, so `a..b..c` is an error
+    fn parse_range(&mut self) -> Result<Expression, ParseError> {
+        let start = self.parse_addition()?;
+        if self.peek() != Some(&Token::Operator(Operator::Range)) {
+            return Ok(start);
         }
-        expression
+        self.advance();
+        let end = self.parse_addition()?;
+        let span = start.span.to(end.span);
+        Ok(Expression::new(
+            ExpressionKind::Range {
+                start: Box::new(start),
+                end: Box::new(end),
+            },
+            span,
+        ))
     }
 
-    fn parse_term(&mut self) -> Expression {
-        let mut expr = self.parse_factor();
+    fn parse_addition(&mut self) -> Result<Expression, ParseError> {
+        self.parse_binary(|op| matches!(op, BinOp::Add | BinOp::Sub), Self::parse_term)
+    }
 
-        while let Some(Token::Operator(op)) = self.peek() {
-            if op == "*" || op == "/" {
-                let op = op.clone();
-                self.advance();
-                let right = self.parse_factor();
-                expr = Expression::BinaryOperation {
-                    left: Box::new(expr),
-                    operator: op,
-                    right: Box::new(right),
-                };
-            } else {
-                break;
-            }
+    fn parse_term(&mut self) -> Result<Expression, ParseError> {
+        self.parse_binary(
+            |op| matches!(op, BinOp::Mul | BinOp::Div | BinOp::Rem),
+            Self::parse_unary,
+        )
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, ParseError> {
+        let (operator, spelling) = match self.peek() {
+            Some(Token::Operator(Operator::Not)) => (UnaryOp::Not, Spelling::Symbol),
+            Some(Token::WordOperator(Operator::Not)) => (UnaryOp::Not, Spelling::Word),
+            Some(Token::Operator(Operator::Binary(BinOp::Sub))) => (UnaryOp::Neg, Spelling::Symbol),
+            _ => return self.parse_factor(),
+        };
+        let start = self.peek_span();
+        self.advance();
+
+        // the smallest number is only written negated, as its magnitude is one past the
+        // largest, so the minus is folded into the literal
+        if let (
+            UnaryOp::Neg,
+            Some(SpannedToken {
+                token: Token::Number(i64::MIN, literal),
+                span,
+            }),
+        ) = (operator, self.lookahead.front())
+        {
+            let kind = ExpressionKind::Number(i64::MIN, format!("-{}", literal));
+            let span = start.to(*span);
+            self.advance();
+            return Ok(Expression::new(kind, span));
         }
-        expr
+
+        let operand = self.nested(Self::parse_unary)?;
+        let span = start.to(operand.span);
+        Ok(Expression::new(
+            ExpressionKind::UnaryOperation {
+                operator,
+                operand: Box::new(operand),
+                spelling,
+            },
+            span,
+        ))
     }
 
-    fn expect(&mut self, token: Token) {
+    fn expect(&mut self, token: Token) -> Result<(), ParseError> {
         if self.peek() == Some(&token) {
             self.advance();
-            return;
+            return Ok(());
+        }
+        Err(ParseError::unexpected(
+            &token.to_string(),
+            self.peek(),
+            self.peek_span(),
+        ))
+    }
+
+    // the ';' ending a statement, which the last statement of a block may leave out. A
+    // statement missing it at the end of a line is the usual slip, so the error is kept and
+    // parsing goes on with the next line as a statement of its own
+    fn expect_semicolon(&mut self) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(Token::Punctuation(p)) if p == ";" => {
+                self.advance();
+                return Ok(());
+            }
+            Some(Token::Punctuation(p)) if p == "}" => return Ok(()),
+            _ => {}
+        }
+        let error = ParseError {
+            kind: ParseErrorKind::MissingSemicolon {
+                found: self.peek().cloned().unwrap_or(Token::EOF),
+            },
+            span: self.previous_span(),
+        };
+        let next_line = self.peek_span().line > self.previous_span().line;
+        if next_line && !matches!(self.peek(), None | Some(Token::EOF)) {
+            self.errors.push(error);
+            return Ok(());
         }
-        panic!("Expected token {:?}, but got {:?}", token, self.peek());
+        Err(error)
     }
 
-    fn parse_factor(&mut self) -> Expression {
-        match self.advance() {
-            Some(Token::Number(n)) => Expression::Number(*n),
-            Some(Token::Bool(b)) => Expression::Bool(*b),
+    fn parse_factor(&mut self) -> Result<Expression, ParseError> {
+        let start = self.peek_span();
+        let kind = match self.advance() {
+            // one past the largest number, which only a minus in front makes a number
+            Some(Token::Number(i64::MIN, literal)) => {
+                return Err(ParseError {
+                    kind: ParseErrorKind::Lex(LexErrorKind::NumberOutOfRange(literal)),
+                    span: start,
+                });
+            }
+            Some(Token::Number(n, literal)) => ExpressionKind::Number(n, literal),
+            Some(Token::Float(f, literal)) => ExpressionKind::Float(f, literal),
+            Some(Token::String(s, literal)) => ExpressionKind::String(s, literal),
+            Some(Token::Bool(b)) => ExpressionKind::Bool(b),
+            Some(Token::Keyword(Keyword::None)) => ExpressionKind::None,
             Some(Token::Identifier(name)) => {
-                let name = name.clone();
                 if self.peek() == Some(&Token::Punctuation("(".to_string())) {
+                    let arguments = self.parse_function_args()?;
+                    ExpressionKind::FunctionCall { name, arguments }
+                } else if self.peek() == Some(&Token::Punctuation(".".to_string())) {
                     self.advance();
 
-                    let arguments = self.parse_function_args();
-
-                    self.expect(Token::Punctuation(")".to_string()));
-
-                    Expression::FunctionCall { name, arguments }
+                    let span = self.peek_span();
+                    let variant = match self.advance() {
+                        Some(Token::Identifier(variant)) => variant,
+                        t => {
+                            return Err(ParseError::unexpected(
+                                "variant after '.'",
+                                t.as_ref(),
+                                span,
+                            ));
+                        }
+                    };
+                    ExpressionKind::EnumVariant {
+                        enum_name: name,
+                        variant,
+                    }
                 } else {
-                    Expression::Variable(name)
+                    ExpressionKind::Variable(name)
                 }
             }
-            Some(Token::Punctuation(p)) if p == "(" => {
-                let expr = self.parse_expression();
-                self.expect(Token::Punctuation(")".to_string()));
-                expr
-            }
-            Some(t) => {
-                panic!("Unexpected token {:?}", t)
+            Some(Token::Punctuation(p)) if p == "{" => {
+                let mut statements = self.parse_block_body(start)?;
+                // the value is an expression statement left without its ';' at the end
+                let value = match statements.pop() {
+                    Some(Statement {
+                        kind: StatementKind::Expression(value),
+                        span,
+                    }) if span.end == value.span.end => value,
+                    _ => {
+                        return Err(ParseError::unexpected(
+                            "value at the end of the block",
+                            Some(&Token::Punctuation("}".to_string())),
+                            self.previous_span(),
+                        ));
+                    }
+                };
+                ExpressionKind::Block {
+                    statements,
+                    value: Box::new(value),
+                }
             }
-            None => {
-                panic!("Unexpected EOF")
+            Some(Token::Punctuation(p)) if p == "(" => {
+                let mut expr = self.parse_expression()?;
+                self.expect(Token::Punctuation(")".to_string()))?;
+                // the grouping parentheses are part of the expression's source
+                expr.span = start.to(self.previous_span());
+                return Ok(expr);
             }
-        }
+            t => return Err(ParseError::unexpected("expression", t.as_ref(), start)),
+        };
+        Ok(Expression::new(kind, start.to(self.previous_span())))
     }
 
-    // parses function call arguments
-    fn parse_function_args(&mut self) -> Vec<Expression> {
-        let mut args = Vec::new();
+    // parses function call arguments, parentheses included; like a block, arguments cut
+    // short by the end of input are reported at the opening parenthesis
+    fn parse_function_args(&mut self) -> Result<Vec<Expression>, ParseError> {
+        let open = self.peek_span();
+        self.expect(Token::Punctuation("(".to_string()))?;
 
+        let mut args = Vec::new();
         if Some(&Token::Punctuation(")".to_string())) == self.peek() {
-            return args;
+            self.advance();
+            return Ok(args);
         }
 
         loop {
-            let arg = self.parse_expression();
+            if matches!(self.peek(), None | Some(Token::EOF)) {
+                return Err(ParseError::unclosed("(", open));
+            }
+            let arg = self.parse_expression()?;
             args.push(arg);
 
             match self.peek() {
@@ -484,32 +1579,38 @@ impl Parser {
                     self.advance();
                     continue;
                 }
-                a => panic!("Unexpected token {:?}", a),
+                None | Some(Token::EOF) => return Err(ParseError::unclosed("(", open)),
+                t => return Err(ParseError::unexpected("',' or ')'", t, self.peek_span())),
             }
         }
-        args
+        self.advance();
+        Ok(args)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::lexer::Token;
+    use crate::lexer::{Lexer, Token};
 
-    fn token_number(n: i32) -> Token {
-        Token::Number(n)
+    fn parse_source(source: &str) -> Result<Vec<Statement>, Vec<ParseError>> {
+        Parser::from_lexer(Lexer::new(source)).parse()
+    }
+
+    fn token_number(n: i64) -> Token {
+        Token::Number(n, n.to_string())
     }
 
     fn token_ident(name: &str) -> Token {
-        Token::Identifier(name.to_string())
+        Token::Identifier(name.into())
     }
 
     fn token_keyword(word: &str) -> Token {
-        Token::Keyword(word.to_string())
+        Token::Keyword(Keyword::from_word(word).unwrap())
     }
 
     fn token_operator(op: &str) -> Token {
-        Token::Operator(op.to_string())
+        Lexer::new(op).parse().unwrap().remove(0).token
     }
 
     fn token_punct(p: &str) -> Token {
@@ -524,6 +1625,39 @@ mod tests {
         Token::EOF
     }
 
+    // hand-written tokens carry no location, so every parsed node ends up with a default span
+    fn parse(tokens: Vec<Token>) -> Result<Vec<Statement>, Vec<ParseError>> {
+        let tokens = tokens
+            .into_iter()
+            .map(|token| SpannedToken {
+                token,
+                span: Span::default(),
+            })
+            .collect();
+        Parser::new(tokens).parse()
+    }
+
+    fn stmt(kind: StatementKind) -> Statement {
+        Statement::new(kind, Span::default())
+    }
+
+    fn expr(kind: ExpressionKind) -> Expression {
+        Expression::new(kind, Span::default())
+    }
+
+    fn number(n: i64) -> Expression {
+        expr(ExpressionKind::Number(n, n.to_string()))
+    }
+
+    fn binop(left: Expression, op: BinOp, right: Expression) -> Expression {
+        expr(ExpressionKind::BinaryOperation {
+            left: Box::new(left),
+            operator: op,
+            right: Box::new(right),
+            spelling: Spelling::Symbol,
+        })
+    }
+
     #[test]
     fn test_parse_assignment() {
         let tokens = vec![
@@ -537,13 +1671,13 @@ mod tests {
             eof(),
         ];
 
-        let mut parser = Parser::new(tokens);
-        let ast = parser.parse();
+        let ast = parse(tokens).unwrap();
 
-        let expected = vec![Statement::Assignment(
-            "x".to_string(),
-            Expression::Number(42),
-        )];
+        let expected = vec![stmt(StatementKind::Declaration(
+            "x".into(),
+            number(42),
+            Some(Type::Number),
+        ))];
 
         assert_eq!(ast, expected);
     }
@@ -557,10 +1691,12 @@ mod tests {
             eof(),
         ];
 
-        let mut parser = Parser::new(tokens);
-        let ast = parser.parse();
+        let ast = parse(tokens).unwrap();
 
-        let expected = vec![Statement::Print(Expression::Variable("x".to_string()))];
+        let expected = vec![stmt(StatementKind::Print {
+            value: expr(ExpressionKind::Variable("x".into())),
+            debug: false,
+        })];
 
         assert_eq!(ast, expected);
     }
@@ -583,20 +1719,19 @@ mod tests {
             eof(),
         ];
 
-        let mut parser = Parser::new(tokens);
-        let ast = parser.parse();
+        let ast = parse(tokens).unwrap();
 
-        let expected_expr = Expression::BinaryOperation {
-            left: Box::new(Expression::Number(1)),
-            operator: "+".to_string(),
-            right: Box::new(Expression::BinaryOperation {
-                left: Box::new(Expression::Number(2)),
-                operator: "*".to_string(),
-                right: Box::new(Expression::Number(3)),
-            }),
-        };
+        let expected_expr = binop(
+            number(1),
+            BinOp::Add,
+            binop(number(2), BinOp::Mul, number(3)),
+        );
 
-        let expected = vec![Statement::Assignment("x".to_string(), expected_expr)];
+        let expected = vec![stmt(StatementKind::Declaration(
+            "x".into(),
+            expected_expr,
+            Some(Type::Number),
+        ))];
 
         assert_eq!(ast, expected);
     }
@@ -621,21 +1756,526 @@ mod tests {
             eof(),
         ];
 
-        let mut parser = Parser::new(tokens);
-        let ast = parser.parse();
+        let ast = parse(tokens).unwrap();
 
-        let expected_expr = Expression::BinaryOperation {
-            left: Box::new(Expression::BinaryOperation {
-                left: Box::new(Expression::Number(1)),
-                operator: "+".to_string(),
-                right: Box::new(Expression::Number(2)),
-            }),
-            operator: "*".to_string(),
-            right: Box::new(Expression::Number(3)),
+        let expected_expr = binop(
+            binop(number(1), BinOp::Add, number(2)),
+            BinOp::Mul,
+            number(3),
+        );
+
+        let expected = vec![stmt(StatementKind::Declaration(
+            "x".into(),
+            expected_expr,
+            Some(Type::Number),
+        ))];
+
+        assert_eq!(ast, expected);
+    }
+
+    #[test]
+    fn test_parse_string_declaration() {
+        // let s: string = "ribbit";
+        let tokens = vec![
+            token_keyword("let"),
+            token_ident("s"),
+            token_punct(":"),
+            token_type("string"),
+            token_operator("="),
+            Token::String("ribbit".to_string(), "\"ribbit\"".to_string()),
+            token_punct(";"),
+            eof(),
+        ];
+
+        let ast = parse(tokens).unwrap();
+
+        let expected = vec![stmt(StatementKind::Declaration(
+            "s".into(),
+            expr(ExpressionKind::String(
+                "ribbit".to_string(),
+                "\"ribbit\"".to_string(),
+            )),
+            Some(Type::String),
+        ))];
+
+        assert_eq!(ast, expected);
+    }
+
+    #[test]
+    fn test_parse_missing_semicolon() {
+        let missing = |found| ParseErrorKind::MissingSemicolon { found };
+
+        // croak x croak y;
+        let tokens = vec![
+            token_keyword("croak"),
+            token_ident("x"),
+            token_keyword("croak"),
+            token_ident("y"),
+            token_punct(";"),
+            eof(),
+        ];
+        assert_eq!(
+            parse(tokens).unwrap_err()[0].kind,
+            missing(token_keyword("croak"))
+        );
+
+        // parsing goes on at the next line, so every missing semicolon is found at once
+        let errors = parse_source("let a = 1\ncroak a\nlet b = a;\ncroak b + 1 2;").unwrap_err();
+        let found: Vec<_> = errors
+            .iter()
+            .map(|e| (e.kind.to_string(), e.span.line, e.span.column))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    "missing ';' at end of statement, found keyword 'croak'".to_string(),
+                    1,
+                    9
+                ),
+                (
+                    "missing ';' at end of statement, found keyword 'let'".to_string(),
+                    2,
+                    7
+                ),
+                (
+                    "missing ';' at end of statement, found number 2".to_string(),
+                    4,
+                    11
+                ),
+            ]
+        );
+        assert_eq!(parse_source("croak 1").unwrap_err()[0].kind, missing(eof()));
+
+        // the last statement of a block may leave it out
+        assert!(parse_source("if true { croak 1; croak 2 }").is_ok());
+        assert!(parse_source("func f(): number { return 1 }").is_ok());
+    }
+
+    #[test]
+    fn test_parse_block_expression() {
+        let program = parse_source("let x = { let a = 1; a + 2 };").unwrap();
+        let StatementKind::Declaration(_, value, None) = &program[0].kind else {
+            panic!("expected a declaration");
+        };
+        let ExpressionKind::Block { statements, value } = &value.kind else {
+            panic!("expected a block expression");
+        };
+        assert_eq!(statements.len(), 1);
+        assert!(matches!(value.kind, ExpressionKind::BinaryOperation { .. }));
+        assert_eq!((value.span.start, value.span.end), (21, 26));
+
+        // any expression can be a statement, so the value can start with a name
+        assert!(parse_source("f(1) + 2; i++; { hop() }").is_ok());
+        let error = parse_source("let x = { let a = 1; a; };")
+            .unwrap_err()
+            .remove(0);
+        assert_eq!(
+            error.kind,
+            ParseErrorKind::UnexpectedToken {
+                expected: "value at the end of the block".to_string(),
+                found: token_punct("}"),
+            }
+        );
+        assert_eq!(error.span.column, 25);
+    }
+
+    #[test]
+    fn test_statement_and_expression_spans() {
+        let source = "let x = 1;\ncroak x + 22;";
+        let ast = parse_source(source).unwrap();
+
+        assert_eq!(&source[ast[1].span.start..ast[1].span.end], "croak x + 22;");
+        assert_eq!(ast[1].span.line, 2);
+
+        let StatementKind::Print { value: printed, .. } = &ast[1].kind else {
+            panic!("expected print statement");
         };
+        assert_eq!(&source[printed.span.start..printed.span.end], "x + 22");
+        assert_eq!(printed.span.column, 7);
+    }
+
+    #[test]
+    fn test_parse_for_loop() {
+        // for i = 1 to 3 { croak i; }
+        let tokens = vec![
+            token_keyword("for"),
+            token_ident("i"),
+            token_operator("="),
+            token_number(1),
+            token_keyword("to"),
+            token_number(3),
+            token_punct("{"),
+            token_keyword("croak"),
+            token_ident("i"),
+            token_punct(";"),
+            token_punct("}"),
+            eof(),
+        ];
+
+        let ast = parse(tokens).unwrap();
+
+        let expected = vec![stmt(StatementKind::For {
+            variable: "i".into(),
+            annotation: None,
+            start: number(1),
+            end: number(3),
+            body: vec![stmt(StatementKind::Print {
+                value: expr(ExpressionKind::Variable("i".into())),
+                debug: false,
+            })],
+        })];
+
+        assert_eq!(ast, expected);
+    }
+
+    #[test]
+    fn test_keywords_are_not_names() {
+        for (_, keyword) in crate::lexer::KEYWORDS {
+            let source = format!("let {} = 1;", keyword);
+            assert!(
+                parse_source(&source).is_err(),
+                "{} was taken as a name",
+                keyword
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_logical_precedence() {
+        // let b = !x || y && 1 < 2;
+        let tokens = vec![
+            token_keyword("let"),
+            token_ident("b"),
+            token_operator("="),
+            token_operator("!"),
+            token_ident("x"),
+            token_operator("||"),
+            token_ident("y"),
+            token_operator("&&"),
+            token_number(1),
+            token_operator("<"),
+            token_number(2),
+            token_punct(";"),
+            eof(),
+        ];
+
+        let ast = parse(tokens).unwrap();
+
+        let variable = |name: &str| expr(ExpressionKind::Variable(name.into()));
+        let not_x = expr(ExpressionKind::UnaryOperation {
+            operator: UnaryOp::Not,
+            operand: Box::new(variable("x")),
+            spelling: Spelling::Symbol,
+        });
+        let expected_expr = binop(
+            not_x,
+            BinOp::Or,
+            binop(
+                variable("y"),
+                BinOp::And,
+                binop(number(1), BinOp::Lt, number(2)),
+            ),
+        );
+
+        let expected = vec![stmt(StatementKind::Declaration(
+            "b".into(),
+            expected_expr,
+            None,
+        ))];
 
-        let expected = vec![Statement::Assignment("x".to_string(), expected_expr)];
+        assert_eq!(ast, expected);
+    }
+
+    #[test]
+    fn test_parse_unary_minus() {
+        // let x = -5 * 2;
+        let tokens = vec![
+            token_keyword("let"),
+            token_ident("x"),
+            token_operator("="),
+            token_operator("-"),
+            token_number(5),
+            token_operator("*"),
+            token_number(2),
+            token_punct(";"),
+            eof(),
+        ];
+
+        let ast = parse(tokens).unwrap();
+
+        let negated = expr(ExpressionKind::UnaryOperation {
+            operator: UnaryOp::Neg,
+            operand: Box::new(number(5)),
+            spelling: Spelling::Symbol,
+        });
+        let expected = vec![stmt(StatementKind::Declaration(
+            "x".into(),
+            binop(negated, BinOp::Mul, number(2)),
+            None,
+        ))];
 
         assert_eq!(ast, expected);
+
+        // the smallest number is a literal of its own, and its magnitude is out of range alone
+        let ast = parse_source("croak -9223372036854775808;").unwrap();
+        let StatementKind::Print { value, .. } = &ast[0].kind else {
+            panic!("expected croak, got {:?}", ast[0].kind);
+        };
+        assert_eq!(
+            value.kind,
+            ExpressionKind::Number(i64::MIN, "-9223372036854775808".to_string())
+        );
+        assert_eq!(
+            parse_source("croak 1 - 9223372036854775808;").unwrap_err()[0].kind,
+            ParseErrorKind::Lex(LexErrorKind::NumberOutOfRange(
+                "9223372036854775808".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_import() {
+        let tokens = vec![
+            token_keyword("import"),
+            Token::String("math.frog".to_string(), "\"math.frog\"".to_string()),
+            token_punct(";"),
+            eof(),
+        ];
+
+        let ast = parse(tokens).unwrap();
+
+        assert_eq!(
+            ast,
+            vec![stmt(StatementKind::Import("math.frog".to_string()))]
+        );
+    }
+
+    #[test]
+    fn test_parse_function_type() {
+        // let f: func(number, bool): number = g;
+        let tokens = vec![
+            token_keyword("let"),
+            token_ident("f"),
+            token_punct(":"),
+            token_keyword("func"),
+            token_punct("("),
+            Token::Type("number".to_string()),
+            token_punct(","),
+            Token::Type("bool".to_string()),
+            token_punct(")"),
+            token_punct(":"),
+            Token::Type("number".to_string()),
+            token_operator("="),
+            token_ident("g"),
+            token_punct(";"),
+            eof(),
+        ];
+
+        let ast = parse(tokens).unwrap();
+
+        let function_type =
+            Type::Function(vec![Type::Number, Type::Boolean], Box::new(Type::Number));
+        assert_eq!(function_type.to_string(), "func(number, bool): number");
+        assert_eq!(
+            ast,
+            vec![stmt(StatementKind::Declaration(
+                "f".into(),
+                expr(ExpressionKind::Variable("g".into())),
+                Some(function_type),
+            ))]
+        );
+
+        // written out the way function types are displayed
+        let StatementKind::Declaration(_, _, Some(annotation)) =
+            &parse_source("let f: func(number): void = g;").unwrap()[0].kind
+        else {
+            panic!("expected an annotated declaration");
+        };
+        assert_eq!(
+            *annotation,
+            Type::Function(vec![Type::Number], Box::new(Type::Void))
+        );
+    }
+
+    #[test]
+    fn test_parse_increment_and_decrement() {
+        let step = |operator: &str| {
+            let ast = parse_source(&format!("i{}{};", operator, operator)).unwrap();
+            let [
+                Statement {
+                    kind: StatementKind::Assignment(name, value),
+                    ..
+                },
+            ] = ast.as_slice()
+            else {
+                panic!("expected a single assignment");
+            };
+            assert_eq!(name, "i");
+            crate::formatter::format_expression(value)
+        };
+
+        assert_eq!(step("+"), "i + 1");
+        assert_eq!(step("-"), "i - 1");
+        // the two operators must touch
+        assert!(parse_source("i+ +;").is_err());
+    }
+
+    #[test]
+    fn test_parse_match() {
+        let source = "match x { 1 => { croak 1; }, -2 => {} _ => { croak 0; } }";
+        let ast = parse_source(source).unwrap();
+
+        let [
+            Statement {
+                kind: StatementKind::Match { subject, arms },
+                ..
+            },
+        ] = ast.as_slice()
+        else {
+            panic!("expected a single match statement");
+        };
+        assert_eq!(subject.kind, ExpressionKind::Variable("x".into()));
+        let patterns: Vec<String> = arms
+            .iter()
+            .map(|arm| crate::formatter::format_pattern(&arm.pattern))
+            .collect();
+        assert_eq!(patterns, ["1", "-2", "_"]);
+        assert_eq!(
+            &source[arms[0].span.start..arms[0].span.end],
+            "1 => { croak 1; }"
+        );
+        assert!(is_exhaustive(arms));
+    }
+
+    #[test]
+    fn test_parse_do_while() {
+        let source = "do { break; } while x;";
+        let ast = parse_source(source).unwrap();
+
+        let StatementKind::DoWhile { body, condition } = &ast[0].kind else {
+            panic!("expected a do-while loop");
+        };
+        assert_eq!(body[0].kind, StatementKind::Break);
+        assert_eq!(condition.kind, ExpressionKind::Variable("x".into()));
+        // the trailing semicolon is required
+        assert!(parse_source("do { } while x").is_err());
+    }
+
+    #[test]
+    fn test_parse_try() {
+        let source = "try { raise \"oops\"; } handle err { croak err; }";
+        let ast = parse_source(source).unwrap();
+
+        let StatementKind::Try {
+            body,
+            error,
+            handler,
+        } = &ast[0].kind
+        else {
+            panic!("expected a try statement");
+        };
+        assert!(matches!(&body[0].kind, StatementKind::Raise(Expression {
+                kind: ExpressionKind::String(message, _),
+                ..
+            }) if message == "oops"));
+        assert_eq!(*error, "err");
+        assert_eq!(handler.len(), 1);
+        // the handler has to name the error
+        assert!(parse_source("try { } handle { }").is_err());
+    }
+
+    #[test]
+    fn test_parse_enum() {
+        let source = "enum Color { Red, Green, } let c: Color = Color.Green;";
+        let ast = parse_source(source).unwrap();
+
+        assert_eq!(
+            ast[0].kind,
+            StatementKind::Enum {
+                name: "Color".into(),
+                variants: vec!["Red".into(), "Green".into()],
+            }
+        );
+        let StatementKind::Declaration(_, value, declared_type) = &ast[1].kind else {
+            panic!("expected a declaration");
+        };
+        assert_eq!(declared_type, &Some(Type::Enum("Color".into())));
+        assert_eq!(
+            value.kind,
+            ExpressionKind::EnumVariant {
+                enum_name: "Color".into(),
+                variant: "Green".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_from_lexer() {
+        let source = "let a = 1; if a > 0 { croak a++; }";
+        let tokens = crate::lexer::Lexer::new(source).parse().unwrap();
+        assert_eq!(parse_source(source), Parser::new(tokens).parse());
+
+        // a lex error stops the stream, and is what parsing up to it fails with
+        let error = parse_source("let x = 4 $ 2;").unwrap_err().remove(0);
+        assert_eq!(
+            error.kind,
+            ParseErrorKind::Lex(LexErrorKind::UnexpectedCharacter('$'))
+        );
+        assert_eq!(error.span.column, 11);
+        // a syntax error in front of it is reported first
+        let error = parse_source("let = 4 $ 2;").unwrap_err().remove(0);
+        assert!(matches!(error.kind, ParseErrorKind::UnexpectedToken { .. }));
+    }
+
+    #[test]
+    fn test_unclosed_delimiters_point_at_the_opening_one() {
+        let first_error = |source| parse_source(source).unwrap_err().remove(0);
+        let unclosed = |delimiter: &str| ParseErrorKind::Unclosed {
+            delimiter: delimiter.to_string(),
+        };
+
+        let error = first_error("func f() {\n    while true {\n        croak 1;\n}");
+        assert_eq!(error.kind, unclosed("{"));
+        assert_eq!((error.span.line, error.span.column), (1, 10));
+        assert_eq!(first_error("if x { croak 1; } else {").kind, unclosed("{"));
+
+        let error = first_error("let a = max(1, f(2)");
+        assert_eq!(error.kind, unclosed("("));
+        assert_eq!(error.span.column, 12);
+        assert_eq!(first_error("hop(1,").kind, unclosed("("));
+    }
+
+    #[test]
+    fn test_nesting_is_limited() {
+        let too_deep = |source: String| {
+            parse_source(&source).map_err(|errors| errors[0].kind.clone())
+                == Err(ParseErrorKind::TooDeep)
+        };
+        let nested = |open: &str, inner: &str, close: &str, depth: usize| {
+            format!("{}{}{}", open.repeat(depth), inner, close.repeat(depth))
+        };
+
+        // a block level takes tens of kilobytes of stack in a debug build, more than the
+        // 2MB of a test thread holds at the limit
+        let deep = std::thread::Builder::new()
+            .stack_size(64 << 20)
+            .spawn(move || {
+                // the blocks, the croak and its value
+                assert!(parse_source(&nested("{", "croak 1;", "}", MAX_DEPTH - 2)).is_ok());
+                assert!(too_deep(nested("{", "croak 1;", "}", MAX_DEPTH - 1)));
+                assert!(too_deep(nested("{", "croak 1;", "}", MAX_DEPTH * 8)));
+                assert!(too_deep(format!(
+                    "croak {};",
+                    nested("(", "1", ")", MAX_DEPTH * 8)
+                )));
+                assert!(too_deep(format!("croak {}1;", "-".repeat(MAX_DEPTH * 8))));
+                assert!(too_deep(format!(
+                    "let f: {} = none;",
+                    nested("func(", "number", ")", MAX_DEPTH * 8)
+                )));
+            })
+            .unwrap();
+        assert!(deep.join().is_ok());
     }
 }
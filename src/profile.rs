@@ -0,0 +1,169 @@
+use froggle::interpreter::{Env, Hook};
+use froggle::lexer::Span;
+use froggle::symbol::Symbol;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Stats {
+    count: u64,
+    // from start to end, counting what runs nested in it; the outermost of recursive calls
+    // to a function only, so the same time isn't counted more than once
+    total: Duration,
+    // spent in it and not in nested calls or statements
+    own: Duration,
+}
+
+// a call or statement that is running: when it started and how long what it ran nested in it
+// took
+struct Running {
+    start: Instant,
+    nested: Duration,
+}
+
+impl Running {
+    fn new() -> Running {
+        Running {
+            start: Instant::now(),
+            nested: Duration::ZERO,
+        }
+    }
+}
+
+// the interpreter's hook for `--profile`: counts calls to every froggle function and runs of
+// every kind of statement, with the wall time they take
+#[derive(Default)]
+pub struct Profiler {
+    functions: HashMap<Symbol, Stats>,
+    statements: HashMap<String, Stats>,
+    calls: Vec<Running>,
+    running: Vec<Running>,
+    // calls in progress by function, to tell the outermost of recursive ones
+    active: HashMap<Symbol, usize>,
+}
+
+// ends `running`, counting its time as nested in the one it ran in; how long it took in all
+// and on its own
+fn finish(running: &mut Vec<Running>) -> Option<(Duration, Duration)> {
+    let done = running.pop()?;
+    let total = done.start.elapsed();
+    if let Some(outer) = running.last_mut() {
+        outer.nested += total;
+    }
+    Some((total, total.saturating_sub(done.nested)))
+}
+
+impl Profiler {
+    // a table of the functions, slowest first, and one of the kinds of statements, taking
+    // the most time on their own first
+    pub fn report(&self) -> String {
+        let mut report = format!(
+            "{:<16}{:>10}{:>14}{:>14}\n",
+            "function", "calls", "total", "self"
+        );
+        let mut functions: Vec<_> = self.functions.iter().collect();
+        functions.sort_by_key(|(name, stats)| (Reverse(stats.total), name.as_str()));
+        for (name, stats) in functions {
+            let _ = writeln!(
+                report,
+                "{:<16}{:>10}{:>14.3?}{:>14.3?}",
+                name, stats.count, stats.total, stats.own
+            );
+        }
+
+        let _ = write!(
+            report,
+            "\n{:<16}{:>10}{:>14}\n",
+            "statement", "runs", "self"
+        );
+        let mut statements: Vec<_> = self.statements.iter().collect();
+        statements.sort_by_key(|(kind, stats)| (Reverse(stats.own), kind.as_str()));
+        for (kind, stats) in statements {
+            let _ = writeln!(
+                report,
+                "{:<16}{:>10}{:>14.3?}",
+                kind, stats.count, stats.own
+            );
+        }
+        report
+    }
+}
+
+impl Hook for Profiler {
+    fn on_statement(&mut self, _: Span, _: &Env) {
+        self.running.push(Running::new());
+    }
+
+    fn on_statement_end(&mut self, _: Span, kind: &str, _: &Env) {
+        if let Some((_, own)) = finish(&mut self.running) {
+            let stats = self.statements.entry(kind.to_string()).or_default();
+            stats.count += 1;
+            stats.own += own;
+        }
+    }
+
+    fn on_call(&mut self, name: Symbol, _: Span, _: &Env) {
+        self.calls.push(Running::new());
+        *self.active.entry(name).or_default() += 1;
+    }
+
+    fn on_return(&mut self, name: Symbol, _: &Env) {
+        let Some((total, own)) = finish(&mut self.calls) else {
+            return;
+        };
+        let active = self.active.entry(name).or_default();
+        *active -= 1;
+        let stats = self.functions.entry(name).or_default();
+        stats.count += 1;
+        stats.own += own;
+        if *active == 0 {
+            stats.total += total;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use froggle::interpreter::Interpreter;
+    use std::cell::RefCell;
+    use std::path::Path;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_counts_calls_and_statements() {
+        let profiler = Rc::new(RefCell::new(Profiler::default()));
+        let mut interpreter = Interpreter::new();
+        interpreter.capture_output();
+        interpreter.hook = Some(Box::new(profiler.clone()));
+        let source = "\
+func fib(n: number): number {
+    if n < 2 { return n; }
+    return fib(n - 1) + fib(n - 2);
+}
+func countdown(n: number): number {
+    if n == 0 { return 0; }
+    return countdown(n - 1);
+}
+croak fib(5);
+croak countdown(3);";
+        froggle::run(source, Path::new("test.frog"), &mut interpreter).unwrap();
+
+        let profiler = profiler.borrow();
+        let count = |name: &str| profiler.functions[&Symbol::from(name)].count;
+        assert_eq!(count("fib"), 15);
+        // every tail call is a call of its own
+        assert_eq!(count("countdown"), 4);
+        let runs = |kind: &str| profiler.statements[kind].count;
+        assert_eq!(runs("croak"), 2);
+        assert_eq!(runs("if"), 15 + 4);
+        assert_eq!(runs("return"), 15 + 4);
+        assert!(profiler.calls.is_empty() && profiler.running.is_empty());
+
+        let report = profiler.report();
+        assert!(report.starts_with("function"));
+        assert!(report.contains("\nstatement"));
+    }
+}
@@ -0,0 +1,399 @@
+use froggle::Error;
+use froggle::ast_printer::AstPrinter;
+use froggle::interpreter::{Interpreter, Limits, Value};
+use froggle::lexer::{LexErrorKind, Lexer, Token};
+use froggle::parser::{Parser, Statement, StatementKind, Type};
+use froggle::typechecker::TypeChecker;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const PROMPT: &str = "froggle🐸> ";
+const CONTINUATION_PROMPT: &str = "...> ";
+
+const HELP: &str = "\
+Enter froggle statements to run them, or an expression to see its value and type. Commands:
+  :help         show this help
+  :env          show the variables declared so far
+  :type <expr>  show the type of an expression without evaluating it
+  :ast <code>   show the syntax tree of statements without running them
+  :clear        forget everything declared so far (also :reset)
+  :load <file>  run a file in the current session
+  :quit         leave the REPL (so does `exit` or Ctrl+D)
+Ctrl+C stops the entry that is running and returns to the prompt.";
+
+pub fn start(limits: Limits) -> ExitCode {
+    println!("Froggle REPL mode! 🐸 Type your code below, or :help for commands:");
+
+    let mut typechecker = TypeChecker::new();
+    let mut interpreter = Interpreter::new();
+    interpreter.limits = limits;
+    // Ctrl+C stops the entry being run instead of the whole REPL
+    let interrupt = Arc::new(AtomicBool::new(false));
+    let handler_interrupt = interrupt.clone();
+    if let Err(e) = ctrlc::set_handler(move || handler_interrupt.store(true, Ordering::Relaxed)) {
+        eprintln!("Ctrl+C won't interrupt running code: {}", e);
+    }
+    interpreter.interrupt = Some(interrupt.clone());
+    let mut entries = 0;
+    // read
+    while let Some(input) = read_input() {
+        let input = input.trim();
+
+        if input == "exit" {
+            break;
+        }
+
+        if input.is_empty() {
+            continue;
+        }
+
+        if let Some(command) = input.strip_prefix(':') {
+            if !run_command(command, &mut typechecker, &mut interpreter) {
+                break;
+            }
+            continue;
+        }
+
+        // evaluate; a Ctrl+C pressed at the prompt doesn't carry over to the entry
+        interrupt.store(false, Ordering::Relaxed);
+        let offset = record_entry(&mut interpreter, &mut entries, input);
+        match eval(input, offset, &mut typechecker, &mut interpreter) {
+            Ok(Some((value, datatype))) => println!("=> {} : {}", value, datatype),
+            Ok(None) => {}
+            Err(e) => {
+                if let Some(code) = e.exit_code() {
+                    return ExitCode::from(code);
+                }
+                crate::report_in(&interpreter.modules.sources, &e.diagnostics());
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+// names an entry `<repl:1>`, `<repl:2>` and so on and adds it to the interpreter's sources,
+// so an error in a function points into the entry that declared it, not the one that called
+// it. Returns the offset to lex the entry at
+fn record_entry(interpreter: &mut Interpreter, entries: &mut usize, input: &str) -> usize {
+    *entries += 1;
+    let name = format!("<repl:{}>", entries);
+    interpreter.modules.sources.add(name, input)
+}
+
+// runs a :command, returning false when the REPL should stop
+fn run_command(
+    command: &str,
+    typechecker: &mut TypeChecker,
+    interpreter: &mut Interpreter,
+) -> bool {
+    let (name, argument) = command
+        .split_once(char::is_whitespace)
+        .map(|(name, argument)| (name, argument.trim()))
+        .unwrap_or((command, ""));
+
+    match name {
+        "help" => println!("{}", HELP),
+        "quit" | "q" => return false,
+        "env" => {
+            for (name, value) in interpreter.globals() {
+                println!("{} = {}", name, value);
+            }
+        }
+        "type" => match type_of(argument, typechecker) {
+            Ok(datatype) => println!("{}", datatype),
+            Err(e) => crate::report("<repl>", argument, &e.diagnostics()),
+        },
+        "ast" => match ast_of(argument) {
+            Ok(tree) => print!("{}", tree),
+            Err(e) => crate::report("<repl>", argument, &e.diagnostics()),
+        },
+        "clear" | "reset" => {
+            *typechecker = TypeChecker::new();
+            interpreter.reset();
+        }
+        "load" => match fs::read_to_string(argument) {
+            Ok(source) => {
+                let offset = interpreter
+                    .modules
+                    .sources
+                    .add(argument.to_string(), &source);
+                let path = Path::new(argument);
+                if let Err(e) = eval_source(&source, offset, path, typechecker, interpreter) {
+                    crate::report_in(&interpreter.modules.sources, &e.diagnostics());
+                }
+            }
+            Err(_) => eprintln!("Error reading file {}.", argument),
+        },
+        _ => eprintln!("unknown command :{}, see :help", name),
+    }
+    true
+}
+
+fn type_of(input: &str, typechecker: &mut TypeChecker) -> Result<Type, Error> {
+    let mut parser = Parser::from_lexer(Lexer::new(input));
+    let expression = parser.parse_single_expression()?;
+    Ok(typechecker.expression_type(&expression)?)
+}
+
+fn ast_of(input: &str) -> Result<String, Error> {
+    let mut parser = Parser::from_lexer(Lexer::new(input));
+    Ok(AstPrinter::print(&parser.parse()?))
+}
+
+// reads one complete entry, prompting for continuation lines while it is unfinished
+fn read_input() -> Option<String> {
+    let mut input = String::new();
+    let mut prompt = PROMPT;
+
+    loop {
+        print!("{}", prompt);
+        io::stdout().lock().flush().unwrap();
+
+        let mut line = String::new();
+        match io::stdin().read_line(&mut line) {
+            // Ctrl+D; the newline keeps the shell's prompt off the REPL's
+            Ok(0) => {
+                println!();
+                return None;
+            }
+            Ok(_) => input.push_str(&line),
+            Err(_) => {
+                println!("Error reading line. Exiting.");
+                return None;
+            }
+        }
+
+        if !is_incomplete(&input) {
+            return Some(input);
+        }
+        prompt = CONTINUATION_PROMPT;
+    }
+}
+
+// input is incomplete while it has unclosed braces, parentheses or string literals
+fn is_incomplete(input: &str) -> bool {
+    let mut depth = 0;
+    for result in Lexer::new(input) {
+        let spanned = match result {
+            Ok(spanned) => spanned,
+            Err(e) => return e.kind == LexErrorKind::UnterminatedString,
+        };
+        match spanned.token {
+            Token::Punctuation(p) if p == "{" || p == "(" => depth += 1,
+            Token::Punctuation(p) if p == "}" || p == ")" => depth -= 1,
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+// typechecker and interpreter live for the whole session, so every entry sees what
+// earlier entries declared. Returns the value and type of an entry ending in an expression,
+// to be echoed
+fn eval(
+    input: &str,
+    offset: usize,
+    typechecker: &mut TypeChecker,
+    interpreter: &mut Interpreter,
+) -> Result<Option<(Value, Type)>, Error> {
+    // a bare expression like `1 + 2` needs no semicolon
+    let program =
+        match Parser::from_lexer(Lexer::with_offset(input, offset)).parse_single_expression() {
+            Ok(expression) => {
+                let span = expression.span;
+                vec![Statement::new(StatementKind::Expression(expression), span)]
+            }
+            Err(_) => Parser::from_lexer(Lexer::with_offset(input, offset)).parse()?,
+        };
+    // imports are relative to the directory the REPL was started in
+    run(program, Path::new("<repl>"), typechecker, interpreter)
+}
+
+// like eval, for source read from `path`
+fn eval_source(
+    input: &str,
+    offset: usize,
+    path: &Path,
+    typechecker: &mut TypeChecker,
+    interpreter: &mut Interpreter,
+) -> Result<(), Error> {
+    let mut parser = Parser::from_lexer(Lexer::with_offset(input, offset));
+    run(parser.parse()?, path, typechecker, interpreter)?;
+    Ok(())
+}
+
+fn run(
+    program: Vec<Statement>,
+    path: &Path,
+    typechecker: &mut TypeChecker,
+    interpreter: &mut Interpreter,
+) -> Result<Option<(Value, Type)>, Error> {
+    let ast = interpreter.modules.resolve(program, path)?;
+
+    // an entry that fails to typecheck must not leave half of its declarations behind
+    let checkpoint = typechecker.clone();
+    if let Err(e) = typechecker.check(&ast) {
+        *typechecker = checkpoint;
+        return Err(e.into());
+    }
+    let datatype = match ast.last().map(|statement| &statement.kind) {
+        Some(StatementKind::Expression(expression)) => {
+            Some(typechecker.expression_type(expression)?)
+        }
+        _ => None,
+    };
+
+    let value = interpreter.interpret(&ast)?;
+    // calls to void functions have nothing to show
+    Ok(datatype
+        .filter(|_| !matches!(value, Value::Void))
+        .map(|datatype| (value, datatype)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_balanced_input_is_complete() {
+        assert!(!is_incomplete("let x = 1;"));
+        assert!(!is_incomplete("func f(a: number): number { return a; }"));
+        assert!(!is_incomplete("croak \"{\";"));
+    }
+
+    #[test]
+    fn test_unclosed_input_is_incomplete() {
+        assert!(is_incomplete("while x < 10 {"));
+        assert!(is_incomplete(
+            "func f(a: number): number {\n  if a > 0 {\n    return a;\n  }"
+        ));
+        assert!(is_incomplete("croak max(1,"));
+        assert!(is_incomplete("croak \"ribbit"));
+    }
+
+    #[test]
+    fn test_declarations_persist_between_entries() {
+        let mut typechecker = TypeChecker::new();
+        let mut interpreter = Interpreter::new();
+
+        eval("let x = 1;", 0, &mut typechecker, &mut interpreter).unwrap();
+        eval(
+            "func double(n: number): number { return n * 2; }",
+            0,
+            &mut typechecker,
+            &mut interpreter,
+        )
+        .unwrap();
+
+        assert!(eval("let y = double(x);", 0, &mut typechecker, &mut interpreter).is_ok());
+        assert!(matches!(
+            eval("x = true;", 0, &mut typechecker, &mut interpreter),
+            Err(Error::Type(_))
+        ));
+    }
+
+    #[test]
+    fn test_expressions_are_echoed() {
+        let mut typechecker = TypeChecker::new();
+        let mut interpreter = Interpreter::new();
+
+        assert_eq!(
+            eval("1 + 2 * 3", 0, &mut typechecker, &mut interpreter),
+            Ok(Some((Value::Number(7), Type::Number)))
+        );
+        eval("let x = 1.5;", 0, &mut typechecker, &mut interpreter).unwrap();
+        assert_eq!(
+            eval("x * 2", 0, &mut typechecker, &mut interpreter),
+            Ok(Some((Value::Float(3.0), Type::Float)))
+        );
+        assert_eq!(
+            eval("let y = x;", 0, &mut typechecker, &mut interpreter),
+            Ok(None)
+        );
+        assert_eq!(
+            eval(
+                "func hop() { } hop();",
+                0,
+                &mut typechecker,
+                &mut interpreter
+            ),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_errors_point_into_the_entry_they_happened_in() {
+        let mut typechecker = TypeChecker::new();
+        let mut interpreter = Interpreter::new();
+        let mut entries = 0;
+        let mut eval = |input: &str| {
+            let offset = record_entry(&mut interpreter, &mut entries, input);
+            eval(input, offset, &mut typechecker, &mut interpreter)
+        };
+
+        eval("let zero = 0;").unwrap();
+        eval("func divide(n: number): number {\n    return n / zero;\n}").unwrap();
+        let error = eval("divide(1)").unwrap_err();
+
+        let sources = &interpreter.modules.sources;
+        let (name, source, diagnostic) = sources.locate(&error.diagnostics()[0]).unwrap();
+        assert_eq!(name, "<repl:2>");
+        assert_eq!(
+            &source[diagnostic.span.start..diagnostic.span.end],
+            "n / zero"
+        );
+        assert_eq!(diagnostic.span.line, 2);
+    }
+
+    #[test]
+    fn test_failed_entry_is_rolled_back() {
+        let mut typechecker = TypeChecker::new();
+        let mut interpreter = Interpreter::new();
+
+        // `a` is declared before the error, but the entry as a whole is rejected
+        assert!(
+            eval(
+                "let a = 1; let b = a + true;",
+                0,
+                &mut typechecker,
+                &mut interpreter
+            )
+            .is_err()
+        );
+        assert!(matches!(
+            eval("let c = a + 1;", 0, &mut typechecker, &mut interpreter),
+            Err(Error::Type(_))
+        ));
+    }
+
+    #[test]
+    fn test_type_command_uses_session_declarations() {
+        let mut typechecker = TypeChecker::new();
+        let mut interpreter = Interpreter::new();
+        eval("let x = 1.5;", 0, &mut typechecker, &mut interpreter).unwrap();
+
+        assert_eq!(type_of("x * 2", &mut typechecker), Ok(Type::Float));
+        assert_eq!(
+            type_of("x > 1 && true", &mut typechecker),
+            Ok(Type::Boolean)
+        );
+        assert!(matches!(
+            type_of("let y = 1;", &mut typechecker),
+            Err(Error::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_ast_command_prints_tree() {
+        assert_eq!(
+            ast_of("croak -x;"),
+            Ok("Croak [1:1]\n  Unary - [1:7]\n    Variable x [1:8]\n".to_string())
+        );
+        assert!(matches!(ast_of("croak;"), Err(Error::Parse(_))));
+    }
+}
@@ -0,0 +1,513 @@
+use crate::formatter::format_expression;
+use crate::interpreter::Value;
+use crate::lexer::Span;
+use crate::parser::{
+    BinOp, Expression, ExpressionKind, Pattern, Statement, StatementKind, UnaryOp,
+};
+use crate::symbol::Symbol;
+use std::rc::Rc;
+
+// the interpreter's form of a program: the AST with every name resolved to where it will live
+// at runtime, so running it never has to search scopes by name
+
+// where a variable is found when the code referring to it runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Variable {
+    // `index` in the local scope `depth` scopes out from the innermost one
+    Local {
+        depth: usize,
+        index: usize,
+        constant: bool,
+    },
+    // globals, natives and builtins are looked up by name, as REPL entries and imports keep
+    // adding to them
+    Global,
+}
+
+#[derive(Debug)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum ExprKind {
+    Literal(Value),
+    Variable(Symbol, Variable),
+    Binary {
+        left: Box<Expr>,
+        operator: BinOp,
+        right: Box<Expr>,
+    },
+    Unary {
+        operator: UnaryOp,
+        operand: Box<Expr>,
+    },
+    Range {
+        start: Box<Expr>,
+        end: Box<Expr>,
+    },
+    Call {
+        name: Symbol,
+        function: Variable,
+        arguments: Vec<Expr>,
+    },
+    // the value is evaluated in the body's scope once its statements have run
+    Block {
+        body: Body,
+        value: Box<Expr>,
+    },
+}
+
+#[derive(Debug)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum StmtKind {
+    // declares a variable in the innermost scope, at `slot` unless it's a global
+    Declare {
+        name: Symbol,
+        slot: Option<usize>,
+        value: Expr,
+        constant: bool,
+    },
+    Assign {
+        name: Symbol,
+        variable: Variable,
+        value: Expr,
+    },
+    Function {
+        slot: Option<usize>,
+        code: Rc<Code>,
+    },
+    Print {
+        value: Expr,
+        debug: bool,
+    },
+    While {
+        condition: Expr,
+        body: Body,
+    },
+    DoWhile {
+        body: Body,
+        condition: Expr,
+    },
+    // every iteration's scope holds the counter at index 0
+    For {
+        start: Expr,
+        end: Expr,
+        body: Body,
+    },
+    // like For, with the counter at index 0, going over the range `iterable` evaluates to
+    ForIn {
+        iterable: Expr,
+        body: Body,
+    },
+    Block(Body),
+    If {
+        condition: Expr,
+        then_block: Body,
+        else_block: Option<Body>,
+    },
+    // arms without a pattern are wildcards
+    Match {
+        subject: Expr,
+        arms: Vec<(Option<Expr>, Body)>,
+    },
+    Expression(Expr),
+    // the source of the assertion, for the error when it fails
+    Assert {
+        condition: Expr,
+        source: String,
+    },
+    AssertEq {
+        left: Expr,
+        right: Expr,
+        source: String,
+    },
+    Return(Expr),
+    // the handler's scope holds the error message at index 0
+    Try {
+        body: Body,
+        handler: Body,
+    },
+    Raise(Expr),
+    Break,
+    Continue,
+    // imports and enums have no effect at runtime
+    Nothing,
+}
+
+impl StmtKind {
+    // what the statement is called in profiles
+    pub fn name(&self) -> &'static str {
+        match self {
+            StmtKind::Declare {
+                constant: false, ..
+            } => "let",
+            StmtKind::Declare { constant: true, .. } => "const",
+            StmtKind::Assign { .. } => "assignment",
+            StmtKind::Function { .. } => "func",
+            StmtKind::Print { .. } => "croak",
+            StmtKind::While { .. } => "while",
+            StmtKind::DoWhile { .. } => "do while",
+            StmtKind::For { .. } | StmtKind::ForIn { .. } => "for",
+            StmtKind::Block(_) => "block",
+            StmtKind::If { .. } => "if",
+            StmtKind::Match { .. } => "match",
+            StmtKind::Expression(_) => "expression",
+            StmtKind::Assert { .. } => "assert",
+            StmtKind::AssertEq { .. } => "assert_eq",
+            StmtKind::Return(_) => "return",
+            StmtKind::Try { .. } => "try",
+            StmtKind::Raise(_) => "raise",
+            StmtKind::Break => "break",
+            StmtKind::Continue => "continue",
+            StmtKind::Nothing => "import or enum",
+        }
+    }
+}
+
+// statements that run in a local scope of their own, with the names of that scope's
+// variables by slot, so debuggers can show them
+#[derive(Debug)]
+pub struct Body {
+    pub statements: Vec<Stmt>,
+    pub names: Rc<[Symbol]>,
+}
+
+// a declared function; calls run `body` in a scope holding the arguments at 0..params.len()
+#[derive(Debug)]
+pub struct Code {
+    pub name: Symbol,
+    pub params: Vec<Symbol>,
+    pub body: Body,
+}
+
+// names declared in one local scope so far, by index, and whether they are constants
+type Scope = Vec<(Symbol, bool)>;
+
+// mirrors the scopes the interpreter will create: the top level is global, every block,
+// branch, match arm, loop iteration and call gets a local scope of its own
+#[derive(Default)]
+struct Resolver {
+    scopes: Vec<Scope>,
+}
+
+pub fn resolve(program: &[Statement]) -> Vec<Stmt> {
+    Resolver::default().statements(program)
+}
+
+impl Resolver {
+    // resolves `statements` in a new scope that starts out with `scope`
+    fn scoped(&mut self, scope: Scope, statements: &[Statement]) -> Body {
+        self.scoped_with(scope, statements, |_| ()).0
+    }
+
+    // like `scoped`, resolving more with `then` in the scope once the statements are
+    fn scoped_with<T>(
+        &mut self,
+        scope: Scope,
+        statements: &[Statement],
+        then: impl FnOnce(&mut Self) -> T,
+    ) -> (Body, T) {
+        self.scopes.push(scope);
+        let statements = self.statements(statements);
+        let resolved = then(self);
+        let scope = self.scopes.pop().unwrap_or_default();
+        let body = Body {
+            statements,
+            names: scope.into_iter().map(|(name, _)| name).collect(),
+        };
+        (body, resolved)
+    }
+
+    fn block(&mut self, statements: &[Statement]) -> Body {
+        self.scoped(Vec::new(), statements)
+    }
+
+    fn statements(&mut self, statements: &[Statement]) -> Vec<Stmt> {
+        statements
+            .iter()
+            .map(|statement| self.statement(statement))
+            .collect()
+    }
+
+    fn variable(&self, name: Symbol) -> Variable {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if let Some(index) = scope.iter().position(|(declared, _)| *declared == name) {
+                return Variable::Local {
+                    depth,
+                    index,
+                    constant: scope[index].1,
+                };
+            }
+        }
+        Variable::Global
+    }
+
+    // slot of a new local in the innermost scope; declaring a name again there reuses its
+    // slot, like redeclaring a global replaces it
+    fn declare(&mut self, name: Symbol, constant: bool) -> Option<usize> {
+        let scope = self.scopes.last_mut()?;
+        match scope.iter().position(|(declared, _)| *declared == name) {
+            Some(index) => {
+                scope[index].1 = constant;
+                Some(index)
+            }
+            None => {
+                scope.push((name, constant));
+                Some(scope.len() - 1)
+            }
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Stmt {
+        let kind = match &statement.kind {
+            StatementKind::Declaration(name, value, _) | StatementKind::Const(name, value, _) => {
+                let constant = matches!(statement.kind, StatementKind::Const(..));
+                // the value can't see the variable it initializes
+                let value = self.expression(value);
+                StmtKind::Declare {
+                    name: *name,
+                    slot: self.declare(*name, constant),
+                    value,
+                    constant,
+                }
+            }
+            StatementKind::Assignment(name, value) => StmtKind::Assign {
+                name: *name,
+                variable: self.variable(*name),
+                value: self.expression(value),
+            },
+            StatementKind::FunctionDeclaration {
+                name, params, body, ..
+            } => {
+                // declared before the body is resolved so the function can call itself
+                let slot = self.declare(*name, false);
+                let params: Vec<Symbol> = params.iter().map(|(param, _)| *param).collect();
+                let scope = params.iter().map(|param| (*param, false)).collect();
+                let body = self.scoped(scope, body);
+                StmtKind::Function {
+                    slot,
+                    code: Rc::new(Code {
+                        name: *name,
+                        params,
+                        body,
+                    }),
+                }
+            }
+            StatementKind::Print { value, debug } => StmtKind::Print {
+                value: self.expression(value),
+                debug: *debug,
+            },
+            StatementKind::While { condition, body } => StmtKind::While {
+                condition: self.expression(condition),
+                body: self.block(body),
+            },
+            StatementKind::DoWhile { body, condition } => StmtKind::DoWhile {
+                body: self.block(body),
+                condition: self.expression(condition),
+            },
+            StatementKind::For {
+                variable,
+                start,
+                end,
+                body,
+                ..
+            } => StmtKind::For {
+                start: self.expression(start),
+                end: self.expression(end),
+                body: self.scoped(vec![(*variable, false)], body),
+            },
+            StatementKind::ForIn {
+                variable,
+                iterable,
+                body,
+                ..
+            } => StmtKind::ForIn {
+                iterable: self.expression(iterable),
+                body: self.scoped(vec![(*variable, false)], body),
+            },
+            StatementKind::Block(statements) => StmtKind::Block(self.block(statements)),
+            StatementKind::If {
+                condition,
+                then_block,
+                else_block,
+            } => StmtKind::If {
+                condition: self.expression(condition),
+                then_block: self.block(then_block),
+                else_block: else_block.as_ref().map(|block| self.block(block)),
+            },
+            StatementKind::Match { subject, arms } => StmtKind::Match {
+                subject: self.expression(subject),
+                arms: arms
+                    .iter()
+                    .map(|arm| {
+                        let pattern = match &arm.pattern {
+                            Pattern::Value(value) => Some(self.expression(value)),
+                            Pattern::Wildcard => None,
+                        };
+                        (pattern, self.block(&arm.body))
+                    })
+                    .collect(),
+            },
+            StatementKind::Expression(expression) => {
+                StmtKind::Expression(self.expression(expression))
+            }
+            StatementKind::Assert(condition) => StmtKind::Assert {
+                condition: self.expression(condition),
+                source: format_expression(condition),
+            },
+            StatementKind::AssertEq(left, right) => StmtKind::AssertEq {
+                left: self.expression(left),
+                right: self.expression(right),
+                source: format!(
+                    "{} == {}",
+                    format_expression(left),
+                    format_expression(right)
+                ),
+            },
+            StatementKind::Return(value) => StmtKind::Return(self.expression(value)),
+            StatementKind::Try {
+                body,
+                error,
+                handler,
+            } => StmtKind::Try {
+                body: self.block(body),
+                handler: self.scoped(vec![(*error, false)], handler),
+            },
+            StatementKind::Raise(message) => StmtKind::Raise(self.expression(message)),
+            StatementKind::Break => StmtKind::Break,
+            StatementKind::Continue => StmtKind::Continue,
+            StatementKind::Import(_) | StatementKind::Enum { .. } => StmtKind::Nothing,
+        };
+        Stmt {
+            kind,
+            span: statement.span,
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) -> Expr {
+        let kind = match &expression.kind {
+            ExpressionKind::Number(n, _) => ExprKind::Literal(Value::Number(*n)),
+            ExpressionKind::Float(f, _) => ExprKind::Literal(Value::Float(*f)),
+            ExpressionKind::String(s, _) => ExprKind::Literal(Value::String(s.clone())),
+            ExpressionKind::Bool(b) => ExprKind::Literal(Value::Bool(*b)),
+            ExpressionKind::None => ExprKind::Literal(Value::None),
+            ExpressionKind::EnumVariant { enum_name, variant } => {
+                ExprKind::Literal(Value::Enum(*enum_name, *variant))
+            }
+            ExpressionKind::Variable(name) => ExprKind::Variable(*name, self.variable(*name)),
+            ExpressionKind::BinaryOperation {
+                left,
+                operator,
+                right,
+                ..
+            } => ExprKind::Binary {
+                left: Box::new(self.expression(left)),
+                operator: *operator,
+                right: Box::new(self.expression(right)),
+            },
+            ExpressionKind::UnaryOperation {
+                operator, operand, ..
+            } => ExprKind::Unary {
+                operator: *operator,
+                operand: Box::new(self.expression(operand)),
+            },
+            ExpressionKind::Range { start, end } => ExprKind::Range {
+                start: Box::new(self.expression(start)),
+                end: Box::new(self.expression(end)),
+            },
+            ExpressionKind::FunctionCall { name, arguments } => ExprKind::Call {
+                name: *name,
+                function: self.variable(*name),
+                arguments: arguments
+                    .iter()
+                    .map(|argument| self.expression(argument))
+                    .collect(),
+            },
+            ExpressionKind::Block { statements, value } => {
+                let (body, value) = self.scoped_with(Vec::new(), statements, |resolver| {
+                    Box::new(resolver.expression(value))
+                });
+                ExprKind::Block { body, value }
+            }
+        };
+        Expr {
+            kind,
+            span: expression.span,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn resolved(source: &str) -> Vec<Stmt> {
+        let tokens = Lexer::new(source).parse().unwrap();
+        resolve(&Parser::new(tokens).parse().unwrap())
+    }
+
+    #[test]
+    fn test_locals_get_depth_and_slot() {
+        let program = resolved(
+            "let g = 1; func f(a: number, b: number): number { let c = a; { return b + c + g; } }",
+        );
+        let StmtKind::Declare { slot: None, .. } = program[0].kind else {
+            panic!("top level declarations are global");
+        };
+        let StmtKind::Function { slot: None, code } = &program[1].kind else {
+            panic!("expected a global function");
+        };
+        let StmtKind::Block(block) = &code.body.statements[1].kind else {
+            panic!("expected a block");
+        };
+        let StmtKind::Return(sum) = &block.statements[0].kind else {
+            panic!("expected a return");
+        };
+
+        let mut variables = Vec::new();
+        let mut expr = sum;
+        while let ExprKind::Binary { left, right, .. } = &expr.kind {
+            variables.push(&right.kind);
+            expr = left;
+        }
+        variables.push(&expr.kind);
+        let local = |depth, index| Variable::Local {
+            depth,
+            index,
+            constant: false,
+        };
+        assert!(matches!(
+            variables[0],
+            ExprKind::Variable(_, Variable::Global)
+        ));
+        assert!(matches!(variables[1], ExprKind::Variable(_, v) if *v == local(1, 2)));
+        assert!(matches!(variables[2], ExprKind::Variable(_, v) if *v == local(1, 1)));
+    }
+
+    #[test]
+    fn test_redeclaration_reuses_the_slot() {
+        let program = resolved("{ let a = 1; let b = a; const a = b; }");
+        let StmtKind::Block(block) = &program[0].kind else {
+            panic!("expected a block");
+        };
+        let slots: Vec<Option<usize>> = block
+            .statements
+            .iter()
+            .map(|statement| match &statement.kind {
+                StmtKind::Declare { slot, .. } => *slot,
+                _ => None,
+            })
+            .collect();
+        assert_eq!(slots, [Some(0), Some(1), Some(0)]);
+        assert_eq!(*block.names, [Symbol::from("a"), Symbol::from("b")]);
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{LazyLock, Mutex};
+
+// an interned identifier. The lexer interns every name it reads, so the rest of the
+// pipeline copies, compares and hashes a number instead of a string
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+struct Interner {
+    symbols: HashMap<&'static str, Symbol>,
+    names: Vec<&'static str>,
+}
+
+// one table for the whole process, so symbols made on different threads (tests, the language
+// server) still agree; names are never freed, a program only has so many of them
+static INTERNER: LazyLock<Mutex<Interner>> = LazyLock::new(Default::default);
+
+impl Symbol {
+    pub fn intern(name: &str) -> Symbol {
+        let mut interner = INTERNER.lock().unwrap();
+        if let Some(&symbol) = interner.symbols.get(name) {
+            return symbol;
+        }
+        let symbol = Symbol(interner.names.len() as u32);
+        let name: &'static str = Box::leak(name.into());
+        interner.names.push(name);
+        interner.symbols.insert(name, symbol);
+        symbol
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER.lock().unwrap().names[self.0 as usize]
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Symbol {
+        Symbol::intern(name)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+// shows the name, so ASTs print the way they did with strings
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interning() {
+        let frog = Symbol::intern("frog");
+        assert_eq!(frog, Symbol::from("frog"));
+        assert_ne!(frog, Symbol::intern("toad"));
+        assert_eq!(frog.as_str(), "frog");
+        assert_eq!(format!("{:>6}|{:?}", frog, frog), "  frog|\"frog\"");
+    }
+}
@@ -0,0 +1,145 @@
+use froggle::error::Style;
+use froggle::interpreter::Interpreter;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+// test files are ordinary programs whose name ends in this; they pass by running to the end
+const TEST_SUFFIX: &str = "_test.frog";
+
+// runs every test file under `path`, or `path` itself if it is a file
+pub fn run_tests(path: &str) -> ExitCode {
+    let files = match discover(Path::new(path)) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("Error reading {}: {}", path, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if files.is_empty() {
+        println!("no *{} files in {}", TEST_SUFFIX, path);
+        return ExitCode::SUCCESS;
+    }
+
+    let mut failed = 0;
+    for file in &files {
+        match run_test(file) {
+            Ok(()) => println!("ok   {}", file.display()),
+            Err(message) => {
+                failed += 1;
+                println!("FAIL {}", file.display());
+                for line in message.lines() {
+                    println!("     {}", line);
+                }
+            }
+        }
+    }
+
+    println!("\n{} passed, {} failed", files.len() - failed, failed);
+    if failed > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+// test files in and below `path`, sorted so runs are reproducible
+fn discover(path: &Path) -> io::Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let entry = entry?.path();
+        if entry.is_dir() {
+            files.extend(discover(&entry)?);
+        } else if entry
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().ends_with(TEST_SUFFIX))
+        {
+            files.push(entry);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// every test gets an interpreter of its own, so tests can't see each other's declarations;
+// a test calling exit(0) passes early. Failures are rendered like other errors, without colors
+// as they go to stdout
+fn run_test(file: &Path) -> Result<(), String> {
+    let source = fs::read_to_string(file).map_err(|e| e.to_string())?;
+    let style = Style {
+        color: false,
+        ..crate::style()
+    };
+    let mut interpreter = Interpreter::new();
+    match froggle::run(&source, file, &mut interpreter) {
+        Err(e) if e.exit_code() == Some(0) => Ok(()),
+        // shown in the file they are in, which may be a module the test imported
+        result => result.map(|_| ()).map_err(|e| {
+            let rendered: Vec<String> = e
+                .diagnostics()
+                .iter()
+                .map(
+                    |diagnostic| match interpreter.modules.sources.locate(diagnostic) {
+                        Some((name, source, diagnostic)) => diagnostic.render(name, source, style),
+                        None => diagnostic.render("<unknown>", "", style),
+                    },
+                )
+                .collect();
+            rendered.join("\n")
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discover_and_run() {
+        let dir = std::env::temp_dir().join(format!("froggle-tests-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("math_test.frog"), "assert_eq 2 * 2, 4;").unwrap();
+        fs::write(dir.join("nested/broken_test.frog"), "assert 1 > 2;").unwrap();
+        fs::write(dir.join("helper.frog"), "assert false;").unwrap();
+        fs::write(
+            dir.join("nested/checks.frog"),
+            "func check() {\n  assert 1 > 2;\n}",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("nested/import_test.frog"),
+            "import \"checks.frog\";\ncheck();",
+        )
+        .unwrap();
+
+        let files = discover(&dir).unwrap();
+        assert_eq!(
+            files,
+            [
+                dir.join("math_test.frog"),
+                dir.join("nested/broken_test.frog"),
+                dir.join("nested/import_test.frog")
+            ]
+        );
+        assert_eq!(run_test(&files[0]), Ok(()));
+        assert_eq!(
+            run_test(&files[1]),
+            Err(format!(
+                "error[runtime/AssertionFailed]: assertion failed: 1 > 2\n \
+                 --> {}:1:1\n  |\n1 | assert 1 > 2;\n  | ^^^^^^^^^^^^^",
+                files[1].display()
+            ))
+        );
+        // an error in an imported module is shown in it
+        let module = fs::canonicalize(dir.join("nested/checks.frog")).unwrap();
+        assert!(run_test(&files[2]).unwrap_err().contains(&format!(
+            " --> {}:2:3\n  |\n2 |   assert 1 > 2;",
+            module.display()
+        )));
+    }
+}
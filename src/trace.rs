@@ -0,0 +1,103 @@
+use crate::cli::Trace;
+use crate::debugger::show;
+use froggle::interpreter::{Env, Hook, Value};
+use froggle::lexer::Span;
+use std::fs::File;
+use std::io::{self, BufWriter, LineWriter, Write};
+
+// the interpreter's hook for `--trace`: writes a line for every statement before it runs and
+// every expression once it has a value, with its location and how many local scopes deep it
+// runs. Failing to write doesn't stop the program
+pub struct Tracer {
+    output: Box<dyn Write>,
+}
+
+impl Tracer {
+    pub fn open(trace: &Trace) -> io::Result<Tracer> {
+        let output: Box<dyn Write> = match trace {
+            // a line at a time, so the trace stays in order with the program's errors
+            Trace::Stderr => Box::new(LineWriter::new(io::stderr())),
+            Trace::File(path) => Box::new(BufWriter::new(File::create(path)?)),
+        };
+        Ok(Tracer { output })
+    }
+}
+
+impl Hook for Tracer {
+    fn on_statement(&mut self, span: Span, env: &Env) {
+        let _ = writeln!(self.output, "{} scope {} statement", span, env.scopes());
+    }
+
+    fn on_expression(&mut self, span: Span, value: &Value, env: &Env) {
+        let _ = writeln!(
+            self.output,
+            "{} scope {} expression = {}",
+            span,
+            env.scopes(),
+            show(value)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::Profiler;
+    use froggle::interpreter::Interpreter;
+    use std::cell::RefCell;
+    use std::fs;
+    use std::path::Path;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_trace_to_a_file() {
+        let path = std::env::temp_dir().join(format!("froggle-trace-{}.txt", std::process::id()));
+        let trace = Trace::File(path.to_string_lossy().into_owned());
+        let mut interpreter = Interpreter::new();
+        interpreter.capture_output();
+        interpreter.hook = Some(Box::new(Tracer::open(&trace).unwrap()));
+
+        let source = "let word = \"frog\";\n{\n    croak 1 + 2;\n}";
+        froggle::run(source, Path::new("test.frog"), &mut interpreter).unwrap();
+        // dropping the hook flushes the file
+        interpreter.hook = None;
+        let traced = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            traced,
+            "\
+1:1 scope 0 statement
+1:12 scope 0 expression = \"frog\"
+2:1 scope 0 statement
+3:5 scope 1 statement
+3:11 scope 1 expression = 1
+3:15 scope 1 expression = 2
+3:11 scope 1 expression = 3
+"
+        );
+    }
+
+    #[test]
+    fn test_trace_alongside_a_profile() {
+        let path =
+            std::env::temp_dir().join(format!("froggle-trace-profile-{}.txt", std::process::id()));
+        let trace = Trace::File(path.to_string_lossy().into_owned());
+        let profiler = Rc::new(RefCell::new(Profiler::default()));
+        let mut interpreter = Interpreter::new();
+        interpreter.capture_output();
+        let tracer = Tracer::open(&trace).unwrap();
+        interpreter.hook = Some(Box::new((tracer, profiler.clone())));
+
+        froggle::run("croak 1;", Path::new("test.frog"), &mut interpreter).unwrap();
+        interpreter.hook = None;
+        let traced = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            traced,
+            "1:1 scope 0 statement\n1:7 scope 0 expression = 1\n"
+        );
+        assert!(profiler.borrow().report().contains("croak"));
+    }
+}
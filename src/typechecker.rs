@@ -1,311 +1,3847 @@
-use crate::parser::Expression::BinaryOperation;
-use crate::parser::{ASTVisitor, Expression, Statement, Type};
+use crate::builtins::BUILTINS;
+use crate::lexer::Span;
+use crate::parser::ExpressionKind::BinaryOperation;
+use crate::parser::{
+    ASTVisitor, BinOp, Expression, ExpressionKind, MatchArm, Pattern, Statement, StatementKind,
+    Type, UnaryOp, is_exhaustive,
+};
+use crate::symbol;
 use std::collections::HashMap;
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
 
+#[derive(Debug, PartialEq, Clone)]
+pub enum TypeErrorKind {
+    UnknownVariable(String),
+    UnknownFunction(String),
+    NotCallable {
+        name: String,
+        found: Type,
+    },
+    InvalidOperands {
+        operator: String,
+        left: Type,
+        right: Type,
+    },
+    InvalidOperand {
+        operator: String,
+        operand: Type,
+    },
+    DeclarationMismatch {
+        name: String,
+        expected: Type,
+        found: Type,
+    },
+    AssignmentMismatch {
+        name: String,
+        expected: Type,
+        found: Type,
+    },
+    AssignmentToConstant(String),
+    NonBooleanCondition {
+        construct: String,
+        found: Type,
+    },
+    NonNumericLoopBound(Type),
+    // for ... in over a type without elements
+    NotIterable(Type),
+    OutsideLoop(String),
+    ReturnMismatch {
+        expected: Type,
+        found: Type,
+    },
+    // a return disagreeing with an earlier one in a function whose return type is inferred
+    InconsistentReturn {
+        earlier: Type,
+        found: Type,
+    },
+    // the value of a call to a function that returns nothing, by the function's name
+    VoidValue(String),
+    // a call to a function whose return type is inferred from returns not checked yet
+    ReturnTypeNotInferred(String),
+    // a use of a variable whose initializer failed to check, which is never reported: the
+    // error in the initializer was
+    UndeterminedType(String),
+    // a type parameter no parameter's type mentions, which calls could never bind
+    UnusedTypeParameter {
+        function: String,
+        parameter: String,
+    },
+    MissingReturn {
+        name: String,
+        expected: Type,
+    },
+    // a `main` the program starts from, which is called without arguments
+    MainParameters,
+    ReturnOutsideFunction,
+    // return, break or continue in a block used as a value, which has to run to its end
+    JumpOutOfBlock(String),
+    // raise takes the error's message
+    NonStringRaise(Type),
+    ArityMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    ArgumentMismatch {
+        name: String,
+        position: usize,
+        expected: Type,
+        found: Type,
+    },
+    // an operand that makes an expression the wrong type for where its value is wanted
+    ExpectedType {
+        expected: Type,
+        found: Type,
+    },
+    // a call whose result is the wrong type for where its value is wanted
+    ResultMismatch {
+        function: String,
+        expected: Type,
+        found: Type,
+    },
+    NestedImport,
+    PatternMismatch {
+        expected: Type,
+        found: Type,
+    },
+    NonExhaustiveMatch(String),
+    UnknownEnum(String),
+    UnknownVariant {
+        enum_name: String,
+        variant: String,
+    },
+    DuplicateVariant {
+        enum_name: String,
+        variant: String,
+    },
+    // an enum declared again with other variants; enums are seen by the whole program, so
+    // values of the first would be taken for the second
+    DuplicateEnum(String),
+    // assert_eq on values of types that can never be equal
+    IncomparableAssertion {
+        left: Type,
+        right: Type,
+    },
+    // a name declared again in the same scope with another type; functions that captured the
+    // scope would see a value of the wrong type
+    Redeclaration {
+        name: String,
+        previous: Type,
+        found: Type,
+    },
+    // a call to a top-level function declared further down, made before a top-level variable
+    // the function uses is declared
+    UsedBeforeDeclaration {
+        function: String,
+        variable: String,
+    },
+    // a top-level function declared again at the top level; calls before either declaration
+    // couldn't tell which one they mean
+    DuplicateFunction(String),
+}
+
+impl fmt::Display for TypeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeErrorKind::UnknownVariable(name) => {
+                write!(f, "no variable {} in existing scopes", name)
+            }
+            TypeErrorKind::UnknownFunction(name) => {
+                write!(f, "no function {} in existing scopes", name)
+            }
+            TypeErrorKind::NotCallable { name, found } => {
+                write!(f, "{} is a {}, not a function", name, found)
+            }
+            TypeErrorKind::InvalidOperands {
+                operator,
+                left,
+                right,
+            } => write!(
+                f,
+                "operator {} cannot be applied to {} and {}",
+                operator, left, right
+            ),
+            TypeErrorKind::InvalidOperand { operator, operand } => {
+                write!(f, "operator {} cannot be applied to {}", operator, operand)
+            }
+            TypeErrorKind::DeclarationMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "type mismatch in declaration of {}: expected {}, got {}",
+                name, expected, found
+            ),
+            TypeErrorKind::AssignmentMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "cannot assign {} to variable {} of type {}",
+                found, name, expected
+            ),
+            TypeErrorKind::AssignmentToConstant(name) => {
+                write!(f, "cannot assign to constant {}", name)
+            }
+            TypeErrorKind::NonBooleanCondition { construct, found } => {
+                write!(f, "{} condition must be bool, got {}", construct, found)
+            }
+            TypeErrorKind::NonNumericLoopBound(found) => {
+                write!(f, "for loop bounds must be number, got {}", found)
+            }
+            TypeErrorKind::NotIterable(found) => {
+                write!(f, "cannot go over a {} with for ... in", found)
+            }
+            TypeErrorKind::OutsideLoop(keyword) => write!(f, "'{}' outside of a loop", keyword),
+            TypeErrorKind::ReturnMismatch { expected, found } => {
+                write!(f, "expected return type {}, got {}", expected, found)
+            }
+            TypeErrorKind::InconsistentReturn { earlier, found } => write!(
+                f,
+                "this returns {}, but an earlier return gives {}; write the return type if it can be either",
+                found, earlier
+            ),
+            TypeErrorKind::VoidValue(name) => write!(
+                f,
+                "{} returns nothing, so its call has no value to use; call it as a statement of its own",
+                name
+            ),
+            TypeErrorKind::ReturnTypeNotInferred(name) => write!(
+                f,
+                "the return type of {} isn't known here, as none of its returns has been checked yet; write it out to call {} here",
+                name, name
+            ),
+            TypeErrorKind::UndeterminedType(name) => write!(
+                f,
+                "the type of {} isn't known, as its initializer has an error",
+                name
+            ),
+            TypeErrorKind::UnusedTypeParameter {
+                function,
+                parameter,
+            } => write!(
+                f,
+                "type parameter {} of {} is not used by its parameters, so calls can't tell what it is",
+                parameter, function
+            ),
+            TypeErrorKind::MainParameters => write!(
+                f,
+                "main must take no parameters; arg_count and arg give the program's arguments"
+            ),
+            TypeErrorKind::MissingReturn { name, expected } => write!(
+                f,
+                "function {} must return {} on every path",
+                name, expected
+            ),
+            TypeErrorKind::ReturnOutsideFunction => write!(f, "'return' outside of a function"),
+            TypeErrorKind::JumpOutOfBlock(keyword) => {
+                write!(f, "'{}' can't jump out of a block used as a value", keyword)
+            }
+            TypeErrorKind::NonStringRaise(found) => {
+                write!(f, "raise expects a string message, got {}", found)
+            }
+            TypeErrorKind::ArityMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "function {} expects {} arguments, got {}",
+                name, expected, found
+            ),
+            TypeErrorKind::ArgumentMismatch {
+                name,
+                position,
+                expected,
+                found,
+            } => write!(
+                f,
+                "argument {} of {} must be {}, got {}",
+                position, name, expected, found
+            ),
+            TypeErrorKind::ExpectedType { expected, found } => {
+                write!(f, "expected {}, got {}", expected, found)?;
+                if *expected == Type::String && *found != Type::Void {
+                    write!(f, "; convert it with to_string")?;
+                }
+                Ok(())
+            }
+            TypeErrorKind::ResultMismatch {
+                function,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} returns {}, but {} is expected here",
+                function, found, expected
+            ),
+            TypeErrorKind::NestedImport => write!(f, "imports are only allowed at the top level"),
+            TypeErrorKind::PatternMismatch { expected, found } => {
+                write!(f, "cannot match {} against a {} pattern", expected, found)
+            }
+            TypeErrorKind::NonExhaustiveMatch(missing) => {
+                write!(
+                    f,
+                    "match doesn't cover {}; add that arm or a '_' arm",
+                    missing
+                )
+            }
+            TypeErrorKind::UnknownEnum(name) => write!(f, "no enum {} in existing scopes", name),
+            TypeErrorKind::UnknownVariant { enum_name, variant } => {
+                write!(f, "enum {} has no variant {}", enum_name, variant)
+            }
+            TypeErrorKind::DuplicateVariant { enum_name, variant } => {
+                write!(
+                    f,
+                    "variant {} is declared twice in enum {}",
+                    variant, enum_name
+                )
+            }
+            TypeErrorKind::DuplicateEnum(name) => write!(
+                f,
+                "enum {} is already declared with other variants; give this one another name",
+                name
+            ),
+            TypeErrorKind::IncomparableAssertion { left, right } => {
+                write!(f, "assert_eq cannot compare {} with {}", left, right)
+            }
+            TypeErrorKind::Redeclaration {
+                name,
+                previous,
+                found,
+            } => write!(
+                f,
+                "{} is already declared as {} in this scope, cannot redeclare it as {}",
+                name, previous, found
+            ),
+            TypeErrorKind::UsedBeforeDeclaration { function, variable } => write!(
+                f,
+                "{} uses {}, which isn't declared yet here; call {} after the declaration of {}",
+                function, variable, function, variable
+            ),
+            TypeErrorKind::DuplicateFunction(name) => write!(
+                f,
+                "function {} is already declared at the top level, where it can be called before its declaration; give this one another name",
+                name
+            ),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TypeError {
+    pub kind: TypeErrorKind,
+    pub span: Span,
+}
+
+impl TypeError {
+    fn new(kind: TypeErrorKind, span: Span) -> TypeError {
+        TypeError { kind, span }
+    }
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.kind)
+    }
+}
+
+#[derive(Clone)]
+struct Binding {
+    datatype: Type,
+    constant: bool,
+    // statement declaring the name, None for builtins and natives
+    definition: Option<Span>,
+    // an optional variable seen with its inner type, where a check ruled out none
+    narrowed: bool,
+}
+
+// a use of a variable or function, recorded for editor tooling such as hover and go to definition
+#[derive(Debug, PartialEq, Clone)]
+pub struct Symbol {
+    pub name: String,
+    // just the name, without a call's arguments
+    pub span: Span,
+    pub datatype: Type,
+    pub definition: Option<Span>,
+}
+
+// span of the leading `name` in `span`, e.g. the function name of a call
+fn name_span(name: &str, span: Span) -> Span {
+    Span {
+        end: span.start + name.len(),
+        ..span
+    }
+}
+
+// return type of the function whose body is being checked
+#[derive(Clone)]
+enum ReturnType {
+    Declared(Type),
+    // left out of the declaration and taken from the returns, the type they agree on so far
+    Inferred {
+        function: symbol::Symbol,
+        declaration: Span,
+        found: Option<Type>,
+        // a return whose value didn't check, so what the function returns isn't known
+        unknown: bool,
+    },
+}
+
+#[derive(Clone)]
 pub struct TypeChecker {
-    type_envs: Vec<HashMap<String, Type>>,
-    function_envs: Vec<HashMap<String, (Vec<Type>, Type)>>,
+    type_envs: Vec<HashMap<symbol::Symbol, Binding>>,
+    // variants of every enum declared so far, by enum name
+    enums: HashMap<symbol::Symbol, Vec<symbol::Symbol>>,
+    // number of loops around the statement being checked, so break/continue can be validated
+    loop_depth: usize,
+    // whether a block used as a value is being checked, outside any function declared in it
+    block_value: bool,
+    return_type: Option<ReturnType>,
+    // functions whose return type is still to be inferred, by name and declaration; calling
+    // them before then is an error
+    uninferred: Vec<(symbol::Symbol, Span)>,
+    // the uninferred ones whose body failed to check, which reported why
+    failed: Vec<(symbol::Symbol, Span)>,
+    // variables declared without an annotation whose initializer failed to check, by name and
+    // declaration; their type isn't known, and what uses them is left unchecked
+    undetermined: Vec<(symbol::Symbol, Span)>,
+    // type parameters instantiated so far, numbering the names given to them
+    instances: usize,
+    // errors found so far; checking carries on with the next statement after each one
+    diagnostics: Vec<TypeError>,
+    // statement being checked, where names declared right now are defined
+    statement: Option<Span>,
+    // uses of names found by the last `check`
+    symbols: Vec<Symbol>,
+    // the top-level variables each hoisted function uses, those of the hoisted functions it
+    // calls included, with the function's declaration; a call made before they are all
+    // declared would find one missing
+    hoisted_uses: HashMap<symbol::Symbol, (Span, Vec<symbol::Symbol>)>,
+    // top-level function declarations found by hoisting to repeat an earlier one's name
+    duplicates: Vec<Span>,
+}
+
+impl Default for TypeChecker {
+    fn default() -> Self {
+        TypeChecker::new()
+    }
 }
 
 impl TypeChecker {
     pub fn new() -> TypeChecker {
-        TypeChecker {
+        let mut checker = TypeChecker {
             type_envs: vec![HashMap::new()],
-            function_envs: vec![HashMap::new()],
+            enums: HashMap::new(),
+            loop_depth: 0,
+            block_value: false,
+            return_type: None,
+            uninferred: Vec::new(),
+            failed: Vec::new(),
+            undetermined: Vec::new(),
+            instances: 0,
+            diagnostics: Vec::new(),
+            statement: None,
+            symbols: Vec::new(),
+            hoisted_uses: HashMap::new(),
+            duplicates: Vec::new(),
+        };
+        for builtin in BUILTINS.iter() {
+            checker.declare_function(
+                symbol::Symbol::intern(builtin.name),
+                builtin.params.to_vec(),
+                builtin.return_type.clone(),
+            );
         }
+        checker
     }
 
     fn enter_scope(&mut self) {
         self.type_envs.push(HashMap::new());
-        self.function_envs.push(HashMap::new());
     }
     fn exit_scope(&mut self) {
         self.type_envs.pop();
-        self.function_envs.pop();
     }
 
-    fn declare_variable(&mut self, name: String, type_name: Type) {
+    fn declare_variable(&mut self, name: symbol::Symbol, type_name: Type) {
+        self.declare(name, type_name, false);
+    }
+
+    fn declare(&mut self, name: symbol::Symbol, datatype: Type, constant: bool) {
         self.type_envs
             .last_mut()
-            .expect(format!("error declaring variable {}", name).as_str())
-            .insert(name, type_name);
+            .unwrap_or_else(|| panic!("error declaring variable {}", name))
+            .insert(
+                name,
+                Binding {
+                    datatype,
+                    constant,
+                    definition: self.statement,
+                    narrowed: false,
+                },
+            );
+    }
+
+    fn resolve_binding(&self, name: symbol::Symbol, span: Span) -> Result<&Binding, TypeError> {
+        self.type_envs
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name))
+            .ok_or_else(|| TypeError::new(TypeErrorKind::UnknownVariable(name.to_string()), span))
+    }
+
+    // the variable itself rather than a narrowed view of it, for assigning to it
+    fn resolve_declared(&self, name: symbol::Symbol, span: Span) -> Result<&Binding, TypeError> {
+        self.type_envs
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name).filter(|binding| !binding.narrowed))
+            .ok_or_else(|| TypeError::new(TypeErrorKind::UnknownVariable(name.to_string()), span))
+    }
+
+    // optional variables that can't be none when `condition` evaluates to `when`:
+    // `x != none` rules it out when true, `x == none` when false
+    fn narrowed(&self, condition: &Expression, when: bool) -> Vec<(symbol::Symbol, Binding)> {
+        match &condition.kind {
+            BinaryOperation {
+                left,
+                operator: operator @ (BinOp::Eq | BinOp::Ne),
+                right,
+                ..
+            } if (*operator == BinOp::Ne) == when => {
+                let name = match (&left.kind, &right.kind) {
+                    (ExpressionKind::Variable(name), ExpressionKind::None)
+                    | (ExpressionKind::None, ExpressionKind::Variable(name)) => *name,
+                    _ => return Vec::new(),
+                };
+                match self.resolve_binding(name, condition.span) {
+                    Ok(
+                        binding @ Binding {
+                            datatype: Type::Optional(inner),
+                            ..
+                        },
+                    ) => vec![(
+                        name,
+                        Binding {
+                            datatype: (**inner).clone(),
+                            narrowed: true,
+                            ..binding.clone()
+                        },
+                    )],
+                    _ => Vec::new(),
+                }
+            }
+            // both sides hold when `&&` is true, and neither does when `||` is false
+            BinaryOperation {
+                left,
+                operator: operator @ (BinOp::And | BinOp::Or),
+                right,
+                ..
+            } if (*operator == BinOp::And) == when => {
+                let mut narrowed = self.narrowed(left, when);
+                narrowed.extend(self.narrowed(right, when));
+                narrowed
+            }
+            ExpressionKind::UnaryOperation {
+                operator: UnaryOp::Not,
+                operand,
+                ..
+            } => self.narrowed(operand, !when),
+            _ => Vec::new(),
+        }
+    }
+
+    // runs `check` in a new scope holding the narrowed variables
+    fn narrowed_scope<T>(
+        &mut self,
+        narrowed: Vec<(symbol::Symbol, Binding)>,
+        check: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        self.enter_scope();
+        if let Some(scope) = self.type_envs.last_mut() {
+            scope.extend(narrowed);
+        }
+        let result = check(self);
+        self.exit_scope();
+        result
     }
 
-    fn resolve_variable(&mut self, name: &str) -> Type {
-        for scope in self.type_envs.iter_mut().rev() {
-            if let Some(type_name) = scope.get(name) {
-                return type_name.clone();
+    // after a possibly-none value is assigned to a variable, checks made before no longer hold
+    fn forget_narrowing(&mut self, name: symbol::Symbol) {
+        for scope in &mut self.type_envs {
+            if scope.get(&name).is_some_and(|binding| binding.narrowed) {
+                scope.remove(&name);
             }
         }
-        panic!("no variable {} in existing scopes", name);
     }
 
-    fn declare_function(&mut self, name: String, parameters: Vec<Type>, return_type: Type) {
-        self.function_envs
-            .last_mut()
-            .expect(format!("error declaring function {}", name).as_str())
-            .insert(name, (parameters, return_type));
+    // a function declared in the program can assign any variable in scope where it was
+    // declared, so after a call to it, checks made before no longer hold for those; builtins
+    // and natives can't assign variables
+    fn forget_narrowing_at_call(&mut self, function: symbol::Symbol) {
+        let Some(declared) = self
+            .type_envs
+            .iter()
+            .rposition(|scope| scope.contains_key(&function))
+        else {
+            return;
+        };
+        if self.type_envs[declared][&function].definition.is_none() {
+            return;
+        }
+        for narrowed in (declared + 1)..self.type_envs.len() {
+            let names: Vec<_> = self.type_envs[narrowed]
+                .iter()
+                .filter(|(_, binding)| binding.narrowed)
+                .map(|(name, _)| *name)
+                .collect();
+            for name in names {
+                let visible = self.type_envs[..narrowed]
+                    .iter()
+                    .rposition(|scope| scope.get(&name).is_some_and(|binding| !binding.narrowed))
+                    .is_some_and(|scope| scope <= declared);
+                if visible {
+                    self.type_envs[narrowed].remove(&name);
+                }
+            }
+        }
+        // narrowed in the scope the function was declared in, or one around it
+        for scope in &mut self.type_envs[..=declared] {
+            scope.retain(|_, binding| !binding.narrowed);
+        }
+    }
+
+    fn record_use(&mut self, name: symbol::Symbol, span: Span, binding: &Binding) {
+        self.symbols.push(Symbol {
+            name: name.to_string(),
+            span,
+            datatype: binding.datatype.clone(),
+            definition: binding.definition,
+        });
+    }
+
+    fn resolve_variable(&mut self, name: symbol::Symbol, span: Span) -> Result<Type, TypeError> {
+        let binding = self.resolve_binding(name, span)?.clone();
+        self.record_use(name, span, &binding);
+        self.check_determined(name, span)?;
+        Ok(binding.datatype)
+    }
+
+    fn check_determined(&self, name: symbol::Symbol, span: Span) -> Result<(), TypeError> {
+        if self.is_one_of(&self.undetermined, name, span) {
+            return Err(TypeError::new(
+                TypeErrorKind::UndeterminedType(name.to_string()),
+                span,
+            ));
+        }
+        Ok(())
+    }
+
+    // functions are variables of a function type, so they can be passed around like values
+    fn declare_function(&mut self, name: symbol::Symbol, parameters: Vec<Type>, return_type: Type) {
+        self.declare_variable(name, Type::Function(parameters, Box::new(return_type)));
+    }
+
+    // a generic type with every type parameter of a generic function given a name of its own,
+    // so the ones of different uses, or of the function a generic value is passed to, are told
+    // apart; the type parameters of the function being checked are left as they are
+    fn instantiate(&mut self, datatype: &Type) -> Type {
+        let mut fresh = HashMap::new();
+        for name in parameters_in(datatype) {
+            if is_instantiable(name) && !fresh.contains_key(&name) {
+                self.instances += 1;
+                let base = name.as_str().trim_end_matches(|c| c != '\'');
+                let instance = symbol::Symbol::intern(&format!("{}{}", base, self.instances));
+                fresh.insert(name, Type::Parameter(instance));
+            }
+        }
+        substitute(datatype, &fresh)
+    }
+
+    // signature of a function the embedding program registered with the interpreter
+    pub fn declare_native(&mut self, name: &str, parameters: Vec<Type>, return_type: Type) {
+        self.declare_function(symbol::Symbol::intern(name), parameters, return_type);
+    }
+
+    fn resolve_function(
+        &mut self,
+        name: symbol::Symbol,
+        span: Span,
+    ) -> Result<(Vec<Type>, Type), TypeError> {
+        let resolved = self
+            .resolve_variable(name, name_span(name.as_str(), span))
+            .map(|datatype| self.instantiate(&datatype));
+        match resolved {
+            Ok(Type::Function(parameters, return_type)) => Ok((parameters, *return_type)),
+            Ok(found) => Err(TypeError::new(
+                TypeErrorKind::NotCallable {
+                    name: name.to_string(),
+                    found,
+                },
+                span,
+            )),
+            Err(error) if matches!(error.kind, TypeErrorKind::UndeterminedType(_)) => Err(error),
+            Err(_) => Err(TypeError::new(
+                TypeErrorKind::UnknownFunction(name.to_string()),
+                span,
+            )),
+        }
+    }
+
+    // a call to a hoisted function before a top-level variable it uses is declared
+    fn check_uses_declared(&self, name: symbol::Symbol, span: Span) -> Result<(), TypeError> {
+        let Some((declaration, variables)) = self.hoisted_uses.get(&name) else {
+            return Ok(());
+        };
+        if self
+            .resolve_binding(name, span)
+            .ok()
+            .and_then(|b| b.definition)
+            != Some(*declaration)
+        {
+            return Ok(());
+        }
+        match variables
+            .iter()
+            .find(|variable| !self.type_envs[0].contains_key(variable))
+        {
+            Some(variable) => Err(TypeError::new(
+                TypeErrorKind::UsedBeforeDeclaration {
+                    function: name.to_string(),
+                    variable: variable.to_string(),
+                },
+                span,
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn is_uninferred(&self, name: symbol::Symbol, span: Span) -> bool {
+        self.is_one_of(&self.uninferred, name, span)
+    }
+
+    // whether the function or variable `name` seen from `span` is one of `names`, by
+    // declaration
+    fn is_one_of(
+        &self,
+        names: &[(symbol::Symbol, Span)],
+        name: symbol::Symbol,
+        span: Span,
+    ) -> bool {
+        let Ok(binding) = self.resolve_binding(name, span) else {
+            return false;
+        };
+        names.iter().any(|(declared, declaration)| {
+            *declared == name && binding.definition == Some(*declaration)
+        })
+    }
+
+    // gives a function declared without a return type the one inferred for it, so calls to it
+    // from then on know what they get. The returns see a generic function's own type
+    // parameters, which its signature has in their instantiable form
+    fn infer_return_type(&mut self, name: symbol::Symbol, declaration: Span, return_type: Type) {
+        let binding = self.type_envs.iter_mut().rev().find_map(|scope| {
+            scope
+                .get_mut(&name)
+                .filter(|binding| binding.definition == Some(declaration))
+        });
+        if let Some(Binding {
+            datatype: Type::Function(parameters, declared),
+            ..
+        }) = binding
+        {
+            **declared = signature_type(&return_type, &own_type_params(parameters));
+        }
+        self.uninferred
+            .retain(|uninferred| *uninferred != (name, declaration));
+    }
+
+    fn resolve_enum(
+        &self,
+        name: symbol::Symbol,
+        span: Span,
+    ) -> Result<&[symbol::Symbol], TypeError> {
+        self.enums
+            .get(&name)
+            .map(Vec::as_slice)
+            .ok_or_else(|| TypeError::new(TypeErrorKind::UnknownEnum(name.to_string()), span))
+    }
+
+    // a type written in the source, which may name an enum that was never declared
+    fn check_type(&self, datatype: &Type, span: Span) -> Result<(), TypeError> {
+        match datatype {
+            Type::Enum(name) => self.resolve_enum(*name, span).map(|_| ()),
+            Type::Function(params, return_type) => {
+                for param in params {
+                    self.check_type(param, span)?;
+                }
+                self.check_type(return_type, span)
+            }
+            Type::Optional(inner) => self.check_type(inner, span),
+            // the parser only makes parameters of the generic functions around it
+            _ => Ok(()),
+        }
+    }
+
+    // the first variant of the enum the arms match on that none of them covers
+    fn missing_variant(&self, enum_name: symbol::Symbol, arms: &[MatchArm]) -> Option<String> {
+        let variants = self.enums.get(&enum_name)?;
+        variants
+            .iter()
+            .find(|variant| {
+                !arms.iter().any(|arm| {
+                    matches!(
+                        &arm.pattern,
+                        Pattern::Value(Expression {
+                            kind: ExpressionKind::EnumVariant { variant: v, .. },
+                            ..
+                        }) if v == *variant
+                    )
+                })
+            })
+            .map(|variant| format!("{}.{}", enum_name, variant))
+    }
+
+    // like `is_exhaustive`, but also counts arms listing every variant of an enum
+    fn covers_all(&self, arms: &[MatchArm]) -> bool {
+        let enum_name = arms.iter().find_map(|arm| match &arm.pattern {
+            Pattern::Value(Expression {
+                kind: ExpressionKind::EnumVariant { enum_name, .. },
+                ..
+            }) => Some(enum_name),
+            _ => None,
+        });
+        is_exhaustive(arms)
+            || enum_name.is_some_and(|name| {
+                self.enums.contains_key(name) && self.missing_variant(*name, arms).is_none()
+            })
     }
 
-    fn resolve_function(&mut self, name: &str) -> (Vec<Type>, Type) {
-        for func_scope in self.function_envs.iter_mut().rev() {
-            if let Some((parameters, return_type)) = func_scope.get(name) {
-                return (parameters.clone(), return_type.clone());
+    // whether every path through the statements ends in a return or raise; loops are not
+    // assumed to run
+    fn always_returns(&self, statements: &[Statement]) -> bool {
+        statements.iter().any(|statement| match &statement.kind {
+            StatementKind::Return(_) | StatementKind::Raise(_) => true,
+            StatementKind::Block(body) => self.always_returns(body),
+            StatementKind::Try { body, handler, .. } => {
+                self.always_returns(body) && self.always_returns(handler)
+            }
+            StatementKind::If {
+                then_block,
+                else_block: Some(else_block),
+                ..
+            } => self.always_returns(then_block) && self.always_returns(else_block),
+            StatementKind::Match { arms, .. } => {
+                self.covers_all(arms) && arms.iter().all(|arm| self.always_returns(&arm.body))
             }
+            // the body runs at least once, and returns unless a break leaves the loop first
+            StatementKind::DoWhile { body, .. } => self.always_returns(body) && !breaks_out(body),
+            _ => false,
+        })
+    }
+
+    // type of an expression whose value is used, which a call to a void function doesn't have
+    pub fn infer_datatype(&mut self, exp: &Expression) -> Result<Type, TypeError> {
+        let datatype = self.expression_type(exp)?;
+        match &exp.kind {
+            ExpressionKind::FunctionCall { name, .. } if datatype == Type::Void => Err(
+                TypeError::new(TypeErrorKind::VoidValue(name.to_string()), exp.span),
+            ),
+            _ => Ok(datatype),
         }
-        panic!("no function {} in existing scopes", name);
     }
 
-    fn infer_datatype(&mut self, exp: &Expression) -> Type {
-        match exp {
-            Expression::Number(_) => Type::Number,
-            Expression::Bool(_) => Type::Boolean,
-            Expression::Variable(name) => self.resolve_variable(name),
+    // type of an expression, void for a call to a function returning nothing, as run for its
+    // effects by an expression statement
+    pub fn expression_type(&mut self, exp: &Expression) -> Result<Type, TypeError> {
+        let datatype = match &exp.kind {
+            ExpressionKind::Number(_, _) => Type::Number,
+            ExpressionKind::Float(_, _) => Type::Float,
+            ExpressionKind::String(..) => Type::String,
+            ExpressionKind::Bool(_) => Type::Boolean,
+            ExpressionKind::None => Type::None,
+            ExpressionKind::Variable(name) => {
+                let datatype = self.resolve_variable(*name, exp.span)?;
+                self.instantiate(&datatype)
+            }
+            ExpressionKind::EnumVariant { enum_name, variant } => {
+                if !self.resolve_enum(*enum_name, exp.span)?.contains(variant) {
+                    return Err(TypeError::new(
+                        TypeErrorKind::UnknownVariant {
+                            enum_name: enum_name.to_string(),
+                            variant: variant.to_string(),
+                        },
+                        exp.span,
+                    ));
+                }
+                Type::Enum(*enum_name)
+            }
             BinaryOperation {
                 left,
                 operator,
                 right,
+                ..
             } => {
-                let left_type = self.infer_datatype(left);
-                let right_type = self.infer_datatype(right);
-
-                match operator.as_str() {
-                    "+" | "-" | "*" | "/" => {
-                        if left_type == Type::Number && right_type == Type::Number {
-                            Type::Number
-                        } else {
-                            panic!("operator {} requires number operand", operator);
-                        }
+                let left_type = self.infer_datatype(left)?;
+                // the right operand of `x != none && ...` only runs when x isn't none
+                let narrowed = match operator {
+                    BinOp::And => self.narrowed(left, true),
+                    BinOp::Or => self.narrowed(left, false),
+                    _ => Vec::new(),
+                };
+                let right_type =
+                    self.narrowed_scope(narrowed, |checker| checker.infer_datatype(right))?;
+                binary_type(*operator, left_type, right_type, exp.span)?
+            }
+            ExpressionKind::Range { start, end } => {
+                let start_type = self.infer_datatype(start)?;
+                let end_type = self.infer_datatype(end)?;
+                if start_type != Type::Number || end_type != Type::Number {
+                    return Err(TypeError::new(
+                        TypeErrorKind::InvalidOperands {
+                            operator: "..".to_string(),
+                            left: start_type,
+                            right: end_type,
+                        },
+                        exp.span,
+                    ));
+                }
+                Type::Range
+            }
+            ExpressionKind::Block { statements, value } => {
+                // the value is only there once every statement has run, so none of them can
+                // jump out of the block; loops inside it can still be broken out of
+                let outer_loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+                let outer_block_value = std::mem::replace(&mut self.block_value, true);
+                self.enter_scope();
+                self.check_statements(statements);
+                let value_type = self.infer_datatype(value);
+                self.exit_scope();
+                self.loop_depth = outer_loop_depth;
+                self.block_value = outer_block_value;
+                value_type?
+            }
+            ExpressionKind::UnaryOperation {
+                operator, operand, ..
+            } => {
+                let operand_type = self.infer_datatype(operand)?;
+
+                match (operator, operand_type) {
+                    (UnaryOp::Not, Type::Boolean) => Type::Boolean,
+                    (UnaryOp::Neg, Type::Number) => Type::Number,
+                    (UnaryOp::Neg, Type::Float) => Type::Float,
+                    (_, operand_type) => {
+                        return Err(TypeError::new(
+                            TypeErrorKind::InvalidOperand {
+                                operator: operator.to_string(),
+                                operand: operand_type,
+                            },
+                            exp.span,
+                        ));
                     }
+                }
+            }
+            ExpressionKind::FunctionCall { name, arguments } => {
+                let (parameters, return_type) = self.resolve_function(*name, exp.span)?;
+                if self.is_uninferred(*name, exp.span) {
+                    return Err(TypeError::new(
+                        TypeErrorKind::ReturnTypeNotInferred(name.to_string()),
+                        exp.span,
+                    ));
+                }
+                // code inside functions runs once they are called, which this check covers
+                if self.return_type.is_none() {
+                    self.check_uses_declared(*name, exp.span)?;
+                }
+                if parameters.len() != arguments.len() {
+                    return Err(TypeError::new(
+                        TypeErrorKind::ArityMismatch {
+                            name: name.to_string(),
+                            expected: parameters.len(),
+                            found: arguments.len(),
+                        },
+                        exp.span,
+                    ));
+                }
 
-                    ">" | "<" => {
-                        if left_type == Type::Number && right_type == Type::Number {
-                            Type::Boolean
-                        } else {
-                            panic!("operator {} requires number operand", operator);
-                        }
+                // what the type parameters of a generic function stand for in this call, from
+                // the arguments in order
+                let mut bindings = HashMap::new();
+                for (position, (argument, expected)) in arguments.iter().zip(parameters).enumerate()
+                {
+                    let (found, accepted) = if is_generic(&expected) {
+                        let found = self.infer_datatype(argument)?;
+                        let accepted = unify(&expected, &found, &mut bindings);
+                        (found, accepted)
+                    } else {
+                        let found = self.check_expected(argument, &expected)?;
+                        let accepted = accepts(&expected, &found);
+                        (found, accepted)
+                    };
+                    if !accepted {
+                        return Err(TypeError::new(
+                            TypeErrorKind::ArgumentMismatch {
+                                name: name.to_string(),
+                                position: position + 1,
+                                expected: substitute(&expected, &bindings),
+                                found,
+                            },
+                            argument.span,
+                        ));
                     }
+                }
+                self.forget_narrowing_at_call(*name);
+                substitute(&return_type, &bindings)
+            }
+        };
+        Ok(datatype)
+    }
+
+    // type of an expression whose value is wanted as `expected`. The expected type is passed
+    // down into arithmetic, so when one operand has it and the other doesn't, the other one
+    // is reported rather than the whole expression; what isn't accepted otherwise is left to
+    // the caller, which knows what the value was for
+    fn check_expected(&mut self, exp: &Expression, expected: &Type) -> Result<Type, TypeError> {
+        let BinaryOperation {
+            left,
+            operator,
+            right,
+            ..
+        } = &exp.kind
+        else {
+            return self.infer_datatype(exp);
+        };
+        // string + string is the only operation giving a string, and number op number the
+        // only arithmetic giving a number
+        let pushed = match operator {
+            BinOp::Add => matches!(expected, Type::String | Type::Number),
+            BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Rem => *expected == Type::Number,
+            _ => false,
+        };
+        if !pushed {
+            return self.infer_datatype(exp);
+        }
+
+        let left_type = self.check_expected(left, expected)?;
+        let right_type = self.check_expected(right, expected)?;
+        match (
+            accepts(expected, &left_type),
+            accepts(expected, &right_type),
+        ) {
+            (true, false) => Err(mismatch(right, expected, right_type)),
+            (false, true) => Err(mismatch(left, expected, left_type)),
+            _ => binary_type(*operator, left_type, right_type, exp.span),
+        }
+    }
 
-                    "==" => {
-                        if left_type == right_type {
-                            Type::Boolean
-                        } else {
-                            panic!("operator {} requires same type operand", operator);
+    // an annotated variable is declared even if its value doesn't check, so later uses of
+    // it don't turn into errors of their own
+    fn declare_checked(
+        &mut self,
+        name: symbol::Symbol,
+        expr: &Expression,
+        declared_type: Option<&Type>,
+        constant: bool,
+    ) -> Result<(), TypeError> {
+        if let Some(declared_type) = declared_type {
+            self.check_type(declared_type, expr.span)?;
+        }
+        match self.check_declaration(name, expr, declared_type) {
+            Ok(variable_type) => {
+                self.check_redeclaration(name, &variable_type, expr.span)?;
+                self.declare(name, variable_type, constant);
+                Ok(())
+            }
+            // declared all the same, so uses of it don't report it missing
+            Err(error) => {
+                match declared_type {
+                    Some(declared_type) => self.declare(name, declared_type.clone(), constant),
+                    None => {
+                        self.declare(name, Type::Any, constant);
+                        if let Some(declaration) = self.statement {
+                            self.undetermined.push((name, declaration));
                         }
                     }
-                    _ => panic!("unknown operator {}", operator),
                 }
+                Err(error)
             }
-            Expression::FunctionCall { name, .. } => self.resolve_function(name).1,
         }
     }
 
-    pub fn check(&mut self, stmts: Vec<Statement>) {
-        for stmt in stmts {
-            stmt.accept(self);
+    // declaring a name again in the same scope replaces it, which is only allowed with the
+    // same type; builtins and natives can always be shadowed
+    fn check_redeclaration(
+        &self,
+        name: symbol::Symbol,
+        datatype: &Type,
+        span: Span,
+    ) -> Result<(), TypeError> {
+        let scope = self.type_envs.last();
+        match scope.and_then(|scope| scope.get(&name)) {
+            Some(previous)
+                if previous.definition.is_some()
+                    && !previous.narrowed
+                    && previous.datatype != *datatype
+                    && !self.is_one_of(&self.undetermined, name, span) =>
+            {
+                Err(TypeError::new(
+                    TypeErrorKind::Redeclaration {
+                        name: name.to_string(),
+                        previous: previous.datatype.clone(),
+                        found: datatype.clone(),
+                    },
+                    span,
+                ))
+            }
+            _ => Ok(()),
         }
     }
-}
 
-impl ASTVisitor for TypeChecker {
-    fn visit_declaration(&mut self, name: String, expr: Expression, declared_type: Option<Type>) {
-        let variable_type = self.infer_datatype(&expr);
+    // type of a declared variable, which must match its annotation if it has one
+    fn check_declaration(
+        &mut self,
+        name: symbol::Symbol,
+        expr: &Expression,
+        declared_type: Option<&Type>,
+    ) -> Result<Type, TypeError> {
+        let variable_type = match declared_type {
+            Some(dt) => self.check_expected(expr, dt)?,
+            None => self.infer_datatype(expr)?,
+        };
 
-        if let Some(dt) = declared_type {
-            if variable_type != dt {
-                panic!(
-                    "Type mismatch in declaration of {}: expected {:?}, got {:?}",
-                    name, dt, variable_type
-                );
+        match declared_type {
+            // `let x: string = f();` is about what f returns
+            Some(dt)
+                if !accepts(dt, &variable_type)
+                    && matches!(expr.kind, ExpressionKind::FunctionCall { .. }) =>
+            {
+                Err(mismatch(expr, dt, variable_type))
             }
+            Some(dt) if !accepts(dt, &variable_type) => Err(TypeError::new(
+                TypeErrorKind::DeclarationMismatch {
+                    name: name.to_string(),
+                    expected: dt.clone(),
+                    found: variable_type,
+                },
+                expr.span,
+            )),
+            // `let x: number? = 1;` is optional even though its value isn't
+            Some(dt) => Ok(dt.clone()),
+            None => Ok(variable_type),
         }
-
-        self.declare_variable(name, variable_type);
     }
 
-    fn visit_assignment(&mut self, name: String, expr: Expression) {
-        let var_type = self.resolve_variable(&name);
-        let expr_type = self.infer_datatype(&expr);
-        if var_type != expr_type {
-            panic!("variable {} is not equal to type of expression", name);
+    fn check_condition(
+        &mut self,
+        construct: &str,
+        condition: &Expression,
+    ) -> Result<(), TypeError> {
+        let condition_type = self.infer_datatype(condition)?;
+        if condition_type != Type::Boolean {
+            return Err(TypeError::new(
+                TypeErrorKind::NonBooleanCondition {
+                    construct: construct.to_string(),
+                    found: condition_type,
+                },
+                condition.span,
+            ));
         }
+        Ok(())
     }
 
-    fn visit_print(&mut self, _: Expression) {}
-
-    fn visit_while(&mut self, condition: Expression, body: Vec<Statement>) {
-        // TODO: rethink this condition
-        if Type::Boolean != self.infer_datatype(&condition) {
-            panic!("While condition is not boolean");
+    // type of a loop's variable, which holds the loop's elements, so an annotation has to take
+    // them
+    fn loop_variable(
+        &mut self,
+        variable: symbol::Symbol,
+        annotation: Option<&Type>,
+        element: Type,
+        span: Span,
+        values: Span,
+    ) -> Type {
+        let Some(annotation) = annotation else {
+            return element;
+        };
+        let checked = self.check_type(annotation, span).and_then(|()| {
+            if !accepts(annotation, &element) {
+                // at what the values come from, which the annotation doesn't fit
+                return Err(TypeError::new(
+                    TypeErrorKind::DeclarationMismatch {
+                        name: variable.to_string(),
+                        expected: annotation.clone(),
+                        found: element.clone(),
+                    },
+                    values,
+                ));
+            }
+            Ok(())
+        });
+        let accepted = checked.is_ok();
+        self.report(checked);
+        if accepted {
+            annotation.clone()
+        } else {
+            element
         }
-
-        self.enter_scope();
-        self.check(body);
-        self.exit_scope();
     }
 
-    fn visit_block(&mut self, statements: Vec<Statement>) {
-        self.enter_scope();
-        self.check(statements);
-        self.exit_scope();
+    // checks a function's body in the scope entered for it, with its parameters declared
+    fn check_function_body(
+        &mut self,
+        params: &[(symbol::Symbol, Type)],
+        body: &[Statement],
+        return_type: ReturnType,
+    ) -> ReturnType {
+        for (param, param_type) in params {
+            self.declare_variable(*param, param_type.clone());
+        }
+        // loops around the declaration can't be broken out of from inside the function
+        let outer_loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        let outer_block_value = std::mem::replace(&mut self.block_value, false);
+        let outer_return_type = self.return_type.replace(return_type);
+        self.check_statements(body);
+        self.loop_depth = outer_loop_depth;
+        self.block_value = outer_block_value;
+        let return_type = std::mem::replace(&mut self.return_type, outer_return_type);
+        return_type.unwrap_or(ReturnType::Declared(Type::Void))
     }
 
-    fn visit_function_declaration(
+    // declares and checks a function without a return type, whose returns say what it is. It
+    // can call itself once a return has, so it is declared in its own scope, for the body only,
+    // until its type is known. A top-level function hoisted with a type inferred up front
+    // keeps its declaration
+    fn infer_function(
         &mut self,
-        name: String,
-        params: Vec<(String, Type)>,
-        return_type: Type,
-        body: Vec<Statement>,
-    ) {
-        self.declare_function(
-            name,
-            params.iter().map(|(name, t)| t.clone()).collect(),
-            return_type,
-        );
+        name: symbol::Symbol,
+        parameters: Vec<Type>,
+        params: &[(symbol::Symbol, Type)],
+        body: &[Statement],
+        span: Span,
+    ) -> Result<(), TypeError> {
+        let hoisted = self
+            .type_envs
+            .last()
+            .and_then(|scope| scope.get(&name))
+            .is_some_and(|binding| binding.definition == Some(span));
+
         self.enter_scope();
-        // adding params to scope
-        for param in params {
-            self.declare_variable(param.0, param.1);
+        if !hoisted {
+            self.declare_function(name, parameters.clone(), Type::Void);
+            self.uninferred.push((name, span));
         }
-        self.check(body);
+        let inferred = ReturnType::Inferred {
+            function: name,
+            declaration: span,
+            found: None,
+            unknown: false,
+        };
+        let (found, unknown) = match self.check_function_body(params, body, inferred) {
+            ReturnType::Inferred { found, unknown, .. } => (found, unknown),
+            ReturnType::Declared(_) => (None, false),
+        };
         self.exit_scope();
-    }
 
-    fn visit_if(
-        &mut self,
-        condition: Expression,
-        body: Vec<Statement>,
-        else_branch: Option<Vec<Statement>>,
-    ) {
-        if self.infer_datatype(&condition) != Type::Boolean {
-            panic!("If condition is not boolean");
+        // a return that didn't check has been reported, so calls get what the others agree on;
+        // if none of them checked, calls can't be told what they get, and the error they would
+        // report is the one already reported here
+        if unknown && found.is_none() {
+            if !self.uninferred.contains(&(name, span)) {
+                self.uninferred.push((name, span));
+            }
+            self.failed.push((name, span));
+        } else {
+            self.infer_return_type(name, span, found.clone().unwrap_or(Type::Void));
         }
-        self.enter_scope();
-        self.check(body);
-        self.exit_scope();
-        if let Some(else_branch) = else_branch {
-            self.enter_scope();
-            self.check(else_branch);
-            self.exit_scope();
+        let return_type = found.unwrap_or(Type::Void);
+        if !hoisted {
+            let signature_return = signature_type(&return_type, &own_type_params(&parameters));
+            let function_type =
+                Type::Function(parameters.clone(), Box::new(signature_return.clone()));
+            self.check_redeclaration(name, &function_type, span)?;
+            self.declare_function(name, parameters, signature_return);
+        }
+
+        if return_type != Type::Void && !self.always_returns(body) {
+            return Err(TypeError::new(
+                TypeErrorKind::MissingReturn {
+                    name: name.to_string(),
+                    expected: return_type,
+                },
+                span,
+            ));
         }
+        Ok(())
     }
 
-    fn visit_expression(&mut self, expr: Expression) {
-        self.infer_datatype(&expr);
+    fn check_loop_body(&mut self, body: &[Statement], narrowed: Vec<(symbol::Symbol, Binding)>) {
+        self.loop_depth += 1;
+        self.narrowed_scope(narrowed, |checker| checker.check_statements(body));
+        self.loop_depth -= 1;
     }
 
-    fn visit_return(&mut self, expr: Expression) {
-        // TODO: add declared return type lookup
-        self.infer_datatype(&expr);
+    fn check_inside_loop(&self, keyword: &str, span: Span) -> Result<(), TypeError> {
+        if self.loop_depth == 0 {
+            let kind = if self.block_value {
+                TypeErrorKind::JumpOutOfBlock(keyword.to_string())
+            } else {
+                TypeErrorKind::OutsideLoop(keyword.to_string())
+            };
+            return Err(TypeError::new(kind, span));
+        }
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::parser::Expression::{Number, Variable};
-    use crate::parser::{Expression, Statement, Type};
+    // checks a whole program, returning every error in it
+    pub fn check(&mut self, stmts: &[Statement]) -> Result<(), Vec<TypeError>> {
+        self.diagnostics.clear();
+        self.symbols.clear();
+        self.hoist_functions(stmts);
+        self.check_statements(stmts);
+        if self.diagnostics.is_empty() {
+            Ok(())
+        } else {
+            Err(self.diagnostics.clone())
+        }
+    }
 
-    fn number_expr(n: i32) -> Expression {
-        Expression::Number(n)
+    // errors found by the last `check`, in source order
+    pub fn diagnostics(&self) -> &[TypeError] {
+        &self.diagnostics
     }
 
-    fn bool_expr(b: bool) -> Expression {
-        Expression::Bool(b)
+    // type of a top-level variable or function, e.g. to see what `main` returns once its return
+    // type is inferred
+    pub fn global_type(&self, name: &str) -> Option<&Type> {
+        self.type_envs[0]
+            .get(&symbol::Symbol::intern(name))
+            .map(|binding| &binding.datatype)
     }
 
-    fn var(name: &str) -> Expression {
-        Expression::Variable(name.to_string())
+    // every variable and function use found by the last `check`, in source order
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
     }
 
-    fn binop(left: Expression, op: &str, right: Expression) -> Expression {
-        Expression::BinaryOperation {
-            left: Box::new(left),
-            operator: op.to_string(),
-            right: Box::new(right),
+    // declares the top-level functions up front, so they can be called before their
+    // declaration, e.g. by each other. A name declared by a variable at the top level too is
+    // left to be declared in order, and so is a function that would clash with an earlier
+    // declaration, which its declaration then reports. A second top-level function with the
+    // same name is reported where it is declared, the first one being hoisted. The return
+    // types left out are inferred
+    // by checking the functions on a copy of the checker, again while that tells more of them,
+    // as they may call each other; the ones that still can't be told are inferred where they
+    // are declared, and can't be called before
+    fn hoist_functions(&mut self, stmts: &[Statement]) {
+        let variables: Vec<symbol::Symbol> = stmts
+            .iter()
+            .filter_map(|stmt| match &stmt.kind {
+                StatementKind::Declaration(name, ..) | StatementKind::Const(name, ..) => {
+                    Some(*name)
+                }
+                _ => None,
+            })
+            .collect();
+        let mut functions = Vec::new();
+        self.duplicates.clear();
+        // names each hoisted function uses without declaring them
+        let mut free = HashMap::new();
+        for stmt in stmts {
+            let StatementKind::FunctionDeclaration {
+                name,
+                type_params,
+                params,
+                return_type,
+                body,
+            } = &stmt.kind
+            else {
+                continue;
+            };
+            let parameters = signature(type_params, params);
+            let return_type = &signature_type(return_type, type_params);
+            let function_type = Type::Function(parameters.clone(), Box::new(return_type.clone()));
+            if variables.contains(name) {
+                continue;
+            }
+            if functions.contains(name) {
+                self.duplicates.push(stmt.span);
+                continue;
+            }
+            functions.push(*name);
+            if self
+                .check_redeclaration(*name, &function_type, stmt.span)
+                .is_err()
+            {
+                continue;
+            }
+            self.statement = Some(stmt.span);
+            self.declare_function(*name, parameters, return_type.clone());
+            if *return_type == Type::Void && returns_a_value(body) {
+                self.uninferred.push((*name, stmt.span));
+            }
+            free.insert(*name, (stmt.span, free_names(params, body)));
         }
-    }
+        self.hoisted_uses = free
+            .keys()
+            .map(|&name| {
+                (
+                    name,
+                    (free[&name].0, used_variables(name, &free, &variables)),
+                )
+            })
+            .collect();
 
-    #[test]
-    fn test_variable_declaration_and_assignment() {
-        let mut checker = TypeChecker::new();
-        let stmts = vec![
-            Statement::Declaration("x".into(), number_expr(10),None),
-            Statement::Assignment("x".into(), number_expr(42)),
-        ];
-        checker.check(stmts);
+        let mut inferring = true;
+        while inferring {
+            inferring = false;
+            for stmt in stmts {
+                let StatementKind::FunctionDeclaration { name, .. } = &stmt.kind else {
+                    continue;
+                };
+                if !self.uninferred.contains(&(*name, stmt.span)) {
+                    continue;
+                }
+                let mut probe = self.clone();
+                probe.statement = Some(stmt.span);
+                let _ = stmt.accept(&mut probe);
+                if probe.uninferred.contains(&(*name, stmt.span)) {
+                    continue;
+                }
+                if let Ok(Binding {
+                    datatype: Type::Function(_, return_type),
+                    ..
+                }) = probe.resolve_binding(*name, stmt.span)
+                {
+                    self.infer_return_type(*name, stmt.span, (**return_type).clone());
+                    inferring = true;
+                }
+            }
+        }
+        self.statement = None;
     }
 
-    #[test]
-    #[should_panic(expected = "variable x is not equal to type of expression")]
-    fn test_type_mismatch_assignment() {
-        let mut checker = TypeChecker::new();
-        let stmts = vec![
-            Statement::Declaration("x".into(), number_expr(10), None),
-            Statement::Assignment("x".into(), bool_expr(true)),
-        ];
-        checker.check(stmts);
+    fn check_statements(&mut self, stmts: &[Statement]) {
+        let outer = self.statement;
+        for stmt in stmts {
+            self.statement = Some(stmt.span);
+            let result = stmt.accept(self);
+            self.report(result);
+        }
+        self.statement = outer;
     }
 
-    #[test]
-    fn test_binary_operation_number_addition() {
-        let mut checker = TypeChecker::new();
-        let expr = binop(number_expr(1), "+", number_expr(2));
-        let inferred = checker.infer_datatype(&expr);
-        assert_eq!(inferred, Type::Number);
+    fn report(&mut self, result: Result<(), TypeError>) {
+        let Err(error) = result else {
+            return;
+        };
+        // a call to a function whose returns all failed to check follows from those failures,
+        // which were reported in its body
+        if let TypeErrorKind::ReturnTypeNotInferred(name) = &error.kind
+            && self.is_one_of(&self.failed, symbol::Symbol::intern(name), error.span)
+        {
+            return;
+        }
+        // as does a use of a variable whose initializer didn't check
+        if let TypeErrorKind::UndeterminedType(_) = &error.kind {
+            return;
+        }
+        self.diagnostics.push(error);
     }
+}
 
-    #[test]
-    #[should_panic(expected = "While condition is not boolean")]
-    fn test_while_condition_type_check() {
-        let mut checker = TypeChecker::new();
-        let stmts = vec![
-            Statement::While {
-                condition: number_expr(1),
-                body: vec![],
-            }, // wrong type
-        ];
-        checker.check(stmts);
-    }
+// a top-level statement checked by an IncrementalChecker, with what checking it found
+#[derive(Clone)]
+struct CheckedItem {
+    // where the statement starts and a hash of its source; the same key means the same AST
+    key: (usize, u64),
+    diagnostics: Vec<TypeError>,
+    symbols: Vec<Symbol>,
+}
 
-    #[test]
-    fn test_valid_while_condition() {
-        let mut checker = TypeChecker::new();
-        let stmts = vec![
-            Statement::Declaration("cond".into(), bool_expr(true) , None),
-            Statement::While {
-                condition: var("cond"),
-                body: vec![
-                    Statement::Declaration("x".into(), number_expr(5), None),
-                    Statement::Assignment("x".into(), number_expr(10)),
-                ],
-            },
-        ];
-        checker.check(stmts); // should not panic
+// typechecks new versions of the same file, as an editor sends them on every keystroke. What
+// checking every top-level statement found is kept, so a recheck reports again from the first
+// statement that changed instead of from the top; everything after it is checked again, as it
+// may depend on what changed. The checker's state is kept once, from before the statement the
+// last edit was in: edits keep coming in the same place, and every one after the first starts
+// right there
+pub struct IncrementalChecker {
+    // the checker every version starts out with, e.g. with natives declared
+    base: TypeChecker,
+    // hash of what every statement can see regardless of order: the prelude and the hoisted
+    // function signatures. When it changes, nothing checked before can be reused
+    context: u64,
+    // hash of the whole source last checked, which comes back unchanged e.g. on save
+    source: u64,
+    items: Vec<CheckedItem>,
+    // how many statements the checker had checked when it was kept, and its state then
+    checkpoint: Option<(usize, TypeChecker)>,
+    // top-level statements checked by the last `check`
+    rechecked: usize,
+}
+
+impl IncrementalChecker {
+    pub fn new(base: TypeChecker) -> IncrementalChecker {
+        IncrementalChecker {
+            base,
+            context: 0,
+            source: 0,
+            items: Vec::new(),
+            checkpoint: None,
+            rechecked: 0,
+        }
     }
 
-    #[test]
-    fn test_scope_within_while_block() {
-        let mut checker = TypeChecker::new();
-        let stmts = vec![
-            Statement::Declaration("x".to_string(), Number(0), None),
-            Statement::While {
-                condition: bool_expr(true),
-                body: vec![Statement::Assignment("x".to_string(), Number(10))],
+    // checks `program`, parsed from `source`, like TypeChecker::check does, after `prelude`:
+    // statements from other files, such as imported functions, whose own errors aren't
+    // reported
+    pub fn check(
+        &mut self,
+        source: &str,
+        prelude: &[Statement],
+        program: &[Statement],
+    ) -> Result<(), Vec<TypeError>> {
+        let context = hash((format!("{:?}", prelude), signatures(source, program)));
+        let whole = hash(source);
+        if context == self.context && whole == self.source && self.items.len() == program.len() {
+            self.rechecked = 0;
+            return self.result();
+        }
+        self.source = whole;
+        if context != self.context {
+            self.context = context;
+            self.items.clear();
+            self.checkpoint = None;
+        }
+
+        let keys: Vec<(usize, u64)> = program
+            .iter()
+            .map(|stmt| {
+                let text = source
+                    .get(stmt.span.start..stmt.span.end)
+                    .unwrap_or_default();
+                (stmt.span.start, hash(text))
+            })
+            .collect();
+        let unchanged = self
+            .items
+            .iter()
+            .zip(&keys)
+            .take_while(|(item, key)| item.key == **key)
+            .count();
+        self.items.truncate(unchanged);
+        self.rechecked = program.len() - unchanged;
+
+        // statements between the checkpoint and the edit are checked again only to get the
+        // checker's state after them; what they found is known already
+        let mut checker = match self.checkpoint.take() {
+            Some((checked, checker)) if checked <= unchanged => {
+                let mut checker = checker;
+                checker.check_statements(&program[checked..unchanged]);
+                checker
+            }
+            _ => {
+                let mut checker = self.base.clone();
+                let _ = checker.check(prelude);
+                checker.hoist_functions(program);
+                checker.check_statements(&program[..unchanged]);
+                checker
+            }
+        };
+        checker.diagnostics.clear();
+        checker.symbols.clear();
+        self.checkpoint = Some((unchanged, checker.clone()));
+
+        for (stmt, key) in program.iter().zip(keys).skip(unchanged) {
+            checker.check_statements(std::slice::from_ref(stmt));
+            self.items.push(CheckedItem {
+                key,
+                diagnostics: std::mem::take(&mut checker.diagnostics),
+                symbols: std::mem::take(&mut checker.symbols),
+            });
+        }
+        self.result()
+    }
+
+    fn result(&self) -> Result<(), Vec<TypeError>> {
+        let diagnostics: Vec<TypeError> = self
+            .items
+            .iter()
+            .flat_map(|item| item.diagnostics.iter().cloned())
+            .collect();
+        if diagnostics.is_empty() {
+            Ok(())
+        } else {
+            Err(diagnostics)
+        }
+    }
+
+    // uses of names found in the program by the last `check`
+    pub fn symbols(&self) -> Vec<Symbol> {
+        self.items
+            .iter()
+            .flat_map(|item| item.symbols.iter().cloned())
+            .collect()
+    }
+
+    // how many top-level statements the last `check` had to check again
+    pub fn rechecked(&self) -> usize {
+        self.rechecked
+    }
+}
+
+// what hoisting depends on: the signatures of the top-level functions, with the source of the
+// ones whose return type is inferred from their body, and the names of the other top-level
+// declarations
+fn signatures(source: &str, program: &[Statement]) -> String {
+    let mut signatures = String::new();
+    for stmt in program {
+        match &stmt.kind {
+            StatementKind::FunctionDeclaration {
+                name,
+                type_params,
+                params,
+                return_type,
+                body,
+            } => {
+                signatures += &format!("{:?}{:?}{:?}{:?};", name, type_params, params, return_type);
+                // which top-level variables a call before the declaration needs
+                signatures += &format!("{:?};", free_names(params, body));
+                if *return_type == Type::Void && returns_a_value(body) {
+                    signatures += source
+                        .get(stmt.span.start..stmt.span.end)
+                        .unwrap_or_default();
+                }
+            }
+            StatementKind::Declaration(name, ..) | StatementKind::Const(name, ..) => {
+                signatures += &format!("{:?};", name)
+            }
+            _ => {}
+        }
+    }
+    signatures
+}
+
+fn hash(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+// result type of mixing two numeric operands: number op number stays a number,
+// anything involving a float is promoted to float
+fn numeric_type(left: &Type, right: &Type) -> Option<Type> {
+    match (left, right) {
+        (Type::Number, Type::Number) => Some(Type::Number),
+        (Type::Number | Type::Float, Type::Number | Type::Float) => Some(Type::Float),
+        _ => None,
+    }
+}
+
+// whether a value of type `found` can be used where `expected` is wanted; optional types also
+// take none and values of their inner type, and builtins taking any type take anything but void
+fn accepts(expected: &Type, found: &Type) -> bool {
+    accepts_as_is(expected, found)
+        // a generic function fits where one of its instances is wanted
+        || (is_generic(found) && unify(expected, found, &mut HashMap::new()))
+}
+
+// `accepts` without binding type parameters
+fn accepts_as_is(expected: &Type, found: &Type) -> bool {
+    expected == found
+        || matches!(expected, Type::Optional(inner) if **inner == *found || *found == Type::None)
+        || (*expected == Type::Any && *found != Type::Void)
+}
+
+// type of the values for ... in gives when going over a value of type `iterable`, if it has
+// any; the interpreter's Value::elements gives the values themselves
+fn element_type(iterable: &Type) -> Option<Type> {
+    match iterable {
+        Type::Range => Some(Type::Number),
+        Type::String => Some(Type::String),
+        _ => None,
+    }
+}
+
+// whether a break among the statements leaves the loop they are the body of, rather than one
+// inside it
+fn breaks_out(statements: &[Statement]) -> bool {
+    statements.iter().any(|statement| match &statement.kind {
+        StatementKind::Break => true,
+        StatementKind::Block(body) => breaks_out(body),
+        StatementKind::Try { body, handler, .. } => breaks_out(body) || breaks_out(handler),
+        StatementKind::If {
+            then_block,
+            else_block,
+            ..
+        } => breaks_out(then_block) || else_block.as_deref().is_some_and(breaks_out),
+        StatementKind::Match { arms, .. } => arms.iter().any(|arm| breaks_out(&arm.body)),
+        _ => false,
+    })
+}
+
+// whether a type mentions the type parameter
+fn mentions(datatype: &Type, type_param: symbol::Symbol) -> bool {
+    match datatype {
+        Type::Parameter(name) => *name == type_param,
+        Type::Optional(inner) => mentions(inner, type_param),
+        Type::Function(params, return_type) => {
+            params.iter().any(|param| mentions(param, type_param))
+                || mentions(return_type, type_param)
+        }
+        _ => false,
+    }
+}
+
+// whether a type has type parameters a use of it can bind; those of the function being
+// checked stand for one type it doesn't know and take nothing else
+fn is_generic(datatype: &Type) -> bool {
+    match datatype {
+        Type::Parameter(name) => is_instantiable(*name),
+        Type::Optional(inner) => is_generic(inner),
+        Type::Function(params, return_type) => {
+            params.iter().any(is_generic) || is_generic(return_type)
+        }
+        _ => false,
+    }
+}
+
+// the type taking values of both types, if any: the wider of the two, or an optional type
+// when one of them is none
+fn join(earlier: &Type, found: &Type) -> Option<Type> {
+    if accepts(earlier, found) {
+        Some(earlier.clone())
+    } else if accepts(found, earlier) {
+        Some(found.clone())
+    } else if *earlier == Type::None {
+        Some(Type::Optional(Box::new(found.clone())))
+    } else if *found == Type::None {
+        Some(Type::Optional(Box::new(earlier.clone())))
+    } else {
+        None
+    }
+}
+
+// the top-level `variables` a call to the hoisted function `function` can use: those it uses
+// itself and those of the hoisted functions it calls, in turn
+fn used_variables(
+    function: symbol::Symbol,
+    free: &HashMap<symbol::Symbol, (Span, Vec<symbol::Symbol>)>,
+    variables: &[symbol::Symbol],
+) -> Vec<symbol::Symbol> {
+    let mut used = Vec::new();
+    let mut seen = vec![function];
+    let mut pending = vec![function];
+    while let Some(function) = pending.pop() {
+        for name in &free[&function].1 {
+            if variables.contains(name) {
+                if !used.contains(name) {
+                    used.push(*name);
+                }
+            } else if free.contains_key(name) && !seen.contains(name) {
+                seen.push(*name);
+                pending.push(*name);
+            }
+        }
+    }
+    used
+}
+
+// the names a function's body reads, assigns or calls without declaring them itself, in the
+// order they come up
+fn free_names(params: &[(symbol::Symbol, Type)], body: &[Statement]) -> Vec<symbol::Symbol> {
+    let mut names = FreeNames {
+        bound: params.iter().map(|(param, _)| *param).collect(),
+        free: Vec::new(),
+    };
+    names.block(body);
+    names.free
+}
+
+struct FreeNames {
+    // names declared in the scopes around the code being walked
+    bound: Vec<symbol::Symbol>,
+    free: Vec<symbol::Symbol>,
+}
+
+impl FreeNames {
+    fn name(&mut self, name: symbol::Symbol) {
+        if !self.bound.contains(&name) && !self.free.contains(&name) {
+            self.free.push(name);
+        }
+    }
+
+    fn block(&mut self, statements: &[Statement]) {
+        let outer = self.bound.len();
+        for statement in statements {
+            self.statement(statement);
+        }
+        self.bound.truncate(outer);
+    }
+
+    // `body` in a scope of its own that starts out with `names`
+    fn scoped(&mut self, names: impl IntoIterator<Item = symbol::Symbol>, body: &[Statement]) {
+        let outer = self.bound.len();
+        self.bound.extend(names);
+        self.block(body);
+        self.bound.truncate(outer);
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match &statement.kind {
+            StatementKind::Declaration(name, value, _) | StatementKind::Const(name, value, _) => {
+                self.expression(value);
+                self.bound.push(*name);
+            }
+            StatementKind::Assignment(name, value) => {
+                self.name(*name);
+                self.expression(value);
+            }
+            StatementKind::Print { value, .. }
+            | StatementKind::Expression(value)
+            | StatementKind::Assert(value)
+            | StatementKind::Return(value)
+            | StatementKind::Raise(value) => self.expression(value),
+            StatementKind::AssertEq(left, right) => {
+                self.expression(left);
+                self.expression(right);
+            }
+            StatementKind::While { condition, body }
+            | StatementKind::DoWhile { body, condition } => {
+                self.expression(condition);
+                self.block(body);
+            }
+            StatementKind::For {
+                variable,
+                start,
+                end,
+                body,
+                ..
+            } => {
+                self.expression(start);
+                self.expression(end);
+                self.scoped([*variable], body);
+            }
+            StatementKind::ForIn {
+                variable,
+                iterable,
+                body,
+                ..
+            } => {
+                self.expression(iterable);
+                self.scoped([*variable], body);
+            }
+            StatementKind::Block(body) => self.block(body),
+            StatementKind::FunctionDeclaration {
+                name, params, body, ..
+            } => {
+                self.bound.push(*name);
+                self.scoped(params.iter().map(|(param, _)| *param), body);
+            }
+            StatementKind::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                self.expression(condition);
+                self.block(then_block);
+                if let Some(else_block) = else_block {
+                    self.block(else_block);
+                }
+            }
+            StatementKind::Match { subject, arms } => {
+                self.expression(subject);
+                for arm in arms {
+                    if let Pattern::Value(value) = &arm.pattern {
+                        self.expression(value);
+                    }
+                    self.block(&arm.body);
+                }
+            }
+            StatementKind::Try {
+                body,
+                error,
+                handler,
+            } => {
+                self.block(body);
+                self.scoped([*error], handler);
+            }
+            StatementKind::Enum { .. }
+            | StatementKind::Import(_)
+            | StatementKind::Break
+            | StatementKind::Continue => {}
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match &expression.kind {
+            ExpressionKind::Variable(name) => self.name(*name),
+            ExpressionKind::FunctionCall { name, arguments } => {
+                self.name(*name);
+                for argument in arguments {
+                    self.expression(argument);
+                }
+            }
+            ExpressionKind::BinaryOperation { left, right, .. }
+            | ExpressionKind::Range {
+                start: left,
+                end: right,
+            } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            ExpressionKind::UnaryOperation { operand, .. } => self.expression(operand),
+            ExpressionKind::Block { statements, value } => {
+                let outer = self.bound.len();
+                for statement in statements {
+                    self.statement(statement);
+                }
+                self.expression(value);
+                self.bound.truncate(outer);
+            }
+            ExpressionKind::Number(..)
+            | ExpressionKind::Float(..)
+            | ExpressionKind::String(..)
+            | ExpressionKind::Bool(_)
+            | ExpressionKind::None
+            | ExpressionKind::EnumVariant { .. } => {}
+        }
+    }
+}
+
+// whether a function's body returns anything, not counting the functions declared in it
+fn returns_a_value(statements: &[Statement]) -> bool {
+    statements.iter().any(|statement| match &statement.kind {
+        StatementKind::Return(_) => true,
+        StatementKind::While { body, .. }
+        | StatementKind::DoWhile { body, .. }
+        | StatementKind::For { body, .. }
+        | StatementKind::ForIn { body, .. }
+        | StatementKind::Block(body) => returns_a_value(body),
+        StatementKind::If {
+            then_block,
+            else_block,
+            ..
+        } => returns_a_value(then_block) || else_block.as_deref().is_some_and(returns_a_value),
+        StatementKind::Match { arms, .. } => arms.iter().any(|arm| returns_a_value(&arm.body)),
+        StatementKind::Try { body, handler, .. } => {
+            returns_a_value(body) || returns_a_value(handler)
+        }
+        _ => false,
+    })
+}
+
+// whether an argument of type `found` fits a generic parameter of type `expected`, binding the
+// type parameters it meets for the first time to what they match. A parameter bound already
+// has to take what it meets again, so `max<T>(a: T, b: T)` takes two numbers but not a number
+// and a string; meeting a value and none makes it optional. A generic function passed as the
+// argument has its own parameters bound the same way, to what the expected type has there
+fn unify(expected: &Type, found: &Type, bindings: &mut HashMap<symbol::Symbol, Type>) -> bool {
+    match (expected, found) {
+        (Type::Parameter(name), Type::Parameter(other)) if name == other => true,
+        (Type::Parameter(name), found) if is_instantiable(*name) => {
+            match bindings.get(name).cloned() {
+                None => bind(*name, found, bindings),
+                // bound to a parameter of the argument, which takes what this one meets
+                Some(bound @ Type::Parameter(_)) if is_generic(&bound) => {
+                    unify(&bound, found, bindings)
+                }
+                Some(bound) if is_generic(found) => unify(found, &bound, bindings),
+                Some(bound) => match join(&bound, found) {
+                    Some(joined) => {
+                        bindings.insert(*name, joined);
+                        true
+                    }
+                    None => false,
+                },
+            }
+        }
+        (expected, Type::Parameter(name)) if is_instantiable(*name) => {
+            match bindings.get(name).cloned() {
+                None => bind(*name, expected, bindings),
+                Some(bound) => unify(expected, &bound, bindings),
+            }
+        }
+        (Type::Optional(inner), Type::Optional(found)) => unify(inner, found, bindings),
+        (Type::Optional(_), Type::None) => true,
+        (Type::Optional(inner), found) => unify(inner, found, bindings),
+        (Type::Function(params, return_type), Type::Function(found_params, found_return)) => {
+            params.len() == found_params.len()
+                && params
+                    .iter()
+                    .zip(found_params)
+                    .all(|(param, found)| unify(param, found, bindings))
+                && unify(return_type, found_return, bindings)
+        }
+        (expected, found) => accepts_as_is(expected, found),
+    }
+}
+
+// binds a type parameter met for the first time, unless that would make it stand for a type
+// containing itself
+fn bind(name: symbol::Symbol, to: &Type, bindings: &mut HashMap<symbol::Symbol, Type>) -> bool {
+    let to = substitute(to, bindings);
+    if to == Type::Parameter(name) {
+        return true;
+    }
+    if to == Type::Void || mentions(&to, name) {
+        return false;
+    }
+    bindings.insert(name, to);
+    true
+}
+
+// a generic type with the type parameters replaced by what they are bound to
+fn substitute(datatype: &Type, bindings: &HashMap<symbol::Symbol, Type>) -> Type {
+    match datatype {
+        Type::Parameter(name) => match bindings.get(name) {
+            Some(bound) => substitute(bound, bindings),
+            None => datatype.clone(),
+        },
+        Type::Optional(inner) => match substitute(inner, bindings) {
+            // a parameter bound to an optional type stays optional once
+            inner @ Type::Optional(_) => inner,
+            inner => Type::Optional(Box::new(inner)),
+        },
+        Type::Function(params, return_type) => Type::Function(
+            params
+                .iter()
+                .map(|param| substitute(param, bindings))
+                .collect(),
+            Box::new(substitute(return_type, bindings)),
+        ),
+        _ => datatype.clone(),
+    }
+}
+
+// the type parameters a type mentions, in order
+fn parameters_in(datatype: &Type) -> Vec<symbol::Symbol> {
+    match datatype {
+        Type::Parameter(name) => vec![*name],
+        Type::Optional(inner) => parameters_in(inner),
+        Type::Function(params, return_type) => params
+            .iter()
+            .chain([&**return_type])
+            .flat_map(parameters_in)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// The type parameters of a generic function, as its signature has them: `T` becomes `T'`, and
+// every use of the function instantiates it with names like `T'1`. Names can't have a `'`, so
+// these are told apart from the type parameters of the function being checked, which its body
+// sees as they are written
+fn is_instantiable(name: symbol::Symbol) -> bool {
+    name.as_str().contains('\'')
+}
+
+fn signature_type(datatype: &Type, type_params: &[symbol::Symbol]) -> Type {
+    let instantiable = type_params
+        .iter()
+        .map(|name| {
+            let signature_name = symbol::Symbol::intern(&format!("{}'", name));
+            (*name, Type::Parameter(signature_name))
+        })
+        .collect();
+    substitute(datatype, &instantiable)
+}
+
+// the parameter types of a function as its signature has them
+fn signature(type_params: &[symbol::Symbol], params: &[(symbol::Symbol, Type)]) -> Vec<Type> {
+    params
+        .iter()
+        .map(|(_, param_type)| signature_type(param_type, type_params))
+        .collect()
+}
+
+// the type parameters of a generic function, from the parameter types of its signature, which
+// mention all of them
+fn own_type_params(parameters: &[Type]) -> Vec<symbol::Symbol> {
+    parameters
+        .iter()
+        .flat_map(parameters_in)
+        .filter(|name| is_instantiable(*name))
+        .map(|name| {
+            symbol::Symbol::intern(
+                name.as_str()
+                    .trim_end_matches(|c| c != '\'')
+                    .trim_end_matches('\''),
+            )
+        })
+        .collect()
+}
+
+// whether `==` can compare values of the two types
+fn comparable(left: &Type, right: &Type) -> bool {
+    accepts(left, right) || accepts(right, left) || numeric_type(left, right).is_some()
+}
+
+// the error for a value of type `found` where `expected` is wanted, naming the function when
+// the value is a call's result
+fn mismatch(exp: &Expression, expected: &Type, found: Type) -> TypeError {
+    let expected = expected.clone();
+    let kind = match &exp.kind {
+        ExpressionKind::FunctionCall { name, .. } => TypeErrorKind::ResultMismatch {
+            function: name.to_string(),
+            expected,
+            found,
+        },
+        _ => TypeErrorKind::ExpectedType { expected, found },
+    };
+    TypeError::new(kind, exp.span)
+}
+
+// type of a binary operation on operands of the given types
+fn binary_type(
+    operator: BinOp,
+    left_type: Type,
+    right_type: Type,
+    span: Span,
+) -> Result<Type, TypeError> {
+    let result_type = match operator {
+        BinOp::Add if left_type == Type::String && right_type == Type::String => Some(Type::String),
+        BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Rem => {
+            numeric_type(&left_type, &right_type)
+        }
+        BinOp::Gt | BinOp::Lt | BinOp::Ge | BinOp::Le => {
+            numeric_type(&left_type, &right_type).map(|_| Type::Boolean)
+        }
+        BinOp::Eq | BinOp::Ne => comparable(&left_type, &right_type).then_some(Type::Boolean),
+        BinOp::And | BinOp::Or => {
+            if left_type == Type::Boolean && right_type == Type::Boolean {
+                Some(Type::Boolean)
+            } else {
+                None
+            }
+        }
+    };
+
+    result_type.ok_or_else(|| {
+        TypeError::new(
+            TypeErrorKind::InvalidOperands {
+                operator: operator.to_string(),
+                left: left_type,
+                right: right_type,
+            },
+            span,
+        )
+    })
+}
+
+impl ASTVisitor for TypeChecker {
+    type Output = Result<(), TypeError>;
+
+    fn visit_declaration(
+        &mut self,
+        name: symbol::Symbol,
+        expr: &Expression,
+        declared_type: Option<&Type>,
+        _: Span,
+    ) -> Self::Output {
+        self.declare_checked(name, expr, declared_type, false)
+    }
+
+    fn visit_const(
+        &mut self,
+        name: symbol::Symbol,
+        expr: &Expression,
+        declared_type: Option<&Type>,
+        _: Span,
+    ) -> Self::Output {
+        self.declare_checked(name, expr, declared_type, true)
+    }
+
+    fn visit_assignment(
+        &mut self,
+        name: symbol::Symbol,
+        expr: &Expression,
+        span: Span,
+    ) -> Self::Output {
+        let binding = self.resolve_declared(name, span)?.clone();
+        self.record_use(name, name_span(name.as_str(), span), &binding);
+        self.check_determined(name, span)?;
+        if binding.constant {
+            return Err(TypeError::new(
+                TypeErrorKind::AssignmentToConstant(name.to_string()),
+                span,
+            ));
+        }
+        let var_type = binding.datatype;
+        let expr_type = self.check_expected(expr, &var_type)?;
+        if matches!(expr_type, Type::Optional(_) | Type::None) {
+            self.forget_narrowing(name);
+        }
+        if !accepts(&var_type, &expr_type) {
+            return Err(TypeError::new(
+                TypeErrorKind::AssignmentMismatch {
+                    name: name.to_string(),
+                    expected: var_type,
+                    found: expr_type,
+                },
+                expr.span,
+            ));
+        }
+        Ok(())
+    }
+
+    fn visit_print(&mut self, expr: &Expression, _: bool, _: Span) -> Self::Output {
+        self.infer_datatype(expr)?;
+        Ok(())
+    }
+
+    fn visit_while(&mut self, condition: &Expression, body: &[Statement], _: Span) -> Self::Output {
+        let checked = self.check_condition("while", condition);
+        self.report(checked);
+        let narrowed = self.narrowed(condition, true);
+        self.check_loop_body(body, narrowed);
+        Ok(())
+    }
+
+    fn visit_do_while(
+        &mut self,
+        body: &[Statement],
+        condition: &Expression,
+        _: Span,
+    ) -> Self::Output {
+        // the body's scope has ended by the time the condition is checked
+        self.check_loop_body(body, Vec::new());
+        self.check_condition("do-while", condition)
+    }
+
+    fn visit_for(
+        &mut self,
+        variable: symbol::Symbol,
+        annotation: Option<&Type>,
+        start: &Expression,
+        end: &Expression,
+        body: &[Statement],
+        span: Span,
+    ) -> Self::Output {
+        let bounds = start.span.to(end.span);
+        let variable_type = self.loop_variable(variable, annotation, Type::Number, span, bounds);
+        for bound in [start, end] {
+            let bound_type = self
+                .check_expected(bound, &Type::Number)
+                .and_then(|bound_type| {
+                    if bound_type != Type::Number {
+                        return Err(TypeError::new(
+                            TypeErrorKind::NonNumericLoopBound(bound_type),
+                            bound.span,
+                        ));
+                    }
+                    Ok(())
+                });
+            self.report(bound_type);
+        }
+
+        self.enter_scope();
+        self.declare_variable(variable, variable_type);
+        self.check_loop_body(body, Vec::new());
+        self.exit_scope();
+        Ok(())
+    }
+
+    fn visit_for_in(
+        &mut self,
+        variable: symbol::Symbol,
+        annotation: Option<&Type>,
+        iterable: &Expression,
+        body: &[Statement],
+        span: Span,
+    ) -> Self::Output {
+        let element = self.infer_datatype(iterable).and_then(|found| {
+            element_type(&found)
+                .ok_or_else(|| TypeError::new(TypeErrorKind::NotIterable(found), iterable.span))
+        });
+        // the loop is still checked when its iterable is wrong, as if it counted
+        let element = match element {
+            Ok(element) => element,
+            Err(error) => {
+                self.report(Err(error));
+                Type::Number
+            }
+        };
+        let variable_type = self.loop_variable(variable, annotation, element, span, iterable.span);
+
+        self.enter_scope();
+        self.declare_variable(variable, variable_type);
+        self.check_loop_body(body, Vec::new());
+        self.exit_scope();
+        Ok(())
+    }
+
+    fn visit_block(&mut self, statements: &[Statement], _: Span) -> Self::Output {
+        self.enter_scope();
+        self.check_statements(statements);
+        self.exit_scope();
+        Ok(())
+    }
+
+    fn visit_function_declaration(
+        &mut self,
+        name: symbol::Symbol,
+        type_params: &[symbol::Symbol],
+        params: &[(symbol::Symbol, Type)],
+        return_type: &Type,
+        body: &[Statement],
+        span: Span,
+    ) -> Self::Output {
+        if self.duplicates.contains(&span) {
+            return Err(TypeError::new(
+                TypeErrorKind::DuplicateFunction(name.to_string()),
+                span,
+            ));
+        }
+        for type_param in type_params {
+            let used = params
+                .iter()
+                .any(|(_, param_type)| mentions(param_type, *type_param));
+            if !used {
+                return Err(TypeError::new(
+                    TypeErrorKind::UnusedTypeParameter {
+                        function: name.to_string(),
+                        parameter: type_param.to_string(),
+                    },
+                    span,
+                ));
+            }
+        }
+        for (_, param_type) in params {
+            self.check_type(param_type, span)?;
+        }
+        self.check_type(return_type, span)?;
+
+        let parameters = signature(type_params, params);
+        // a function declared without a return type returns what its returns agree on, or
+        // nothing if it has none
+        if *return_type == Type::Void && returns_a_value(body) {
+            return self.infer_function(name, parameters, params, body, span);
+        }
+        let signature_return = signature_type(return_type, type_params);
+        let function_type = Type::Function(parameters.clone(), Box::new(signature_return.clone()));
+        self.check_redeclaration(name, &function_type, span)?;
+        // declared before the body is checked so the function can call itself
+        self.declare_function(name, parameters, signature_return);
+        self.enter_scope();
+        self.check_function_body(params, body, ReturnType::Declared(return_type.clone()));
+        self.exit_scope();
+
+        if *return_type != Type::Void && !self.always_returns(body) {
+            return Err(TypeError::new(
+                TypeErrorKind::MissingReturn {
+                    name: name.to_string(),
+                    expected: return_type.clone(),
+                },
+                span,
+            ));
+        }
+        Ok(())
+    }
+
+    fn visit_if(
+        &mut self,
+        condition: &Expression,
+        body: &[Statement],
+        else_branch: Option<&[Statement]>,
+        _: Span,
+    ) -> Self::Output {
+        let checked = self.check_condition("if", condition);
+        self.report(checked);
+
+        let narrowed = self.narrowed(condition, true);
+        self.narrowed_scope(narrowed, |checker| checker.check_statements(body));
+
+        if let Some(else_branch) = else_branch {
+            let narrowed = self.narrowed(condition, false);
+            self.narrowed_scope(narrowed, |checker| checker.check_statements(else_branch));
+        }
+        Ok(())
+    }
+
+    fn visit_match(&mut self, subject: &Expression, arms: &[MatchArm], span: Span) -> Self::Output {
+        let subject_type = self.infer_datatype(subject)?;
+
+        for arm in arms {
+            if let Pattern::Value(value) = &arm.pattern {
+                let pattern = self.infer_datatype(value).and_then(|found| {
+                    if !accepts(&subject_type, &found) {
+                        return Err(TypeError::new(
+                            TypeErrorKind::PatternMismatch {
+                                expected: subject_type.clone(),
+                                found,
+                            },
+                            value.span,
+                        ));
+                    }
+                    Ok(())
+                });
+                self.report(pattern);
+            }
+
+            self.enter_scope();
+            self.check_statements(&arm.body);
+            self.exit_scope();
+        }
+
+        if let Type::Enum(name) = &subject_type
+            && !is_exhaustive(arms)
+            && let Some(missing) = self.missing_variant(*name, arms)
+        {
+            return Err(TypeError::new(
+                TypeErrorKind::NonExhaustiveMatch(missing),
+                span,
+            ));
+        }
+
+        // other types have too many values to list, so only bools and enums must be covered
+        if subject_type == Type::Boolean && !is_exhaustive(arms) {
+            let covers_true = arms.iter().any(|arm| {
+                matches!(&arm.pattern, Pattern::Value(value) if value.kind == ExpressionKind::Bool(true))
+            });
+            let missing = if covers_true { "false" } else { "true" };
+            return Err(TypeError::new(
+                TypeErrorKind::NonExhaustiveMatch(missing.to_string()),
+                span,
+            ));
+        }
+        Ok(())
+    }
+
+    fn visit_enum(
+        &mut self,
+        name: symbol::Symbol,
+        variants: &[symbol::Symbol],
+        span: Span,
+    ) -> Self::Output {
+        for (i, variant) in variants.iter().enumerate() {
+            if variants[..i].contains(variant) {
+                return Err(TypeError::new(
+                    TypeErrorKind::DuplicateVariant {
+                        enum_name: name.to_string(),
+                        variant: variant.to_string(),
+                    },
+                    span,
+                ));
+            }
+        }
+        // declaring the same variants again changes nothing, like a variable redeclared with
+        // its type
+        if self
+            .enums
+            .get(&name)
+            .is_some_and(|previous| previous != variants)
+        {
+            return Err(TypeError::new(
+                TypeErrorKind::DuplicateEnum(name.to_string()),
+                span,
+            ));
+        }
+        self.enums.insert(name, variants.to_vec());
+        Ok(())
+    }
+
+    fn visit_expression(&mut self, expr: &Expression, _: Span) -> Self::Output {
+        self.expression_type(expr)?;
+        Ok(())
+    }
+
+    fn visit_assert(&mut self, condition: &Expression, _: Span) -> Self::Output {
+        self.check_condition("assert", condition)
+    }
+
+    fn visit_assert_eq(
+        &mut self,
+        left: &Expression,
+        right: &Expression,
+        span: Span,
+    ) -> Self::Output {
+        let left = self.infer_datatype(left)?;
+        let right = self.infer_datatype(right)?;
+        if !comparable(&left, &right) {
+            return Err(TypeError::new(
+                TypeErrorKind::IncomparableAssertion { left, right },
+                span,
+            ));
+        }
+        Ok(())
+    }
+
+    fn visit_return(&mut self, expr: &Expression, span: Span) -> Self::Output {
+        if self.block_value {
+            return Err(TypeError::new(
+                TypeErrorKind::JumpOutOfBlock("return".to_string()),
+                span,
+            ));
+        }
+        let (function, declaration, earlier) = match self.return_type.clone() {
+            None => return Err(TypeError::new(TypeErrorKind::ReturnOutsideFunction, span)),
+            Some(ReturnType::Declared(expected)) => {
+                let found = self.check_expected(expr, &expected)?;
+                if !accepts(&expected, &found) {
+                    return Err(TypeError::new(
+                        TypeErrorKind::ReturnMismatch { expected, found },
+                        expr.span,
+                    ));
+                }
+                return Ok(());
+            }
+            Some(ReturnType::Inferred {
+                function,
+                declaration,
+                found,
+                ..
+            }) => (function, declaration, found),
+        };
+
+        let found = match &earlier {
+            Some(earlier) => self.check_expected(expr, earlier),
+            None => self.infer_datatype(expr),
+        };
+        let found = match found {
+            Ok(found) => found,
+            Err(error) => {
+                if let Some(ReturnType::Inferred { unknown, .. }) = &mut self.return_type {
+                    *unknown = true;
+                }
+                return Err(error);
+            }
+        };
+        let return_type = match earlier {
+            None => found,
+            Some(earlier) => match join(&earlier, &found) {
+                Some(joined) => joined,
+                None => {
+                    return Err(TypeError::new(
+                        TypeErrorKind::InconsistentReturn { earlier, found },
+                        expr.span,
+                    ));
+                }
             },
+        };
+        if let Some(ReturnType::Inferred { found, .. }) = &mut self.return_type {
+            *found = Some(return_type.clone());
+        }
+        self.infer_return_type(function, declaration, return_type);
+        Ok(())
+    }
+
+    fn visit_try(
+        &mut self,
+        body: &[Statement],
+        error: symbol::Symbol,
+        handler: &[Statement],
+        _: Span,
+    ) -> Self::Output {
+        self.enter_scope();
+        self.check_statements(body);
+        self.exit_scope();
+
+        self.enter_scope();
+        self.declare_variable(error, Type::String);
+        self.check_statements(handler);
+        self.exit_scope();
+        Ok(())
+    }
+
+    fn visit_raise(&mut self, message: &Expression, _: Span) -> Self::Output {
+        let found = self.check_expected(message, &Type::String)?;
+        if found != Type::String {
+            return Err(TypeError::new(
+                TypeErrorKind::NonStringRaise(found),
+                message.span,
+            ));
+        }
+        Ok(())
+    }
+
+    fn visit_break(&mut self, span: Span) -> Self::Output {
+        self.check_inside_loop("break", span)
+    }
+
+    fn visit_continue(&mut self, span: Span) -> Self::Output {
+        self.check_inside_loop("continue", span)
+    }
+
+    // top-level imports are replaced by the module loader before typechecking,
+    // so any import left over sits inside a block
+    fn visit_import(&mut self, _: &str, span: Span) -> Self::Output {
+        Err(TypeError::new(TypeErrorKind::NestedImport, span))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Expression, Spelling, Statement, Type};
+
+    fn expr(kind: ExpressionKind) -> Expression {
+        Expression::new(kind, Span::default())
+    }
+
+    fn stmt(kind: StatementKind) -> Statement {
+        Statement::new(kind, Span::default())
+    }
+
+    fn number_expr(n: i64) -> Expression {
+        expr(ExpressionKind::Number(n, n.to_string()))
+    }
+
+    fn bool_expr(b: bool) -> Expression {
+        expr(ExpressionKind::Bool(b))
+    }
+
+    fn string_expr(s: &str) -> Expression {
+        expr(ExpressionKind::String(
+            s.to_string(),
+            crate::formatter::quote(s),
+        ))
+    }
+
+    fn var(name: &str) -> Expression {
+        expr(ExpressionKind::Variable(name.into()))
+    }
+
+    fn binop(left: Expression, op: BinOp, right: Expression) -> Expression {
+        expr(ExpressionKind::BinaryOperation {
+            left: Box::new(left),
+            operator: op,
+            right: Box::new(right),
+            spelling: Spelling::Symbol,
+        })
+    }
+
+    fn type_error(kind: TypeErrorKind) -> TypeError {
+        TypeError::new(kind, Span::default())
+    }
+
+    fn parse(source: &str) -> Vec<Statement> {
+        let tokens = crate::lexer::Lexer::new(source).parse().unwrap();
+        crate::parser::Parser::new(tokens).parse().unwrap()
+    }
+
+    // the kind of the first error the checker finds in `source`
+    fn check(source: &str) -> Result<(), TypeErrorKind> {
+        TypeChecker::new()
+            .check(&parse(source))
+            .map_err(|errors| errors[0].kind.clone())
+    }
+
+    #[test]
+    fn test_variable_declaration_and_assignment() {
+        let mut checker = TypeChecker::new();
+        let stmts = vec![
+            stmt(StatementKind::Declaration(
+                "x".into(),
+                number_expr(10),
+                None,
+            )),
+            stmt(StatementKind::Assignment("x".into(), number_expr(42))),
+        ];
+        assert_eq!(checker.check(&stmts), Ok(()));
+    }
+
+    #[test]
+    fn test_type_mismatch_assignment() {
+        let mut checker = TypeChecker::new();
+        let stmts = vec![
+            stmt(StatementKind::Declaration(
+                "x".into(),
+                number_expr(10),
+                None,
+            )),
+            stmt(StatementKind::Assignment("x".into(), bool_expr(true))),
+        ];
+        assert_eq!(
+            checker.check(&stmts),
+            Err(vec![type_error(TypeErrorKind::AssignmentMismatch {
+                name: "x".into(),
+                expected: Type::Number,
+                found: Type::Boolean,
+            })])
+        );
+    }
+
+    #[test]
+    fn test_binary_operation_number_addition() {
+        let mut checker = TypeChecker::new();
+        let sum = binop(number_expr(1), BinOp::Add, number_expr(2));
+        let inferred = checker.infer_datatype(&sum);
+        assert_eq!(inferred, Ok(Type::Number));
+    }
+
+    #[test]
+    fn test_while_condition_type_check() {
+        let mut checker = TypeChecker::new();
+        let stmts = vec![
+            stmt(StatementKind::While {
+                condition: number_expr(1),
+                body: vec![],
+            }), // wrong type
+        ];
+        assert_eq!(
+            checker.check(&stmts),
+            Err(vec![type_error(TypeErrorKind::NonBooleanCondition {
+                construct: "while".into(),
+                found: Type::Number,
+            })])
+        );
+    }
+
+    #[test]
+    fn test_valid_while_condition() {
+        let mut checker = TypeChecker::new();
+        let stmts = vec![
+            stmt(StatementKind::Declaration(
+                "cond".into(),
+                bool_expr(true),
+                None,
+            )),
+            stmt(StatementKind::While {
+                condition: var("cond"),
+                body: vec![
+                    stmt(StatementKind::Declaration("x".into(), number_expr(5), None)),
+                    stmt(StatementKind::Assignment("x".into(), number_expr(10))),
+                ],
+            }),
+        ];
+        assert_eq!(checker.check(&stmts), Ok(()));
+    }
+
+    #[test]
+    fn test_scope_within_while_block() {
+        let mut checker = TypeChecker::new();
+        let stmts = vec![
+            stmt(StatementKind::Declaration("x".into(), number_expr(0), None)),
+            stmt(StatementKind::While {
+                condition: bool_expr(true),
+                body: vec![stmt(StatementKind::Assignment("x".into(), number_expr(10)))],
+            }),
         ];
-        checker.check(stmts);
+        assert_eq!(checker.check(&stmts), Ok(()));
     }
 
     #[test]
     fn test_function_declaration_and_return_type() {
         let mut checker = TypeChecker::new();
-        let stmts = vec![Statement::FunctionDeclaration {
+        let stmts = vec![stmt(StatementKind::FunctionDeclaration {
             name: "add".into(),
+            type_params: vec![],
             params: vec![("a".into(), Type::Number), ("b".into(), Type::Number)],
             return_type: Type::Number,
-            body: vec![Statement::Return(binop(var("a"), "+", var("b")))],
-        }];
-        checker.check(stmts);
+            body: vec![stmt(StatementKind::Return(binop(
+                var("a"),
+                BinOp::Add,
+                var("b"),
+            )))],
+        })];
+        assert_eq!(checker.check(&stmts), Ok(()));
+    }
+
+    #[test]
+    fn test_string_concatenation() {
+        let mut checker = TypeChecker::new();
+        let concat = binop(string_expr("frog"), BinOp::Add, string_expr("gy"));
+        assert_eq!(checker.infer_datatype(&concat), Ok(Type::String));
+    }
+
+    #[test]
+    fn test_string_plus_number() {
+        let mut checker = TypeChecker::new();
+        let sum = binop(string_expr("frog"), BinOp::Add, number_expr(1));
+        assert_eq!(
+            checker.infer_datatype(&sum),
+            Err(type_error(TypeErrorKind::InvalidOperands {
+                operator: "+".into(),
+                left: Type::String,
+                right: Type::Number,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_unknown_variable() {
+        let mut checker = TypeChecker::new();
+        assert_eq!(
+            checker.infer_datatype(&var("ghost")),
+            Err(type_error(TypeErrorKind::UnknownVariable("ghost".into())))
+        );
+    }
+
+    #[test]
+    fn test_mixed_arithmetic_promotes_to_float() {
+        let mut checker = TypeChecker::new();
+        let product = binop(
+            number_expr(1),
+            BinOp::Mul,
+            expr(ExpressionKind::Float(0.5, "0.5".to_string())),
+        );
+        assert_eq!(checker.infer_datatype(&product), Ok(Type::Float));
+
+        let cmp = binop(
+            expr(ExpressionKind::Float(0.5, "0.5".to_string())),
+            BinOp::Lt,
+            number_expr(1),
+        );
+        assert_eq!(checker.infer_datatype(&cmp), Ok(Type::Boolean));
+    }
+
+    #[test]
+    fn test_error_points_at_offending_expression() {
+        let source = "let x = 1;\nlet y = x + true;";
+        let ast = parse(source);
+
+        let error = &TypeChecker::new().check(&ast).unwrap_err()[0];
+        assert_eq!(&source[error.span.start..error.span.end], "x + true");
+        assert_eq!((error.span.line, error.span.column), (2, 9));
+    }
+
+    #[test]
+    fn test_for_loop_variable_is_scoped_number() {
+        let for_loop = |end: Expression| {
+            stmt(StatementKind::For {
+                variable: "i".into(),
+                annotation: None,
+                start: number_expr(0),
+                end,
+                body: vec![stmt(StatementKind::Declaration(
+                    "double".into(),
+                    binop(var("i"), BinOp::Mul, number_expr(2)),
+                    Some(Type::Number),
+                ))],
+            })
+        };
+
+        assert_eq!(
+            TypeChecker::new().check(&[for_loop(number_expr(3))]),
+            Ok(())
+        );
+        assert_eq!(
+            TypeChecker::new().check(&[for_loop(bool_expr(true))]),
+            Err(vec![type_error(TypeErrorKind::NonNumericLoopBound(
+                Type::Boolean
+            ))])
+        );
+
+        let mut checker = TypeChecker::new();
+        checker.check(&[for_loop(number_expr(3))]).unwrap();
+        assert!(checker.infer_datatype(&var("i")).is_err());
+    }
+
+    #[test]
+    fn test_break_and_continue_only_inside_loops() {
+        let in_loop = stmt(StatementKind::While {
+            condition: bool_expr(true),
+            body: vec![
+                stmt(StatementKind::Continue),
+                stmt(StatementKind::Block(vec![stmt(StatementKind::Break)])),
+            ],
+        });
+        assert_eq!(TypeChecker::new().check(&[in_loop]), Ok(()));
+
+        assert_eq!(
+            TypeChecker::new().check(&[stmt(StatementKind::Break)]),
+            Err(vec![type_error(TypeErrorKind::OutsideLoop("break".into()))])
+        );
+
+        // a function declared inside a loop does not inherit it
+        let function_in_loop = stmt(StatementKind::While {
+            condition: bool_expr(true),
+            body: vec![stmt(StatementKind::FunctionDeclaration {
+                name: "f".into(),
+                type_params: vec![],
+                params: vec![],
+                return_type: Type::Void,
+                body: vec![stmt(StatementKind::Continue)],
+            })],
+        });
+        assert_eq!(
+            TypeChecker::new().check(&[function_in_loop]),
+            Err(vec![type_error(TypeErrorKind::OutsideLoop(
+                "continue".into()
+            ))])
+        );
+    }
+
+    #[test]
+    fn test_logical_operators_require_bools() {
+        let mut checker = TypeChecker::new();
+        let not = |operand| {
+            expr(ExpressionKind::UnaryOperation {
+                operator: UnaryOp::Not,
+                operand: Box::new(operand),
+                spelling: Spelling::Symbol,
+            })
+        };
+
+        let logic = binop(not(bool_expr(false)), BinOp::And, bool_expr(true));
+        assert_eq!(checker.infer_datatype(&logic), Ok(Type::Boolean));
+
+        assert_eq!(
+            checker.infer_datatype(&binop(bool_expr(true), BinOp::Or, number_expr(0))),
+            Err(type_error(TypeErrorKind::InvalidOperands {
+                operator: "||".into(),
+                left: Type::Boolean,
+                right: Type::Number,
+            }))
+        );
+        assert_eq!(
+            checker.infer_datatype(&not(number_expr(1))),
+            Err(type_error(TypeErrorKind::InvalidOperand {
+                operator: "!".into(),
+                operand: Type::Number,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_unary_minus_requires_numeric_operand() {
+        let mut checker = TypeChecker::new();
+        let negate = |operand| {
+            expr(ExpressionKind::UnaryOperation {
+                operator: UnaryOp::Neg,
+                operand: Box::new(operand),
+                spelling: Spelling::Symbol,
+            })
+        };
+
+        assert_eq!(
+            checker.infer_datatype(&negate(number_expr(5))),
+            Ok(Type::Number)
+        );
+        assert_eq!(
+            checker.infer_datatype(&negate(bool_expr(true))),
+            Err(type_error(TypeErrorKind::InvalidOperand {
+                operator: "-".into(),
+                operand: Type::Boolean,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_return_type_must_match_declaration() {
+        let function = |return_type, body| {
+            stmt(StatementKind::FunctionDeclaration {
+                name: "f".into(),
+                type_params: vec![],
+                params: vec![("n".into(), Type::Number)],
+                return_type,
+                body,
+            })
+        };
+
+        let wrong_type = function(
+            Type::Number,
+            vec![stmt(StatementKind::Return(bool_expr(true)))],
+        );
+        assert_eq!(
+            TypeChecker::new().check(&[wrong_type]),
+            Err(vec![type_error(TypeErrorKind::ReturnMismatch {
+                expected: Type::Number,
+                found: Type::Boolean,
+            })])
+        );
+
+        // recursive call resolves to the declared return type
+        let recursive = function(
+            Type::Number,
+            vec![stmt(StatementKind::Return(expr(
+                ExpressionKind::FunctionCall {
+                    name: "f".into(),
+                    arguments: vec![var("n")],
+                },
+            )))],
+        );
+        assert_eq!(TypeChecker::new().check(&[recursive]), Ok(()));
+
+        assert_eq!(
+            TypeChecker::new().check(&[stmt(StatementKind::Return(number_expr(1)))]),
+            Err(vec![type_error(TypeErrorKind::ReturnOutsideFunction)])
+        );
+    }
+
+    #[test]
+    fn test_non_void_function_must_return_on_every_path() {
+        let function = |body| {
+            stmt(StatementKind::FunctionDeclaration {
+                name: "sign".into(),
+                type_params: vec![],
+                params: vec![("n".into(), Type::Number)],
+                return_type: Type::Number,
+                body,
+            })
+        };
+        let if_positive = |else_block| {
+            stmt(StatementKind::If {
+                condition: binop(var("n"), BinOp::Gt, number_expr(0)),
+                then_block: vec![stmt(StatementKind::Return(number_expr(1)))],
+                else_block,
+            })
+        };
+
+        assert_eq!(
+            TypeChecker::new().check(&[function(vec![if_positive(None)])]),
+            Err(vec![type_error(TypeErrorKind::MissingReturn {
+                name: "sign".into(),
+                expected: Type::Number,
+            })])
+        );
+
+        let both_branches = if_positive(Some(vec![stmt(StatementKind::Return(number_expr(-1)))]));
+        assert_eq!(
+            TypeChecker::new().check(&[function(vec![both_branches])]),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_call_arguments_checked_against_signature() {
+        let mut checker = TypeChecker::new();
+        checker
+            .check(&[stmt(StatementKind::FunctionDeclaration {
+                name: "add".into(),
+                type_params: vec![],
+                params: vec![("a".into(), Type::Number), ("b".into(), Type::Number)],
+                return_type: Type::Number,
+                body: vec![stmt(StatementKind::Return(binop(
+                    var("a"),
+                    BinOp::Add,
+                    var("b"),
+                )))],
+            })])
+            .unwrap();
+        let call = |arguments| {
+            expr(ExpressionKind::FunctionCall {
+                name: "add".into(),
+                arguments,
+            })
+        };
+
+        assert_eq!(
+            checker.infer_datatype(&call(vec![number_expr(1), number_expr(2)])),
+            Ok(Type::Number)
+        );
+        assert_eq!(
+            checker.infer_datatype(&call(vec![number_expr(1)])),
+            Err(type_error(TypeErrorKind::ArityMismatch {
+                name: "add".into(),
+                expected: 2,
+                found: 1,
+            }))
+        );
+        assert_eq!(
+            checker.infer_datatype(&call(vec![number_expr(1), bool_expr(false)])),
+            Err(type_error(TypeErrorKind::ArgumentMismatch {
+                name: "add".into(),
+                position: 2,
+                expected: Type::Number,
+                found: Type::Boolean,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_builtins_have_signatures() {
+        let mut checker = TypeChecker::new();
+        let call = |name: &str, arguments| {
+            expr(ExpressionKind::FunctionCall {
+                name: name.into(),
+                arguments,
+            })
+        };
+
+        assert_eq!(
+            checker.infer_datatype(&call("min", vec![number_expr(1), number_expr(2)])),
+            Ok(Type::Number)
+        );
+        assert_eq!(
+            checker.infer_datatype(&call("input", vec![])),
+            Ok(Type::String)
+        );
+        assert_eq!(
+            checker.infer_datatype(&call("abs", vec![string_expr("frog")])),
+            Err(type_error(TypeErrorKind::ArgumentMismatch {
+                name: "abs".into(),
+                position: 1,
+                expected: Type::Number,
+                found: Type::String,
+            }))
+        );
+        // typeof takes a value of any type, but a void call doesn't give one
+        for argument in [number_expr(1), string_expr("frog"), var("abs")] {
+            assert_eq!(
+                checker.infer_datatype(&call("typeof", vec![argument])),
+                Ok(Type::String)
+            );
+        }
+        assert_eq!(
+            checker.infer_datatype(&call("typeof", vec![call("exit", vec![number_expr(0)])])),
+            Err(type_error(TypeErrorKind::VoidValue("exit".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_functions_are_values() {
+        let mut checker = TypeChecker::new();
+        let apply = stmt(StatementKind::FunctionDeclaration {
+            name: "apply".into(),
+            type_params: vec![],
+            params: vec![
+                (
+                    "f".into(),
+                    Type::Function(vec![Type::Number], Box::new(Type::Number)),
+                ),
+                ("n".into(), Type::Number),
+            ],
+            return_type: Type::Number,
+            body: vec![stmt(StatementKind::Return(expr(
+                ExpressionKind::FunctionCall {
+                    name: "f".into(),
+                    arguments: vec![var("n")],
+                },
+            )))],
+        });
+        checker.check(&[apply]).unwrap();
+
+        let call_apply = |function: &str| {
+            expr(ExpressionKind::FunctionCall {
+                name: "apply".into(),
+                arguments: vec![var(function), number_expr(-3)],
+            })
+        };
+        assert_eq!(checker.infer_datatype(&call_apply("abs")), Ok(Type::Number));
+        assert_eq!(
+            checker.infer_datatype(&call_apply("max")),
+            Err(type_error(TypeErrorKind::ArgumentMismatch {
+                name: "apply".into(),
+                position: 1,
+                expected: Type::Function(vec![Type::Number], Box::new(Type::Number)),
+                found: Type::Function(vec![Type::Number, Type::Number], Box::new(Type::Number)),
+            }))
+        );
+
+        checker
+            .check(&[stmt(StatementKind::Declaration(
+                "n".into(),
+                number_expr(1),
+                None,
+            ))])
+            .unwrap();
+        assert_eq!(
+            checker.infer_datatype(&expr(ExpressionKind::FunctionCall {
+                name: "n".into(),
+                arguments: vec![],
+            })),
+            Err(type_error(TypeErrorKind::NotCallable {
+                name: "n".into(),
+                found: Type::Number,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_constants_cannot_be_reassigned() {
+        let mut checker = TypeChecker::new();
+        let result = checker.check(&[
+            stmt(StatementKind::Const("limit".into(), number_expr(10), None)),
+            stmt(StatementKind::Assignment("limit".into(), number_expr(11))),
+        ]);
+        assert_eq!(
+            result,
+            Err(vec![type_error(TypeErrorKind::AssignmentToConstant(
+                "limit".into()
+            ))])
+        );
+
+        // a `let` in an inner scope shadows the constant and can be assigned
+        TypeChecker::new()
+            .check(&[
+                stmt(StatementKind::Const("limit".into(), number_expr(10), None)),
+                stmt(StatementKind::Block(vec![
+                    stmt(StatementKind::Declaration(
+                        "limit".into(),
+                        number_expr(1),
+                        None,
+                    )),
+                    stmt(StatementKind::Assignment("limit".into(), number_expr(2))),
+                ])),
+            ])
+            .unwrap();
+    }
+
+    #[test]
+    fn test_check_collects_every_error() {
+        let source = "let a: number = true;\nlet b = a + 1;\nwhile 1 {\n  ghost = 1;\n}";
+        let ast = parse(source);
+        let mut checker = TypeChecker::new();
+
+        let errors = checker.check(&ast).unwrap_err();
+
+        // `a` keeps its annotated type, so `a + 1` is not reported as well
+        let kinds: Vec<&TypeErrorKind> = errors.iter().map(|e| &e.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &TypeErrorKind::DeclarationMismatch {
+                    name: "a".into(),
+                    expected: Type::Number,
+                    found: Type::Boolean,
+                },
+                &TypeErrorKind::NonBooleanCondition {
+                    construct: "while".into(),
+                    found: Type::Number,
+                },
+                &TypeErrorKind::UnknownVariable("ghost".into()),
+            ]
+        );
+        assert_eq!(checker.diagnostics(), errors.as_slice());
+
+        // without an annotation its type isn't known, and its uses aren't checked
+        let errors = TypeChecker::new()
+            .check(&parse(
+                "let x = 1 + true; croak x; x = 3; let y = x * 2; func f() { x = 4; }
+                 let x = \"frog\"; let z: string = x;",
+            ))
+            .unwrap_err();
+        assert_eq!(
+            errors.iter().map(|e| &e.kind).collect::<Vec<_>>(),
+            vec![&TypeErrorKind::InvalidOperands {
+                operator: "+".into(),
+                left: Type::Number,
+                right: Type::Boolean,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_match_patterns_and_exhaustiveness() {
+        assert_eq!(
+            check("match 1 { \"one\" => {} _ => {} }"),
+            Err(TypeErrorKind::PatternMismatch {
+                expected: Type::Number,
+                found: Type::String,
+            })
+        );
+        assert_eq!(
+            check("match true { true => {} }"),
+            Err(TypeErrorKind::NonExhaustiveMatch("false".into()))
+        );
+        // a match returning from every arm counts as a return
+        check(
+            "func sign(b: bool): number { match b { true => { return 1; } false => { return -1; } } }",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_enums() {
+        let color = "enum Color { Red, Green, Blue } ";
+
+        assert_eq!(
+            check(&format!("{}let c = Color.Pink;", color)),
+            Err(TypeErrorKind::UnknownVariant {
+                enum_name: "Color".into(),
+                variant: "Pink".into(),
+            })
+        );
+        assert_eq!(
+            check("func paint(c: Colour) {}"),
+            Err(TypeErrorKind::UnknownEnum("Colour".into()))
+        );
+        assert_eq!(
+            check("enum Dup { A, A }"),
+            Err(TypeErrorKind::DuplicateVariant {
+                enum_name: "Dup".into(),
+                variant: "A".into(),
+            })
+        );
+        assert_eq!(
+            check(&format!("{}enum Color {{ Red, Green }}", color)),
+            Err(TypeErrorKind::DuplicateEnum("Color".into()))
+        );
+        assert_eq!(check(&format!("{}{}", color, color)), Ok(()));
+        assert_eq!(
+            check(&format!("{}let same = Color.Red == 1;", color)),
+            Err(TypeErrorKind::InvalidOperands {
+                operator: "==".into(),
+                left: Type::Enum("Color".into()),
+                right: Type::Number,
+            })
+        );
+        assert_eq!(
+            check(&format!(
+                "{}match Color.Red {{ Color.Red => {{}} Color.Green => {{}} }}",
+                color
+            )),
+            Err(TypeErrorKind::NonExhaustiveMatch("Color.Blue".into()))
+        );
+        // covering every variant counts as a return on every path
+        check(&format!(
+            "{}func hex(c: Color): string {{ match c {{ Color.Red => {{ return \"f00\"; }} \
+             Color.Green => {{ return \"0f0\"; }} Color.Blue => {{ return \"00f\"; }} }} }}",
+            color
+        ))
+        .unwrap();
+    }
+
+    #[test]
+    fn test_assertions() {
+        assert_eq!(check("assert 1 == 1.0; assert_eq 2, 2.0;"), Ok(()));
+        assert_eq!(
+            check("assert 1;"),
+            Err(TypeErrorKind::NonBooleanCondition {
+                construct: "assert".into(),
+                found: Type::Number,
+            })
+        );
+        assert_eq!(
+            check("assert_eq \"1\", 1;"),
+            Err(TypeErrorKind::IncomparableAssertion {
+                left: Type::String,
+                right: Type::Number,
+            })
+        );
+    }
+
+    #[test]
+    fn test_do_while() {
+        assert_eq!(check("let i = 0; do { i++; break; } while i < 3;"), Ok(()));
+        assert_eq!(
+            check("do { } while 1;"),
+            Err(TypeErrorKind::NonBooleanCondition {
+                construct: "do-while".into(),
+                found: Type::Number,
+            })
+        );
+        // the condition can't see the body's declarations
+        assert_eq!(
+            check("do { let done = true; } while !done;"),
+            Err(TypeErrorKind::UnknownVariable("done".into()))
+        );
+        // the body runs at least once, so a function can end with it returning
+        assert_eq!(
+            check("func f(): number { do { return 1; } while false; }"),
+            Ok(())
+        );
+        assert_eq!(
+            check(
+                "func f(n: number): number { do { if n > 0 { break; } return 1; } while false; }"
+            ),
+            Err(TypeErrorKind::MissingReturn {
+                name: "f".into(),
+                expected: Type::Number,
+            })
+        );
+    }
+
+    #[test]
+    fn test_try_and_raise() {
+        assert_eq!(
+            check("try { raise \"oops\"; } handle err { let message: string = err; }"),
+            Ok(())
+        );
+        assert_eq!(
+            check("raise 1;"),
+            Err(TypeErrorKind::NonStringRaise(Type::Number))
+        );
+        // the error is only in scope in the handler
+        assert_eq!(
+            check("try { } handle err { } let message = err;"),
+            Err(TypeErrorKind::UnknownVariable("err".into()))
+        );
+        // raising ends a path like returning does
+        assert_eq!(
+            check("func f(n: number): number { try { return 10 / n; } handle err { raise err; } }"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_functions_declared_in_blocks_are_local() {
+        assert_eq!(
+            check("func f(): number { func g(): number { return 1; } return g(); } croak f();"),
+            Ok(())
+        );
+        assert_eq!(
+            check("{ func inner(): number { return 1; } } croak inner();"),
+            Err(TypeErrorKind::UnknownFunction("inner".into()))
+        );
+        // like variables, they are only visible after their declaration
+        assert_eq!(
+            check("{ croak later(); func later() { } }"),
+            Err(TypeErrorKind::UnknownFunction("later".into()))
+        );
+    }
+
+    #[test]
+    fn test_incremental_check_reuses_unchanged_statements() {
+        let mut incremental = IncrementalChecker::new(TypeChecker::new());
+        let mut check = |source: &str| {
+            let program = parse(source);
+            let result = incremental.check(source, &[], &program);
+            // always what checking the whole program finds
+            assert_eq!(result, TypeChecker::new().check(&program));
+            (result.is_ok(), incremental.rechecked())
+        };
+
+        let first =
+            "let a = twice(1);\nlet b = a;\nfunc twice(n: number): number { return n * 2; }";
+        assert_eq!(check(first), (true, 3));
+        assert_eq!(check(first), (true, 0));
+        // only the changed statement and the ones after it are checked again
+        let second = first.replace("n * 2", "n * 3");
+        assert_eq!(check(&second), (true, 1));
+        assert_eq!(
+            check(&second.replace("let b = a;", "let b = !a;")),
+            (false, 2)
+        );
+        // a hoisted signature can change what the statements before it mean
+        assert_eq!(
+            check(&first.replace("): number {", "): bool {")),
+            (false, 3)
+        );
+
+        // the checker's state is only kept from before the statement edited last
+        let third = first.replace("let b = a;", "let b = a + 1;");
+        for source in [third.clone(), third.replace("a + 1", "a + 2")] {
+            incremental.check(&source, &[], &parse(&source)).unwrap();
+        }
+        assert_eq!(incremental.rechecked(), 2);
+        assert_eq!(
+            incremental.checkpoint.as_ref().map(|(checked, _)| *checked),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_top_level_functions_are_hoisted() {
+        assert_eq!(
+            check(
+                "let ten = is_even(10);
+                func is_even(n: number): bool { if n == 0 { return true; } return is_odd(n - 1); }
+                func is_odd(n: number): bool { if n == 0 { return false; } return is_even(n - 1); }"
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            check("let x: string = later(); func later(): number { return 1; }"),
+            Err(TypeErrorKind::ResultMismatch {
+                function: "later".into(),
+                expected: Type::String,
+                found: Type::Number,
+            })
+        );
+        // a name also declared by a variable isn't hoisted, it would mean different things
+        // before and after that declaration
+        assert_eq!(
+            check("let a = f(); let f = 1; func f(): number { return 1; }"),
+            Err(TypeErrorKind::UnknownFunction("f".into()))
+        );
+        // two top-level functions with one name are an error where the second is declared,
+        // rather than leaving calls before them with no function
+        assert_eq!(
+            check("croak f(); func f(): number { return 1; } func f(): number { return 2; }"),
+            Err(TypeErrorKind::DuplicateFunction("f".into()))
+        );
+        // a call can't come before a variable the function uses, even through another function
+        assert_eq!(
+            check(
+                "croak f(); let x = 5; croak f();
+                func f(): number { return g(); } func g(): number { return x; }"
+            ),
+            Err(TypeErrorKind::UsedBeforeDeclaration {
+                function: "f".into(),
+                variable: "x".into(),
+            })
+        );
+        assert_eq!(
+            check(
+                "let x = 5; croak f(); func f(): number { let y = x; return y; }
+                croak g(1); func g(y: number): number { return y; } let y = 2;"
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_optional_types() {
+        let optional_number = Type::Optional(Box::new(Type::Number));
+
+        assert_eq!(
+            check(
+                "func half(n: number): number? { if n % 2 == 0 { return n / 2; } return none; }
+                 let h = half(4);
+                 assert h == none || h > 1;
+                 if h != none { let doubled: number = h * 2; }
+                 while h != none and h > 0 { h = h - 1; }"
+            ),
+            Ok(())
+        );
+        // using the value needs a check for none first
+        assert_eq!(
+            check("let h: number? = 2; let x = h + 1;"),
+            Err(TypeErrorKind::InvalidOperands {
+                operator: "+".into(),
+                left: optional_number.clone(),
+                right: Type::Number,
+            })
+        );
+        // assigning none undoes the check
+        assert_eq!(
+            check("let h: number? = 2; if h != none { h = none; let x = -h; }"),
+            Err(TypeErrorKind::InvalidOperand {
+                operator: "-".into(),
+                operand: optional_number.clone(),
+            })
+        );
+        // and so does calling a function that can assign it
+        assert_eq!(
+            check(
+                "let x: number? = 1; func g() { x = none; }
+                 if x != none { g(); let y = x + 1; }"
+            ),
+            Err(TypeErrorKind::InvalidOperands {
+                operator: "+".into(),
+                left: optional_number.clone(),
+                right: Type::Number,
+            })
+        );
+        assert_eq!(
+            check(
+                "func f(h: number?) { let x: number? = h; if x != none { assert x > 0; } }
+                 func g(h: number?) { if h != none { f(h); let y = h + 1; } }
+                 let x: number? = 1;
+                 if x != none { let y = length(\"frog\") + x; }"
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            check("let n: number = none;"),
+            Err(TypeErrorKind::DeclarationMismatch {
+                name: "n".into(),
+                expected: Type::Number,
+                found: Type::None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_redeclaration_in_same_scope() {
+        assert_eq!(
+            check("let x = 1; let x = true;"),
+            Err(TypeErrorKind::Redeclaration {
+                name: "x".into(),
+                previous: Type::Number,
+                found: Type::Boolean,
+            })
+        );
+        assert_eq!(
+            check("let f = 1; func f() {}"),
+            Err(TypeErrorKind::Redeclaration {
+                name: "f".into(),
+                previous: Type::Number,
+                found: Type::Function(vec![], Box::new(Type::Void)),
+            })
+        );
+        // same type, an inner scope or a builtin's name are all fine
+        assert_eq!(check("let x = 1; let x = 2; { let x = true; }"), Ok(()));
+        assert_eq!(check("let length = 1;"), Ok(()));
+    }
+
+    #[test]
+    fn test_annotations_are_expected_types() {
+        // the kind of the first error, with the source it points at
+        let check = |source: &str| {
+            let ast = parse(source);
+            TypeChecker::new().check(&ast).map_err(|errors| {
+                let span = errors[0].span;
+                (
+                    errors[0].kind.clone(),
+                    source[span.start..span.end].to_string(),
+                )
+            })
+        };
+
+        assert_eq!(
+            check("let n = 3; let s: string = \"frogs: \" + n + \"!\";"),
+            Err((
+                TypeErrorKind::ExpectedType {
+                    expected: Type::String,
+                    found: Type::Number,
+                },
+                "n".to_string()
+            ))
+        );
+        assert_eq!(
+            check("func half(n: number): float { return n / 2.0; } let n: number = 1 + half(4);"),
+            Err((
+                TypeErrorKind::ResultMismatch {
+                    function: "half".into(),
+                    expected: Type::Number,
+                    found: Type::Float,
+                },
+                "half(4)".to_string()
+            ))
+        );
+        // nothing in the expression has the expected type, so the declaration is at fault
+        assert_eq!(
+            check("let s: string = 1 + 2;").map_err(|(kind, _)| kind),
+            Err(TypeErrorKind::DeclarationMismatch {
+                name: "s".into(),
+                expected: Type::String,
+                found: Type::Number,
+            })
+        );
+        assert_eq!(
+            check("let f: float = 1 + 2.5; let n: number = 7 % 2;"),
+            Ok(())
+        );
+
+        assert_eq!(
+            check("for i: number = 1 to 3 { let s: string = to_string(i); }"),
+            Ok(())
+        );
+        assert_eq!(
+            check("for i: bool = 1 to 3 { }"),
+            Err((
+                TypeErrorKind::DeclarationMismatch {
+                    name: "i".into(),
+                    expected: Type::Boolean,
+                    found: Type::Number,
+                },
+                "1 to 3".to_string()
+            ))
+        );
+        assert_eq!(
+            check("for c: number in \"frog\" { }"),
+            Err((
+                TypeErrorKind::DeclarationMismatch {
+                    name: "c".into(),
+                    expected: Type::Number,
+                    found: Type::String,
+                },
+                "\"frog\"".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_void_calls_have_no_value() {
+        let check = |source: &str| {
+            let ast = parse(source);
+            TypeChecker::new().check(&ast).map_err(|errors| {
+                errors
+                    .iter()
+                    .map(|error| {
+                        (
+                            error.kind.clone(),
+                            source[error.span.start..error.span.end].to_string(),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+        };
+        let void = |source: &str| {
+            (
+                TypeErrorKind::VoidValue("hop".to_string()),
+                source.to_string(),
+            )
+        };
+
+        assert_eq!(check("func hop() { croak 1; } hop();"), Ok(()));
+        assert_eq!(
+            check("func hop() { } let x: number = 1 + hop(); croak hop(); if hop() { }"),
+            Err(vec![void("hop()"), void("hop()"), void("hop()")])
+        );
+    }
+
+    #[test]
+    fn test_return_types_are_inferred_from_returns() {
+        // called before its declaration, and calling itself once a return says what it gives
+        assert_eq!(
+            check(
+                "let n: number = fact(5) + half(4);
+func fact(n: number) { if n < 2 { return 1; } return n * fact(n - 1); }
+func half(n: number) { return n / 2; }
+func find(n: number) { if n > 0 { return n; } return none; }
+let found: number? = find(3);"
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            check("func f(n: number) { if n > 0 { return 1; } return \"none\"; }"),
+            Err(TypeErrorKind::InconsistentReturn {
+                earlier: Type::Number,
+                found: Type::String,
+            })
+        );
+        assert_eq!(
+            check("func f(n: number) { return f(n - 1); }"),
+            Err(TypeErrorKind::ReturnTypeNotInferred("f".to_string()))
+        );
+        // functions calling each other get the type their other returns agree on
+        assert_eq!(
+            check(
+                "let even: bool = is_even(4);
+func is_even(n: number) { if n == 0 { return true; } return is_odd(n - 1); }
+func is_odd(n: number) { if n == 0 { return false; } return is_even(n - 1); }"
+            ),
+            Ok(())
+        );
+        // without one, only the first call that can't be told is reported
+        let errors = TypeChecker::new()
+            .check(&parse(
+                "func a(n: number) { return b(n); } func b(n: number) { return a(n); } croak a(1);",
+            ))
+            .unwrap_err();
+        assert_eq!(
+            errors.iter().map(|e| e.kind.clone()).collect::<Vec<_>>(),
+            vec![TypeErrorKind::ReturnTypeNotInferred("b".to_string())]
+        );
+        assert_eq!(
+            check("func f(n: number) { if n > 0 { return 1; } } let s: string = f(1);"),
+            Err(TypeErrorKind::MissingReturn {
+                name: "f".to_string(),
+                expected: Type::Number,
+            })
+        );
+    }
+
+    #[test]
+    fn test_generic_functions() {
+        let functions = "
+func identity<T>(x: T): T { let y: T = x; return y; }
+func pick<T>(first: bool, a: T, b: T): T { if first { return a; } return b; }
+func apply<A, B>(f: func(A): B, x: A): B { return f(x); }
+func double(n: number): number { return n * 2; }
+";
+
+        assert_eq!(
+            check(&format!(
+                "{}let n: number = identity(3) + pick(true, 1, 2);
+let s: string = identity(\"ribbit\");
+let m: number? = pick(false, none, 4);
+let d: number = apply(double, identity(5));",
+                functions
+            )),
+            Ok(())
+        );
+        assert_eq!(
+            check(&format!("{}croak pick(true, 1, \"one\");", functions)),
+            Err(TypeErrorKind::ArgumentMismatch {
+                name: "pick".to_string(),
+                position: 3,
+                expected: Type::Number,
+                found: Type::String,
+            })
+        );
+        assert_eq!(
+            check(&format!("{}let s: string = apply(double, 1);", functions)),
+            Err(TypeErrorKind::ResultMismatch {
+                function: "apply".into(),
+                expected: Type::String,
+                found: Type::Number,
+            })
+        );
+        // a generic function passed along or stored gets its own type parameters
+        assert_eq!(
+            check(&format!(
+                "{}func twice<T>(f: func(T): T, x: T): T {{ return f(f(x)); }}
+let t: number = twice(identity, 3);
+let g: func(number): number = identity;
+let h: func(string): string = identity;
+let u: number = g(4);",
+                functions
+            )),
+            Ok(())
+        );
+        assert_eq!(
+            check(&format!(
+                "{}let g: func(number): string = identity;",
+                functions
+            )),
+            Err(TypeErrorKind::DeclarationMismatch {
+                name: "g".to_string(),
+                expected: Type::Function(vec![Type::Number], Box::new(Type::String)),
+                found: Type::Function(
+                    vec![Type::Parameter("T'1".into())],
+                    Box::new(Type::Parameter("T'1".into()))
+                ),
+            })
+        );
+        // nothing is known about a type parameter inside the function
+        assert_eq!(
+            check("func add<T>(a: T, b: T): T { return a + b; }"),
+            Err(TypeErrorKind::InvalidOperands {
+                operator: "+".to_string(),
+                left: Type::Parameter("T".into()),
+                right: Type::Parameter("T".into()),
+            })
+        );
+        assert_eq!(
+            check("func nothing<T>(): T? { return none; }"),
+            Err(TypeErrorKind::UnusedTypeParameter {
+                function: "nothing".to_string(),
+                parameter: "T".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_for_in_goes_over_ranges() {
+        assert_eq!(
+            check("let r: range = 0..3; for i in r { let n: number = i * 2; } for j in 1..2 { }"),
+            Ok(())
+        );
+        assert_eq!(
+            check("for c in \"ribbit\" { let s: string = c + \"!\"; }"),
+            Ok(())
+        );
+        assert_eq!(
+            check("for c: number in \"ribbit\" { }"),
+            Err(TypeErrorKind::DeclarationMismatch {
+                name: "c".into(),
+                expected: Type::Number,
+                found: Type::String,
+            })
+        );
+        assert_eq!(
+            check("for b in true { }"),
+            Err(TypeErrorKind::NotIterable(Type::Boolean))
+        );
+        assert_eq!(
+            check("let r = 0..1.5;"),
+            Err(TypeErrorKind::InvalidOperands {
+                operator: "..".to_string(),
+                left: Type::Number,
+                right: Type::Float,
+            })
+        );
+    }
+
+    #[test]
+    fn test_block_expressions() {
+        assert_eq!(
+            check("let s: string = { let n = 2; for i in 0..n { break; } to_string(n) };"),
+            Ok(())
+        );
+        assert_eq!(
+            check("let n = { let hidden = 1; hidden }; croak hidden;"),
+            Err(TypeErrorKind::UnknownVariable("hidden".to_string()))
+        );
+        assert_eq!(
+            check("func f(): number { let x = { return 1; 2 }; return x; }"),
+            Err(TypeErrorKind::JumpOutOfBlock("return".to_string()))
+        );
+        assert_eq!(
+            check("while true { let x = { continue; 1 }; }"),
+            Err(TypeErrorKind::JumpOutOfBlock("continue".to_string()))
+        );
+        // a function declared in the block can return from itself
+        assert_eq!(
+            check("let f = { func one(): number { return 1; } one }; croak f();"),
+            Ok(())
+        );
     }
 }
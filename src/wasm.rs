@@ -0,0 +1,74 @@
+// bindings for a web playground, built with `--features wasm-bindgen` for wasm32 and
+// wrapped with wasm-bindgen's tooling
+
+use crate::interpreter::{Interpreter, Limits};
+use std::path::Path;
+use wasm_bindgen::prelude::*;
+
+// statements and loop iterations a playground program may run; the browser tab would hang on
+// an endless loop otherwise. Timeouts need a clock the browser target doesn't have
+const MAX_STEPS: u64 = 10_000_000;
+
+// what running a program produced: everything it croaked, and its errors as a JSON array like
+// `froggle check --error-format=json` prints, `[]` when it ran cleanly
+#[wasm_bindgen(getter_with_clone)]
+pub struct RunOutput {
+    pub output: String,
+    pub errors: String,
+}
+
+// name the program's errors are reported in
+const PATH: &str = "playground.frog";
+
+// runs a program the way `froggle run` does, so a `main` it declares is called
+#[wasm_bindgen]
+pub fn run(source: &str) -> RunOutput {
+    let mut interpreter = Interpreter::new();
+    interpreter.limits = Limits {
+        max_steps: Some(MAX_STEPS),
+        ..Limits::default()
+    };
+    interpreter.capture_output();
+
+    let diagnostics = match crate::run(source, Path::new(PATH), &mut interpreter) {
+        Ok(_) => Vec::new(),
+        // exit(0), or a main returning 0, is a program ending as it should
+        Err(e) if e.exit_code() == Some(0) => Vec::new(),
+        Err(e) => e.diagnostics(),
+    };
+    let errors: Vec<String> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.to_json(PATH))
+        .collect();
+    RunOutput {
+        output: interpreter.take_output(),
+        errors: format!("[{}]", errors.join(",")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_captures_output_and_errors() {
+        let ran = run("croak \"ribbit\"; croak 1 / 0;");
+        assert_eq!(ran.output, "ribbit\n");
+        assert!(ran.errors.contains("\"code\":\"runtime/DivisionByZero\""));
+        assert_eq!(
+            run("while true { }")
+                .errors
+                .matches("StepLimitExceeded")
+                .count(),
+            1
+        );
+        let ran = run("func main(): number { croak \"main\"; return 0; } croak \"first\";");
+        assert_eq!(ran.output, "first\nmain\n");
+        assert_eq!(ran.errors, "[]");
+        assert!(
+            run("func main(n: number) { }")
+                .errors
+                .contains("MainParameters")
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use crate::cli::RunOptions;
+use std::fs;
+use std::io::{self, Write};
+use std::process::ExitCode;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+// how often the file is looked at for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+// moves the cursor home and clears the terminal
+const CLEAR_SCREEN: &str = "\x1b[H\x1b[2J";
+
+// runs a program like `run` does, then again every time its file is saved, until stopped with
+// Ctrl+C. The file is polled rather than watched, which needs no platform support and is
+// quick enough for one file
+pub fn watch(options: &RunOptions) -> ExitCode {
+    let mut last_run = None;
+    loop {
+        // editors that save by replacing the file make it disappear for a moment
+        let modified = modified(&options.path);
+        if modified.is_some() && modified != last_run {
+            last_run = modified;
+            // flushed before the program runs, or errors on stderr could be cleared away
+            print!("{}", CLEAR_SCREEN);
+            let _ = io::stdout().flush();
+            let outcome = if crate::run_file(options) == ExitCode::SUCCESS {
+                "finished"
+            } else {
+                "failed"
+            };
+            println!(
+                "\n[{} {}, waiting for changes, Ctrl+C to stop]",
+                options.path, outcome
+            );
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
@@ -0,0 +1,106 @@
+use froggle::Limits;
+use froggle::error::Style;
+use froggle::interpreter::Interpreter;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// every program in examples/ runs through the whole pipeline and what it croaks is compared with
+// the .out file next to it. A program that fails has its error, rendered without colors,
+// appended to the output, so error messages are covered too. Running with FROGGLE_BLESS=1
+// rewrites the .out files from the current output instead
+#[test]
+fn test_examples_match_their_expected_output() {
+    let bless = std::env::var_os("FROGGLE_BLESS").is_some();
+    let mut failures = Vec::new();
+
+    for program in programs() {
+        let expected_path = program.with_extension("out");
+        let actual = run(&program);
+        if bless {
+            fs::write(&expected_path, &actual).unwrap();
+            continue;
+        }
+        let Ok(expected) = fs::read_to_string(&expected_path) else {
+            failures.push(format!(
+                "{}: missing, run with FROGGLE_BLESS=1 to create it",
+                expected_path.display()
+            ));
+            continue;
+        };
+        if let Some(difference) = difference(&expected, &actual) {
+            failures.push(format!("{}: {}", program.display(), difference));
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+fn programs() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+    let mut programs: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "frog")
+        })
+        .collect();
+    programs.sort();
+    assert!(!programs.is_empty(), "no programs in examples/");
+    programs
+}
+
+fn run(program: &Path) -> String {
+    let source = fs::read_to_string(program).unwrap();
+    let mut interpreter = Interpreter::new();
+    interpreter.capture_output();
+    // a regression that makes a program loop forever fails instead of hanging the tests
+    interpreter.limits = Limits {
+        max_steps: Some(10_000_000),
+        ..Limits::default()
+    };
+
+    let result = froggle::run(&source, program, &mut interpreter);
+    let mut output = interpreter.take_output();
+    if let Err(e) = result {
+        // shown in the file they are in, which may be a module the program imported, with
+        // paths relative to examples/ so the expected output doesn't depend on the checkout
+        let dir = fs::canonicalize(program.parent().unwrap()).unwrap();
+        for diagnostic in e.diagnostics() {
+            let (name, source, diagnostic) =
+                interpreter.modules.sources.locate(&diagnostic).unwrap();
+            let path = Path::new(name);
+            let path = path
+                .strip_prefix(&dir)
+                .or_else(|_| path.strip_prefix(program.parent().unwrap()))
+                .unwrap_or(path);
+            output += &diagnostic.render(&path.display().to_string(), source, Style::default());
+            output.push('\n');
+        }
+    }
+    output
+}
+
+// the first line that differs, with both versions of it
+fn difference(expected: &str, actual: &str) -> Option<String> {
+    if expected == actual {
+        return None;
+    }
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    let mut line = 1;
+    loop {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(e), Some(a)) if e == a => line += 1,
+            (None, None) => return Some("line endings differ".to_string()),
+            (e, a) => {
+                return Some(format!(
+                    "line {} differs\n  expected: {}\n  actual:   {}",
+                    line,
+                    e.unwrap_or("<end of output>"),
+                    a.unwrap_or("<end of output>")
+                ));
+            }
+        }
+    }
+}